@@ -1,3 +1,4 @@
+use crate::constraints::DelegationConstraints;
 use crate::subject::Subject;
 use ssi::did::Document;
 use thiserror::Error;
@@ -27,5 +28,15 @@ pub trait Attestor: Subject {
     /// Attests to a DID Document. Subject attests to a DID document by signing the document with (one of) its private signing key(s).
     /// It doesn't matter which signing key you use, there's the option to pick one using the key index.
     /// Typically, the signer will be a controller, but not necessarily. However, every signer is the subject of its own DID.
-    fn attest(&self, doc: &Document, key_id: Option<&str>) -> Result<String, AttestorError>;
+    fn attest(&self, doc: &Document, key_id: Option<&str>) -> Result<String, AttestorError> {
+        self.attest_with_constraints(doc, None, key_id)
+    }
+    /// Attests to a DID Document subject to delegation constraints on the downstream DID.
+    /// The constraints are covered by the signature and must be published alongside the proof.
+    fn attest_with_constraints(
+        &self,
+        doc: &Document,
+        constraints: Option<&DelegationConstraints>,
+        key_id: Option<&str>,
+    ) -> Result<String, AttestorError>;
 }
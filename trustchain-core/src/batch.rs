@@ -0,0 +1,662 @@
+//! Batch issuance of credentials from a credential template and subject data.
+//!
+//! Each record of subject data (a line of JSONL, or a row of CSV under a header row) is merged
+//! into the `credentialSubject` of the template, and the resulting credential signed by a single
+//! issuer. Issued credentials are written to an output directory (one file each) or to a JSONL
+//! file, together with a manifest of their SHA-256 hashes and of any records that failed.
+use crate::issuer::{Issuer, IssuerError, ProofOptions};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use ssi::did_resolve::DIDResolver;
+use ssi::vc::Credential;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// File name of the manifest in an output directory.
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// An error relating to batch issuance.
+#[derive(Error, Debug)]
+pub enum BatchError {
+    /// Failed to write the output.
+    #[error("Failed to write output: {0}")]
+    FailedToWrite(std::io::Error),
+    /// Invalid CSV header row.
+    #[error("Invalid CSV header: {0}")]
+    InvalidHeader(String),
+    /// Invalid record of subject data.
+    #[error("Invalid record on line {0}: {1}")]
+    InvalidRecord(usize, String),
+    /// Invalid credential template.
+    #[error("Invalid credential template: {0}")]
+    InvalidTemplate(String),
+    /// Failed to serialize a credential or the manifest.
+    #[error("Failed to serialize: {0}")]
+    FailedToSerialize(serde_json::Error),
+    /// Failed to get the verification method of the issuer's proofs.
+    #[error("Failed to get the issuer's verification method: {0}")]
+    FailedToGetVerificationMethod(IssuerError),
+}
+
+/// Format of the subject data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubjectFormat {
+    /// A JSON object per line.
+    Jsonl,
+    /// A header row of (dot-separated, for nested objects) property names and a row per subject.
+    Csv,
+}
+
+impl SubjectFormat {
+    /// Returns the format of a file of subject data by its extension, if recognised.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        match path.as_ref().extension()?.to_str()? {
+            "jsonl" | "ndjson" => Some(SubjectFormat::Jsonl),
+            "csv" => Some(SubjectFormat::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// A record of subject data, with the line of the input on which it starts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubjectRecord {
+    /// Line (from 1) of the input on which the record starts.
+    pub line: usize,
+    /// Properties of the subject, or an error if the record is invalid.
+    pub properties: Result<Map<String, Value>, String>,
+}
+
+/// Destination of issued credentials.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BatchOutput {
+    /// A directory, with a file per credential and the manifest.
+    Directory(PathBuf),
+    /// A JSONL file with a credential per line, and the manifest alongside it.
+    Jsonl(PathBuf),
+}
+
+impl BatchOutput {
+    /// Returns the path of the manifest: in the output directory, or alongside the JSONL file
+    /// with the extension `manifest.json`.
+    pub fn manifest_path(&self) -> PathBuf {
+        match self {
+            BatchOutput::Directory(dir) => dir.join(MANIFEST_FILE),
+            BatchOutput::Jsonl(path) => path.with_extension(MANIFEST_FILE),
+        }
+    }
+}
+
+/// Outcome of issuing the credential for a record of subject data.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestEntry {
+    /// Index (from 0) of the record.
+    pub index: usize,
+    /// Line (from 1) of the subject data on which the record starts.
+    pub line: usize,
+    /// ID of the credential subject, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+    /// Name of the credential's file in an output directory.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// Line (from 1) of the credential in an output JSONL file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_line: Option<usize>,
+    /// Hex-encoded SHA-256 hash of the credential as written.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Reason the credential was not issued.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Manifest of a batch of issued credentials.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchManifest {
+    /// DID of the issuer.
+    pub issuer: String,
+    /// Time of issuance (RFC 3339).
+    pub created: String,
+    /// Number of credentials issued.
+    pub issued: usize,
+    /// Number of records for which no credential was issued.
+    pub failed: usize,
+    /// Outcome for each record, in order.
+    pub entries: Vec<ManifestEntry>,
+}
+
+impl BatchManifest {
+    /// Returns the entries of the records for which no credential was issued.
+    pub fn failures(&self) -> impl Iterator<Item = &ManifestEntry> {
+        self.entries.iter().filter(|entry| entry.error.is_some())
+    }
+}
+
+/// Reads records of subject data in the given format.
+pub fn read_subjects(input: &str, format: SubjectFormat) -> Result<Vec<SubjectRecord>, BatchError> {
+    match format {
+        SubjectFormat::Jsonl => Ok(read_jsonl(input)),
+        SubjectFormat::Csv => read_csv(input),
+    }
+}
+
+/// Reads a JSON object from each non-empty line.
+fn read_jsonl(input: &str) -> Vec<SubjectRecord> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| SubjectRecord {
+            line: index + 1,
+            properties: match serde_json::from_str(line) {
+                Ok(Value::Object(properties)) => Ok(properties),
+                Ok(_) => Err("not a JSON object".to_string()),
+                Err(e) => Err(e.to_string()),
+            },
+        })
+        .collect()
+}
+
+/// Reads the rows of a CSV (RFC 4180) under its header row as records of string properties,
+/// nested by the dot-separated property names of the header. Empty fields are omitted.
+fn read_csv(input: &str) -> Result<Vec<SubjectRecord>, BatchError> {
+    let mut rows = parse_csv(input)?.into_iter();
+    let header = match rows.next() {
+        Some((_, header)) => header,
+        None => return Ok(Vec::new()),
+    };
+    let paths = header
+        .iter()
+        .map(|name| {
+            let path: Vec<&str> = name.trim().split('.').collect();
+            match path.iter().any(|key| key.is_empty()) {
+                true => Err(BatchError::InvalidHeader(format!(
+                    "invalid property name: \"{}\"",
+                    name
+                ))),
+                false => Ok(path),
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(rows
+        .filter(|(_, row)| !(row.len() == 1 && row[0].is_empty()))
+        .map(|(line, row)| SubjectRecord {
+            line,
+            properties: match row.len() == paths.len() {
+                true => paths
+                    .iter()
+                    .zip(row)
+                    .filter(|(_, field)| !field.is_empty())
+                    .try_fold(Map::new(), |mut properties, (path, field)| {
+                        insert_path(&mut properties, path, Value::String(field))?;
+                        Ok(properties)
+                    }),
+                false => Err(format!(
+                    "expected {} fields, found {}",
+                    paths.len(),
+                    row.len()
+                )),
+            },
+        })
+        .collect())
+}
+
+/// Parses CSV into rows of fields, each with the line on which it starts.
+fn parse_csv(input: &str) -> Result<Vec<(usize, Vec<String>)>, BatchError> {
+    let mut rows = Vec::new();
+    let (mut row, mut field) = (Vec::new(), String::new());
+    let (mut line, mut row_line) = (1, 1);
+    let mut quoted = false;
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (false, '"') if field.is_empty() => quoted = true,
+            (false, ',') => row.push(std::mem::take(&mut field)),
+            (false, '\r') if chars.peek() == Some(&'\n') => (),
+            (false, '\n') => {
+                row.push(std::mem::take(&mut field));
+                rows.push((row_line, std::mem::take(&mut row)));
+                line += 1;
+                row_line = line;
+            }
+            (_, c) => {
+                if c == '\n' {
+                    line += 1;
+                }
+                field.push(c);
+            }
+        }
+    }
+    if quoted {
+        return Err(BatchError::InvalidRecord(
+            row_line,
+            "unterminated quoted field".to_string(),
+        ));
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push((row_line, row));
+    }
+    Ok(rows)
+}
+
+/// Inserts a value into an object at a path of nested property names.
+fn insert_path(
+    properties: &mut Map<String, Value>,
+    path: &[&str],
+    value: Value,
+) -> Result<(), String> {
+    match path {
+        [key] => {
+            properties.insert(key.to_string(), value);
+            Ok(())
+        }
+        [key, rest @ ..] => match properties
+            .entry(key.to_string())
+            .or_insert_with(|| Value::Object(Map::new()))
+        {
+            Value::Object(nested) => insert_path(nested, rest, value),
+            _ => Err(format!("property {} is not an object", key)),
+        },
+        [] => Ok(()),
+    }
+}
+
+/// Merges a value into another: objects are merged by property, and other values replaced.
+fn merge(target: &mut Value, value: Value) {
+    match (target, value) {
+        (Value::Object(target), Value::Object(value)) => {
+            for (key, value) in value {
+                match target.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        target.insert(key, value);
+                    }
+                }
+            }
+        }
+        (target, value) => *target = value,
+    }
+}
+
+/// Makes a credential from a template by merging the properties of a subject (including any
+/// `id`) into its single `credentialSubject`.
+pub fn credential_from_template(
+    template: &Credential,
+    properties: &Map<String, Value>,
+) -> Result<Credential, BatchError> {
+    let mut credential =
+        serde_json::to_value(template).map_err(|e| BatchError::InvalidTemplate(e.to_string()))?;
+    match credential.get_mut("credentialSubject") {
+        Some(subject @ Value::Object(_)) => merge(subject, Value::Object(properties.clone())),
+        _ => {
+            return Err(BatchError::InvalidTemplate(
+                "template must have a single credentialSubject".to_string(),
+            ))
+        }
+    }
+    serde_json::from_value(credential).map_err(|e| BatchError::InvalidTemplate(e.to_string()))
+}
+
+/// Returns the hex-encoded SHA-256 hash of the given bytes.
+fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Writer of issued credentials to a batch output.
+enum Writer<'a> {
+    /// Output directory.
+    Directory(&'a Path),
+    /// Output JSONL file, with the number of lines written.
+    Jsonl(File, usize),
+}
+
+/// Issues a credential from the template for each record of subject data, signed by the issuer
+/// with the given proof options, writing the issued credentials and the manifest to the output.
+/// Records that fail to parse or to be signed are reported in the manifest, which is returned.
+pub async fn issue_batch<I, T>(
+    issuer: &I,
+    template: &Credential,
+    subjects: &[SubjectRecord],
+    options: &ProofOptions,
+    resolver: &T,
+    output: &BatchOutput,
+) -> Result<BatchManifest, BatchError>
+where
+    I: Issuer + Sync,
+    T: DIDResolver,
+{
+    let mut writer = match output {
+        BatchOutput::Directory(dir) => {
+            std::fs::create_dir_all(dir).map_err(BatchError::FailedToWrite)?;
+            Writer::Directory(dir)
+        }
+        BatchOutput::Jsonl(path) => {
+            Writer::Jsonl(File::create(path).map_err(BatchError::FailedToWrite)?, 0)
+        }
+    };
+    // Resolve the issuer's verification method once for the batch rather than per credential.
+    let options = ProofOptions {
+        verification_method: Some(
+            issuer
+                .verification_method(options, resolver)
+                .await
+                .map_err(BatchError::FailedToGetVerificationMethod)?,
+        ),
+        ..options.clone()
+    };
+    let width = subjects.len().saturating_sub(1).to_string().len();
+    let mut entries = Vec::new();
+    for (index, record) in subjects.iter().enumerate() {
+        let mut entry = ManifestEntry {
+            index,
+            line: record.line,
+            subject: None,
+            file: None,
+            output_line: None,
+            sha256: None,
+            error: None,
+        };
+        let credential = match &record.properties {
+            Ok(properties) => {
+                credential_from_template(template, properties).map_err(|e| e.to_string())
+            }
+            Err(e) => Err(e.to_owned()),
+        };
+        let signed = match credential {
+            Ok(credential) => {
+                entry.subject = credential
+                    .credential_subject
+                    .first()
+                    .and_then(|subject| subject.id.as_ref())
+                    .map(|id| id.to_string());
+                issuer
+                    .sign(&credential, &options, resolver)
+                    .await
+                    .map_err(|e| e.to_string())
+            }
+            Err(e) => Err(e),
+        };
+        match (signed, &mut writer) {
+            (Ok(credential), Writer::Jsonl(file, output_line)) => {
+                let bytes =
+                    serde_json::to_vec(&credential).map_err(BatchError::FailedToSerialize)?;
+                file.write_all(&bytes)
+                    .and_then(|_| file.write_all(b"\n"))
+                    .map_err(BatchError::FailedToWrite)?;
+                *output_line += 1;
+                entry.output_line = Some(*output_line);
+                entry.sha256 = Some(sha256_hex(&bytes));
+            }
+            (Ok(credential), Writer::Directory(dir)) => {
+                let bytes = serde_json::to_vec_pretty(&credential)
+                    .map_err(BatchError::FailedToSerialize)?;
+                let file_name = format!("credential-{:0width$}.json", index, width = width);
+                std::fs::write(dir.join(&file_name), &bytes).map_err(BatchError::FailedToWrite)?;
+                entry.file = Some(file_name);
+                entry.sha256 = Some(sha256_hex(&bytes));
+            }
+            (Err(e), _) => entry.error = Some(e),
+        }
+        entries.push(entry);
+    }
+
+    let failed = entries.iter().filter(|entry| entry.error.is_some()).count();
+    let manifest = BatchManifest {
+        issuer: issuer.did().to_owned(),
+        created: Utc::now().to_rfc3339(),
+        issued: entries.len() - failed,
+        failed,
+        entries,
+    };
+    let file = File::create(output.manifest_path()).map_err(BatchError::FailedToWrite)?;
+    serde_json::to_writer_pretty(file, &manifest).map_err(BatchError::FailedToSerialize)?;
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::TEST_CREDENTIAL;
+    use crate::methods::DIDKeyResolver;
+    use crate::subject::Subject;
+    use async_trait::async_trait;
+    use serde_json::json;
+    use ssi::jwk::Algorithm;
+    use ssi::vc::URI;
+
+    const UNSIGNED_SUBJECT: &str = "did:example:unsigned";
+
+    // Mock issuer returning credentials unsigned, except to one subject which it fails to sign.
+    struct TestIssuer;
+
+    impl Subject for TestIssuer {
+        fn did(&self) -> &str {
+            "did:example:issuer"
+        }
+    }
+
+    #[async_trait]
+    impl Issuer for TestIssuer {
+        async fn sign<T: DIDResolver>(
+            &self,
+            credential: &Credential,
+            options: &ProofOptions,
+            _resolver: &T,
+        ) -> Result<Credential, IssuerError> {
+            // Batches sign with the verification method resolved up front.
+            assert_eq!(
+                options.verification_method.as_deref(),
+                Some("did:example:issuer#key-1")
+            );
+            match credential.credential_subject.first() {
+                Some(subject) if subject.id == Some(URI::String(UNSIGNED_SUBJECT.to_owned())) => {
+                    Err(IssuerError::NoVerificationMethod(self.did().to_owned()))
+                }
+                _ => Ok(credential.clone()),
+            }
+        }
+
+        async fn verification_method<T: DIDResolver>(
+            &self,
+            options: &ProofOptions,
+            _resolver: &T,
+        ) -> Result<String, IssuerError> {
+            Ok(options
+                .verification_method
+                .clone()
+                .unwrap_or_else(|| format!("{}#key-1", self.did())))
+        }
+
+        async fn sign_jwt<T: DIDResolver>(
+            &self,
+            _credential: &Credential,
+            _options: &ProofOptions,
+            _algorithm: Option<Algorithm>,
+            _resolver: &T,
+        ) -> Result<String, IssuerError> {
+            Err(IssuerError::NoVerificationMethod(self.did().to_owned()))
+        }
+    }
+
+    fn properties(record: &SubjectRecord) -> Value {
+        Value::Object(record.properties.clone().unwrap())
+    }
+
+    #[test]
+    fn test_read_jsonl() {
+        let input = "{\"id\": \"did:example:a\", \"givenName\": \"Ann\"}\n\n[1]\n{\"givenName\": \"Bob\"}\n";
+        let records = read_subjects(input, SubjectFormat::Jsonl).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(
+            properties(&records[0]),
+            json!({"id": "did:example:a", "givenName": "Ann"})
+        );
+        assert_eq!(records[1].line, 3);
+        assert!(records[1].properties.is_err());
+        assert_eq!(records[2].line, 4);
+        assert_eq!(properties(&records[2]), json!({"givenName": "Bob"}));
+    }
+
+    #[test]
+    fn test_read_csv() {
+        let input = "id,givenName,degree.name,degree.type\r\n\
+            did:example:a,Ann,\"Science, and \"\"Arts\"\"\",BachelorDegree\r\n\
+            did:example:b,,\"Multi\nline\",\n\
+            did:example:c,Cat\n";
+        let records = read_subjects(input, SubjectFormat::Csv).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(
+            properties(&records[0]),
+            json!({
+                "id": "did:example:a",
+                "givenName": "Ann",
+                "degree": {"name": "Science, and \"Arts\"", "type": "BachelorDegree"}
+            })
+        );
+        assert_eq!(
+            properties(&records[1]),
+            json!({"id": "did:example:b", "degree": {"name": "Multi\nline"}})
+        );
+        assert_eq!(records[2].line, 5);
+        assert_eq!(
+            records[2].properties,
+            Err("expected 4 fields, found 2".to_string())
+        );
+
+        assert!(matches!(
+            read_subjects("id,degree.\n", SubjectFormat::Csv),
+            Err(BatchError::InvalidHeader(_))
+        ));
+        assert!(matches!(
+            read_subjects("id\n\"did:example:a\n", SubjectFormat::Csv),
+            Err(BatchError::InvalidRecord(2, _))
+        ));
+        assert_eq!(
+            SubjectFormat::from_path("subjects.csv"),
+            Some(SubjectFormat::Csv)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_issue_batch() {
+        let template: Credential = serde_json::from_str(TEST_CREDENTIAL).unwrap();
+        let csv = format!(
+            "id,givenName\ndid:example:a,Ann\ndid:example:b\n{},Una\ndid:example:c,Cat\n",
+            UNSIGNED_SUBJECT
+        );
+        let subjects = read_subjects(&csv, SubjectFormat::Csv).unwrap();
+        let options = ProofOptions::default();
+        let dir = tempfile::tempdir().unwrap();
+        let issue = |output| {
+            issue_batch(
+                &TestIssuer,
+                &template,
+                &subjects,
+                &options,
+                &DIDKeyResolver,
+                output,
+            )
+        };
+
+        // Credentials written to a directory, with the records that failed to parse or to be
+        // signed reported in the saved manifest.
+        let output = BatchOutput::Directory(dir.path().join("credentials"));
+        let manifest = issue(&output).await.unwrap();
+        assert_eq!(manifest.issuer, "did:example:issuer");
+        assert_eq!((manifest.issued, manifest.failed), (2, 2));
+        assert_eq!(
+            manifest
+                .failures()
+                .map(|entry| entry.line)
+                .collect::<Vec<_>>(),
+            vec![3, 4]
+        );
+        let saved: BatchManifest =
+            serde_json::from_reader(File::open(output.manifest_path()).unwrap()).unwrap();
+        assert_eq!(saved.entries.len(), 4);
+        for (entry, subject) in manifest
+            .entries
+            .iter()
+            .filter(|entry| entry.error.is_none())
+            .zip(["did:example:a", "did:example:c"])
+        {
+            assert_eq!(entry.subject.as_deref(), Some(subject));
+            let file = entry.file.as_ref().unwrap();
+            let bytes = std::fs::read(dir.path().join("credentials").join(file)).unwrap();
+            assert_eq!(entry.sha256, Some(sha256_hex(&bytes)));
+        }
+        assert_eq!(
+            manifest.entries[3].file.as_deref(),
+            Some("credential-3.json")
+        );
+
+        // Credentials written to a JSONL file, with the manifest alongside.
+        let path = dir.path().join("credentials.jsonl");
+        let output = BatchOutput::Jsonl(path.clone());
+        let manifest = issue(&output).await.unwrap();
+        assert_eq!(
+            output.manifest_path(),
+            dir.path().join("credentials.manifest.json")
+        );
+        assert!(output.manifest_path().exists());
+        let lines = std::fs::read_to_string(path).unwrap();
+        let lines: Vec<&str> = lines.lines().collect();
+        assert_eq!(lines.len(), 2);
+        for entry in manifest
+            .entries
+            .iter()
+            .filter(|entry| entry.error.is_none())
+        {
+            let line = lines[entry.output_line.unwrap() - 1];
+            assert_eq!(entry.sha256, Some(sha256_hex(line.as_bytes())));
+            let credential: Credential = serde_json::from_str(line).unwrap();
+            assert_eq!(
+                credential.credential_subject.first().unwrap().id,
+                entry.subject.clone().map(URI::String)
+            );
+        }
+    }
+
+    #[test]
+    fn test_credential_from_template() {
+        let template: Credential = serde_json::from_str(TEST_CREDENTIAL).unwrap();
+        let properties = json!({
+            "id": "did:example:a",
+            "givenName": "Ann",
+            "degree": {"name": "Bachelor of Arts"}
+        });
+        let credential =
+            credential_from_template(&template, properties.as_object().unwrap()).unwrap();
+        let subject = credential.credential_subject.to_single().unwrap();
+        assert_eq!(subject.id, Some(URI::String("did:example:a".to_string())));
+        let subject = serde_json::to_value(subject).unwrap();
+        assert_eq!(subject["givenName"], "Ann");
+        assert_eq!(subject["familyName"], "Doe");
+        assert_eq!(subject["degree"]["name"], "Bachelor of Arts");
+        assert_eq!(subject["degree"]["type"], "BachelorDegree");
+
+        let mut template = serde_json::to_value(template).unwrap();
+        template["credentialSubject"] = json!([{"givenName": "Jane"}, {"givenName": "John"}]);
+        let template: Credential = serde_json::from_value(template).unwrap();
+        assert!(matches!(
+            credential_from_template(&template, properties.as_object().unwrap()),
+            Err(BatchError::InvalidTemplate(_))
+        ));
+    }
+}
@@ -0,0 +1,533 @@
+//! Caching of DID resolution results.
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use ssi::did::Document;
+use ssi::did_resolve::{
+    DIDResolver, DocumentMetadata, ResolutionInputMetadata, ResolutionMetadata,
+};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+#[cfg(feature = "sqlite")]
+use crate::TRUSTCHAIN_DATA;
+#[cfg(feature = "sqlite")]
+use std::path::Path;
+
+/// Default time to live for cached resolutions.
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// Default capacity of the in-memory resolution cache.
+pub const DEFAULT_CACHE_CAPACITY: usize = 1000;
+
+/// An error relating to the resolution cache.
+#[derive(Error, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CacheError {
+    /// Failure in the cache storage backend.
+    #[error("Cache storage failure: {0}")]
+    Storage(String),
+    /// Failed to serialize or deserialize a cached resolution.
+    #[error("Failed to serialize or deserialize cached resolution: {0}")]
+    Serialization(String),
+    /// No TRUSTCHAIN_DATA environment variable.
+    #[error("No TRUSTCHAIN_DATA environment variable.")]
+    TrustchainDataNotPresent,
+}
+
+/// A cached DID resolution result.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheEntry {
+    pub resolution_metadata: ResolutionMetadata,
+    pub document: Option<Document>,
+    pub document_metadata: Option<DocumentMetadata>,
+    /// Unix time (in seconds) at which the entry expires.
+    pub expires: u64,
+}
+
+impl CacheEntry {
+    /// Returns whether the entry has expired at the given Unix time.
+    pub fn is_expired(&self, now: u64) -> bool {
+        now >= self.expires
+    }
+}
+
+/// Storage for cached DID resolution results, keyed by DID and by a version key identifying the
+/// requested `versionId` and `versionTime` (empty for the current version).
+pub trait ResolutionCache: Send + Sync {
+    /// Gets the cached entry for a DID and version key, if present.
+    fn get(&self, did: &str, version: &str) -> Result<Option<CacheEntry>, CacheError>;
+    /// Inserts an entry for a DID and version key, replacing any existing entry.
+    fn insert(&self, did: &str, version: &str, entry: CacheEntry) -> Result<(), CacheError>;
+    /// Removes all cached entries for a DID.
+    fn invalidate(&self, did: &str) -> Result<(), CacheError>;
+    /// Removes all cached entries.
+    fn clear(&self) -> Result<(), CacheError>;
+}
+
+/// State of the in-memory cache: entries with the tick at which they were last used.
+#[derive(Default)]
+struct MemoryCacheState {
+    entries: HashMap<(String, String), (CacheEntry, u64)>,
+    tick: u64,
+}
+
+/// In-memory resolution cache evicting the least recently used entry when full.
+pub struct MemoryCache {
+    capacity: usize,
+    state: Mutex<MemoryCacheState>,
+}
+
+impl MemoryCache {
+    /// Constructs a new in-memory cache holding at most `capacity` entries.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            state: Mutex::new(MemoryCacheState::default()),
+        }
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, MemoryCacheState>, CacheError> {
+        self.state
+            .lock()
+            .map_err(|e| CacheError::Storage(e.to_string()))
+    }
+}
+
+impl Default for MemoryCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CACHE_CAPACITY)
+    }
+}
+
+impl ResolutionCache for MemoryCache {
+    fn get(&self, did: &str, version: &str) -> Result<Option<CacheEntry>, CacheError> {
+        let mut state = self.lock()?;
+        state.tick += 1;
+        let tick = state.tick;
+        Ok(state
+            .entries
+            .get_mut(&(did.to_owned(), version.to_owned()))
+            .map(|(entry, last_used)| {
+                *last_used = tick;
+                entry.clone()
+            }))
+    }
+
+    fn insert(&self, did: &str, version: &str, entry: CacheEntry) -> Result<(), CacheError> {
+        if self.capacity == 0 {
+            return Ok(());
+        }
+        let mut state = self.lock()?;
+        let key = (did.to_owned(), version.to_owned());
+        // Evict the least recently used entry to make room for a new key.
+        if !state.entries.contains_key(&key) && state.entries.len() >= self.capacity {
+            let lru_key = state
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(key, _)| key.to_owned());
+            if let Some(lru_key) = lru_key {
+                state.entries.remove(&lru_key);
+            }
+        }
+        state.tick += 1;
+        let tick = state.tick;
+        state.entries.insert(key, (entry, tick));
+        Ok(())
+    }
+
+    fn invalidate(&self, did: &str) -> Result<(), CacheError> {
+        self.lock()?
+            .entries
+            .retain(|(cached_did, _), _| cached_did != did);
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<(), CacheError> {
+        self.lock()?.entries.clear();
+        Ok(())
+    }
+}
+
+/// Persistent resolution cache backed by an SQLite database.
+#[cfg(feature = "sqlite")]
+pub struct SqliteCache {
+    connection: Mutex<rusqlite::Connection>,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteCache {
+    /// Opens (or creates) a cache database at the given path.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, CacheError> {
+        let connection =
+            rusqlite::Connection::open(path).map_err(|e| CacheError::Storage(e.to_string()))?;
+        Self::init(connection)
+    }
+
+    /// Opens (or creates) the cache database in the Trustchain data directory.
+    pub fn open_default() -> Result<Self, CacheError> {
+        let path =
+            std::env::var(TRUSTCHAIN_DATA).map_err(|_| CacheError::TrustchainDataNotPresent)?;
+        let directory = Path::new(path.as_str()).join("cache");
+        std::fs::create_dir_all(&directory).map_err(|e| CacheError::Storage(e.to_string()))?;
+        Self::open(directory.join("resolution_cache.sqlite"))
+    }
+
+    /// Opens a cache database held in memory.
+    pub fn open_in_memory() -> Result<Self, CacheError> {
+        let connection = rusqlite::Connection::open_in_memory()
+            .map_err(|e| CacheError::Storage(e.to_string()))?;
+        Self::init(connection)
+    }
+
+    fn init(connection: rusqlite::Connection) -> Result<Self, CacheError> {
+        connection
+            .execute(
+                "CREATE TABLE IF NOT EXISTS resolution_cache (
+                    did TEXT NOT NULL,
+                    version TEXT NOT NULL,
+                    entry TEXT NOT NULL,
+                    PRIMARY KEY (did, version)
+                )",
+                [],
+            )
+            .map_err(|e| CacheError::Storage(e.to_string()))?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    fn execute<P: rusqlite::Params>(&self, sql: &str, params: P) -> Result<(), CacheError> {
+        self.connection
+            .lock()
+            .map_err(|e| CacheError::Storage(e.to_string()))?
+            .execute(sql, params)
+            .map_err(|e| CacheError::Storage(e.to_string()))?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl ResolutionCache for SqliteCache {
+    fn get(&self, did: &str, version: &str) -> Result<Option<CacheEntry>, CacheError> {
+        use rusqlite::OptionalExtension;
+        let entry: Option<String> = self
+            .connection
+            .lock()
+            .map_err(|e| CacheError::Storage(e.to_string()))?
+            .query_row(
+                "SELECT entry FROM resolution_cache WHERE did = ?1 AND version = ?2",
+                [did, version],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| CacheError::Storage(e.to_string()))?;
+        entry
+            .map(|entry| {
+                serde_json::from_str(&entry).map_err(|e| CacheError::Serialization(e.to_string()))
+            })
+            .transpose()
+    }
+
+    fn insert(&self, did: &str, version: &str, entry: CacheEntry) -> Result<(), CacheError> {
+        let entry =
+            serde_json::to_string(&entry).map_err(|e| CacheError::Serialization(e.to_string()))?;
+        self.execute(
+            "INSERT OR REPLACE INTO resolution_cache (did, version, entry) VALUES (?1, ?2, ?3)",
+            [did, version, entry.as_str()],
+        )
+    }
+
+    fn invalidate(&self, did: &str) -> Result<(), CacheError> {
+        self.execute("DELETE FROM resolution_cache WHERE did = ?1", [did])
+    }
+
+    fn clear(&self) -> Result<(), CacheError> {
+        self.execute("DELETE FROM resolution_cache", [])
+    }
+}
+
+/// Returns the current Unix time in seconds.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// Returns the cache version key for the resolution input metadata.
+fn version_key(input_metadata: &ResolutionInputMetadata) -> String {
+    match (&input_metadata.version_id, &input_metadata.version_time) {
+        (None, None) => String::new(),
+        (version_id, version_time) => format!(
+            "versionId={}&versionTime={}",
+            version_id.as_deref().unwrap_or_default(),
+            version_time.as_deref().unwrap_or_default()
+        ),
+    }
+}
+
+/// DID resolver caching the successful resolutions of a wrapped resolver.
+///
+/// The cache is bypassed for a single resolution with the `noCache` resolution option, in which
+/// case the cached entry is refreshed, or for all resolutions with [with_bypass](Self::with_bypass).
+pub struct CachingResolver<T: DIDResolver> {
+    wrapped_resolver: T,
+    cache: Box<dyn ResolutionCache>,
+    ttl: Duration,
+    bypass: bool,
+}
+
+impl<T: DIDResolver> CachingResolver<T> {
+    /// Constructs a caching resolver with the default time to live.
+    pub fn new<C: ResolutionCache + 'static>(resolver: T, cache: C) -> Self {
+        Self {
+            wrapped_resolver: resolver,
+            cache: Box::new(cache),
+            ttl: DEFAULT_CACHE_TTL,
+            bypass: false,
+        }
+    }
+
+    /// Sets the time to live of entries cached by subsequent resolutions.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Sets whether the cache is bypassed for all resolutions.
+    pub fn with_bypass(mut self, bypass: bool) -> Self {
+        self.bypass = bypass;
+        self
+    }
+
+    /// Removes all cached resolutions of a DID.
+    pub fn invalidate(&self, did: &str) -> Result<(), CacheError> {
+        self.cache.invalidate(did)
+    }
+
+    /// Removes all cached resolutions.
+    pub fn clear(&self) -> Result<(), CacheError> {
+        self.cache.clear()
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl<T: DIDResolver + Sync + Send> DIDResolver for CachingResolver<T> {
+    async fn resolve(
+        &self,
+        did: &str,
+        input_metadata: &ResolutionInputMetadata,
+    ) -> (
+        ResolutionMetadata,
+        Option<Document>,
+        Option<DocumentMetadata>,
+    ) {
+        if self.bypass {
+            return self.wrapped_resolver.resolve(did, input_metadata).await;
+        }
+        let version = version_key(input_metadata);
+
+        // Return an unexpired cached resolution unless the noCache option is set.
+        if input_metadata.no_cache != Some(true) {
+            match self.cache.get(did, &version) {
+                Ok(Some(entry)) if !entry.is_expired(now()) => {
+                    return (
+                        entry.resolution_metadata,
+                        entry.document,
+                        entry.document_metadata,
+                    )
+                }
+                Ok(_) => (),
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+
+        let (res_meta, doc, doc_meta) = self.wrapped_resolver.resolve(did, input_metadata).await;
+
+        // Only successful resolutions are cached.
+        if res_meta.error.is_none() && doc.is_some() {
+            let entry = CacheEntry {
+                resolution_metadata: res_meta.clone(),
+                document: doc.clone(),
+                document_metadata: doc_meta.clone(),
+                expires: now().saturating_add(self.ttl.as_secs()),
+            };
+            if let Err(e) = self.cache.insert(did, &version, entry) {
+                eprintln!("{}", e);
+            }
+        }
+        (res_meta, doc, doc_meta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{TEST_ROOT_DOCUMENT, TEST_ROOT_DOCUMENT_METADATA};
+    use crate::resolver::Resolver;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    const ROOT_DID: &str = "did:ion:test:EiCClfEdkTv_aM3UnBBhlOV89LlGhpQAbfeZLFdFxVFkEg";
+
+    // Mock resolver counting the resolutions it performs.
+    struct CountingResolver {
+        count: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl DIDResolver for CountingResolver {
+        async fn resolve(
+            &self,
+            did: &str,
+            _input_metadata: &ResolutionInputMetadata,
+        ) -> (
+            ResolutionMetadata,
+            Option<Document>,
+            Option<DocumentMetadata>,
+        ) {
+            self.count.fetch_add(1, Ordering::SeqCst);
+            if did != ROOT_DID {
+                return (ResolutionMetadata::from_error("notFound"), None, None);
+            }
+            (
+                ResolutionMetadata::default(),
+                Some(serde_json::from_str(TEST_ROOT_DOCUMENT).unwrap()),
+                Some(serde_json::from_str(TEST_ROOT_DOCUMENT_METADATA).unwrap()),
+            )
+        }
+    }
+
+    fn counting_resolver() -> (CountingResolver, Arc<AtomicUsize>) {
+        let count = Arc::new(AtomicUsize::new(0));
+        (
+            CountingResolver {
+                count: Arc::clone(&count),
+            },
+            count,
+        )
+    }
+
+    fn entry(expires: u64) -> CacheEntry {
+        CacheEntry {
+            resolution_metadata: ResolutionMetadata::default(),
+            document: None,
+            document_metadata: None,
+            expires,
+        }
+    }
+
+    #[test]
+    fn test_caching_resolver() {
+        let (wrapped, count) = counting_resolver();
+        let resolver = Resolver::new(CachingResolver::new(wrapped, MemoryCache::default()));
+
+        // Repeated resolutions are served from the cache.
+        let (_, doc, _) = resolver.resolve_as_result(ROOT_DID).unwrap();
+        assert_eq!(doc.unwrap().id, ROOT_DID);
+        resolver.resolve_as_result(ROOT_DID).unwrap();
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+
+        // Versioned resolutions are cached separately.
+        let input_metadata = ResolutionInputMetadata {
+            version_time: Some("2022-10-20T11:30:05Z".to_string()),
+            ..Default::default()
+        };
+        resolver
+            .resolve_as_result_with_input(ROOT_DID, &input_metadata)
+            .unwrap();
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+
+        // The noCache option bypasses the cache.
+        let input_metadata = ResolutionInputMetadata {
+            no_cache: Some(true),
+            ..Default::default()
+        };
+        resolver
+            .resolve_as_result_with_input(ROOT_DID, &input_metadata)
+            .unwrap();
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+
+        // Failed resolutions are not cached.
+        let invalid_did = "did:ion:test:EiCClfEdkTv_aM3UnBBh10V89L1GhpQAbfeZLFdFxVFkEg";
+        assert!(resolver.resolve_as_result(invalid_did).is_err());
+        assert!(resolver.resolve_as_result(invalid_did).is_err());
+        assert_eq!(count.load(Ordering::SeqCst), 5);
+    }
+
+    #[test]
+    fn test_caching_resolver_ttl_and_bypass() {
+        let (wrapped, count) = counting_resolver();
+        let resolver = Resolver::new(
+            CachingResolver::new(wrapped, MemoryCache::default()).with_ttl(Duration::ZERO),
+        );
+        resolver.resolve_as_result(ROOT_DID).unwrap();
+        resolver.resolve_as_result(ROOT_DID).unwrap();
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+
+        let (wrapped, count) = counting_resolver();
+        let resolver =
+            Resolver::new(CachingResolver::new(wrapped, MemoryCache::default()).with_bypass(true));
+        resolver.resolve_as_result(ROOT_DID).unwrap();
+        resolver.resolve_as_result(ROOT_DID).unwrap();
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_invalidate() {
+        let (wrapped, count) = counting_resolver();
+        let caching_resolver = CachingResolver::new(wrapped, MemoryCache::default());
+        let input_metadata = ResolutionInputMetadata::default();
+        futures::executor::block_on(async {
+            caching_resolver.resolve(ROOT_DID, &input_metadata).await;
+            caching_resolver.invalidate(ROOT_DID).unwrap();
+            caching_resolver.resolve(ROOT_DID, &input_metadata).await;
+            caching_resolver.clear().unwrap();
+            caching_resolver.resolve(ROOT_DID, &input_metadata).await;
+        });
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_memory_cache_eviction() -> Result<(), CacheError> {
+        let cache = MemoryCache::new(2);
+        cache.insert("did:a", "", entry(1))?;
+        cache.insert("did:b", "", entry(2))?;
+        // Use the first entry so that the second is least recently used.
+        assert!(cache.get("did:a", "")?.is_some());
+        cache.insert("did:c", "", entry(3))?;
+        assert!(cache.get("did:a", "")?.is_some());
+        assert!(cache.get("did:b", "")?.is_none());
+        assert_eq!(cache.get("did:c", "")?.unwrap().expires, 3);
+        assert!(cache.get("did:c", "")?.unwrap().is_expired(3));
+        Ok(())
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_cache() -> Result<(), CacheError> {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("cache.sqlite");
+        let cache = SqliteCache::open(&path)?;
+        cache.insert(ROOT_DID, "", entry(10))?;
+        cache.insert(ROOT_DID, "versionId=1", entry(20))?;
+        cache.insert("did:other", "", entry(30))?;
+        assert_eq!(cache.get(ROOT_DID, "versionId=1")?.unwrap().expires, 20);
+
+        // Entries persist when the database is reopened.
+        let cache = SqliteCache::open(&path)?;
+        assert_eq!(cache.get(ROOT_DID, "")?.unwrap().expires, 10);
+
+        cache.invalidate(ROOT_DID)?;
+        assert!(cache.get(ROOT_DID, "")?.is_none());
+        assert!(cache.get(ROOT_DID, "versionId=1")?.is_none());
+        assert!(cache.get("did:other", "")?.is_some());
+        cache.clear()?;
+        assert!(cache.get("did:other", "")?.is_none());
+        Ok(())
+    }
+}
@@ -1,13 +1,14 @@
-use crate::display::PrettyDID;
+use crate::constraints::{attestation_payload, DelegationConstraints};
+use crate::display::{get_service_endpoint_string, PrettyDID};
 use crate::resolver::Resolver;
-use crate::utils::{canonicalize, decode, decode_verify, extract_keys, hash};
+use crate::utils::{decode, decode_verify, extract_keys};
 use crate::ROOT_EVENT_TIME_2378493;
-use chrono::{TimeZone, Utc};
+use chrono::{SecondsFormat, TimeZone, Utc};
 use serde::{Deserialize, Serialize};
 use ssi::did_resolve::Metadata;
 use ssi::{
     did::Document,
-    did_resolve::{DIDResolver, DocumentMetadata},
+    did_resolve::{DIDResolver, DocumentMetadata, ResolutionInputMetadata},
     one_or_many::OneOrMany,
 };
 use std::collections::HashMap;
@@ -32,6 +33,21 @@ pub enum ChainError {
     /// Failure to verify payload.
     #[error("Payload of JWT does not match reconstructed payload.")]
     InvalidPayload,
+    /// DID has been deactivated.
+    #[error("DID: {0} is deactivated.")]
+    DeactivatedDID(String),
+    /// Delegation constraints could not be parsed.
+    #[error("Invalid delegation constraints for DID: {0}.")]
+    InvalidConstraints(String),
+    /// DID attests downstream despite a constraint forbidding attestation.
+    #[error("DID: {0} is not permitted to attest to downstream DIDs.")]
+    AttestationNotPermitted(String),
+    /// Chain extends beyond the maximum path length permitted for a DID.
+    #[error("Maximum path length exceeded below DID: {0}.")]
+    PathLengthExceeded(String),
+    /// Service endpoint of a DID is outside the domains permitted upstream.
+    #[error("Service endpoint of DID: {0} is not within the domains permitted for DID: {1}.")]
+    DomainNotPermitted(String, String),
 }
 
 /// A chain of DIDs.
@@ -52,6 +68,8 @@ pub trait Chain {
     fn data(&self, did: &str) -> Option<&(Document, DocumentMetadata)>;
     /// Verify all of the proofs in the chain.
     fn verify_proofs(&self) -> Result<(), ChainError>;
+    /// Verify that the chain satisfies the delegation constraints in each attestation.
+    fn verify_constraints(&self) -> Result<(), ChainError>;
     /// Returns a vector of DID strings ordered by the level in the chain, starting at the root (level 0).
     fn level_vec(&self) -> &Vec<String>;
     /// Returns a vector of Documents and Document Metadata for each DID ordered by the level in the chain, starting at the root (level 0).
@@ -87,6 +105,44 @@ fn get_proof(doc_meta: &DocumentMetadata) -> Result<&str, ChainError> {
     }
 }
 
+/// Gets delegation constraints (if any) from DocumentMetadata.
+fn get_constraints(
+    did: &str,
+    doc_meta: &DocumentMetadata,
+) -> Result<Option<DelegationConstraints>, ChainError> {
+    let constraints = doc_meta
+        .property_set
+        .as_ref()
+        .and_then(|property_set| property_set.get("proof"))
+        .and_then(|proof| match proof {
+            Metadata::Map(proof) => proof.get("constraints"),
+            _ => None,
+        });
+    match constraints {
+        Some(Metadata::String(constraints)) => serde_json::from_str(constraints)
+            .map(Some)
+            .map_err(|_| ChainError::InvalidConstraints(did.to_owned())),
+        Some(_) => Err(ChainError::InvalidConstraints(did.to_owned())),
+        None => Ok(None),
+    }
+}
+
+/// Returns whether DID document metadata indicates a DID that has not been anchored, such as a
+/// long-form Sidetree DID resolved from its embedded initial state (`method.published` is
+/// `false`).
+pub fn is_unpublished(doc_meta: &DocumentMetadata) -> bool {
+    let method = doc_meta
+        .property_set
+        .as_ref()
+        .and_then(|property_set| property_set.get("method"));
+    match method {
+        Some(Metadata::Map(method)) => {
+            matches!(method.get("published"), Some(Metadata::Boolean(false)))
+        }
+        _ => false,
+    }
+}
+
 /// Max width in chars for printing
 const MAX_WIDTH: usize = 79;
 
@@ -98,6 +154,18 @@ pub struct DIDChain {
 
     // Vector to keep track of the level of each DID.
     level_vec: Vec<String>,
+
+    // DIDs whose upstream DID was recovered after attesting to them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    recovered_upstream: Vec<String>,
+
+    // DIDs resolved from unpublished (not yet anchored) state.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    unpublished: Vec<String>,
+
+    // Unix time at which the state of each DID was resolved, if not the current state.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    version_time: Option<u32>,
 }
 
 impl fmt::Display for DIDChain {
@@ -123,11 +191,28 @@ impl fmt::Display for DIDChain {
                     ),
                     box_width
                 )?;
+                if let Some(version_time) = self.version_time {
+                    writeln!(
+                        f,
+                        "{0:^1$}",
+                        format!(
+                            "🕑 As of: {0} 🕑",
+                            Utc.timestamp_opt(i64::from(version_time), 0).unwrap()
+                        ),
+                        box_width
+                    )?;
+                }
             }
             write!(f, "{}", PrettyDID::new(doc, i, MAX_WIDTH))?;
+            if self.is_unpublished(did) {
+                writeln!(f, "{0:^1$}", "⚠️ Unpublished ⚠️", box_width)?;
+            }
             let link_string = "⛓⛓⛓⛓";
-            if self.downstream(did).is_some() {
+            if let Some(ddid) = self.downstream(did) {
                 writeln!(f, "{0:^1$}", link_string, box_width)?;
+                if self.recovered_upstream.contains(ddid) {
+                    writeln!(f, "{0:^1$}", "⚠️ Recovered since attestation ⚠️", box_width)?;
+                }
                 writeln!(f, "{0:^1$}", link_string, box_width)?;
             }
         }
@@ -140,6 +225,35 @@ impl DIDChain {
     pub fn new<T: DIDResolver + Sync + Send>(
         did: &str,
         resolver: &Resolver<T>,
+    ) -> Result<Self, ChainError> {
+        Self::new_with_input(did, resolver, &ResolutionInputMetadata::default())
+    }
+
+    /// Constructs a chain from the state of each DID at a past Unix time.
+    pub fn new_at<T: DIDResolver + Sync + Send>(
+        did: &str,
+        version_time: u32,
+        resolver: &Resolver<T>,
+    ) -> Result<Self, ChainError> {
+        // All u32 Unix times are within the range of valid timestamps.
+        let input_metadata = ResolutionInputMetadata {
+            version_time: Some(
+                Utc.timestamp_opt(i64::from(version_time), 0)
+                    .unwrap()
+                    .to_rfc3339_opts(SecondsFormat::Secs, true),
+            ),
+            ..Default::default()
+        };
+        let mut chain = Self::new_with_input(did, resolver, &input_metadata)?;
+        chain.version_time = Some(version_time);
+        Ok(chain)
+    }
+
+    /// Constructs a chain, passing the given resolution input metadata when resolving each DID.
+    fn new_with_input<T: DIDResolver + Sync + Send>(
+        did: &str,
+        resolver: &Resolver<T>,
+        input_metadata: &ResolutionInputMetadata,
     ) -> Result<Self, ChainError> {
         // Construct an empty chain.
         let mut chain = DIDChain::empty();
@@ -150,9 +264,19 @@ impl DIDChain {
         // Loop up the DID chain until the root is reached or an error occurs.
         loop {
             // Resolve the current DID.
-            let resolved = resolver.resolve_as_result(&ddid);
+            let resolved = resolver.resolve_as_result_with_input(&ddid, input_metadata);
 
             if let Ok((_, Some(ddoc), Some(ddoc_meta))) = resolved {
+                // A deactivated DID cannot be part of a valid chain.
+                if let Some(true) = ddoc_meta.deactivated {
+                    return Err(ChainError::DeactivatedDID(ddid));
+                }
+
+                // Mark DIDs resolved from state that has not been anchored.
+                if is_unpublished(&ddoc_meta) {
+                    chain.unpublished.push(ddoc.id.to_owned());
+                }
+
                 // Clone the controller information before moving ddoc into the chain.
                 let controller = ddoc.controller.to_owned();
 
@@ -187,9 +311,39 @@ impl DIDChain {
         Self {
             did_map: HashMap::<String, (Document, DocumentMetadata)>::new(),
             level_vec: Vec::<String>::new(),
+            recovered_upstream: Vec::<String>::new(),
+            unpublished: Vec::<String>::new(),
+            version_time: None,
         }
     }
 
+    /// Flags the link to a DID whose upstream DID was recovered after attesting to it.
+    pub fn flag_recovered_upstream(&mut self, did: &str) {
+        if !self.recovered_upstream.iter().any(|x| x == did) {
+            self.recovered_upstream.push(did.to_owned());
+        }
+    }
+
+    /// Returns the DIDs whose upstream DID was recovered after attesting to them.
+    pub fn recovered_upstream(&self) -> &Vec<String> {
+        &self.recovered_upstream
+    }
+
+    /// Returns the DIDs in the chain resolved from unpublished (not yet anchored) state.
+    pub fn unpublished(&self) -> &Vec<String> {
+        &self.unpublished
+    }
+
+    /// Returns whether a DID in the chain was resolved from unpublished state.
+    pub fn is_unpublished(&self, did: &str) -> bool {
+        self.unpublished.iter().any(|x| x == did)
+    }
+
+    /// Returns the Unix time at which the chain was resolved, if not resolved at the current time.
+    pub fn version_time(&self) -> Option<u32> {
+        self.version_time
+    }
+
     /// Prepend a DID to the chain.
     fn prepend(&mut self, tuple: (Document, DocumentMetadata)) {
         let (doc, doc_meta) = tuple;
@@ -242,9 +396,13 @@ impl Chain for DIDChain {
             // Extract the controller proof from the document metadata.
             let proof = get_proof(did_doc_meta)?;
 
+            // Extract any delegation constraints covered by the proof.
+            let constraints = get_constraints(did, did_doc_meta)?;
+
             // TODO: consider whether to use detached JWS instead making verification one step.
             // 1. Reconstruct the actual payload.
-            let actual_payload = hash(&canonicalize(&did_doc).unwrap());
+            let actual_payload = attestation_payload(did_doc, constraints.as_ref())
+                .map_err(|_| ChainError::InvalidPayload)?;
 
             // Decode the payload from the proof
             let decoded_payload = decode(proof);
@@ -283,6 +441,39 @@ impl Chain for DIDChain {
         Ok(())
     }
 
+    fn verify_constraints(&self) -> Result<(), ChainError> {
+        // The root is not attested so carries no constraints.
+        for (level, did) in self.level_vec.iter().enumerate().skip(1) {
+            let (_, did_doc_meta) = self.data(did).unwrap();
+            let constraints = match get_constraints(did, did_doc_meta)? {
+                Some(constraints) => constraints,
+                None => continue,
+            };
+
+            // Number of DIDs in the chain below the attested DID.
+            let depth = self.len() - 1 - level;
+            if !constraints.permits_depth(depth) {
+                return Err(match constraints.permits_attestation() {
+                    true => ChainError::PathLengthExceeded(did.to_owned()),
+                    false => ChainError::AttestationNotPermitted(did.to_owned()),
+                });
+            }
+
+            // Domain constraints apply to the attested DID and every DID below it.
+            for ddid in &self.level_vec[level..] {
+                let (ddoc, _) = self.data(ddid).unwrap();
+                let endpoint = get_service_endpoint_string(ddoc);
+                if !constraints.permits_endpoint(endpoint.as_deref()) {
+                    return Err(ChainError::DomainNotPermitted(
+                        ddid.to_owned(),
+                        did.to_owned(),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Returns the DID immediately upstream from the given DID in the chain.
     fn upstream(&self, did: &str) -> Option<&String> {
         let index = self.level_vec.iter().position(|x| x == did).unwrap();
@@ -320,6 +511,7 @@ mod tests {
         TEST_ROOT_PLUS_2_DOCUMENT_METADATA, TEST_TRUSTCHAIN_DOCUMENT,
         TEST_TRUSTCHAIN_DOCUMENT_METADATA,
     };
+    use crate::utils::canonicalize;
 
     const ROOT_SIGNING_KEYS: &str = r##"
     [
@@ -479,6 +671,257 @@ mod tests {
         assert!(target.verify_proofs().is_err());
     }
 
+    // Helper function adds delegation constraints to the proof of a DID in a chain.
+    fn add_constraints(chain: &mut DIDChain, did: &str, constraints: &DelegationConstraints) {
+        let (_, doc_meta) = chain.did_map.get_mut(did).unwrap();
+        let property_set = doc_meta.property_set.as_mut().unwrap();
+        if let Some(Metadata::Map(proof)) = property_set.get_mut("proof") {
+            proof.insert(
+                "constraints".to_string(),
+                Metadata::String(serde_json::to_string(constraints).unwrap()),
+            );
+        } else {
+            panic!()
+        }
+    }
+
+    // Helper function appends a DID below the leaf of a chain with a copy of the leaf's data.
+    fn extend_chain(chain: &mut DIDChain, did: &str) {
+        let (mut doc, doc_meta) = chain.data(chain.leaf()).unwrap().clone();
+        doc.id = did.to_string();
+        chain.did_map.insert(did.to_string(), (doc, doc_meta));
+        chain.level_vec.push(did.to_string());
+    }
+
+    #[test]
+    fn test_verify_proofs_with_constraints() {
+        // Constraints not covered by the attestation signature invalidate the proof.
+        let mut target = test_chain();
+        let level1_did = "did:ion:test:EiBVpjUxXeSRJpvj2TewlX9zNF3GKMCKWwGmKBZqF6pk_A";
+        let constraints = DelegationConstraints {
+            can_attest: Some(true),
+            ..Default::default()
+        };
+        add_constraints(&mut target, level1_did, &constraints);
+        assert_eq!(target.verify_proofs(), Err(ChainError::InvalidPayload));
+    }
+
+    #[test]
+    fn test_verify_constraints() {
+        let level1_did = "did:ion:test:EiBVpjUxXeSRJpvj2TewlX9zNF3GKMCKWwGmKBZqF6pk_A";
+        let level2_did = "did:ion:test:EiAtHHKFJWAk5AsM3tgCut3OiBY4ekHTf66AAjoysXL65Q";
+
+        // No constraints present.
+        assert!(test_chain().verify_constraints().is_ok());
+
+        // Intermediate DID that may not attest.
+        let leaf_only = DelegationConstraints {
+            can_attest: Some(false),
+            ..Default::default()
+        };
+        let mut target = test_chain();
+        add_constraints(&mut target, level1_did, &leaf_only);
+        assert_eq!(
+            target.verify_constraints(),
+            Err(ChainError::AttestationNotPermitted(level1_did.to_string()))
+        );
+
+        // Leaf DID that may not attest.
+        let mut target = test_chain();
+        add_constraints(&mut target, level2_did, &leaf_only);
+        assert!(target.verify_constraints().is_ok());
+
+        // Intermediate DID that may only attest to leaf DIDs.
+        let intermediate = DelegationConstraints {
+            max_path_length: Some(0),
+            ..Default::default()
+        };
+        let mut target = test_chain();
+        add_constraints(&mut target, level1_did, &intermediate);
+        assert!(target.verify_constraints().is_ok());
+        extend_chain(&mut target, "did:example:level3");
+        assert_eq!(
+            target.verify_constraints(),
+            Err(ChainError::PathLengthExceeded(level1_did.to_string()))
+        );
+
+        // Domain constraints.
+        let permitted = DelegationConstraints {
+            permitted_domains: Some(vec!["identity.foundation".to_string()]),
+            ..Default::default()
+        };
+        let mut target = test_chain();
+        add_constraints(&mut target, level1_did, &permitted);
+        assert!(target.verify_constraints().is_ok());
+
+        let not_permitted = DelegationConstraints {
+            permitted_domains: Some(vec!["example.com".to_string()]),
+            ..Default::default()
+        };
+        let mut target = test_chain();
+        add_constraints(&mut target, level1_did, &not_permitted);
+        assert_eq!(
+            target.verify_constraints(),
+            Err(ChainError::DomainNotPermitted(
+                level1_did.to_string(),
+                level1_did.to_string()
+            ))
+        );
+    }
+
+    // Mock resolver returning documents from the test chain, optionally with one deactivated or
+    // unpublished.
+    struct TestResolver {
+        chain: DIDChain,
+        deactivated: Option<String>,
+        unpublished: Option<String>,
+        // A DID and the time before which it does not exist.
+        created: Option<(String, String)>,
+    }
+
+    #[async_trait::async_trait]
+    impl DIDResolver for TestResolver {
+        async fn resolve(
+            &self,
+            did: &str,
+            input_metadata: &ResolutionInputMetadata,
+        ) -> (
+            ssi::did_resolve::ResolutionMetadata,
+            Option<Document>,
+            Option<DocumentMetadata>,
+        ) {
+            if let (Some((created_did, created_time)), Some(version_time)) =
+                (&self.created, &input_metadata.version_time)
+            {
+                if created_did == did && version_time < created_time {
+                    return (
+                        ssi::did_resolve::ResolutionMetadata::from_error("notFound"),
+                        None,
+                        None,
+                    );
+                }
+            }
+            match self.chain.data(did) {
+                Some((doc, doc_meta)) => {
+                    let mut doc_meta = doc_meta.clone();
+                    if self.deactivated.as_deref() == Some(did) {
+                        doc_meta.deactivated = Some(true);
+                    }
+                    if self.unpublished.as_deref() == Some(did) {
+                        let mut method = HashMap::new();
+                        method.insert("published".to_string(), Metadata::Boolean(false));
+                        doc_meta
+                            .property_set
+                            .get_or_insert_with(HashMap::new)
+                            .insert("method".to_string(), Metadata::Map(method));
+                    }
+                    (
+                        ssi::did_resolve::ResolutionMetadata::default(),
+                        Some(doc.clone()),
+                        Some(doc_meta),
+                    )
+                }
+                None => (
+                    ssi::did_resolve::ResolutionMetadata::from_error("notFound"),
+                    None,
+                    None,
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn test_new() {
+        let leaf_did = "did:ion:test:EiAtHHKFJWAk5AsM3tgCut3OiBY4ekHTf66AAjoysXL65Q";
+        let resolver = Resolver::new(TestResolver {
+            chain: test_chain(),
+            deactivated: None,
+            unpublished: None,
+            created: None,
+        });
+        let target = DIDChain::new(leaf_did, &resolver).unwrap();
+        assert_eq!(target.level_vec(), test_chain().level_vec());
+        assert!(target.recovered_upstream().is_empty());
+    }
+
+    #[test]
+    fn test_new_with_deactivated_did() {
+        let leaf_did = "did:ion:test:EiAtHHKFJWAk5AsM3tgCut3OiBY4ekHTf66AAjoysXL65Q";
+        let level1_did = "did:ion:test:EiBVpjUxXeSRJpvj2TewlX9zNF3GKMCKWwGmKBZqF6pk_A";
+        let resolver = Resolver::new(TestResolver {
+            chain: test_chain(),
+            deactivated: Some(level1_did.to_string()),
+            unpublished: None,
+            created: None,
+        });
+        let result = DIDChain::new(leaf_did, &resolver);
+        assert!(matches!(result, Err(ChainError::DeactivatedDID(did)) if did == level1_did));
+    }
+
+    #[test]
+    fn test_new_with_unpublished_did() {
+        let leaf_did = "did:ion:test:EiAtHHKFJWAk5AsM3tgCut3OiBY4ekHTf66AAjoysXL65Q";
+        let resolver = Resolver::new(TestResolver {
+            chain: test_chain(),
+            deactivated: None,
+            unpublished: Some(leaf_did.to_string()),
+            created: None,
+        });
+        let target = DIDChain::new(leaf_did, &resolver).unwrap();
+        assert_eq!(target.unpublished(), &vec![leaf_did.to_string()]);
+        assert!(target.is_unpublished(leaf_did));
+        assert!(!target.is_unpublished(target.root()));
+        assert!(target.to_string().contains("Unpublished"));
+
+        // Check the marker survives serialization.
+        let target: DIDChain =
+            serde_json::from_str(&serde_json::to_string(&target).unwrap()).unwrap();
+        assert!(target.is_unpublished(leaf_did));
+        assert!(test_chain().unpublished().is_empty());
+    }
+
+    #[test]
+    fn test_new_at() {
+        let leaf_did = "did:ion:test:EiAtHHKFJWAk5AsM3tgCut3OiBY4ekHTf66AAjoysXL65Q";
+        let resolver = Resolver::new(TestResolver {
+            chain: test_chain(),
+            deactivated: None,
+            unpublished: None,
+            created: Some((leaf_did.to_string(), "2022-10-20T00:00:00Z".to_string())),
+        });
+
+        // Before the leaf DID was created the chain cannot be built.
+        let result = DIDChain::new_at(leaf_did, 1666137600, &resolver);
+        assert!(matches!(result, Err(ChainError::ResolutionFailure(did)) if did == leaf_did));
+
+        // Afterwards the chain is built and records the time at which it was resolved.
+        let target = DIDChain::new_at(leaf_did, 1666310400, &resolver).unwrap();
+        assert_eq!(target.level_vec(), test_chain().level_vec());
+        assert_eq!(target.version_time(), Some(1666310400));
+        assert!(target
+            .to_string()
+            .contains("As of: 2022-10-21 00:00:00 UTC"));
+        assert_eq!(
+            DIDChain::new(leaf_did, &resolver).unwrap().version_time(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_flag_recovered_upstream() {
+        let leaf_did = "did:ion:test:EiAtHHKFJWAk5AsM3tgCut3OiBY4ekHTf66AAjoysXL65Q";
+        let mut target = test_chain();
+        target.flag_recovered_upstream(leaf_did);
+        target.flag_recovered_upstream(leaf_did);
+        assert_eq!(target.recovered_upstream(), &vec![leaf_did.to_string()]);
+        assert!(target.to_string().contains("Recovered since attestation"));
+
+        // Check the flag survives serialization.
+        let target: DIDChain =
+            serde_json::from_str(&serde_json::to_string(&target).unwrap()).unwrap();
+        assert_eq!(target.recovered_upstream(), &vec![leaf_did.to_string()]);
+    }
+
     #[test]
     fn test_level() {
         // Test the level returned for each node in the test chain
@@ -0,0 +1,171 @@
+//! Delegation constraints carried in Trustchain attestations.
+use crate::utils::{canonicalize, hash};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use ssi::did::Document;
+use url::Url;
+
+/// Constraints placed by an attestor on a downstream DID, analogous to the X.509
+/// `basicConstraints` and `nameConstraints` certificate extensions.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DelegationConstraints {
+    /// Maximum number of intermediate DIDs that may follow the attested DID in a chain.
+    /// A value of zero permits the attested DID to attest only to leaf DIDs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_path_length: Option<usize>,
+    /// Whether the attested DID may itself attest to downstream DIDs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub can_attest: Option<bool>,
+    /// Domains permitted for the Trustchain service endpoint of the attested DID and of every DID
+    /// downstream of it. Subdomains of a permitted domain are also permitted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub permitted_domains: Option<Vec<String>>,
+}
+
+impl DelegationConstraints {
+    /// Returns whether no constraints are set.
+    pub fn is_empty(&self) -> bool {
+        self.max_path_length.is_none()
+            && self.can_attest.is_none()
+            && self.permitted_domains.is_none()
+    }
+
+    /// Returns whether the attested DID may attest to downstream DIDs.
+    pub fn permits_attestation(&self) -> bool {
+        !matches!(self.can_attest, Some(false))
+    }
+
+    /// Returns whether a chain may continue for `depth` further DIDs below the attested DID.
+    pub fn permits_depth(&self, depth: usize) -> bool {
+        if depth > 0 && !self.permits_attestation() {
+            return false;
+        }
+        match self.max_path_length {
+            // The last DID in the chain is a leaf, so is not counted as an intermediate.
+            Some(max_path_length) => depth <= max_path_length.saturating_add(1),
+            None => true,
+        }
+    }
+
+    /// Returns whether a service endpoint URI is within the permitted domains.
+    pub fn permits_endpoint(&self, endpoint: Option<&str>) -> bool {
+        let domains = match &self.permitted_domains {
+            Some(domains) => domains,
+            None => return true,
+        };
+        // If domains are constrained, an endpoint must be present with a host.
+        let host = match endpoint.and_then(|endpoint| Url::parse(endpoint).ok()) {
+            Some(url) => match url.host_str() {
+                Some(host) => host.to_lowercase(),
+                None => return false,
+            },
+            None => return false,
+        };
+        domains.iter().any(|domain| {
+            let domain = domain.to_lowercase();
+            host == domain || host.ends_with(&format!(".{}", domain))
+        })
+    }
+}
+
+/// Returns the payload signed in an attestation to a DID document with controller.
+///
+/// Without constraints the payload is the hash of the canonicalized document, otherwise the
+/// constraints are canonicalized together with the document so that they are covered by the
+/// attestor's signature.
+pub fn attestation_payload(
+    doc: &Document,
+    constraints: Option<&DelegationConstraints>,
+) -> Result<String, serde_json::Error> {
+    match constraints.filter(|constraints| !constraints.is_empty()) {
+        Some(constraints) => Ok(hash(&canonicalize(&json!({
+            "constraints": constraints,
+            "document": doc
+        }))?)),
+        None => Ok(hash(&canonicalize(doc)?)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{TEST_ROOT_PLUS_1_DOCUMENT, TEST_ROOT_PLUS_1_JWT};
+    use crate::utils::decode;
+
+    #[test]
+    fn test_serialize_constraints() -> Result<(), Box<dyn std::error::Error>> {
+        let constraints = DelegationConstraints {
+            max_path_length: Some(0),
+            can_attest: None,
+            permitted_domains: Some(vec!["example.com".to_string()]),
+        };
+        assert_eq!(
+            canonicalize(&constraints)?,
+            r#"{"maxPathLength":0,"permittedDomains":["example.com"]}"#
+        );
+        let empty: DelegationConstraints = serde_json::from_str("{}")?;
+        assert!(empty.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_permits_depth() {
+        let unconstrained = DelegationConstraints::default();
+        assert!(unconstrained.permits_depth(10));
+
+        let leaf_only = DelegationConstraints {
+            can_attest: Some(false),
+            ..Default::default()
+        };
+        assert!(leaf_only.permits_depth(0));
+        assert!(!leaf_only.permits_depth(1));
+
+        let intermediate = DelegationConstraints {
+            max_path_length: Some(0),
+            ..Default::default()
+        };
+        assert!(intermediate.permits_depth(1));
+        assert!(!intermediate.permits_depth(2));
+
+        // The maximum path length is taken from attestations, so may be any value.
+        let unbounded: DelegationConstraints =
+            serde_json::from_value(json!({ "maxPathLength": usize::MAX })).unwrap();
+        assert!(unbounded.permits_depth(usize::MAX));
+    }
+
+    #[test]
+    fn test_permits_endpoint() {
+        let constraints = DelegationConstraints {
+            permitted_domains: Some(vec!["identity.foundation".to_string()]),
+            ..Default::default()
+        };
+        assert!(constraints.permits_endpoint(Some("https://identity.foundation/ion")));
+        assert!(constraints.permits_endpoint(Some("https://www.identity.foundation")));
+        assert!(!constraints.permits_endpoint(Some("https://evilidentity.foundation")));
+        assert!(!constraints.permits_endpoint(Some("https://example.com")));
+        assert!(!constraints.permits_endpoint(None));
+        assert!(DelegationConstraints::default().permits_endpoint(None));
+    }
+
+    #[test]
+    fn test_attestation_payload() -> Result<(), Box<dyn std::error::Error>> {
+        let doc: Document = serde_json::from_str(TEST_ROOT_PLUS_1_DOCUMENT)?;
+
+        // Without constraints the payload matches existing attestations.
+        let expected = decode(TEST_ROOT_PLUS_1_JWT)?;
+        assert_eq!(attestation_payload(&doc, None)?, expected);
+        assert_eq!(
+            attestation_payload(&doc, Some(&DelegationConstraints::default()))?,
+            expected
+        );
+
+        // Constraints change the payload.
+        let constraints = DelegationConstraints {
+            can_attest: Some(false),
+            ..Default::default()
+        };
+        assert_ne!(attestation_payload(&doc, Some(&constraints))?, expected);
+        Ok(())
+    }
+}
@@ -0,0 +1,360 @@
+//! JSON-LD contexts used by Trustchain credentials that are not built into the SSI library.
+//!
+//! The SSI library loads only a fixed set of contexts, and strictly checks the `@context` of a
+//! credential when generating and verifying linked-data proofs. Other contexts are loaded by a
+//! [`ContextLoader`], from a bundled set and from the `contexts` directory of the Trustchain data
+//! directory, and inlined as context objects before proofs are generated or verified, so that
+//! issuance and verification work offline.
+use crate::exchange::{
+    PRESENTATION_SUBMISSION_V1_CONTEXT, PRESENTATION_SUBMISSION_V1_CONTEXT_DOCUMENT,
+};
+use crate::status::{STATUS_LIST_2021_V1_CONTEXT, STATUS_LIST_2021_V1_CONTEXT_DOCUMENT};
+use crate::TRUSTCHAIN_DATA;
+use serde_json::Value;
+use ssi::one_or_many::OneOrMany;
+use ssi::vc::{Context, Contexts, Credential, CredentialOrJWT, Presentation, URI};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Directory of JSON-LD context documents in the Trustchain data directory.
+pub const CONTEXTS_DIR: &str = "contexts";
+
+/// Index of a contexts directory, mapping each context URL to the file name of its document.
+pub const CONTEXTS_INDEX: &str = "contexts.json";
+
+/// Context documents bundled with Trustchain, by URL.
+const BUNDLED_CONTEXTS: &[(&str, &str)] = &[
+    (
+        STATUS_LIST_2021_V1_CONTEXT,
+        STATUS_LIST_2021_V1_CONTEXT_DOCUMENT,
+    ),
+    (
+        PRESENTATION_SUBMISSION_V1_CONTEXT,
+        PRESENTATION_SUBMISSION_V1_CONTEXT_DOCUMENT,
+    ),
+];
+
+/// An error relating to the loading of JSON-LD contexts.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ContextError {
+    /// Failed to read a file of a contexts directory.
+    #[error("Failed to read {0}: {1}")]
+    FailedToRead(String, String),
+    /// Invalid index of a contexts directory.
+    #[error("Invalid contexts index {0}: {1}")]
+    InvalidIndex(String, String),
+    /// Document is not a JSON-LD context document.
+    #[error("Invalid context document for: {0}")]
+    InvalidDocument(String),
+    /// Failed to save a context document.
+    #[error("Failed to save context document: {0}")]
+    Storage(String),
+    /// No TRUSTCHAIN_DATA environment variable.
+    #[error("No TRUSTCHAIN_DATA environment variable.")]
+    TrustchainDataNotPresent,
+}
+
+/// Loader of JSON-LD context documents that are not built into the SSI library.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContextLoader {
+    contexts: HashMap<String, HashMap<String, Value>>,
+}
+
+impl Default for ContextLoader {
+    /// Makes a loader of the bundled contexts and of the contexts in the Trustchain data
+    /// directory, if present. Unreadable or invalid context documents are ignored.
+    fn default() -> Self {
+        let mut loader = Self::bundled();
+        if let Ok(path) = trustchain_contexts_dir() {
+            let _ = loader.load_dir_lenient(&path);
+        }
+        loader
+    }
+}
+
+/// Returns the path of the contexts directory in the Trustchain data directory.
+pub fn trustchain_contexts_dir() -> Result<PathBuf, ContextError> {
+    let path =
+        std::env::var(TRUSTCHAIN_DATA).map_err(|_| ContextError::TrustchainDataNotPresent)?;
+    Ok(Path::new(path.as_str()).join(CONTEXTS_DIR))
+}
+
+/// Returns the `@context` object of a context document.
+fn parse_document(url: &str, document: &Value) -> Result<HashMap<String, Value>, ContextError> {
+    document
+        .get("@context")
+        .and_then(|context| serde_json::from_value(context.clone()).ok())
+        .ok_or_else(|| ContextError::InvalidDocument(url.to_owned()))
+}
+
+/// Reads the index of a contexts directory, or an empty index if there is none.
+fn read_index(dir: &Path) -> Result<BTreeMap<String, String>, ContextError> {
+    let path = dir.join(CONTEXTS_INDEX);
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let file = File::open(&path)
+        .map_err(|e| ContextError::FailedToRead(path.display().to_string(), e.to_string()))?;
+    serde_json::from_reader(file)
+        .map_err(|e| ContextError::InvalidIndex(path.display().to_string(), e.to_string()))
+}
+
+/// Reads a context document from a file.
+fn read_document(url: &str, path: &Path) -> Result<HashMap<String, Value>, ContextError> {
+    let file = File::open(path)
+        .map_err(|e| ContextError::FailedToRead(path.display().to_string(), e.to_string()))?;
+    let document: Value =
+        serde_json::from_reader(file).map_err(|_| ContextError::InvalidDocument(url.to_owned()))?;
+    parse_document(url, &document)
+}
+
+impl ContextLoader {
+    /// Makes a loader of the bundled contexts only.
+    pub fn bundled() -> Self {
+        let contexts = BUNDLED_CONTEXTS
+            .iter()
+            .map(|(url, document)| {
+                let document = serde_json::from_str(document).unwrap();
+                (url.to_string(), parse_document(url, &document).unwrap())
+            })
+            .collect();
+        Self { contexts }
+    }
+
+    /// Makes a loader of the bundled contexts and of the contexts in the given directory, which
+    /// has an index file mapping each context URL to the file name of its document.
+    pub fn from_dir<P: AsRef<Path>>(dir: P) -> Result<Self, ContextError> {
+        let mut loader = Self::bundled();
+        loader.load_dir(dir.as_ref())?;
+        Ok(loader)
+    }
+
+    /// Loads the contexts of a directory, failing on any invalid document.
+    fn load_dir(&mut self, dir: &Path) -> Result<(), ContextError> {
+        for (url, file_name) in read_index(dir)? {
+            let context = read_document(&url, &dir.join(file_name))?;
+            self.contexts.insert(url, context);
+        }
+        Ok(())
+    }
+
+    /// Loads the contexts of a directory, skipping any invalid document.
+    fn load_dir_lenient(&mut self, dir: &Path) -> Result<(), ContextError> {
+        for (url, file_name) in read_index(dir)? {
+            if let Ok(context) = read_document(&url, &dir.join(file_name)) {
+                self.contexts.insert(url, context);
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds a context document to the loader.
+    pub fn insert(&mut self, url: &str, document: &Value) -> Result<(), ContextError> {
+        let context = parse_document(url, document)?;
+        self.contexts.insert(url.to_owned(), context);
+        Ok(())
+    }
+
+    /// Returns the `@context` object of a loaded context document, if any.
+    pub fn get(&self, url: &str) -> Option<&HashMap<String, Value>> {
+        self.contexts.get(url)
+    }
+
+    /// Returns the URLs of the loaded contexts, in order.
+    pub fn urls(&self) -> Vec<&str> {
+        let mut urls: Vec<&str> = self.contexts.keys().map(String::as_str).collect();
+        urls.sort_unstable();
+        urls
+    }
+
+    /// Replaces each loaded context URL with its context object.
+    fn inline(&self, contexts: &Contexts) -> Contexts {
+        let inline_context = |context: &Context| match context {
+            Context::URI(URI::String(url)) => self
+                .get(url)
+                .cloned()
+                .map(Context::Object)
+                .unwrap_or_else(|| context.clone()),
+            context => context.clone(),
+        };
+        match contexts {
+            Contexts::One(context) => Contexts::One(inline_context(context)),
+            Contexts::Many(contexts) => {
+                Contexts::Many(contexts.iter().map(inline_context).collect())
+            }
+        }
+    }
+
+    /// Returns a copy of the credential with each loaded context URL replaced by the context
+    /// object, for the generation and verification of linked-data proofs. The RDF dataset of the
+    /// credential, and so its proof, is unchanged.
+    pub fn inline_contexts(&self, credential: &Credential) -> Credential {
+        let mut credential = credential.clone();
+        credential.context = self.inline(&credential.context);
+        credential
+    }
+
+    /// Returns a copy of the presentation with contexts inlined as by
+    /// [`ContextLoader::inline_contexts`], including in the credentials it contains.
+    pub fn inline_presentation_contexts(&self, presentation: &Presentation) -> Presentation {
+        let mut presentation = presentation.clone();
+        presentation.context = self.inline(&presentation.context);
+        let inline_credential = |credential: CredentialOrJWT| match credential {
+            CredentialOrJWT::Credential(credential) => {
+                CredentialOrJWT::Credential(self.inline_contexts(&credential))
+            }
+            jwt => jwt,
+        };
+        presentation.verifiable_credential =
+            presentation
+                .verifiable_credential
+                .map(|credentials| match credentials {
+                    OneOrMany::One(credential) => OneOrMany::One(inline_credential(credential)),
+                    OneOrMany::Many(credentials) => {
+                        OneOrMany::Many(credentials.into_iter().map(inline_credential).collect())
+                    }
+                });
+        presentation
+    }
+}
+
+/// Saves a context document to the given contexts directory, adding it to the directory's index
+/// under the given file name. Returns the path of the saved document.
+pub fn save_context<P: AsRef<Path>>(
+    dir: P,
+    url: &str,
+    file_name: &str,
+    document: &Value,
+) -> Result<PathBuf, ContextError> {
+    let dir = dir.as_ref();
+    parse_document(url, document)?;
+    if Path::new(file_name).file_name() != Some(file_name.as_ref()) || file_name == CONTEXTS_INDEX {
+        return Err(ContextError::Storage(format!(
+            "invalid file name: {}",
+            file_name
+        )));
+    }
+    std::fs::create_dir_all(dir).map_err(|e| ContextError::Storage(e.to_string()))?;
+    let path = dir.join(file_name);
+    let file = File::create(&path).map_err(|e| ContextError::Storage(e.to_string()))?;
+    serde_json::to_writer_pretty(file, document)
+        .map_err(|e| ContextError::Storage(e.to_string()))?;
+
+    let mut index = read_index(dir)?;
+    index.insert(url.to_owned(), file_name.to_owned());
+    let file =
+        File::create(dir.join(CONTEXTS_INDEX)).map_err(|e| ContextError::Storage(e.to_string()))?;
+    serde_json::to_writer_pretty(file, &index).map_err(|e| ContextError::Storage(e.to_string()))?;
+    Ok(path)
+}
+
+/// Returns the `@context` object of a context document known to Trustchain (bundled or in the
+/// Trustchain data directory), if any.
+pub fn context_object(url: &str) -> Option<HashMap<String, Value>> {
+    ContextLoader::default().get(url).cloned()
+}
+
+/// Returns a copy of the credential with contexts inlined by the default [`ContextLoader`].
+pub fn inline_contexts(credential: &Credential) -> Credential {
+    ContextLoader::default().inline_contexts(credential)
+}
+
+/// Returns a copy of the presentation with contexts inlined by the default [`ContextLoader`].
+pub fn inline_presentation_contexts(presentation: &Presentation) -> Presentation {
+    ContextLoader::default().inline_presentation_contexts(presentation)
+}
+
+/// Returns the contexts with a context URL appended, unless already present.
+fn with_context(contexts: &Contexts, url: &str) -> Contexts {
+    if contexts.contains_uri(url) {
+        return contexts.clone();
+    }
+    let context = Context::URI(URI::String(url.to_owned()));
+    match contexts {
+        Contexts::One(first) => Contexts::Many(vec![first.clone(), context]),
+        Contexts::Many(contexts) => {
+            let mut contexts = contexts.clone();
+            contexts.push(context);
+            Contexts::Many(contexts)
+        }
+    }
+}
+
+/// Adds a context URL to the credential, unless already present.
+pub fn add_context(credential: &mut Credential, url: &str) {
+    credential.context = with_context(&credential.context, url);
+}
+
+/// Adds a context URL to the presentation, unless already present.
+pub fn add_presentation_context(presentation: &mut Presentation, url: &str) {
+    presentation.context = with_context(&presentation.context, url);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::TEST_CREDENTIAL;
+    use serde_json::json;
+
+    #[test]
+    fn test_inline_contexts() {
+        let mut credential: Credential = serde_json::from_str(TEST_CREDENTIAL).unwrap();
+        add_context(&mut credential, STATUS_LIST_2021_V1_CONTEXT);
+        add_context(&mut credential, STATUS_LIST_2021_V1_CONTEXT);
+        assert!(credential.context.contains_uri(STATUS_LIST_2021_V1_CONTEXT));
+        let contexts = serde_json::to_value(&credential.context).unwrap();
+        assert_eq!(contexts.as_array().unwrap().len(), 4);
+
+        // Only the known context is inlined.
+        let inlined = serde_json::to_value(&inline_contexts(&credential).context).unwrap();
+        let inlined = inlined.as_array().unwrap();
+        assert_eq!(&inlined[..3], &contexts.as_array().unwrap()[..3]);
+        assert!(inlined[3]["StatusList2021Entry"].is_object());
+    }
+
+    #[test]
+    fn test_context_loader_from_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let url = "https://example.com/contexts/degree/v1";
+        let document = json!({
+            "@context": {"DegreeCredential": "https://example.com/vocab#DegreeCredential"}
+        });
+        assert_eq!(
+            ContextLoader::from_dir(dir.path()),
+            Ok(ContextLoader::bundled())
+        );
+
+        // Saved contexts are loaded with the bundled ones.
+        save_context(dir.path(), url, "degree-v1.jsonld", &document).unwrap();
+        let loader = ContextLoader::from_dir(dir.path()).unwrap();
+        assert_eq!(
+            loader.urls(),
+            vec![
+                url,
+                PRESENTATION_SUBMISSION_V1_CONTEXT,
+                STATUS_LIST_2021_V1_CONTEXT
+            ]
+        );
+        assert!(loader.get(url).unwrap()["DegreeCredential"].is_string());
+
+        let mut credential: Credential = serde_json::from_str(TEST_CREDENTIAL).unwrap();
+        add_context(&mut credential, url);
+        let inlined = serde_json::to_value(&loader.inline_contexts(&credential).context).unwrap();
+        assert_eq!(inlined[3], document["@context"]);
+
+        // Invalid documents and file names are rejected.
+        assert_eq!(
+            save_context(dir.path(), url, "invalid.jsonld", &json!({})),
+            Err(ContextError::InvalidDocument(url.to_owned()))
+        );
+        assert!(save_context(dir.path(), url, "../degree.jsonld", &document).is_err());
+        assert!(save_context(dir.path(), url, CONTEXTS_INDEX, &document).is_err());
+        std::fs::write(dir.path().join("degree-v1.jsonld"), "[]").unwrap();
+        assert_eq!(
+            ContextLoader::from_dir(dir.path()),
+            Err(ContextError::InvalidDocument(url.to_owned()))
+        );
+    }
+}
@@ -0,0 +1,359 @@
+//! DID URL dereferencing of Trustchain-resolved DID documents.
+use crate::resolver::{Resolver, ResolverError};
+use ssi::did::{Document, Resource, ServiceEndpoint, DIDURL};
+use ssi::did_resolve::{
+    Content, ContentMetadata, DIDResolver, DereferencingMetadata, ResolutionInputMetadata,
+    TYPE_DID_LD_JSON, TYPE_LD_JSON, TYPE_URL,
+};
+use ssi::one_or_many::OneOrMany;
+use std::str::FromStr;
+use url::Url;
+
+/// Result of dereferencing a DID URL.
+type DereferencingResult = Result<(DereferencingMetadata, Content, ContentMetadata), ResolverError>;
+
+/// DID parameters supported in the query of a DID URL.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DIDURLParameters {
+    /// `versionId` parameter.
+    pub version_id: Option<String>,
+    /// `versionTime` parameter.
+    pub version_time: Option<String>,
+    /// `service` parameter, selecting a service by the fragment of its ID.
+    pub service: Option<String>,
+    /// `relativeRef` parameter, resolved against the selected service endpoint URL.
+    pub relative_ref: Option<String>,
+}
+
+impl DIDURLParameters {
+    /// Parses the DID parameters from a DID URL query, ignoring unrecognised parameters.
+    pub fn from_query(query: &str) -> Self {
+        let mut parameters = Self::default();
+        for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+            let value = Some(value.into_owned());
+            match key.as_ref() {
+                "versionId" => parameters.version_id = value,
+                "versionTime" => parameters.version_time = value,
+                "service" => parameters.service = value,
+                "relativeRef" | "relative-ref" => parameters.relative_ref = value,
+                _ => (),
+            }
+        }
+        parameters
+    }
+
+    /// Returns the resolution input metadata selecting the requested version of the DID.
+    pub fn to_input_metadata(&self) -> ResolutionInputMetadata {
+        ResolutionInputMetadata {
+            version_id: self.version_id.to_owned(),
+            version_time: self.version_time.to_owned(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Selects a resource from a DID document by the fragment of its ID, looking first for a
+/// verification method and then for a service.
+fn select_resource(doc: &Document, fragment: &str) -> Option<Resource> {
+    let did_url = DIDURL {
+        did: doc.id.to_owned(),
+        path_abempty: String::new(),
+        query: None,
+        fragment: Some(fragment.to_string()),
+    };
+    if let Ok(resource) = doc.select_object(&did_url) {
+        return Some(resource);
+    }
+    let service = select_service(doc, fragment)?;
+    match serde_json::to_value(service) {
+        Ok(serde_json::Value::Object(map)) => Some(Resource::Object(map.into_iter().collect())),
+        _ => None,
+    }
+}
+
+/// Selects a service from a DID document by the fragment of its ID.
+fn select_service<'a>(doc: &'a Document, fragment: &str) -> Option<&'a ssi::did::Service> {
+    doc.service
+        .iter()
+        .flatten()
+        .find(|service| service.id.rsplit('#').next() == Some(fragment))
+}
+
+/// Constructs the URL of a service endpoint, with an optional relative reference resolved against
+/// it as specified by RFC 3986.
+fn service_endpoint_url(
+    doc: &Document,
+    service: &str,
+    relative_ref: Option<&str>,
+) -> Result<String, String> {
+    let service =
+        select_service(doc, service).ok_or_else(|| format!("Service not found: {}", service))?;
+    let endpoint = match &service.service_endpoint {
+        Some(OneOrMany::One(ServiceEndpoint::URI(uri))) => uri,
+        Some(_) => return Err(format!("Service {} has no single URI endpoint", service.id)),
+        None => return Err(format!("Service {} has no endpoint", service.id)),
+    };
+    let relative_ref = match relative_ref {
+        Some(relative_ref) => relative_ref,
+        None => return Ok(endpoint.to_owned()),
+    };
+    Url::parse(endpoint)
+        .and_then(|url| url.join(relative_ref))
+        .map(String::from)
+        .map_err(|e| format!("Invalid service endpoint URL {}: {}", endpoint, e))
+}
+
+impl<T: DIDResolver + Sync + Send> Resolver<T> {
+    /// Dereferences a DID URL to the resource it identifies within the Trustchain-resolved DID
+    /// document: the document itself, a verification method or service (selected by fragment)
+    /// or a service endpoint URL (selected by the `service` and `relativeRef` parameters). The
+    /// `versionId` and `versionTime` parameters select the version of the document.
+    pub fn dereference_as_result(&self, did_url: &str) -> DereferencingResult {
+        let did_url = DIDURL::from_str(did_url)
+            .map_err(|_| ResolverError::InvalidDIDURL(did_url.to_string()))?;
+        if !did_url.path_abempty.is_empty() {
+            return Err(ResolverError::FailedToDereference(
+                did_url.to_string(),
+                "DID URL paths are not supported".to_string(),
+            ));
+        }
+        let parameters = did_url
+            .query
+            .as_deref()
+            .map(DIDURLParameters::from_query)
+            .unwrap_or_default();
+
+        // Resolve the requested version of the DID document in Trustchain format.
+        let (_, doc, doc_meta) =
+            self.resolve_as_result_with_input(&did_url.did, &parameters.to_input_metadata())?;
+        let (doc, doc_meta) = match (doc, doc_meta) {
+            (Some(doc), Some(doc_meta)) => (doc, doc_meta),
+            _ => return Err(ResolverError::DIDNotFound(did_url.did.to_owned())),
+        };
+
+        // Service endpoint URL, with any fragment appended.
+        if let Some(service) = &parameters.service {
+            let mut url =
+                service_endpoint_url(&doc, service, parameters.relative_ref.as_deref())
+                    .map_err(|e| ResolverError::FailedToDereference(did_url.to_string(), e))?;
+            if let Some(fragment) = &did_url.fragment {
+                url.push('#');
+                url.push_str(fragment);
+            }
+            return Ok((
+                DereferencingMetadata {
+                    content_type: Some(TYPE_URL.to_string()),
+                    ..Default::default()
+                },
+                Content::URL(url),
+                ContentMetadata::default(),
+            ));
+        }
+
+        // Resource within the DID document.
+        if let Some(fragment) = &did_url.fragment {
+            let resource = select_resource(&doc, fragment).ok_or_else(|| {
+                ResolverError::FailedToDereference(
+                    did_url.to_string(),
+                    format!("No resource with fragment: {}", fragment),
+                )
+            })?;
+            return Ok((
+                DereferencingMetadata {
+                    content_type: Some(TYPE_LD_JSON.to_string()),
+                    ..Default::default()
+                },
+                Content::Object(resource),
+                ContentMetadata::default(),
+            ));
+        }
+
+        // The DID document itself.
+        Ok((
+            DereferencingMetadata {
+                content_type: Some(TYPE_DID_LD_JSON.to_string()),
+                ..Default::default()
+            },
+            Content::DIDDocument(doc),
+            ContentMetadata::DIDDocument(doc_meta),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::TEST_TRUSTCHAIN_DOCUMENT;
+    use async_trait::async_trait;
+    use ssi::did_resolve::{DocumentMetadata, ResolutionMetadata, ERROR_NOT_FOUND};
+
+    const DID: &str = "did:ion:test:EiBVpjUxXeSRJpvj2TewlX9zNF3GKMCKWwGmKBZqF6pk_A";
+
+    // Mock resolver returning a document with a Trustchain proof service and a TrustchainID
+    // service, which is only available at or after a given version time.
+    struct TestResolver;
+
+    #[async_trait]
+    impl DIDResolver for TestResolver {
+        async fn resolve(
+            &self,
+            did: &str,
+            input_metadata: &ResolutionInputMetadata,
+        ) -> (
+            ResolutionMetadata,
+            Option<Document>,
+            Option<DocumentMetadata>,
+        ) {
+            let too_early = matches!(
+                &input_metadata.version_time,
+                Some(version_time) if version_time.as_str() < "2022-01-01T00:00:00Z"
+            );
+            if did != DID || too_early {
+                return (ResolutionMetadata::from_error(ERROR_NOT_FOUND), None, None);
+            }
+            let mut doc: serde_json::Value =
+                serde_json::from_str(TEST_TRUSTCHAIN_DOCUMENT).unwrap();
+            doc["id"] = serde_json::json!(DID);
+            doc["service"] = serde_json::json!([
+                {
+                    "id": "#TrustchainID",
+                    "type": "Identity",
+                    "serviceEndpoint": "https://example.com/trustchain/"
+                },
+                {
+                    "id": "#trustchain-controller-proof",
+                    "type": "TrustchainProofService",
+                    "serviceEndpoint": {
+                        "controller": "did:ion:test:EiCClfEdkTv_aM3UnBBhlOV89LlGhpQAbfeZLFdFxVFkEg",
+                        "proofValue": "dummy"
+                    }
+                }
+            ]);
+            doc.as_object_mut().unwrap().remove("controller");
+            (
+                ResolutionMetadata::default(),
+                Some(serde_json::from_value(doc).unwrap()),
+                Some(serde_json::from_str(r#"{"method": {"published": true}}"#).unwrap()),
+            )
+        }
+    }
+
+    #[test]
+    fn test_from_query() {
+        let parameters = DIDURLParameters::from_query(
+            "service=TrustchainID&relativeRef=%2Fcredentials%2F1&versionTime=2022-10-20T12:00:00Z",
+        );
+        assert_eq!(parameters.service.as_deref(), Some("TrustchainID"));
+        assert_eq!(parameters.relative_ref.as_deref(), Some("/credentials/1"));
+        assert_eq!(
+            parameters.version_time.as_deref(),
+            Some("2022-10-20T12:00:00Z")
+        );
+        assert!(parameters.version_id.is_none());
+    }
+
+    #[test]
+    fn test_dereference_document() {
+        let resolver = Resolver::new(TestResolver);
+        let (deref_meta, content, content_meta) = resolver.dereference_as_result(DID).unwrap();
+        assert_eq!(deref_meta.content_type.as_deref(), Some(TYPE_DID_LD_JSON));
+        // The Trustchain transform is applied.
+        let doc = match content {
+            Content::DIDDocument(doc) => doc,
+            _ => panic!("Expected a DID document."),
+        };
+        assert!(doc.controller.is_some());
+        assert!(select_service(&doc, "trustchain-controller-proof").is_none());
+        assert!(matches!(content_meta, ContentMetadata::DIDDocument(_)));
+    }
+
+    #[test]
+    fn test_dereference_fragment() {
+        let resolver = Resolver::new(TestResolver);
+        let (_, content, _) = resolver
+            .dereference_as_result(&format!("{}#TrustchainID", DID))
+            .unwrap();
+        match content {
+            Content::Object(Resource::Object(service)) => {
+                assert_eq!(service["type"], "Identity")
+            }
+            _ => panic!("Expected a service."),
+        }
+
+        let doc: Document = serde_json::from_str(TEST_TRUSTCHAIN_DOCUMENT).unwrap();
+        let vm_fragment = match doc.verification_method.as_ref().unwrap().first().unwrap() {
+            ssi::did::VerificationMethod::Map(vm) => vm.id.rsplit('#').next().unwrap().to_owned(),
+            _ => panic!(),
+        };
+        let (deref_meta, content, _) = resolver
+            .dereference_as_result(&format!("{}#{}", DID, vm_fragment))
+            .unwrap();
+        assert_eq!(deref_meta.content_type.as_deref(), Some(TYPE_LD_JSON));
+        assert!(matches!(
+            content,
+            Content::Object(Resource::VerificationMethod(_))
+        ));
+
+        // The proof service is moved to metadata by the Trustchain transform.
+        assert!(matches!(
+            resolver.dereference_as_result(&format!("{}#trustchain-controller-proof", DID)),
+            Err(ResolverError::FailedToDereference(_, _))
+        ));
+    }
+
+    #[test]
+    fn test_dereference_service() {
+        let resolver = Resolver::new(TestResolver);
+        let (deref_meta, content, _) = resolver
+            .dereference_as_result(&format!(
+                "{}?service=TrustchainID&relativeRef=credentials%2F1",
+                DID
+            ))
+            .unwrap();
+        assert_eq!(deref_meta.content_type.as_deref(), Some(TYPE_URL));
+        assert_eq!(
+            content,
+            Content::URL("https://example.com/trustchain/credentials/1".to_string())
+        );
+
+        // Relative references are resolved against the endpoint as specified by RFC 3986.
+        for (relative_ref, url) in [
+            ("%2Fcredentials%2F1", "https://example.com/credentials/1"),
+            ("%3Fid%3D1", "https://example.com/trustchain/?id=1"),
+            ("%23status", "https://example.com/trustchain/#status"),
+            ("..%2Fother", "https://example.com/other"),
+        ] {
+            let (_, content, _) = resolver
+                .dereference_as_result(&format!(
+                    "{}?service=TrustchainID&relativeRef={}",
+                    DID, relative_ref
+                ))
+                .unwrap();
+            assert_eq!(content, Content::URL(url.to_string()));
+        }
+
+        assert!(matches!(
+            resolver.dereference_as_result(&format!("{}?service=Unknown", DID)),
+            Err(ResolverError::FailedToDereference(_, _))
+        ));
+    }
+
+    #[test]
+    fn test_dereference_version_time() {
+        let resolver = Resolver::new(TestResolver);
+        assert!(resolver
+            .dereference_as_result(&format!("{}?versionTime=2022-10-20T12:00:00Z", DID))
+            .is_ok());
+        assert!(matches!(
+            resolver.dereference_as_result(&format!(
+                "{}?versionTime=2021-10-20T12:00:00Z#TrustchainID",
+                DID
+            )),
+            Err(ResolverError::DIDNotFound(_))
+        ));
+        assert!(matches!(
+            resolver.dereference_as_result("not a did url"),
+            Err(ResolverError::InvalidDIDURL(_))
+        ));
+    }
+}
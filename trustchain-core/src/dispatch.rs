@@ -0,0 +1,201 @@
+//! Resolution of DIDs of multiple methods by dispatching to method-specific resolvers.
+use async_trait::async_trait;
+use ssi::did::Document;
+use ssi::did_resolve::{
+    DIDResolver, DocumentMetadata, ResolutionInputMetadata, ResolutionMetadata, ERROR_INVALID_DID,
+    ERROR_METHOD_NOT_SUPPORTED,
+};
+use std::collections::HashMap;
+
+/// Returns the method name of a DID, e.g. `ion` for `did:ion:test:EiC...`.
+pub fn did_method(did: &str) -> Option<&str> {
+    match did.split(':').collect::<Vec<&str>>().as_slice() {
+        ["did", method, id, ..] if !method.is_empty() && !id.is_empty() => Some(method),
+        _ => None,
+    }
+}
+
+/// A resolver routing each DID to the resolver registered for its method, so that a single
+/// Trustchain [`Resolver`](crate::resolver::Resolver) can follow DID chains across methods.
+#[derive(Default)]
+pub struct DispatchResolver {
+    resolvers: HashMap<String, Box<dyn DIDResolver + Send + Sync>>,
+}
+
+impl DispatchResolver {
+    /// Makes a new dispatching resolver with no registered methods.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the resolver for a DID method (e.g. `ion`, `key` or `web`), replacing any
+    /// resolver previously registered for the method.
+    pub fn with_method<R: DIDResolver + Send + Sync + 'static>(
+        mut self,
+        method: &str,
+        resolver: R,
+    ) -> Self {
+        self.resolvers
+            .insert(method.to_string(), Box::new(resolver));
+        self
+    }
+
+    /// Returns the registered DID methods.
+    pub fn methods(&self) -> Vec<&str> {
+        let mut methods: Vec<&str> = self.resolvers.keys().map(|m| m.as_str()).collect();
+        methods.sort_unstable();
+        methods
+    }
+}
+
+#[async_trait]
+impl DIDResolver for DispatchResolver {
+    async fn resolve(
+        &self,
+        did: &str,
+        input_metadata: &ResolutionInputMetadata,
+    ) -> (
+        ResolutionMetadata,
+        Option<Document>,
+        Option<DocumentMetadata>,
+    ) {
+        let method = match did_method(did) {
+            Some(method) => method,
+            None => {
+                return (
+                    ResolutionMetadata::from_error(ERROR_INVALID_DID),
+                    None,
+                    None,
+                )
+            }
+        };
+        match self.resolvers.get(method) {
+            Some(resolver) => resolver.resolve(did, input_metadata).await,
+            None => (
+                ResolutionMetadata::from_error(ERROR_METHOD_NOT_SUPPORTED),
+                None,
+                None,
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::{Chain, DIDChain};
+    use crate::resolver::{Resolver, ResolverError};
+    use crate::utils::canonicalize;
+    use ssi::did_resolve::ERROR_NOT_FOUND;
+
+    const ION_ROOT: &str = "did:ion:test:EiCClfEdkTv_aM3UnBBhlOV89LlGhpQAbfeZLFdFxVFkEg";
+    const WEB_LEAF: &str = "did:web:example.com";
+
+    /// Resolver returning fixed documents for the DIDs of a single method.
+    struct StaticResolver {
+        documents: HashMap<String, Document>,
+    }
+
+    #[async_trait]
+    impl DIDResolver for StaticResolver {
+        async fn resolve(
+            &self,
+            did: &str,
+            _: &ResolutionInputMetadata,
+        ) -> (
+            ResolutionMetadata,
+            Option<Document>,
+            Option<DocumentMetadata>,
+        ) {
+            match self.documents.get(did) {
+                Some(doc) => (
+                    ResolutionMetadata::default(),
+                    Some(doc.clone()),
+                    Some(DocumentMetadata::default()),
+                ),
+                None => (ResolutionMetadata::from_error(ERROR_NOT_FOUND), None, None),
+            }
+        }
+    }
+
+    fn static_resolver(docs: Vec<(&str, String)>) -> StaticResolver {
+        StaticResolver {
+            documents: docs
+                .into_iter()
+                .map(|(did, json)| (did.to_string(), Document::from_json(&json).unwrap()))
+                .collect(),
+        }
+    }
+
+    fn dispatcher() -> DispatchResolver {
+        let root = format!(
+            r##"{{"@context": "https://www.w3.org/ns/did/v1", "id": "{}"}}"##,
+            ION_ROOT
+        );
+        // A did:web document attested by the did:ion root.
+        let proof_value = serde_json::json!({"controller": ION_ROOT, "proofValue": "dummy"});
+        let leaf = format!(
+            r##"{{
+                "@context": "https://www.w3.org/ns/did/v1",
+                "id": "{}",
+                "service": [{{
+                    "id": "#trustchain-controller-proof",
+                    "type": "TrustchainProofService",
+                    "serviceEndpoint": {}
+                }}]
+            }}"##,
+            WEB_LEAF,
+            serde_json::to_string(&proof_value).unwrap()
+        );
+        DispatchResolver::new()
+            .with_method("ion", static_resolver(vec![(ION_ROOT, root)]))
+            .with_method("web", static_resolver(vec![(WEB_LEAF, leaf)]))
+    }
+
+    #[test]
+    fn test_did_method() {
+        assert_eq!(did_method(ION_ROOT), Some("ion"));
+        assert_eq!(did_method("did:key:z6Mkabc"), Some("key"));
+        assert_eq!(did_method("did:web:example.com:user:alice"), Some("web"));
+        assert_eq!(did_method("did::abc"), None);
+        assert_eq!(did_method("did:ion"), None);
+        assert_eq!(did_method("ion:test:abc"), None);
+    }
+
+    #[test]
+    fn test_dispatch() {
+        assert_eq!(dispatcher().methods(), vec!["ion", "web"]);
+        let resolver = Resolver::new(dispatcher());
+
+        // The proof-service transform applies to non-ION methods.
+        let (_, doc, doc_meta) = resolver.resolve_as_result(WEB_LEAF).unwrap();
+        let doc = doc.unwrap();
+        assert!(doc.service.is_none());
+        assert_eq!(
+            canonicalize(&doc.controller).unwrap(),
+            canonicalize(&Some(ssi::one_or_many::OneOrMany::One(
+                ION_ROOT.to_string()
+            )))
+            .unwrap()
+        );
+        assert!(doc_meta.unwrap().property_set.is_some());
+
+        assert!(matches!(
+            resolver.resolve_as_result("did:example:123"),
+            Err(ResolverError::MethodNotSupported(_))
+        ));
+        assert!(matches!(
+            resolver.resolve_as_result("not-a-did"),
+            Err(ResolverError::NonExistentDID(_))
+        ));
+    }
+
+    #[test]
+    fn test_chain_across_methods() {
+        let resolver = Resolver::new(dispatcher());
+        let chain = DIDChain::new(WEB_LEAF, &resolver).unwrap();
+        assert_eq!(chain.len(), 2);
+        assert_eq!(chain.root(), ION_ROOT);
+        assert_eq!(chain.leaf(), WEB_LEAF);
+    }
+}
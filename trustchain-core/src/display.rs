@@ -14,7 +14,7 @@ fn truncate(s: &str, max_chars: usize) -> String {
 
 /// Extracts the service endpoint string from a DID `Document` if exactly one service with
 /// "id": `TrustchainID` is present.
-fn get_service_endpoint_string(doc: &Document) -> Option<String> {
+pub(crate) fn get_service_endpoint_string(doc: &Document) -> Option<String> {
     match doc.select_service(TRUSTCHAIN_SERVICE_ID_VALUE) {
         Some(Service {
             service_endpoint: Some(OneOrMany::One(ServiceEndpoint::URI(service_endpoint))),
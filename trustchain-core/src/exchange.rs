@@ -0,0 +1,809 @@
+//! DIF Presentation Exchange: matching credentials to a relying party's presentation definition,
+//! and making and verifying presentations with a presentation submission.
+//!
+//! A [`PresentationDefinition`] has input descriptors, each constraining the credentials that
+//! satisfy it by fields: JSONPath expressions into a credential (or the claims of a VC-JWT), with
+//! an optional JSON Schema filter for the selected value. Submission requirements and required
+//! limited disclosure are not supported, so every input descriptor must be satisfied by a
+//! credential presented in full.
+use crate::context::add_presentation_context;
+use crate::policy::IssuerPolicy;
+use crate::presentation::{
+    verify_presentation_with_policy, PresentationError, VerifiedPresentation,
+};
+use crate::schema::{validate, SchemaError};
+use crate::verifier::Verifier;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use ssi::did_resolve::DIDResolver;
+use ssi::one_or_many::OneOrMany;
+use ssi::vc::{CredentialOrJWT, Presentation, URI};
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// The Presentation Exchange submission JSON-LD context URL.
+pub const PRESENTATION_SUBMISSION_V1_CONTEXT: &str =
+    "https://identity.foundation/presentation-exchange/submission/v1";
+
+/// The Presentation Exchange submission JSON-LD context document.
+pub const PRESENTATION_SUBMISSION_V1_CONTEXT_DOCUMENT: &str = r##"{
+  "@context": {
+    "@version": 1.1,
+    "PresentationSubmission": {
+      "@id": "https://identity.foundation/presentation-exchange/#presentation-submission",
+      "@context": {
+        "@version": 1.1,
+        "presentation_submission": {
+          "@id": "https://identity.foundation/presentation-exchange/#presentation-submission",
+          "@type": "@json"
+        }
+      }
+    }
+  }
+}"##;
+
+/// Type of a presentation with a presentation submission.
+pub const PRESENTATION_SUBMISSION_TYPE: &str = "PresentationSubmission";
+
+/// Property of a presentation holding its presentation submission.
+const PRESENTATION_SUBMISSION: &str = "presentation_submission";
+
+/// Format designation of a credential with a linked-data proof.
+pub const LDP_VC: &str = "ldp_vc";
+/// Format designation of a JWT-encoded credential (VC-JWT).
+pub const JWT_VC: &str = "jwt_vc";
+
+/// An error relating to Presentation Exchange.
+#[derive(Error, Debug)]
+pub enum ExchangeError {
+    /// Invalid JSONPath expression.
+    #[error("Invalid JSONPath: {0}: {1}")]
+    InvalidPath(String, String),
+    /// Invalid filter of a field of an input descriptor.
+    #[error("Invalid filter in input descriptor: {0}: {1}")]
+    InvalidFilter(String, SchemaError),
+    /// Presentation definition uses a feature that is not supported.
+    #[error("Unsupported presentation definition feature: {0}")]
+    Unsupported(String),
+    /// No credential satisfies an input descriptor.
+    #[error("No credential satisfies input descriptor: {0}")]
+    NoMatch(String),
+    /// Credential is not a valid credential.
+    #[error("Invalid credential: {0}")]
+    InvalidCredential(String),
+    /// Presentation has no valid presentation submission.
+    #[error("Invalid presentation submission: {0}")]
+    InvalidSubmission(String),
+    /// Presentation submission is for a different presentation definition.
+    #[error("Presentation submission is for definition: {0}, not: {1}")]
+    MismatchedDefinition(String, String),
+    /// Presentation submission has no entry for an input descriptor.
+    #[error("Presentation submission has no entry for input descriptor: {0}")]
+    MissingDescriptor(String),
+    /// Credential submitted for an input descriptor does not satisfy it.
+    #[error("Credential at {1} does not satisfy input descriptor: {0}")]
+    NotSatisfied(String, String),
+    /// Trustchain verification of the presentation failed.
+    #[error("Presentation verification failed: {0}")]
+    Presentation(PresentationError),
+}
+
+impl From<PresentationError> for ExchangeError {
+    fn from(err: PresentationError) -> Self {
+        ExchangeError::Presentation(err)
+    }
+}
+
+/// A presentation definition of the credentials requested by a relying party.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PresentationDefinition {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub purpose: Option<String>,
+    /// Formats of credentials accepted for every input descriptor, by designation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<HashMap<String, Value>>,
+    pub input_descriptors: Vec<InputDescriptor>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub submission_requirements: Option<Value>,
+}
+
+/// An input descriptor of a credential requested by a presentation definition.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputDescriptor {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub purpose: Option<String>,
+    /// Formats of credentials accepted for the descriptor, by designation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<HashMap<String, Value>>,
+    #[serde(default)]
+    pub constraints: Constraints,
+}
+
+/// Constraints of an input descriptor.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Constraints {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<Field>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit_disclosure: Option<String>,
+}
+
+/// A field constraint: the first value selected by any of the paths (in order) that is valid
+/// against the filter, if any.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Field {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub path: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub purpose: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter: Option<Value>,
+    /// Whether the field may be absent from a credential satisfying the descriptor.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub optional: bool,
+}
+
+/// A presentation submission, mapping input descriptors to the presented credentials.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PresentationSubmission {
+    pub id: String,
+    pub definition_id: String,
+    pub descriptor_map: Vec<DescriptorMapEntry>,
+}
+
+/// Entry of a presentation submission: the path in the presentation of the credential
+/// submitted for an input descriptor.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DescriptorMapEntry {
+    pub id: String,
+    pub format: String,
+    pub path: String,
+}
+
+/// Segment of a JSONPath expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    /// Member of an object (`.name`, `['name']`).
+    Child(String),
+    /// Element of an array (`[0]`).
+    Index(usize),
+    /// Members of an object or elements of an array (`.*`, `[*]`).
+    Wildcard,
+    /// The segment applied to a value and all of its descendants (`..`).
+    Descendants(Box<Segment>),
+}
+
+/// Parses a JSONPath expression, supporting child, index, wildcard and recursive descent
+/// segments.
+fn parse_path(path: &str) -> Result<Vec<Segment>, ExchangeError> {
+    let invalid = |reason: &str| ExchangeError::InvalidPath(path.to_owned(), reason.to_owned());
+    let mut rest = path
+        .trim()
+        .strip_prefix('$')
+        .ok_or_else(|| invalid("must start with $"))?;
+    let mut segments = Vec::new();
+    while !rest.is_empty() {
+        let (recursive, after) = match rest.strip_prefix("..") {
+            Some(after) => (true, after),
+            None => (false, rest),
+        };
+        let (segment, after) = if let Some(after) = after.strip_prefix('[') {
+            let end = after.find(']').ok_or_else(|| invalid("unclosed ["))?;
+            let (selector, after) = (after[..end].trim(), &after[end + 1..]);
+            let segment = if selector == "*" {
+                Segment::Wildcard
+            } else if let Some(name) = selector
+                .strip_prefix('\'')
+                .and_then(|name| name.strip_suffix('\''))
+                .or_else(|| {
+                    selector
+                        .strip_prefix('"')
+                        .and_then(|name| name.strip_suffix('"'))
+                })
+            {
+                Segment::Child(name.to_owned())
+            } else {
+                Segment::Index(
+                    selector
+                        .parse()
+                        .map_err(|_| invalid("unsupported selector"))?,
+                )
+            };
+            (segment, after)
+        } else {
+            let after = match recursive {
+                true => after,
+                false => after
+                    .strip_prefix('.')
+                    .ok_or_else(|| invalid("expected . or ["))?,
+            };
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            let segment = match &after[..end] {
+                "" => return Err(invalid("empty name")),
+                "*" => Segment::Wildcard,
+                name => Segment::Child(name.to_owned()),
+            };
+            (segment, &after[end..])
+        };
+        segments.push(match recursive {
+            true => Segment::Descendants(Box::new(segment)),
+            false => segment,
+        });
+        rest = after;
+    }
+    Ok(segments)
+}
+
+/// Returns the value and all of its descendants, in document order.
+fn descendants<'a>(value: &'a Value, values: &mut Vec<&'a Value>) {
+    values.push(value);
+    match value {
+        Value::Object(object) => object.values().for_each(|value| descendants(value, values)),
+        Value::Array(array) => array.iter().for_each(|value| descendants(value, values)),
+        _ => (),
+    }
+}
+
+/// Applies a JSONPath segment to each of the values.
+fn apply<'a>(segment: &Segment, values: Vec<&'a Value>) -> Vec<&'a Value> {
+    match segment {
+        Segment::Child(name) => values.into_iter().filter_map(|v| v.get(name)).collect(),
+        Segment::Index(index) => values
+            .into_iter()
+            .filter_map(|v| v.as_array().and_then(|array| array.get(*index)))
+            .collect(),
+        Segment::Wildcard => values
+            .into_iter()
+            .flat_map(|v| match v {
+                Value::Object(object) => object.values().collect(),
+                Value::Array(array) => array.iter().collect(),
+                _ => Vec::new(),
+            })
+            .collect(),
+        Segment::Descendants(segment) => {
+            let mut all = Vec::new();
+            values.into_iter().for_each(|v| descendants(v, &mut all));
+            apply(segment, all)
+        }
+    }
+}
+
+/// Returns the values selected from a value by a JSONPath expression.
+pub fn select_path<'a>(path: &str, value: &'a Value) -> Result<Vec<&'a Value>, ExchangeError> {
+    Ok(parse_path(path)?
+        .iter()
+        .fold(vec![value], |values, segment| apply(segment, values)))
+}
+
+/// Returns the format designation of a credential.
+pub fn credential_format(credential: &CredentialOrJWT) -> &'static str {
+    match credential {
+        CredentialOrJWT::Credential(_) => LDP_VC,
+        CredentialOrJWT::JWT(_) => JWT_VC,
+    }
+}
+
+/// Returns whether a set of accepted formats, if any, accepts a format.
+fn accepts(formats: Option<&HashMap<String, Value>>, format: &str) -> bool {
+    formats.is_none_or(|formats| {
+        formats.contains_key(format) || (format == JWT_VC && formats.contains_key("jwt_vc_json"))
+    })
+}
+
+/// Returns the JSON value against which the fields of an input descriptor are evaluated: the
+/// credential, or the claims of a VC-JWT.
+fn credential_value(credential: &CredentialOrJWT) -> Result<Value, ExchangeError> {
+    let invalid = |reason: String| ExchangeError::InvalidCredential(reason);
+    match credential {
+        CredentialOrJWT::Credential(credential) => {
+            serde_json::to_value(credential).map_err(|e| invalid(e.to_string()))
+        }
+        CredentialOrJWT::JWT(jwt) => {
+            let payload = jwt
+                .split('.')
+                .nth(1)
+                .ok_or_else(|| invalid("JWT has no payload".to_string()))?;
+            let payload = base64::decode_config(payload, base64::URL_SAFE_NO_PAD)
+                .map_err(|e| invalid(e.to_string()))?;
+            serde_json::from_slice(&payload).map_err(|e| invalid(e.to_string()))
+        }
+    }
+}
+
+impl PresentationDefinition {
+    /// Checks that the definition uses only supported features and that its paths are valid.
+    pub fn validate(&self) -> Result<(), ExchangeError> {
+        if self.submission_requirements.is_some() {
+            return Err(ExchangeError::Unsupported(
+                "submission_requirements".to_string(),
+            ));
+        }
+        for descriptor in &self.input_descriptors {
+            if descriptor.constraints.limit_disclosure.as_deref() == Some("required") {
+                return Err(ExchangeError::Unsupported(format!(
+                    "limit_disclosure required by input descriptor: {}",
+                    descriptor.id
+                )));
+            }
+            for field in &descriptor.constraints.fields {
+                for path in &field.path {
+                    parse_path(path)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the indices of the credentials satisfying each input descriptor, in order.
+    pub fn select(
+        &self,
+        credentials: &[CredentialOrJWT],
+    ) -> Result<Vec<Vec<usize>>, ExchangeError> {
+        self.validate()?;
+        let values = credentials
+            .iter()
+            .map(credential_value)
+            .collect::<Result<Vec<_>, _>>()?;
+        self.input_descriptors
+            .iter()
+            .map(|descriptor| {
+                let mut indices = Vec::new();
+                for (index, (credential, value)) in credentials.iter().zip(&values).enumerate() {
+                    if self.is_satisfied(descriptor, credential_format(credential), value)? {
+                        indices.push(index);
+                    }
+                }
+                Ok(indices)
+            })
+            .collect()
+    }
+
+    /// Returns whether a credential of the given format and value satisfies an input descriptor.
+    fn is_satisfied(
+        &self,
+        descriptor: &InputDescriptor,
+        format: &str,
+        value: &Value,
+    ) -> Result<bool, ExchangeError> {
+        if !accepts(descriptor.format.as_ref().or(self.format.as_ref()), format) {
+            return Ok(false);
+        }
+        for field in &descriptor.constraints.fields {
+            if !field.optional && !field.is_satisfied(&descriptor.id, value)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl Field {
+    /// Returns whether, for any of the paths, the first value selected is valid against the
+    /// filter, if any.
+    fn is_satisfied(&self, descriptor_id: &str, value: &Value) -> Result<bool, ExchangeError> {
+        for path in &self.path {
+            let candidate = match select_path(path, value)?.first() {
+                Some(candidate) => *candidate,
+                None => continue,
+            };
+            let valid = match &self.filter {
+                Some(filter) => validate(descriptor_id, filter, candidate)
+                    .map_err(|e| ExchangeError::InvalidFilter(descriptor_id.to_owned(), e))?
+                    .is_empty(),
+                None => true,
+            };
+            if valid {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+/// Makes an unsigned presentation by the holder satisfying a presentation definition, with a
+/// credential satisfying each input descriptor (each presented once) and a presentation
+/// submission mapping the descriptors to them.
+pub fn create_presentation(
+    definition: &PresentationDefinition,
+    credentials: &[CredentialOrJWT],
+    holder: &str,
+) -> Result<Presentation, ExchangeError> {
+    let selected = definition.select(credentials)?;
+    let mut presented: Vec<usize> = Vec::new();
+    let mut descriptor_map = Vec::new();
+    for (descriptor, indices) in definition.input_descriptors.iter().zip(selected) {
+        // Prefer a credential already presented for another descriptor.
+        let index = *indices
+            .iter()
+            .find(|index| presented.contains(index))
+            .or_else(|| indices.first())
+            .ok_or_else(|| ExchangeError::NoMatch(descriptor.id.to_owned()))?;
+        let position = match presented.iter().position(|i| *i == index) {
+            Some(position) => position,
+            None => {
+                presented.push(index);
+                presented.len() - 1
+            }
+        };
+        descriptor_map.push(DescriptorMapEntry {
+            id: descriptor.id.to_owned(),
+            format: credential_format(&credentials[index]).to_owned(),
+            path: format!("$.verifiableCredential[{}]", position),
+        });
+    }
+    let submission = PresentationSubmission {
+        id: uuid::Uuid::new_v4().to_string(),
+        definition_id: definition.id.to_owned(),
+        descriptor_map,
+    };
+
+    let mut presentation = Presentation {
+        verifiable_credential: Some(OneOrMany::Many(
+            presented
+                .into_iter()
+                .map(|index| credentials[index].clone())
+                .collect(),
+        )),
+        holder: Some(URI::String(holder.to_owned())),
+        ..Default::default()
+    };
+    add_presentation_context(&mut presentation, PRESENTATION_SUBMISSION_V1_CONTEXT);
+    presentation.type_ = OneOrMany::Many(vec![
+        "VerifiablePresentation".to_string(),
+        PRESENTATION_SUBMISSION_TYPE.to_string(),
+    ]);
+    presentation.property_set = Some(HashMap::from([(
+        PRESENTATION_SUBMISSION.to_string(),
+        serde_json::to_value(submission).unwrap(),
+    )]));
+    Ok(presentation)
+}
+
+/// Returns the presentation submission of a presentation.
+pub fn presentation_submission(
+    presentation: &Presentation,
+) -> Result<PresentationSubmission, ExchangeError> {
+    let submission = presentation
+        .property_set
+        .as_ref()
+        .and_then(|properties| properties.get(PRESENTATION_SUBMISSION))
+        .ok_or_else(|| ExchangeError::InvalidSubmission("not present".to_string()))?;
+    serde_json::from_value(submission.clone())
+        .map_err(|e| ExchangeError::InvalidSubmission(e.to_string()))
+}
+
+/// Checks that the presentation submission of a presentation satisfies a presentation
+/// definition: that it maps every input descriptor to a presented credential of an accepted
+/// format that satisfies the descriptor.
+pub fn check_submission(
+    presentation: &Presentation,
+    definition: &PresentationDefinition,
+) -> Result<PresentationSubmission, ExchangeError> {
+    let submission = presentation_submission(presentation)?;
+    check_descriptor_map(presentation, &submission, definition)?;
+    Ok(submission)
+}
+
+/// Returns the presented credential at a path of a descriptor map: `$.verifiableCredential` for a
+/// single credential, or `$.verifiableCredential[n]` for one of several. Paths to any other value
+/// of the presentation are not followed, as only the presented credentials are verified.
+fn submitted_credential<'a>(
+    presentation: &'a Presentation,
+    path: &str,
+) -> Result<Option<&'a CredentialOrJWT>, ExchangeError> {
+    let segments = parse_path(path)?;
+    let credentials = match (segments.first(), &presentation.verifiable_credential) {
+        (Some(Segment::Child(name)), Some(credentials)) if name == "verifiableCredential" => {
+            credentials
+        }
+        _ => return Ok(None),
+    };
+    Ok(match (&segments[1..], credentials) {
+        ([], OneOrMany::One(credential)) => Some(credential),
+        ([Segment::Index(index)], OneOrMany::Many(credentials)) => credentials.get(*index),
+        _ => None,
+    })
+}
+
+/// Checks that a presentation submission given apart from the presentation (as in OpenID for
+/// Verifiable Presentations) satisfies a presentation definition, with the paths of its
+/// descriptor map relative to the presentation.
+pub fn check_descriptor_map(
+    presentation: &Presentation,
+    submission: &PresentationSubmission,
+    definition: &PresentationDefinition,
+) -> Result<(), ExchangeError> {
+    definition.validate()?;
+    if submission.definition_id != definition.id {
+        return Err(ExchangeError::MismatchedDefinition(
+            submission.definition_id.to_owned(),
+            definition.id.to_owned(),
+        ));
+    }
+    for descriptor in &definition.input_descriptors {
+        let entry = submission
+            .descriptor_map
+            .iter()
+            .find(|entry| entry.id == descriptor.id)
+            .ok_or_else(|| ExchangeError::MissingDescriptor(descriptor.id.to_owned()))?;
+        let not_satisfied =
+            || ExchangeError::NotSatisfied(descriptor.id.to_owned(), entry.path.to_owned());
+        let credential =
+            submitted_credential(presentation, &entry.path)?.ok_or_else(not_satisfied)?;
+        let format = credential_format(credential);
+        if entry.format != format
+            || !definition.is_satisfied(descriptor, format, &credential_value(credential)?)?
+        {
+            return Err(not_satisfied());
+        }
+    }
+    Ok(())
+}
+
+/// Verifies a presentation submitted for a presentation definition: the presentation as by
+/// [`verify_presentation_with_policy`] (including the Trustchain DID chains of the holder and of
+/// the issuer of every credential, and the issuer policy, if given), and that its presentation
+/// submission satisfies the definition.
+pub fn verify_submission<T, V>(
+    presentation: &Presentation,
+    definition: &PresentationDefinition,
+    challenge: Option<&str>,
+    domain: Option<&str>,
+    root_event_time: u32,
+    issuer_policy: Option<&IssuerPolicy>,
+    verifier: &V,
+) -> Result<(PresentationSubmission, VerifiedPresentation), ExchangeError>
+where
+    T: DIDResolver + Sync + Send,
+    V: Verifier<T>,
+{
+    let submission = check_submission(presentation, definition)?;
+    let verified = verify_presentation_with_policy(
+        presentation,
+        challenge,
+        domain,
+        root_event_time,
+        issuer_policy,
+        verifier,
+    )?;
+    Ok((submission, verified))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::TEST_CREDENTIAL;
+    use serde_json::json;
+    use ssi::vc::Credential;
+
+    fn definition() -> PresentationDefinition {
+        serde_json::from_value(json!({
+            "id": "degree-check",
+            "input_descriptors": [
+                {
+                    "id": "degree",
+                    "constraints": {
+                        "fields": [
+                            {
+                                "path": ["$.credentialSubject.degree.type", "$.vc.credentialSubject.degree.type"],
+                                "filter": {"type": "string", "enum": ["BachelorDegree"]}
+                            },
+                            {"path": ["$.credentialSubject.gpa"], "optional": true}
+                        ]
+                    }
+                },
+                {
+                    "id": "name",
+                    "format": {"ldp_vc": {"proof_type": ["JsonWebSignature2020"]}},
+                    "constraints": {"fields": [{"path": ["$..familyName"]}]}
+                }
+            ]
+        }))
+        .unwrap()
+    }
+
+    fn credential(degree: &str) -> CredentialOrJWT {
+        let mut credential: Credential = serde_json::from_str(TEST_CREDENTIAL).unwrap();
+        let mut subject = serde_json::to_value(credential.credential_subject).unwrap();
+        subject["id"] = json!("did:example:holder");
+        subject["degree"]["type"] = json!(degree);
+        credential.credential_subject = serde_json::from_value(subject).unwrap();
+        CredentialOrJWT::Credential(credential)
+    }
+
+    #[test]
+    fn test_select_path() {
+        let value = json!({
+            "a": {"b": [{"c": 1}, {"c": 2}], "d": {"c": 3}},
+            "e f": true
+        });
+        let select = |path| select_path(path, &value).unwrap();
+        assert_eq!(select("$"), vec![&value]);
+        assert_eq!(select("$.a.b[1].c"), vec![&json!(2)]);
+        assert_eq!(select("$['a'][\"d\"].c"), vec![&json!(3)]);
+        assert_eq!(select("$.a.b[*].c"), vec![&json!(1), &json!(2)]);
+        assert_eq!(select("$['e f']"), vec![&json!(true)]);
+        let mut all = select("$..c");
+        all.sort_by_key(|value| value.as_i64());
+        assert_eq!(all, vec![&json!(1), &json!(2), &json!(3)]);
+        assert!(select("$.a.x").is_empty());
+        assert!(select("$.a.b[5]").is_empty());
+        for invalid in ["a.b", "$.", "$.a[", "$[?(@.c)]", "$a"] {
+            assert!(
+                matches!(
+                    select_path(invalid, &value),
+                    Err(ExchangeError::InvalidPath(..))
+                ),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_select() {
+        let definition = definition();
+        let credentials = vec![credential("MasterDegree"), credential("BachelorDegree")];
+        assert_eq!(
+            definition.select(&credentials).unwrap(),
+            vec![vec![1], vec![0, 1]]
+        );
+
+        // JWT credentials are not accepted for the descriptor requiring linked-data proofs.
+        let claims = json!({"vc": {"credentialSubject": {"degree": {"type": "BachelorDegree"}, "familyName": "Doe"}}});
+        let jwt = format!(
+            "e30.{}.sig",
+            base64::encode_config(claims.to_string(), base64::URL_SAFE_NO_PAD)
+        );
+        assert_eq!(
+            definition.select(&[CredentialOrJWT::JWT(jwt)]).unwrap(),
+            vec![vec![0], vec![]]
+        );
+
+        let mut unsupported = definition.clone();
+        unsupported.submission_requirements = Some(json!([]));
+        assert!(matches!(
+            unsupported.select(&credentials),
+            Err(ExchangeError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_type_filter() {
+        let definition: PresentationDefinition = serde_json::from_value(json!({
+            "id": "degree-type",
+            "input_descriptors": [{
+                "id": "degree",
+                "constraints": {
+                    "fields": [{
+                        "path": ["$.type", "$.vc.type"],
+                        "filter": {"type": "array", "contains": {"const": "DegreeCredential"}}
+                    }]
+                }
+            }]
+        }))
+        .unwrap();
+        let mut degree = credential("BachelorDegree");
+        if let CredentialOrJWT::Credential(credential) = &mut degree {
+            credential.type_ =
+                serde_json::from_value(json!(["VerifiableCredential", "DegreeCredential"]))
+                    .unwrap();
+        }
+        let credentials = vec![credential("BachelorDegree"), degree];
+        assert_eq!(definition.select(&credentials).unwrap(), vec![vec![1]]);
+
+        let presentation =
+            create_presentation(&definition, &credentials, "did:example:holder").unwrap();
+        assert_eq!(
+            serde_json::to_value(&presentation.verifiable_credential).unwrap(),
+            json!([serde_json::to_value(&credentials[1]).unwrap()])
+        );
+        assert!(check_submission(&presentation, &definition).is_ok());
+
+        // A credential without the type does not satisfy the descriptor.
+        let mut invalid = presentation.clone();
+        invalid.verifiable_credential = Some(OneOrMany::Many(vec![credentials[0].clone()]));
+        assert!(matches!(
+            check_submission(&invalid, &definition),
+            Err(ExchangeError::NotSatisfied(id, _)) if id == "degree"
+        ));
+
+        // Filters with unsupported keywords are rejected rather than ignored.
+        let mut unsupported = definition.clone();
+        unsupported.input_descriptors[0].constraints.fields[0].filter =
+            Some(json!({"type": "array", "minProperties": 1}));
+        assert!(matches!(
+            unsupported.select(&credentials),
+            Err(ExchangeError::InvalidFilter(id, SchemaError::InvalidSchema(..))) if id == "degree"
+        ));
+    }
+
+    #[test]
+    fn test_create_check_presentation() {
+        let definition = definition();
+        let credentials = vec![credential("MasterDegree"), credential("BachelorDegree")];
+        let presentation =
+            create_presentation(&definition, &credentials, "did:example:holder").unwrap();
+        // The credential satisfying both descriptors is presented once.
+        assert_eq!(
+            presentation.verifiable_credential.as_ref().unwrap().len(),
+            1
+        );
+        let submission = check_submission(&presentation, &definition).unwrap();
+        assert_eq!(submission.definition_id, "degree-check");
+        assert_eq!(
+            submission
+                .descriptor_map
+                .iter()
+                .map(|entry| (entry.id.as_str(), entry.path.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("degree", "$.verifiableCredential[0]"),
+                ("name", "$.verifiableCredential[0]")
+            ]
+        );
+        assert!(presentation
+            .context
+            .contains_uri(PRESENTATION_SUBMISSION_V1_CONTEXT));
+
+        assert!(matches!(
+            create_presentation(&definition, &credentials[..1], "did:example:holder"),
+            Err(ExchangeError::NoMatch(id)) if id == "degree"
+        ));
+
+        // A submission mapping a descriptor to a credential that does not satisfy it.
+        let mut invalid = presentation.clone();
+        invalid.verifiable_credential = Some(OneOrMany::Many(vec![credentials[0].clone()]));
+        assert!(matches!(
+            check_submission(&invalid, &definition),
+            Err(ExchangeError::NotSatisfied(id, _)) if id == "degree"
+        ));
+
+        // A submission mapping a descriptor to a forged credential outside the presented ones,
+        // which are the only ones verified.
+        let mut forged = invalid.clone();
+        let mut properties = forged.property_set.take().unwrap();
+        properties.insert(
+            "forged".to_string(),
+            serde_json::to_value(&credentials[1]).unwrap(),
+        );
+        let mut submission = presentation_submission(&presentation).unwrap();
+        for entry in submission.descriptor_map.iter_mut() {
+            entry.path = "$.forged".to_string();
+        }
+        properties.insert(
+            PRESENTATION_SUBMISSION.to_string(),
+            serde_json::to_value(&submission).unwrap(),
+        );
+        forged.property_set = Some(properties);
+        assert!(matches!(
+            check_submission(&forged, &definition),
+            Err(ExchangeError::NotSatisfied(id, path)) if id == "degree" && path == "$.forged"
+        ));
+        for entry in submission.descriptor_map.iter_mut() {
+            entry.path = "$..credentialSubject".to_string();
+        }
+        assert!(matches!(
+            check_descriptor_map(&forged, &submission, &definition),
+            Err(ExchangeError::NotSatisfied(..))
+        ));
+
+        let mut other = definition.clone();
+        other.id = "other".to_string();
+        assert!(matches!(
+            check_submission(&presentation, &other),
+            Err(ExchangeError::MismatchedDefinition(..))
+        ));
+        assert!(matches!(
+            check_submission(&Presentation::default(), &definition),
+            Err(ExchangeError::InvalidSubmission(_))
+        ));
+    }
+}
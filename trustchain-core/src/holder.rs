@@ -0,0 +1,80 @@
+use crate::issuer::IssuerError;
+use crate::key_manager::KeyManagerError;
+use crate::subject::Subject;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use ssi::did_resolve::DIDResolver;
+use ssi::vc::Presentation;
+use thiserror::Error;
+
+/// JWT `typ` header of a key proof, as in OpenID for Verifiable Credential Issuance.
+pub const KEY_PROOF_JWT_TYPE: &str = "openid4vci-proof+jwt";
+
+/// An error relating to a Trustchain Holder.
+#[derive(Error, Debug)]
+pub enum HolderError {
+    /// Wrapped error for SSI error.
+    #[error("A wrapped variant for an SSI error.")]
+    SSI(ssi::error::Error),
+    /// Wrapped error for key manager error.
+    #[error("A wrapped variant for a key manager error.")]
+    KeyManager(KeyManagerError),
+    /// Presentation holder does not match the holder signing it.
+    #[error("Presentation holder: {0} does not match the signing holder: {1}.")]
+    MismatchedHolder(String, String),
+    /// Failed to resolve the verification method of the signing key.
+    #[error("Failed to resolve the verification method of the signing key: {0}")]
+    VerificationMethod(IssuerError),
+}
+
+impl From<ssi::error::Error> for HolderError {
+    fn from(err: ssi::error::Error) -> Self {
+        HolderError::SSI(err)
+    }
+}
+
+impl From<KeyManagerError> for HolderError {
+    fn from(err: KeyManagerError) -> Self {
+        HolderError::KeyManager(err)
+    }
+}
+
+/// Claims of a key proof JWT.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyProofClaims {
+    /// Credential issuer to which control of the key is proved.
+    pub aud: String,
+    /// Unix time at which the proof was made.
+    pub iat: i64,
+    /// Nonce given by the credential issuer, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+}
+
+/// A credential holder signs a presentation of credentials to prove control of the holder DID.
+#[async_trait]
+pub trait Holder: Subject {
+    /// Signs a presentation with one of the holder's private signing keys, setting the holder of
+    /// the presentation to the holder DID if absent. The proof is bound to the challenge and
+    /// domain given by the verifier, if any, to prevent replay of the presentation.
+    async fn sign_presentation<T: DIDResolver>(
+        &self,
+        presentation: &Presentation,
+        key_id: Option<&str>,
+        challenge: Option<&str>,
+        domain: Option<&str>,
+        resolver: &T,
+    ) -> Result<Presentation, HolderError>;
+
+    /// Signs a key proof JWT with one of the holder's private signing keys, proving control of
+    /// the key to a credential issuer (the audience) so that credentials are issued to the holder
+    /// DID. The `kid` header is the holder's verification method for the key, and the proof is
+    /// bound to the nonce given by the issuer, if any.
+    async fn sign_key_proof<T: DIDResolver>(
+        &self,
+        audience: &str,
+        nonce: Option<&str>,
+        key_id: Option<&str>,
+        resolver: &T,
+    ) -> Result<String, HolderError>;
+}
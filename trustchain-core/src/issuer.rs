@@ -1,8 +1,12 @@
 use crate::key_manager::KeyManagerError;
+use crate::schema::SchemaError;
 use crate::subject::Subject;
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use ssi::did::{VerificationMethod, VerificationMethodMap};
 use ssi::did_resolve::DIDResolver;
-use ssi::vc::Credential;
+use ssi::jwk::{Algorithm, JWK};
+use ssi::vc::{Credential, LinkedDataProofOptions, ProofPurpose, URI};
 use thiserror::Error;
 
 /// An error relating to a Trustchain Issuer.
@@ -14,6 +18,15 @@ pub enum IssuerError {
     /// Wrapped error for key manager error.
     #[error("A wrapped variant for a key manager error.")]
     KeyManager(KeyManagerError),
+    /// Failed to resolve the issuer's DID document.
+    #[error("Failed to resolve the DID document of issuer: {0}")]
+    FailedToResolve(String),
+    /// Issuer's DID document has no verification method for the signing key.
+    #[error("DID document of issuer: {0} has no verification method for the signing key.")]
+    NoVerificationMethod(String),
+    /// Credential does not conform to its schemas, or they could not be checked.
+    #[error("Credential schema validation failed: {0}")]
+    Schema(SchemaError),
 }
 
 impl From<ssi::error::Error> for IssuerError {
@@ -22,20 +35,114 @@ impl From<ssi::error::Error> for IssuerError {
     }
 }
 
+impl From<SchemaError> for IssuerError {
+    fn from(err: SchemaError) -> Self {
+        IssuerError::Schema(err)
+    }
+}
+
 impl From<KeyManagerError> for IssuerError {
     fn from(err: KeyManagerError) -> Self {
         IssuerError::KeyManager(err)
     }
 }
 
+/// Options for the proof of a signed credential.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProofOptions {
+    /// ID of the issuer's signing key, or the issuer's first signing key if `None`.
+    pub key_id: Option<String>,
+    /// Verification method of the proof, or the verification method of the signing key in the
+    /// issuer's resolved DID document if `None`.
+    pub verification_method: Option<String>,
+    /// Purpose of the proof, or `assertionMethod` if `None`.
+    pub proof_purpose: Option<ProofPurpose>,
+    /// Creation time of the proof, or the time of signing if `None`.
+    pub created: Option<DateTime<Utc>>,
+    /// Domain to which the proof is bound.
+    pub domain: Option<String>,
+    /// Challenge to which the proof is bound.
+    pub challenge: Option<String>,
+}
+
+impl ProofOptions {
+    /// Makes options for a proof with the given signing key and defaults otherwise.
+    pub fn with_key_id(key_id: Option<&str>) -> Self {
+        Self {
+            key_id: key_id.map(str::to_owned),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the linked-data proof options with the given verification method.
+    pub fn to_ldp_options(&self, verification_method: &str) -> LinkedDataProofOptions {
+        let defaults = LinkedDataProofOptions::default();
+        LinkedDataProofOptions {
+            verification_method: Some(URI::String(verification_method.to_owned())),
+            proof_purpose: self.proof_purpose.clone().or(defaults.proof_purpose),
+            created: self.created.or(defaults.created),
+            challenge: self.challenge.clone(),
+            domain: self.domain.clone(),
+            ..defaults
+        }
+    }
+}
+
+/// Returns the absolute ID of the verification method in a DID document with the given public
+/// key, if any.
+pub async fn resolve_verification_method<T: DIDResolver>(
+    did: &str,
+    key: &JWK,
+    resolver: &T,
+) -> Result<String, IssuerError> {
+    let (_, document, _) = resolver.resolve(did, &Default::default()).await;
+    let document = document.ok_or_else(|| IssuerError::FailedToResolve(did.to_owned()))?;
+    document
+        .verification_method
+        .iter()
+        .flatten()
+        .find_map(|vm| match vm {
+            VerificationMethod::Map(VerificationMethodMap {
+                id,
+                public_key_jwk: Some(public_key),
+                ..
+            }) if public_key.equals_public(key) => Some(match id.starts_with('#') {
+                true => format!("{}{}", did, id),
+                false => id.to_owned(),
+            }),
+            _ => None,
+        })
+        .ok_or_else(|| IssuerError::NoVerificationMethod(did.to_owned()))
+}
+
 /// A credential issuer signs a credential to generate a verifiable credential.
 #[async_trait]
 pub trait Issuer: Subject {
-    /// Signs a credential. An issuer attests to a credential by signing the credential with one of their private signing keys.
+    /// Signs a credential. An issuer attests to a credential by signing the credential with one of their private signing keys, with a proof as given by the options. Credentials not conforming to their schemas are not signed.
     async fn sign<T: DIDResolver>(
         &self,
         credential: &Credential,
-        key_id: Option<&str>,
+        options: &ProofOptions,
         resolver: &T,
     ) -> Result<Credential, IssuerError>;
+
+    /// Returns the verification method of proofs signed with the given options: that of the
+    /// options if given, otherwise that of the signing key in the issuer's resolved DID document.
+    async fn verification_method<T: DIDResolver>(
+        &self,
+        options: &ProofOptions,
+        resolver: &T,
+    ) -> Result<String, IssuerError>;
+
+    /// Signs a credential, returning it encoded as a JWT (VC-JWT). The JWT is signed with the
+    /// given algorithm, or the default algorithm for the signing key, and its `kid` header is
+    /// the verification method of the options. Options not encodable as JWT claims (`created`
+    /// and proof purposes other than `assertionMethod`) are rejected.
+    async fn sign_jwt<T: DIDResolver>(
+        &self,
+        credential: &Credential,
+        options: &ProofOptions,
+        algorithm: Option<Algorithm>,
+        resolver: &T,
+    ) -> Result<String, IssuerError>;
 }
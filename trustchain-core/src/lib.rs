@@ -1,17 +1,33 @@
 //! Trustchain library.
 pub mod attestor;
+pub mod batch;
+pub mod cache;
 pub mod chain;
+pub mod constraints;
+pub mod context;
 pub mod controller;
 pub mod data;
+pub mod dereference;
+pub mod dispatch;
 pub mod display;
+pub mod exchange;
 pub mod graph;
+pub mod holder;
 pub mod issuer;
 pub mod key_manager;
+pub mod methods;
+pub mod policy;
+pub mod presentation;
+#[cfg(feature = "redactable-signature")]
+pub mod redactablesignature;
 pub mod resolver;
+pub mod schema;
+pub mod status;
 pub mod subject;
 pub mod utils;
+pub mod vc;
 pub mod verifier;
-pub mod redactablesignature;
+pub mod wallet;
 
 // WASM
 use wasm_bindgen::prelude::*;
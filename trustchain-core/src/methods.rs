@@ -0,0 +1,313 @@
+//! Resolvers for the `did:key` and `did:web` DID methods. The `did:web` resolver retrieves
+//! documents over HTTP and requires the `http` feature.
+#[cfg(feature = "http")]
+use crate::resolver::{ERROR_INVALID_DID_DOCUMENT, ERROR_VERSION_NOT_AVAILABLE};
+use async_trait::async_trait;
+use serde_json::json;
+use ssi::did::Document;
+#[cfg(feature = "http")]
+use ssi::did_resolve::ERROR_NOT_FOUND;
+use ssi::did_resolve::{
+    DIDResolver, DocumentMetadata, ResolutionInputMetadata, ResolutionMetadata, ERROR_INVALID_DID,
+};
+use ssi::jwk::{Base64urlUInt, OctetParams, Params, JWK};
+
+/// Multicodec prefix of an Ed25519 public key.
+const MULTICODEC_ED25519_PUB: [u8; 2] = [0xed, 0x01];
+
+/// Multicodec prefix of a compressed secp256k1 public key.
+const MULTICODEC_SECP256K1_PUB: [u8; 2] = [0xe7, 0x01];
+
+/// Resolution metadata, document and document metadata.
+type Resolution = (
+    ResolutionMetadata,
+    Option<Document>,
+    Option<DocumentMetadata>,
+);
+
+/// Returns a resolution consisting only of the given error.
+fn resolution_error(error: &str) -> Resolution {
+    (ResolutionMetadata::from_error(error), None, None)
+}
+
+/// Returns a successful resolution of a document.
+fn resolution(document: Document) -> Resolution {
+    (
+        ResolutionMetadata::default(),
+        Some(document),
+        Some(DocumentMetadata::default()),
+    )
+}
+
+/// Resolver for `did:key` DIDs with Ed25519 or secp256k1 keys, generating the DID document from
+/// the public key encoded in the DID itself.
+#[derive(Debug, Default, Clone)]
+pub struct DIDKeyResolver;
+
+impl DIDKeyResolver {
+    /// Decodes the public key encoded in a `did:key` DID.
+    fn decode(did: &str) -> Option<JWK> {
+        let (_, data) = multibase::decode(did.strip_prefix("did:key:")?).ok()?;
+        if data.len() == 34 && data[..2] == MULTICODEC_ED25519_PUB {
+            Some(JWK::from(Params::OKP(OctetParams {
+                curve: "Ed25519".to_string(),
+                public_key: Base64urlUInt(data[2..].to_vec()),
+                private_key: None,
+            })))
+        } else if data.len() == 35 && data[..2] == MULTICODEC_SECP256K1_PUB {
+            ssi::jwk::secp256k1_parse(&data[2..]).ok()
+        } else {
+            None
+        }
+    }
+
+    /// Generates the DID document of a `did:key` DID.
+    fn document(did: &str, jwk: &JWK) -> Option<Document> {
+        let vm_id = format!("{}#{}", did, did.strip_prefix("did:key:")?);
+        serde_json::from_value(json!({
+            "@context": [
+                "https://www.w3.org/ns/did/v1",
+                "https://w3id.org/security/suites/jws-2020/v1"
+            ],
+            "id": did,
+            "verificationMethod": [{
+                "id": vm_id,
+                "type": "JsonWebKey2020",
+                "controller": did,
+                "publicKeyJwk": jwk
+            }],
+            "authentication": [vm_id],
+            "assertionMethod": [vm_id],
+            "capabilityInvocation": [vm_id],
+            "capabilityDelegation": [vm_id]
+        }))
+        .ok()
+    }
+}
+
+#[async_trait]
+impl DIDResolver for DIDKeyResolver {
+    async fn resolve(&self, did: &str, _: &ResolutionInputMetadata) -> Resolution {
+        // The document of a did:key DID is fixed, so is the same at any version.
+        match DIDKeyResolver::decode(did).and_then(|jwk| DIDKeyResolver::document(did, &jwk)) {
+            Some(document) => resolution(document),
+            None => resolution_error(ERROR_INVALID_DID),
+        }
+    }
+}
+
+/// Resolver for `did:web` DIDs, retrieving the DID document from the web domain in the DID.
+#[cfg(feature = "http")]
+#[derive(Debug, Clone)]
+pub struct DIDWebResolver {
+    scheme: String,
+}
+
+#[cfg(feature = "http")]
+impl Default for DIDWebResolver {
+    fn default() -> Self {
+        Self {
+            scheme: "https".to_string(),
+        }
+    }
+}
+
+#[cfg(feature = "http")]
+impl DIDWebResolver {
+    /// Makes a new `did:web` resolver retrieving documents over HTTPS.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the URL scheme used to retrieve documents (e.g. `http` for local testing).
+    pub fn with_scheme(mut self, scheme: &str) -> Self {
+        self.scheme = scheme.to_string();
+        self
+    }
+
+    /// Returns the URL of the DID document of a `did:web` DID.
+    pub fn document_url(&self, did: &str) -> Option<String> {
+        let mut parts = did.strip_prefix("did:web:")?.split(':');
+        let domain = parts.next().filter(|domain| !domain.is_empty())?;
+        // A port is included in the domain with a percent-encoded colon.
+        let domain = domain.replace("%3A", ":").replace("%3a", ":");
+        let path: Vec<&str> = parts.collect();
+        if path.iter().any(|segment| segment.is_empty()) {
+            return None;
+        }
+        if path.is_empty() {
+            Some(format!("{}://{}/.well-known/did.json", self.scheme, domain))
+        } else {
+            Some(format!(
+                "{}://{}/{}/did.json",
+                self.scheme,
+                domain,
+                path.join("/")
+            ))
+        }
+    }
+}
+
+#[cfg(feature = "http")]
+#[async_trait]
+impl DIDResolver for DIDWebResolver {
+    async fn resolve(&self, did: &str, input_metadata: &ResolutionInputMetadata) -> Resolution {
+        // Only the current document is published at a did:web URL.
+        if input_metadata.version_id.is_some() || input_metadata.version_time.is_some() {
+            return resolution_error(ERROR_VERSION_NOT_AVAILABLE);
+        }
+        let url = match self.document_url(did) {
+            Some(url) => url,
+            None => return resolution_error(ERROR_INVALID_DID),
+        };
+        let response = match reqwest::get(&url).await {
+            Ok(response) => response,
+            Err(err) => {
+                return resolution_error(&format!("Error sending HTTP request: {}", err));
+            }
+        };
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return resolution_error(ERROR_NOT_FOUND);
+        }
+        if !response.status().is_success() {
+            return resolution_error(&format!(
+                "Error sending HTTP request: {} responded with {}",
+                url,
+                response.status()
+            ));
+        }
+        let document: Document = match response.json().await {
+            Ok(document) => document,
+            Err(_) => return resolution_error(ERROR_INVALID_DID_DOCUMENT),
+        };
+        if document.id != did {
+            return resolution_error(ERROR_INVALID_DID_DOCUMENT);
+        }
+        resolution(document)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "http")]
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    #[cfg(feature = "http")]
+    use tokio::net::TcpListener;
+
+    // did:key test vectors from https://w3c-ccg.github.io/did-method-key/.
+    const DID_KEY_ED25519: &str = "did:key:z6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2doK";
+    const DID_KEY_SECP256K1: &str = "did:key:zQ3shokFTS3brHcDQrn82RUDfCZESWL1ZdCEJwekUDPQiYBme";
+
+    #[tokio::test]
+    async fn test_did_key() {
+        let resolver = DIDKeyResolver;
+        for did in [DID_KEY_ED25519, DID_KEY_SECP256K1] {
+            let (res_meta, doc, doc_meta) = resolver.resolve(did, &Default::default()).await;
+            assert!(res_meta.error.is_none());
+            assert!(doc_meta.is_some());
+            let doc = doc.unwrap();
+            assert_eq!(doc.id, did);
+            let doc = serde_json::to_value(&doc).unwrap();
+            let vm = &doc["verificationMethod"][0];
+            assert_eq!(vm["id"], json!(format!("{}#{}", did, &did[8..])));
+            assert!(vm["publicKeyJwk"].is_object());
+        }
+
+        let (res_meta, doc, _) = resolver
+            .resolve("did:key:zInvalid", &Default::default())
+            .await;
+        assert_eq!(res_meta.error, Some(ERROR_INVALID_DID.to_string()));
+        assert!(doc.is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "http")]
+    fn test_did_web_document_url() {
+        let resolver = DIDWebResolver::new();
+        assert_eq!(
+            resolver.document_url("did:web:w3c-ccg.github.io").unwrap(),
+            "https://w3c-ccg.github.io/.well-known/did.json"
+        );
+        assert_eq!(
+            resolver
+                .document_url("did:web:w3c-ccg.github.io:user:alice")
+                .unwrap(),
+            "https://w3c-ccg.github.io/user/alice/did.json"
+        );
+        assert_eq!(
+            resolver.document_url("did:web:example.com%3A3000").unwrap(),
+            "https://example.com:3000/.well-known/did.json"
+        );
+        assert!(resolver.document_url("did:web:").is_none());
+        assert!(resolver
+            .document_url("did:web:example.com::alice")
+            .is_none());
+        assert!(resolver.document_url("did:key:z6Mk").is_none());
+    }
+
+    /// Serves a single HTTP response on a local port, returning the `did:web` DID of the server
+    /// and making the response body from it.
+    #[cfg(feature = "http")]
+    async fn serve_once(status: &'static str, body: fn(&str) -> String) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let did = format!(
+            "did:web:127.0.0.1%3A{}",
+            listener.local_addr().unwrap().port()
+        );
+        let body = body(&did);
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buffer = [0u8; 1024];
+            let _ = socket.read(&mut buffer).await.unwrap();
+            let response = format!(
+                "HTTP/1.1 {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+        did
+    }
+
+    #[cfg(feature = "http")]
+    fn document_json(did: &str) -> String {
+        format!(
+            r##"{{"@context": "https://www.w3.org/ns/did/v1", "id": "{}"}}"##,
+            did
+        )
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "http")]
+    async fn test_did_web() {
+        let resolver = DIDWebResolver::new().with_scheme("http");
+
+        let did = serve_once("200 OK", document_json).await;
+        let (res_meta, doc, _) = resolver.resolve(&did, &Default::default()).await;
+        assert!(res_meta.error.is_none());
+        assert_eq!(doc.unwrap().id, did);
+
+        // A served document for a different DID is rejected.
+        let did = serve_once("200 OK", |_| document_json("did:web:example.com")).await;
+        let (res_meta, doc, _) = resolver.resolve(&did, &Default::default()).await;
+        assert_eq!(res_meta.error, Some(ERROR_INVALID_DID_DOCUMENT.to_string()));
+        assert!(doc.is_none());
+
+        let did = serve_once("404 Not Found", |_| String::new()).await;
+        let (res_meta, _, _) = resolver.resolve(&did, &Default::default()).await;
+        assert_eq!(res_meta.error, Some(ERROR_NOT_FOUND.to_string()));
+
+        // Historical versions are not available.
+        let input = ResolutionInputMetadata {
+            version_time: Some("2022-01-01T00:00:00Z".to_string()),
+            ..Default::default()
+        };
+        let (res_meta, _, _) = resolver.resolve("did:web:example.com", &input).await;
+        assert_eq!(
+            res_meta.error,
+            Some(ERROR_VERSION_NOT_AVAILABLE.to_string())
+        );
+    }
+}
@@ -0,0 +1,333 @@
+//! Issuer authorisation policies, restricting the credential types that may be issued by DIDs to
+//! those in given subtrees of the trust graph.
+//!
+//! A policy is written in JSON as a list of rules, each authorising the DIDs downstream of an
+//! upstream DID (at the given levels below it) to issue credentials of the given types:
+//! ```json
+//! {
+//!   "rules": [
+//!     {
+//!       "credentialTypes": ["DegreeCredential"],
+//!       "upstream": "did:ion:test:EiUniversity",
+//!       "levels": [1]
+//!     }
+//!   ],
+//!   "denyUnlisted": false
+//! }
+//! ```
+//! Here only the direct children of the university's DID may issue a `DegreeCredential`, while
+//! credential types governed by no rule may be issued by any issuer unless `denyUnlisted` is set.
+use crate::chain::Chain;
+use serde::{Deserialize, Serialize};
+use ssi::vc::Credential;
+use std::fs::File;
+use std::path::Path;
+use thiserror::Error;
+
+/// Base type of every credential, which is not governed by the rules of a policy.
+const VERIFIABLE_CREDENTIAL: &str = "VerifiableCredential";
+
+/// An error relating to an issuer authorisation policy.
+#[derive(Error, Debug)]
+pub enum PolicyError {
+    /// Failed to read a policy file.
+    #[error("Failed to read policy file: {0}")]
+    FailedToRead(std::io::Error),
+    /// Failed to parse a policy.
+    #[error("Failed to parse policy: {0}")]
+    FailedToParse(serde_json::Error),
+    /// Rule of a policy has no credential types.
+    #[error("Policy rule {0} has no credential types.")]
+    NoCredentialTypes(usize),
+}
+
+/// A reason that an issuer is not authorised by a policy to issue a credential.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PolicyViolation {
+    /// Issuer is not downstream of any upstream DID authorised to issue the credential type.
+    #[error("Issuer: {1} is not downstream of a DID authorised to issue {0}: {2:?}")]
+    NotDownstream(String, String, Vec<String>),
+    /// Issuer is downstream of an authorised upstream DID, but not at an authorised level.
+    #[error("Issuer: {1} is {3} level(s) below {2}, which is not authorised to issue {0}.")]
+    InvalidLevel(String, String, String, usize),
+    /// No rule governs the credential type, which the policy denies.
+    #[error("No rule authorises the issue of {0}, which is denied by the policy.")]
+    Unlisted(String),
+}
+
+/// A rule authorising the DIDs downstream of an upstream DID to issue credentials of the given
+/// types.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IssuerRule {
+    /// Credential types governed by the rule.
+    pub credential_types: Vec<String>,
+    /// DID under which issuers are authorised, which must be in the issuer's DID chain.
+    pub upstream: String,
+    /// Levels below the upstream DID at which issuers are authorised (e.g. `[1]` for its
+    /// children only, with `0` for the upstream DID itself), or any level below it if `None`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub levels: Option<Vec<usize>>,
+}
+
+/// Policy authorising issuers by the credential types they issue and their DID chains.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IssuerPolicy {
+    /// Rules of the policy.
+    pub rules: Vec<IssuerRule>,
+    /// Whether to deny credential types governed by no rule.
+    #[serde(default)]
+    pub deny_unlisted: bool,
+}
+
+impl IssuerRule {
+    /// Returns whether the rule governs a credential type.
+    fn governs(&self, credential_type: &str) -> bool {
+        self.credential_types
+            .iter()
+            .any(|governed| governed == credential_type)
+    }
+
+    /// Returns the level of the issuer below the upstream DID, if in the chain above the issuer.
+    fn level<C: Chain>(&self, chain: &C) -> Option<usize> {
+        let upstream_level = chain.level(&self.upstream)?;
+        chain.level(chain.leaf())?.checked_sub(upstream_level)
+    }
+
+    /// Returns whether the rule authorises an issuer at the given level below the upstream DID.
+    fn permits_level(&self, level: usize) -> bool {
+        match &self.levels {
+            Some(levels) => levels.contains(&level),
+            None => level > 0,
+        }
+    }
+}
+
+impl IssuerPolicy {
+    /// Reads a policy from a JSON file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, PolicyError> {
+        let file = File::open(path).map_err(PolicyError::FailedToRead)?;
+        let policy: Self = serde_json::from_reader(file).map_err(PolicyError::FailedToParse)?;
+        policy.validate()?;
+        Ok(policy)
+    }
+
+    /// Checks that every rule governs at least one credential type.
+    pub fn validate(&self) -> Result<(), PolicyError> {
+        match self
+            .rules
+            .iter()
+            .position(|rule| rule.credential_types.is_empty())
+        {
+            Some(index) => Err(PolicyError::NoCredentialTypes(index)),
+            None => Ok(()),
+        }
+    }
+
+    /// Evaluates whether the issuer at the leaf of a DID chain is authorised to issue a
+    /// credential, returning a violation for each credential type it is not authorised to issue.
+    /// An issuer is authorised to issue a type if any rule governing the type authorises it.
+    pub fn evaluate<C: Chain>(
+        &self,
+        credential: &Credential,
+        chain: &C,
+    ) -> Result<(), Vec<PolicyViolation>> {
+        let issuer = chain.leaf();
+        let violations: Vec<PolicyViolation> = (&credential.type_)
+            .into_iter()
+            .filter(|credential_type| *credential_type != VERIFIABLE_CREDENTIAL)
+            .filter_map(|credential_type| {
+                let rules: Vec<&IssuerRule> = self
+                    .rules
+                    .iter()
+                    .filter(|rule| rule.governs(credential_type.as_str()))
+                    .collect();
+                if rules.is_empty() {
+                    return match self.deny_unlisted {
+                        true => Some(PolicyViolation::Unlisted(credential_type.to_owned())),
+                        false => None,
+                    };
+                }
+                let levels: Vec<(&IssuerRule, usize)> = rules
+                    .iter()
+                    .filter_map(|rule| rule.level(chain).map(|level| (*rule, level)))
+                    .collect();
+                if levels
+                    .iter()
+                    .any(|(rule, level)| rule.permits_level(*level))
+                {
+                    return None;
+                }
+                // Report the level below the closest authorised upstream DID, if any.
+                Some(match levels.iter().min_by_key(|(_, level)| *level) {
+                    Some((rule, level)) => PolicyViolation::InvalidLevel(
+                        credential_type.to_owned(),
+                        issuer.to_owned(),
+                        rule.upstream.to_owned(),
+                        *level,
+                    ),
+                    None => PolicyViolation::NotDownstream(
+                        credential_type.to_owned(),
+                        issuer.to_owned(),
+                        rules.iter().map(|rule| rule.upstream.to_owned()).collect(),
+                    ),
+                })
+            })
+            .collect();
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::DIDChain;
+    use crate::data::{TEST_CREDENTIAL, TEST_DID_CHAIN};
+    use ssi::one_or_many::OneOrMany;
+
+    fn credential(types: &[&str]) -> Credential {
+        let mut credential: Credential = serde_json::from_str(TEST_CREDENTIAL).unwrap();
+        credential.type_ = OneOrMany::Many(types.iter().map(|t| t.to_string()).collect());
+        credential
+    }
+
+    #[test]
+    fn test_deserialize_policy() {
+        let policy: IssuerPolicy = serde_json::from_str(
+            r#"{"rules": [{"credentialTypes": ["DegreeCredential"], "upstream": "did:example:a"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(policy.rules[0].levels, None);
+        assert!(!policy.deny_unlisted);
+        assert!(policy.validate().is_ok());
+
+        let invalid: IssuerPolicy = serde_json::from_str(
+            r#"{"rules": [{"credentialTypes": [], "upstream": "did:example:a"}]}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            invalid.validate(),
+            Err(PolicyError::NoCredentialTypes(0))
+        ));
+    }
+
+    #[test]
+    fn test_from_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("policy.json");
+        std::fs::write(
+            &path,
+            r#"{"rules": [{"credentialTypes": ["DegreeCredential"], "upstream": "did:example:a", "levels": [1]}]}"#,
+        )
+        .unwrap();
+        let policy = IssuerPolicy::from_file(&path).unwrap();
+        assert_eq!(policy.rules[0].levels, Some(vec![1]));
+
+        // Policies are validated when read.
+        std::fs::write(
+            &path,
+            r#"{"rules": [{"credentialTypes": [], "upstream": "did:example:a"}]}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            IssuerPolicy::from_file(&path),
+            Err(PolicyError::NoCredentialTypes(0))
+        ));
+        std::fs::write(&path, r#"{"rules": {}}"#).unwrap();
+        assert!(matches!(
+            IssuerPolicy::from_file(&path),
+            Err(PolicyError::FailedToParse(_))
+        ));
+        assert!(matches!(
+            IssuerPolicy::from_file(dir.path().join("missing.json")),
+            Err(PolicyError::FailedToRead(_))
+        ));
+    }
+
+    #[test]
+    fn test_evaluate() {
+        // root - root-plus-1 - root-plus-2 (the issuer)
+        let chain: DIDChain = serde_json::from_str(TEST_DID_CHAIN).unwrap();
+        let dids = chain.level_vec().clone();
+        let (root, root_plus_1, issuer) = (&dids[0], &dids[1], &dids[2]);
+        let rule = |types: &[&str], upstream: &str, levels: Option<Vec<usize>>| IssuerRule {
+            credential_types: types.iter().map(|t| t.to_string()).collect(),
+            upstream: upstream.to_owned(),
+            levels,
+        };
+        let degree = credential(&["VerifiableCredential", "DegreeCredential"]);
+
+        // Ungoverned types are allowed unless denied.
+        let mut policy = IssuerPolicy::default();
+        assert_eq!(policy.evaluate(&degree, &chain), Ok(()));
+        policy.deny_unlisted = true;
+        assert_eq!(
+            policy.evaluate(&degree, &chain),
+            Err(vec![PolicyViolation::Unlisted(
+                "DegreeCredential".to_string()
+            )])
+        );
+
+        // Issuers at any level below the upstream DID, or at the given levels.
+        let policy = IssuerPolicy {
+            rules: vec![rule(&["DegreeCredential"], root, None)],
+            deny_unlisted: true,
+        };
+        assert_eq!(policy.evaluate(&degree, &chain), Ok(()));
+        let policy = IssuerPolicy {
+            rules: vec![rule(&["DegreeCredential"], root_plus_1, Some(vec![1]))],
+            deny_unlisted: false,
+        };
+        assert_eq!(policy.evaluate(&degree, &chain), Ok(()));
+        let policy = IssuerPolicy {
+            rules: vec![rule(&["DegreeCredential"], root, Some(vec![1]))],
+            deny_unlisted: false,
+        };
+        assert_eq!(
+            policy.evaluate(&degree, &chain),
+            Err(vec![PolicyViolation::InvalidLevel(
+                "DegreeCredential".to_string(),
+                issuer.to_string(),
+                root.to_string(),
+                2
+            )])
+        );
+
+        // The issuer itself is not below its own DID unless level 0 is authorised.
+        let policy = IssuerPolicy {
+            rules: vec![rule(&["DegreeCredential"], issuer, None)],
+            deny_unlisted: false,
+        };
+        assert!(policy.evaluate(&degree, &chain).is_err());
+        let policy = IssuerPolicy {
+            rules: vec![rule(&["DegreeCredential"], issuer, Some(vec![0]))],
+            deny_unlisted: false,
+        };
+        assert_eq!(policy.evaluate(&degree, &chain), Ok(()));
+
+        // Any governing rule may authorise the issuer, and each type is evaluated.
+        let policy = IssuerPolicy {
+            rules: vec![
+                rule(&["DegreeCredential"], "did:example:other", None),
+                rule(&["DegreeCredential"], root_plus_1, None),
+                rule(&["TranscriptCredential"], "did:example:other", None),
+            ],
+            deny_unlisted: false,
+        };
+        assert_eq!(policy.evaluate(&degree, &chain), Ok(()));
+        let transcript = credential(&["DegreeCredential", "TranscriptCredential"]);
+        assert_eq!(
+            policy.evaluate(&transcript, &chain),
+            Err(vec![PolicyViolation::NotDownstream(
+                "TranscriptCredential".to_string(),
+                issuer.to_string(),
+                vec!["did:example:other".to_string()]
+            )])
+        );
+    }
+}
@@ -0,0 +1,228 @@
+//! Verification of Verifiable Presentations with Trustchain.
+use crate::chain::DIDChain;
+use crate::context::inline_presentation_contexts;
+use crate::policy::{IssuerPolicy, PolicyViolation};
+use crate::vc::{
+    verify_credential, verify_credential_jwt, AuthorisationCheck, CredentialError,
+    CredentialSchemaCheck, CredentialStatusCheck, CredentialVerificationPolicy, ValidityPeriod,
+};
+use crate::verifier::{Verifier, VerifierError};
+use ssi::did_resolve::DIDResolver;
+use ssi::vc::{
+    Credential, CredentialOrJWT, LinkedDataProofOptions, Presentation, ProofPurpose, URI,
+};
+use thiserror::Error;
+
+/// An error relating to the verification of a presentation.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum PresentationError {
+    /// Presentation has no holder.
+    #[error("Presentation has no holder.")]
+    NoHolder,
+    /// Presentation contains no credentials.
+    #[error("Presentation contains no credentials.")]
+    NoCredentials,
+    /// Invalid presentation proof.
+    #[error("Invalid presentation proof: {0:?}")]
+    InvalidProof(Vec<String>),
+    /// Credential in the presentation is an invalid JWT.
+    #[error("Credential {0} in the presentation is an invalid JWT: {1}")]
+    InvalidCredentialJWT(usize, String),
+    /// Invalid proof in a credential in the presentation.
+    #[error("Invalid proof in credential {0}: {1:?}")]
+    InvalidCredentialProof(usize, Vec<String>),
+    /// Credential in the presentation has no issuer.
+    #[error("Credential {0} in the presentation has no issuer.")]
+    NoIssuer(usize),
+    /// Trustchain verification of the issuer of a credential failed.
+    #[error("Trustchain verification of the issuer of credential {0} failed: {1}")]
+    IssuerVerification(usize, VerifierError),
+    /// Trustchain verification of the holder failed.
+    #[error("Trustchain verification of the holder failed: {0}")]
+    HolderVerification(VerifierError),
+    /// Holder is not a subject of a credential in the presentation.
+    #[error("Holder: {0} is not a subject of credential {1}.")]
+    HolderNotSubject(String, usize),
+    /// Credential in the presentation is outside its validity period.
+    #[error("Credential {0} in the presentation is outside its validity period: {1:?}")]
+    InvalidValidityPeriod(usize, ValidityPeriod),
+    /// Credential in the presentation is revoked or suspended, or its status could not be checked.
+    #[error("Credential {0} in the presentation has invalid status: {1:?}")]
+    InvalidStatus(usize, CredentialStatusCheck),
+    /// Credential in the presentation does not conform to its schemas, or they could not be
+    /// checked.
+    #[error("Credential {0} in the presentation has invalid schema: {1:?}")]
+    InvalidSchema(usize, CredentialSchemaCheck),
+    /// Issuer of a credential in the presentation is not authorised by the issuer policy.
+    #[error("Issuer of credential {0} in the presentation is not authorised: {1:?}")]
+    Unauthorised(usize, Vec<PolicyViolation>),
+}
+
+/// The DID chains of a verified presentation's holder and of the issuer of each of its
+/// credentials.
+#[derive(Debug, Clone)]
+pub struct VerifiedPresentation {
+    /// DID chain of the holder.
+    pub holder_chain: DIDChain,
+    /// DID chain of the issuer of each credential, in order of presentation.
+    pub issuer_chains: Vec<DIDChain>,
+}
+
+/// Returns whether the given DID is the `id` of a subject of the credential.
+pub fn is_subject(credential: &Credential, did: &str) -> bool {
+    credential
+        .credential_subject
+        .any(|subject| matches!(&subject.id, Some(URI::String(id)) if id == did))
+}
+
+/// Verifies a presentation: the holder's proof (bound to the given challenge and domain, if
+/// any), every credential it contains as by [`verify_credential`] (including its status and
+/// schemas), that
+/// the holder is a subject of every credential, and the Trustchain DID chain of the holder.
+pub fn verify_presentation<T, V>(
+    presentation: &Presentation,
+    challenge: Option<&str>,
+    domain: Option<&str>,
+    root_event_time: u32,
+    verifier: &V,
+) -> Result<VerifiedPresentation, PresentationError>
+where
+    T: DIDResolver + Sync + Send,
+    V: Verifier<T>,
+{
+    verify_presentation_with_policy(
+        presentation,
+        challenge,
+        domain,
+        root_event_time,
+        None,
+        verifier,
+    )
+}
+
+/// Verifies a presentation as for [`verify_presentation`], and that the issuer of every
+/// credential is authorised by the issuer policy, if given.
+pub fn verify_presentation_with_policy<T, V>(
+    presentation: &Presentation,
+    challenge: Option<&str>,
+    domain: Option<&str>,
+    root_event_time: u32,
+    issuer_policy: Option<&IssuerPolicy>,
+    verifier: &V,
+) -> Result<VerifiedPresentation, PresentationError>
+where
+    T: DIDResolver + Sync + Send,
+    V: Verifier<T>,
+{
+    let holder = match &presentation.holder {
+        Some(URI::String(holder)) => holder.as_str(),
+        None => return Err(PresentationError::NoHolder),
+    };
+    let credentials = match &presentation.verifiable_credential {
+        Some(credentials) if !credentials.is_empty() => credentials,
+        _ => return Err(PresentationError::NoCredentials),
+    };
+    let resolver = verifier.resolver();
+
+    // Verify the holder's proof of the presentation.
+    let options = LinkedDataProofOptions {
+        proof_purpose: Some(ProofPurpose::Authentication),
+        challenge: challenge.map(str::to_owned),
+        domain: domain.map(str::to_owned),
+        ..Default::default()
+    };
+    let result = resolver
+        .runtime
+        .block_on(inline_presentation_contexts(presentation).verify(Some(options), resolver));
+    if !result.errors.is_empty() {
+        return Err(PresentationError::InvalidProof(result.errors));
+    }
+
+    // Verify each credential, its binding to the holder and its issuer.
+    let mut issuer_chains = Vec::new();
+    for (index, credential) in credentials.into_iter().enumerate() {
+        let policy = CredentialVerificationPolicy {
+            issuer_policy: issuer_policy.cloned(),
+            ..CredentialVerificationPolicy::new(root_event_time)
+        };
+        let (credential, result) = match credential {
+            CredentialOrJWT::Credential(credential) => (
+                credential.clone(),
+                verify_credential(credential, verifier, &policy),
+            ),
+            CredentialOrJWT::JWT(jwt) => match verify_credential_jwt(jwt, verifier, &policy) {
+                Ok(verified) => verified,
+                Err(e) => {
+                    return Err(PresentationError::InvalidCredentialJWT(
+                        index,
+                        e.to_string(),
+                    ))
+                }
+            },
+        };
+        if !result.proof_is_valid() {
+            return Err(PresentationError::InvalidCredentialProof(
+                index,
+                result.proof.errors,
+            ));
+        }
+        if !is_subject(&credential, holder) {
+            return Err(PresentationError::HolderNotSubject(
+                holder.to_owned(),
+                index,
+            ));
+        }
+        if result.validity != ValidityPeriod::Valid {
+            return Err(PresentationError::InvalidValidityPeriod(
+                index,
+                result.validity,
+            ));
+        }
+        if !result.status.is_valid() {
+            return Err(PresentationError::InvalidStatus(index, result.status));
+        }
+        if !result.schema.is_valid() {
+            return Err(PresentationError::InvalidSchema(index, result.schema));
+        }
+        let chain = match result.issuer_chain {
+            Some(Ok(chain)) => chain,
+            Some(Err(CredentialError::IssuerVerification(e))) => {
+                return Err(PresentationError::IssuerVerification(index, e))
+            }
+            Some(Err(CredentialError::NoIssuer)) | None => {
+                return Err(PresentationError::NoIssuer(index))
+            }
+        };
+        if let AuthorisationCheck::Unauthorised(violations) = result.authorisation {
+            return Err(PresentationError::Unauthorised(index, violations));
+        }
+        issuer_chains.push(chain);
+    }
+
+    // Verify the holder.
+    let holder_chain = verifier
+        .verify(holder, root_event_time)
+        .map_err(PresentationError::HolderVerification)?;
+
+    Ok(VerifiedPresentation {
+        holder_chain,
+        issuer_chains,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::TEST_CREDENTIAL;
+
+    #[test]
+    fn test_is_subject() {
+        let mut credential: Credential = serde_json::from_str(TEST_CREDENTIAL).unwrap();
+        let subject_did = "did:example:holder";
+        assert!(!is_subject(&credential, subject_did));
+        credential.credential_subject.to_single_mut().unwrap().id =
+            Some(URI::String(subject_did.to_string()));
+        assert!(is_subject(&credential, subject_did));
+        assert!(!is_subject(&credential, "did:example:other"));
+    }
+}
@@ -1,46 +1,913 @@
-use ssi::vc::Credential;
+//! Redactable signatures for the selective disclosure of credential attributes, following
+//! Sanders, "Efficient Redactable Signature and Application to Anonymous Credentials" (PKC 2020),
+//! built on Pointcheval–Sanders signatures over the BLS12-381 pairing.
+//!
+//! An issuer signs a vector of attributes. A holder can then derive, without the issuer's secret
+//! key, a fresh signature on any subset of the attributes that verifies without revealing the
+//! others, and that is unlinkable to the original signature.
+use crate::vc::issuer_did;
+use ff_zeroize::{Field, PrimeField, PrimeFieldRepr};
+use pairing_plus::bls12_381::{Bls12, Fr, FrRepr, G1, G2};
+use pairing_plus::serdes::SerDes;
+use pairing_plus::{CurveProjective, Engine};
+use rand_core::{CryptoRng, RngCore};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha384};
+use ssi::did::{VerificationMethod, VerificationMethodMap};
+use ssi::did_resolve::DIDResolver;
+use ssi::jwk::{Base64urlUInt, OctetParams, Params, JWK};
+use ssi::one_or_many::OneOrMany;
+use ssi::vc::{Credential, Proof};
+use std::collections::{BTreeMap, BTreeSet};
 use thiserror::Error;
-use ssi::jwk::JWK;
-use amcl_wrapper::field_elem::FieldElement;
-use amcl_wrapper::group_elem::GroupElement;
 
-// pk = (X, (Y,Y'i)i in I, {Z_ij})
-pub struct PublicKey{ 
-    pub X: FieldElement,
-    pub Y_Pair: Vec<Vec<FieldElement>>,
-    pub Z_i: Vec<Vec<FieldElement>>
+/// Curve name of the JWK of a redactable signature key.
+pub const REDACTABLE_SIGNATURE_KEY_CURVE: &str = "BLS12381PS";
+
+/// Type of a credential proof with a redactable signature.
+pub const REDACTABLE_SIGNATURE_PROOF_TYPE: &str = "PSRedactableSignature2020";
+
+/// Domain separation tag for hashing messages to scalars.
+const MESSAGE_DST: &[u8] = b"TRUSTCHAIN-PS-RS-MESSAGE";
+
+/// Domain separation tag for hashing derived signatures to challenges.
+const CHALLENGE_DST: &[u8] = b"TRUSTCHAIN-PS-RS-CHALLENGE";
+
+/// Length of a compressed G1 element.
+const G1_LENGTH: usize = 48;
+
+/// Length of a compressed G2 element.
+const G2_LENGTH: usize = 96;
+
+/// Length of a scalar.
+const FR_LENGTH: usize = 32;
+
+/// An error relating to redactable signatures.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum RedactableSignatureError {
+    /// Number of messages does not match the length of the key.
+    #[error("Expected {0} messages but found {1}.")]
+    InvalidMessageCount(usize, usize),
+    /// Message index out of range of the key.
+    #[error("Message index: {0} is out of range for a key of length: {1}.")]
+    InvalidIndex(usize, usize),
+    /// Signature does not verify.
+    #[error("Invalid redactable signature.")]
+    InvalidSignature,
+    /// Failed to decode a key or signature.
+    #[error("Failed to decode {0}.")]
+    FailedToDecode(String),
+    /// Invalid key.
+    #[error("Invalid redactable signature key: {0}")]
+    InvalidKey(String),
+    /// Credential cannot be signed, redacted or verified with a redactable signature.
+    #[error("Invalid credential for redactable signature: {0}")]
+    InvalidCredential(String),
+    /// Failed to resolve the verification method of a proof.
+    #[error("Failed to resolve verification method: {0}")]
+    FailedToResolve(String),
+}
+
+/// Secret key `(x, y_1, ..., y_n)` for signing vectors of `n` messages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecretKey {
+    pub x: Fr,
+    pub y: Vec<Fr>,
+}
+
+/// Public key `(X, (Y_i, Y~_i), (Z_ij) for i != j)`, with `X = g^x`, `Y_i = g^y_i`,
+/// `Y~_i = g~^y_i` and `Z_ij = g^(y_i y_j)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublicKey {
+    pub x: G1,
+    pub y: Vec<G1>,
+    pub y_tilde: Vec<G2>,
+    /// `Z_ij` at `z[i][j]`, with the unused diagonal set to the identity.
+    pub z: Vec<Vec<G1>>,
+}
+
+/// A redactable signature `(sigma_1, sigma_2, sigma~_1, sigma~_2)`. A signature by the issuer
+/// has `sigma_1` and `sigma_2` the identity.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    pub sigma_1: G1,
+    pub sigma_2: G1,
+    pub sigma_tilde_1: G2,
+    pub sigma_tilde_2: G2,
+}
+
+/// Multiplies a group element by a scalar.
+fn mul<G: CurveProjective<Scalar = Fr>>(point: &G, scalar: &Fr) -> G {
+    let mut point = *point;
+    point.mul_assign(*scalar);
+    point
+}
+
+/// Returns a random non-zero scalar.
+fn random_nonzero<R: RngCore + CryptoRng>(rng: &mut R) -> Fr {
+    loop {
+        let scalar = Fr::random(rng);
+        if !scalar.is_zero() {
+            return scalar;
+        }
+    }
+}
+
+/// Returns the scalar encoded by at most 31 big-endian bytes (less than the group order).
+fn scalar_from_be(bytes: &[u8]) -> Fr {
+    let mut padded = [0u8; FR_LENGTH];
+    padded[FR_LENGTH - bytes.len()..].copy_from_slice(bytes);
+    let mut repr = FrRepr::default();
+    repr.read_be(&padded[..]).unwrap();
+    Fr::from_repr(repr).unwrap()
+}
+
+/// Hashes data to a scalar, reducing a 384-bit digest `a + b 2^248` with 248-bit `a`.
+fn hash_to_scalar(dst: &[u8], data: &[u8]) -> Fr {
+    let digest = Sha384::new()
+        .chain_update(dst)
+        .chain_update(data)
+        .finalize();
+    let (b, a) = digest.split_at(digest.len() - 31);
+    let mut scalar = scalar_from_be(b);
+    scalar.mul_assign(&Fr::from_repr(FrRepr([0, 0, 0, 1 << 56])).unwrap());
+    scalar.add_assign(&scalar_from_be(a));
+    scalar
 }
-// sk = (x,y1,…,yn)
-pub struct SecretKey{
-    pub x: FieldElement,
-    pub y: Vec<FieldElement>
+
+/// Hashes a message to the scalar that is signed.
+pub fn message_to_scalar(message: &[u8]) -> Fr {
+    hash_to_scalar(MESSAGE_DST, message)
 }
-// sig = (identity, identity, sigma1, sigma2)
-pub struct PSSignature { 
-    pub sigma_1: SignatureGroup,
-    pub sigma_2: SignatureGroup,
-    pub sigma_prime_1: SignatureGroup,
-    pub sigma__prime2: SignatureGroup,
+
+/// Returns the challenges `c_i` for the disclosed indices of a derived signature.
+fn challenges(
+    sigma_1: &G1,
+    sigma_tilde_1: &G2,
+    sigma_tilde_2: &G2,
+    disclosed: &BTreeSet<usize>,
+) -> Vec<Fr> {
+    let mut data = Vec::new();
+    serialize(sigma_1, &mut data);
+    serialize(sigma_tilde_1, &mut data);
+    serialize(sigma_tilde_2, &mut data);
+    for index in disclosed {
+        data.extend_from_slice(&(*index as u32).to_be_bytes());
+    }
+    disclosed
+        .iter()
+        .map(|index| {
+            let mut data = data.clone();
+            data.extend_from_slice(&(*index as u32).to_be_bytes());
+            hash_to_scalar(CHALLENGE_DST, &data)
+        })
+        .collect()
 }
 
-pub struct Param {
-    pub g: SignatureGroup,
-    pub g_tilde: VerkeyGroup,
+/// Appends the compressed encoding of an element.
+fn serialize<T: SerDes>(element: &T, bytes: &mut Vec<u8>) {
+    element.serialize(bytes, true).unwrap();
 }
 
+/// Reads the compressed encoding of an element.
+fn deserialize<T: SerDes>(reader: &mut &[u8], name: &str) -> Result<T, RedactableSignatureError> {
+    T::deserialize(reader, true)
+        .map_err(|_| RedactableSignatureError::FailedToDecode(name.to_owned()))
+}
 
-pub fn GenerateParam(label: &[u8])->Self{
-    let g = SignatureGroup::from_msg_hash(&[label, " : g".as_bytes()].concat());
-    let g_tilde = VerkeyGroup::from_msg_hash(&[label, " : g_tilde".as_bytes()].concat());
-    Self {g, g_tilde}
+/// Generates a key pair for signing vectors of the given number of messages.
+pub fn keygen<R: RngCore + CryptoRng>(length: usize, rng: &mut R) -> (SecretKey, PublicKey) {
+    let secret_key = SecretKey {
+        x: Fr::random(rng),
+        y: (0..length).map(|_| Fr::random(rng)).collect(),
+    };
+    let public_key = secret_key.public_key();
+    (secret_key, public_key)
 }
 
+impl SecretKey {
+    /// Returns the number of messages signed with the key.
+    pub fn len(&self) -> usize {
+        self.y.len()
+    }
+
+    /// Returns whether the key signs no messages.
+    pub fn is_empty(&self) -> bool {
+        self.y.is_empty()
+    }
+
+    /// Returns the public key.
+    pub fn public_key(&self) -> PublicKey {
+        let z = self
+            .y
+            .iter()
+            .enumerate()
+            .map(|(i, y_i)| {
+                self.y
+                    .iter()
+                    .enumerate()
+                    .map(|(j, y_j)| {
+                        if i == j {
+                            return G1::zero();
+                        }
+                        let mut y_ij = *y_i;
+                        y_ij.mul_assign(y_j);
+                        mul(&G1::one(), &y_ij)
+                    })
+                    .collect()
+            })
+            .collect();
+        PublicKey {
+            x: mul(&G1::one(), &self.x),
+            y: self.y.iter().map(|y_i| mul(&G1::one(), y_i)).collect(),
+            y_tilde: self.y.iter().map(|y_i| mul(&G2::one(), y_i)).collect(),
+            z,
+        }
+    }
+
+    /// Signs a vector of messages: `sigma~_1 = g~^r` and `sigma~_2 = (sigma~_1)^(x + sum y_i m_i)`.
+    pub fn sign<R: RngCore + CryptoRng>(
+        &self,
+        messages: &[Fr],
+        rng: &mut R,
+    ) -> Result<Signature, RedactableSignatureError> {
+        if messages.len() != self.len() {
+            return Err(RedactableSignatureError::InvalidMessageCount(
+                self.len(),
+                messages.len(),
+            ));
+        }
+        let mut exponent = self.x;
+        for (y_i, m_i) in self.y.iter().zip(messages) {
+            let mut term = *y_i;
+            term.mul_assign(m_i);
+            exponent.add_assign(&term);
+        }
+        let sigma_tilde_1 = mul(&G2::one(), &random_nonzero(rng));
+        Ok(Signature {
+            sigma_1: G1::zero(),
+            sigma_2: G1::zero(),
+            sigma_tilde_2: mul(&sigma_tilde_1, &exponent),
+            sigma_tilde_1,
+        })
+    }
+
+    /// Encodes the key as `x || y_1 || ... || y_n`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        serialize(&self.x, &mut bytes);
+        self.y.iter().for_each(|y_i| serialize(y_i, &mut bytes));
+        bytes
+    }
+
+    /// Decodes a key encoded by [`SecretKey::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, RedactableSignatureError> {
+        if bytes.is_empty() || !bytes.len().is_multiple_of(FR_LENGTH) {
+            return Err(RedactableSignatureError::FailedToDecode(
+                "secret key".to_owned(),
+            ));
+        }
+        let reader = &mut &bytes[..];
+        let x = deserialize(reader, "secret key")?;
+        let y = (1..bytes.len() / FR_LENGTH)
+            .map(|_| deserialize(reader, "secret key"))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { x, y })
+    }
+
+    /// Returns the key pair as an `OKP` JWK.
+    pub fn to_jwk(&self) -> JWK {
+        let mut jwk = self.public_key().to_jwk();
+        if let Params::OKP(params) = &mut jwk.params {
+            params.private_key = Some(Base64urlUInt(self.to_bytes()));
+        }
+        jwk
+    }
+
+    /// Returns the secret key of a JWK.
+    pub fn from_jwk(jwk: &JWK) -> Result<Self, RedactableSignatureError> {
+        match &jwk.params {
+            Params::OKP(OctetParams {
+                curve,
+                private_key: Some(private_key),
+                ..
+            }) if curve == REDACTABLE_SIGNATURE_KEY_CURVE => Self::from_bytes(&private_key.0),
+            _ => Err(RedactableSignatureError::InvalidKey(
+                "expected an OKP JWK with a private key on curve BLS12381PS".to_owned(),
+            )),
+        }
+    }
+}
+
+impl PublicKey {
+    /// Returns the number of messages signed with the key.
+    pub fn len(&self) -> usize {
+        self.y.len()
+    }
+
+    /// Returns whether the key signs no messages.
+    pub fn is_empty(&self) -> bool {
+        self.y.is_empty()
+    }
+
+    /// Checks that the indices are in range of the key.
+    fn check_indices<'a, I>(&self, indices: I) -> Result<(), RedactableSignatureError>
+    where
+        I: IntoIterator<Item = &'a usize>,
+    {
+        match indices.into_iter().find(|index| **index >= self.len()) {
+            Some(index) => Err(RedactableSignatureError::InvalidIndex(*index, self.len())),
+            None => Ok(()),
+        }
+    }
+
+    /// Derives a signature on the messages at the disclosed indices from a signature on all of
+    /// the messages. With random `r` and `t`, and `I` the disclosed indices:
+    /// - `sigma_1 = g^t prod_{j not in I} Y_j^m_j`,
+    /// - `sigma~_1 = (sigma~_1)^r` and `sigma~_2 = (sigma~_2 (sigma~_1)^t)^r`,
+    /// - `sigma_2 = prod_{i in I} (Y_i^t prod_{j not in I} Z_ij^m_j)^c_i`, with challenges `c_i`
+    ///   hashed from the rest of the signature.
+    pub fn derive<R: RngCore + CryptoRng>(
+        &self,
+        signature: &Signature,
+        messages: &[Fr],
+        disclosed: &BTreeSet<usize>,
+        rng: &mut R,
+    ) -> Result<Signature, RedactableSignatureError> {
+        if messages.len() != self.len() {
+            return Err(RedactableSignatureError::InvalidMessageCount(
+                self.len(),
+                messages.len(),
+            ));
+        }
+        self.check_indices(disclosed)?;
+        self.verify(signature, &messages.iter().copied().enumerate().collect())?;
+
+        let (r, t) = (random_nonzero(rng), Fr::random(rng));
+        let hidden: Vec<usize> = (0..self.len())
+            .filter(|index| !disclosed.contains(index))
+            .collect();
+
+        let mut sigma_1 = mul(&G1::one(), &t);
+        for j in &hidden {
+            sigma_1.add_assign(&mul(&self.y[*j], &messages[*j]));
+        }
+        let sigma_tilde_1 = mul(&signature.sigma_tilde_1, &r);
+        let mut sigma_tilde_2 = signature.sigma_tilde_2;
+        sigma_tilde_2.add_assign(&mul(&signature.sigma_tilde_1, &t));
+        let sigma_tilde_2 = mul(&sigma_tilde_2, &r);
+
+        let mut sigma_2 = G1::zero();
+        let challenges = challenges(&sigma_1, &sigma_tilde_1, &sigma_tilde_2, disclosed);
+        for (i, c_i) in disclosed.iter().zip(challenges) {
+            let mut term = mul(&self.y[*i], &t);
+            for j in &hidden {
+                term.add_assign(&mul(&self.z[*i][*j], &messages[*j]));
+            }
+            sigma_2.add_assign(&mul(&term, &c_i));
+        }
+
+        Ok(Signature {
+            sigma_1,
+            sigma_2,
+            sigma_tilde_1,
+            sigma_tilde_2,
+        })
+    }
+
+    /// Verifies a signature on the disclosed messages, by index, checking that `sigma~_1` is not
+    /// the identity and that:
+    /// - `e(X prod_{i in I} Y_i^m_i sigma_1, sigma~_1) = e(g, sigma~_2)`,
+    /// - `e(sigma_1, prod_{i in I} Y~_i^c_i) = e(sigma_2, g~)`.
+    pub fn verify(
+        &self,
+        signature: &Signature,
+        disclosed: &BTreeMap<usize, Fr>,
+    ) -> Result<(), RedactableSignatureError> {
+        self.check_indices(disclosed.keys())?;
+        if signature.sigma_tilde_1.is_zero() {
+            return Err(RedactableSignatureError::InvalidSignature);
+        }
+
+        let mut a = self.x;
+        for (i, m_i) in disclosed {
+            a.add_assign(&mul(&self.y[*i], m_i));
+        }
+        a.add_assign(&signature.sigma_1);
+        if Bls12::pairing(a, signature.sigma_tilde_1)
+            != Bls12::pairing(G1::one(), signature.sigma_tilde_2)
+        {
+            return Err(RedactableSignatureError::InvalidSignature);
+        }
 
+        let indices = disclosed.keys().copied().collect();
+        let challenges = challenges(
+            &signature.sigma_1,
+            &signature.sigma_tilde_1,
+            &signature.sigma_tilde_2,
+            &indices,
+        );
+        let mut b = G2::zero();
+        for (i, c_i) in indices.iter().zip(challenges) {
+            b.add_assign(&mul(&self.y_tilde[*i], &c_i));
+        }
+        if Bls12::pairing(signature.sigma_1, b) != Bls12::pairing(signature.sigma_2, G2::one()) {
+            return Err(RedactableSignatureError::InvalidSignature);
+        }
+        Ok(())
+    }
 
+    /// Encodes the key as `n || X || Y_1 .. Y_n || Y~_1 .. Y~_n || Z_ij (i != j)`, with `n` a
+    /// 32-bit big-endian integer and `Z_ij` in order of `i` then `j`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = (self.len() as u32).to_be_bytes().to_vec();
+        serialize(&self.x, &mut bytes);
+        self.y.iter().for_each(|y_i| serialize(y_i, &mut bytes));
+        self.y_tilde
+            .iter()
+            .for_each(|y_i| serialize(y_i, &mut bytes));
+        for (i, z_i) in self.z.iter().enumerate() {
+            for (j, z_ij) in z_i.iter().enumerate() {
+                if i != j {
+                    serialize(z_ij, &mut bytes);
+                }
+            }
+        }
+        bytes
+    }
 
+    /// Decodes a key encoded by [`PublicKey::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, RedactableSignatureError> {
+        let invalid = || RedactableSignatureError::FailedToDecode("public key".to_owned());
+        let (length, mut reader) = match bytes {
+            [a, b, c, d, rest @ ..] => (u32::from_be_bytes([*a, *b, *c, *d]) as usize, rest),
+            _ => return Err(invalid()),
+        };
+        let expected = (length as u64)
+            .checked_mul(
+                G1_LENGTH as u64
+                    + G2_LENGTH as u64
+                    + G1_LENGTH as u64 * (length as u64).saturating_sub(1),
+            )
+            .and_then(|elements| elements.checked_add(G1_LENGTH as u64));
+        if expected != Some(reader.len() as u64) {
+            return Err(invalid());
+        }
+        let reader = &mut reader;
+        let x = deserialize(reader, "public key")?;
+        let y = (0..length)
+            .map(|_| deserialize(reader, "public key"))
+            .collect::<Result<_, _>>()?;
+        let y_tilde = (0..length)
+            .map(|_| deserialize(reader, "public key"))
+            .collect::<Result<_, _>>()?;
+        let z = (0..length)
+            .map(|i| {
+                (0..length)
+                    .map(|j| match i == j {
+                        true => Ok(G1::zero()),
+                        false => deserialize(reader, "public key"),
+                    })
+                    .collect::<Result<_, _>>()
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { x, y, y_tilde, z })
+    }
 
+    /// Returns the key as an `OKP` JWK on curve `BLS12381PS`.
+    pub fn to_jwk(&self) -> JWK {
+        JWK::from(Params::OKP(OctetParams {
+            curve: REDACTABLE_SIGNATURE_KEY_CURVE.to_owned(),
+            public_key: Base64urlUInt(self.to_bytes()),
+            private_key: None,
+        }))
+    }
 
-fn main(){
-    let params = GenerateParam("test".as_bytes());
-    println(params);
-}
\ No newline at end of file
+    /// Returns the public key of a JWK.
+    pub fn from_jwk(jwk: &JWK) -> Result<Self, RedactableSignatureError> {
+        match &jwk.params {
+            Params::OKP(OctetParams {
+                curve, public_key, ..
+            }) if curve == REDACTABLE_SIGNATURE_KEY_CURVE => Self::from_bytes(&public_key.0),
+            _ => Err(RedactableSignatureError::InvalidKey(
+                "expected an OKP JWK on curve BLS12381PS".to_owned(),
+            )),
+        }
+    }
+}
+
+impl Signature {
+    /// Encodes the signature as `sigma_1 || sigma_2 || sigma~_1 || sigma~_2`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        serialize(&self.sigma_1, &mut bytes);
+        serialize(&self.sigma_2, &mut bytes);
+        serialize(&self.sigma_tilde_1, &mut bytes);
+        serialize(&self.sigma_tilde_2, &mut bytes);
+        bytes
+    }
+
+    /// Decodes a signature encoded by [`Signature::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, RedactableSignatureError> {
+        if bytes.len() != 2 * (G1_LENGTH + G2_LENGTH) {
+            return Err(RedactableSignatureError::FailedToDecode(
+                "signature".to_owned(),
+            ));
+        }
+        let reader = &mut &bytes[..];
+        Ok(Self {
+            sigma_1: deserialize(reader, "signature")?,
+            sigma_2: deserialize(reader, "signature")?,
+            sigma_tilde_1: deserialize(reader, "signature")?,
+            sigma_tilde_2: deserialize(reader, "signature")?,
+        })
+    }
+}
+
+/// A credential split into its attributes: the credential without its proof or the properties
+/// of its subject, and each property of the subject by name.
+fn split_credential(
+    credential: &Credential,
+) -> Result<(Value, BTreeMap<String, Value>), RedactableSignatureError> {
+    let mut credential = serde_json::to_value(credential)
+        .map_err(|e| RedactableSignatureError::InvalidCredential(e.to_string()))?;
+    let object = credential.as_object_mut().unwrap();
+    object.remove("proof");
+    let subject = match object.get_mut("credentialSubject") {
+        Some(Value::Object(subject)) => subject,
+        _ => {
+            return Err(RedactableSignatureError::InvalidCredential(
+                "expected a single credential subject".to_owned(),
+            ))
+        }
+    };
+    let names: Vec<String> = subject
+        .keys()
+        .filter(|name| *name != "id")
+        .cloned()
+        .collect();
+    let attributes = names
+        .into_iter()
+        .map(|name| {
+            let value = subject.remove(&name).unwrap();
+            (name, value)
+        })
+        .collect();
+    Ok((credential, attributes))
+}
+
+/// Hashes a JSON value, in canonical form, to a message scalar.
+fn json_message(value: &Value) -> Result<Fr, RedactableSignatureError> {
+    serde_jcs::to_vec(value)
+        .map(|bytes| message_to_scalar(&bytes))
+        .map_err(|e| RedactableSignatureError::InvalidCredential(e.to_string()))
+}
+
+/// The parts of a redactable signature proof: the proof options, the signature and the message
+/// index of each subject property.
+fn proof_parts(
+    credential: &Credential,
+) -> Result<(Value, Signature, BTreeMap<String, usize>), RedactableSignatureError> {
+    let invalid = |reason: &str| RedactableSignatureError::InvalidCredential(reason.to_owned());
+    let proof = match &credential.proof {
+        Some(OneOrMany::One(proof)) if proof.type_ == REDACTABLE_SIGNATURE_PROOF_TYPE => proof,
+        _ => return Err(invalid("expected a single redactable signature proof")),
+    };
+    let mut options = serde_json::to_value(proof).map_err(|e| invalid(&e.to_string()))?;
+    let options_object = options.as_object_mut().unwrap();
+    let signature = match options_object.remove("proofValue") {
+        Some(Value::String(value)) => base64::decode_config(value, base64::URL_SAFE_NO_PAD)
+            .map_err(|_| RedactableSignatureError::FailedToDecode("signature".to_owned()))
+            .and_then(|bytes| Signature::from_bytes(&bytes))?,
+        _ => return Err(invalid("missing proofValue")),
+    };
+    let indices = options_object
+        .remove("attributeIndices")
+        .and_then(|indices| serde_json::from_value(indices).ok())
+        .ok_or_else(|| invalid("missing or invalid attributeIndices"))?;
+    Ok((options, signature, indices))
+}
+
+/// The disclosed messages of a credential with a redactable signature proof: the credential
+/// without the subject's properties and with the proof options at index zero, and each subject
+/// property at its index.
+fn disclosed_messages(
+    credential: &Credential,
+    options: &Value,
+    indices: &BTreeMap<String, usize>,
+) -> Result<BTreeMap<usize, Fr>, RedactableSignatureError> {
+    let (header, attributes) = split_credential(credential)?;
+    if !attributes.keys().eq(indices.keys()) {
+        return Err(RedactableSignatureError::InvalidCredential(
+            "subject properties do not match the attribute indices".to_owned(),
+        ));
+    }
+    let mut messages = BTreeMap::new();
+    messages.insert(
+        0,
+        json_message(&json!({ "credential": header, "proof": options }))?,
+    );
+    for (name, value) in attributes {
+        let index = indices[&name];
+        if index == 0
+            || messages
+                .insert(index, json_message(&json!({ name: value }))?)
+                .is_some()
+        {
+            return Err(RedactableSignatureError::InvalidCredential(
+                "invalid attribute index".to_owned(),
+            ));
+        }
+    }
+    Ok(messages)
+}
+
+/// Adds a redactable signature proof to a credential.
+fn with_proof(
+    credential: &Credential,
+    options: &Value,
+    signature: &Signature,
+    indices: &BTreeMap<String, usize>,
+) -> Result<Credential, RedactableSignatureError> {
+    let mut proof = options.clone();
+    let proof_object = proof.as_object_mut().unwrap();
+    proof_object.insert(
+        "proofValue".to_owned(),
+        json!(base64::encode_config(
+            signature.to_bytes(),
+            base64::URL_SAFE_NO_PAD
+        )),
+    );
+    proof_object.insert("attributeIndices".to_owned(), json!(indices));
+    let proof: Proof = serde_json::from_value(proof)
+        .map_err(|e| RedactableSignatureError::InvalidCredential(e.to_string()))?;
+    let mut credential = credential.clone();
+    credential.proof = Some(OneOrMany::One(proof));
+    Ok(credential)
+}
+
+/// Signs a credential with a redactable signature, with one message for the credential without
+/// the properties of its subject and one for each property of the subject, which can then be
+/// selectively disclosed with [`redact_credential`]. The key must be at least one longer than
+/// the number of subject properties.
+pub fn sign_credential<R: RngCore + CryptoRng>(
+    credential: &Credential,
+    secret_key: &SecretKey,
+    verification_method: &str,
+    rng: &mut R,
+) -> Result<Credential, RedactableSignatureError> {
+    let (_, attributes) = split_credential(credential)?;
+    if attributes.len() >= secret_key.len() {
+        return Err(RedactableSignatureError::InvalidMessageCount(
+            secret_key.len(),
+            attributes.len() + 1,
+        ));
+    }
+    let indices: BTreeMap<String, usize> = attributes
+        .keys()
+        .enumerate()
+        .map(|(index, name)| (name.to_owned(), index + 1))
+        .collect();
+
+    // Proof options, in the form serialized in the credential.
+    let mut options = Proof::new(REDACTABLE_SIGNATURE_PROOF_TYPE);
+    options.proof_purpose = Some(ssi::vc::ProofPurpose::AssertionMethod);
+    options.verification_method = Some(verification_method.to_owned());
+    options.created = Some(ssi::ldp::now_ms());
+    let options = serde_json::to_value(options)
+        .map_err(|e| RedactableSignatureError::InvalidCredential(e.to_string()))?;
+
+    let mut credential = credential.clone();
+    credential.proof = None;
+    let disclosed = disclosed_messages(&credential, &options, &indices)?;
+    let mut messages = vec![message_to_scalar(&[]); secret_key.len()];
+    for (index, message) in disclosed {
+        messages[index] = message;
+    }
+    let signature = secret_key.sign(&messages, rng)?;
+    with_proof(&credential, &options, &signature, &indices)
+}
+
+/// Redacts a credential signed by [`sign_credential`] to the given subject properties, deriving
+/// an unlinkable signature on the disclosed properties only.
+pub fn redact_credential<R: RngCore + CryptoRng>(
+    credential: &Credential,
+    public_key: &PublicKey,
+    disclosed: &[&str],
+    rng: &mut R,
+) -> Result<Credential, RedactableSignatureError> {
+    let (options, signature, indices) = proof_parts(credential)?;
+    let mut messages = vec![message_to_scalar(&[]); public_key.len()];
+    for (index, message) in disclosed_messages(credential, &options, &indices)? {
+        *messages
+            .get_mut(index)
+            .ok_or(RedactableSignatureError::InvalidIndex(
+                index,
+                public_key.len(),
+            ))? = message;
+    }
+
+    // Remove the undisclosed properties of the subject.
+    let mut disclosed_indices: BTreeMap<String, usize> = BTreeMap::new();
+    for name in disclosed {
+        let index = indices.get(*name).ok_or_else(|| {
+            RedactableSignatureError::InvalidCredential(format!("no subject property: {}", name))
+        })?;
+        disclosed_indices.insert(name.to_string(), *index);
+    }
+    let mut redacted = serde_json::to_value(credential)
+        .map_err(|e| RedactableSignatureError::InvalidCredential(e.to_string()))?;
+    if let Some(Value::Object(subject)) = redacted.get_mut("credentialSubject") {
+        subject.retain(|name, _| name == "id" || disclosed_indices.contains_key(name));
+    }
+    let redacted: Credential = serde_json::from_value(redacted)
+        .map_err(|e| RedactableSignatureError::InvalidCredential(e.to_string()))?;
+
+    let mut disclosed_set: BTreeSet<usize> = disclosed_indices.values().copied().collect();
+    disclosed_set.insert(0);
+    let derived = public_key.derive(&signature, &messages, &disclosed_set, rng)?;
+    with_proof(&redacted, &options, &derived, &disclosed_indices)
+}
+
+/// Verifies the redactable signature proof of a credential with the given public key.
+pub fn verify_credential(
+    credential: &Credential,
+    public_key: &PublicKey,
+) -> Result<(), RedactableSignatureError> {
+    let (options, signature, indices) = proof_parts(credential)?;
+    let messages = disclosed_messages(credential, &options, &indices)?;
+    public_key.verify(&signature, &messages)
+}
+
+/// Returns the redactable signature public key of the verification method of a credential's
+/// proof, which must belong to the credential issuer.
+pub async fn resolve_public_key<T: DIDResolver>(
+    credential: &Credential,
+    resolver: &T,
+) -> Result<PublicKey, RedactableSignatureError> {
+    let failed = |reason: &str| RedactableSignatureError::FailedToResolve(reason.to_owned());
+    let (options, _, _) = proof_parts(credential)?;
+    let vm_id = options["verificationMethod"]
+        .as_str()
+        .ok_or_else(|| failed("missing verification method"))?;
+    let did = vm_id
+        .split('#')
+        .next()
+        .filter(|did| did.starts_with("did:"))
+        .ok_or_else(|| failed("invalid verification method"))?;
+    if Some(did) != issuer_did(credential) {
+        return Err(failed("verification method does not belong to the issuer"));
+    }
+    let (_, document, _) = resolver.resolve(did, &Default::default()).await;
+    let document = document.ok_or_else(|| failed(did))?;
+    let fragment = &vm_id[did.len()..];
+    document
+        .verification_method
+        .iter()
+        .flatten()
+        .find_map(|vm| match vm {
+            VerificationMethod::Map(VerificationMethodMap {
+                id,
+                public_key_jwk: Some(jwk),
+                ..
+            }) if id == vm_id || id == fragment => Some(PublicKey::from_jwk(jwk)),
+            _ => None,
+        })
+        .ok_or_else(|| failed(vm_id))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::TEST_CREDENTIAL;
+    use rand_core::OsRng;
+
+    fn messages(length: usize) -> Vec<Fr> {
+        (0..length)
+            .map(|i| message_to_scalar(format!("message {}", i).as_bytes()))
+            .collect()
+    }
+
+    #[test]
+    fn test_sign_derive_verify() {
+        let (secret_key, public_key) = keygen(5, &mut OsRng);
+        let messages = messages(5);
+        let signature = secret_key.sign(&messages, &mut OsRng).unwrap();
+        let all: BTreeMap<usize, Fr> = messages.iter().copied().enumerate().collect();
+        assert_eq!(public_key.verify(&signature, &all), Ok(()));
+
+        // A derived signature verifies with the disclosed messages only.
+        let disclosed: BTreeSet<usize> = [0, 2, 3].iter().copied().collect();
+        let derived = public_key
+            .derive(&signature, &messages, &disclosed, &mut OsRng)
+            .unwrap();
+        let mut disclosed_messages: BTreeMap<usize, Fr> =
+            disclosed.iter().map(|i| (*i, messages[*i])).collect();
+        assert_eq!(public_key.verify(&derived, &disclosed_messages), Ok(()));
+        assert_ne!(derived, signature);
+
+        // The original signature does not verify with a subset of the messages.
+        assert_eq!(
+            public_key.verify(&signature, &disclosed_messages),
+            Err(RedactableSignatureError::InvalidSignature)
+        );
+
+        // Changed, omitted or added messages do not verify.
+        let mut changed = disclosed_messages.clone();
+        changed.insert(2, messages[1]);
+        assert!(public_key.verify(&derived, &changed).is_err());
+        let mut omitted = disclosed_messages.clone();
+        omitted.remove(&3);
+        assert!(public_key.verify(&derived, &omitted).is_err());
+        disclosed_messages.insert(1, messages[1]);
+        assert!(public_key.verify(&derived, &disclosed_messages).is_err());
+
+        // Nor with another key.
+        let (_, other_key) = keygen(5, &mut OsRng);
+        assert!(other_key.verify(&signature, &all).is_err());
+    }
+
+    #[test]
+    fn test_invalid_inputs() {
+        let (secret_key, public_key) = keygen(3, &mut OsRng);
+        assert_eq!(
+            secret_key.sign(&messages(2), &mut OsRng),
+            Err(RedactableSignatureError::InvalidMessageCount(3, 2))
+        );
+        let signature = secret_key.sign(&messages(3), &mut OsRng).unwrap();
+        let disclosed: BTreeSet<usize> = [0, 3].iter().copied().collect();
+        assert_eq!(
+            public_key.derive(&signature, &messages(3), &disclosed, &mut OsRng),
+            Err(RedactableSignatureError::InvalidIndex(3, 3))
+        );
+        let mut other_messages = messages(3);
+        other_messages[0] = message_to_scalar(b"other");
+        let disclosed: BTreeSet<usize> = [0].iter().copied().collect();
+        assert_eq!(
+            public_key.derive(&signature, &other_messages, &disclosed, &mut OsRng),
+            Err(RedactableSignatureError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn test_encoding() {
+        let (secret_key, public_key) = keygen(4, &mut OsRng);
+        let signature = secret_key.sign(&messages(4), &mut OsRng).unwrap();
+        assert_eq!(
+            SecretKey::from_bytes(&secret_key.to_bytes()),
+            Ok(secret_key.clone())
+        );
+        assert_eq!(
+            PublicKey::from_bytes(&public_key.to_bytes()),
+            Ok(public_key.clone())
+        );
+        assert_eq!(
+            Signature::from_bytes(&signature.to_bytes()),
+            Ok(signature.clone())
+        );
+        assert!(PublicKey::from_bytes(&public_key.to_bytes()[1..]).is_err());
+
+        let jwk = secret_key.to_jwk();
+        assert_eq!(SecretKey::from_jwk(&jwk), Ok(secret_key));
+        assert_eq!(PublicKey::from_jwk(&jwk.to_public()), Ok(public_key));
+        assert!(SecretKey::from_jwk(&jwk.to_public()).is_err());
+    }
+
+    #[test]
+    fn test_sign_redact_verify_credential() {
+        let (secret_key, public_key) = keygen(4, &mut OsRng);
+        let mut credential: Credential = serde_json::from_str(TEST_CREDENTIAL).unwrap();
+        credential.issuer = Some(ssi::vc::Issuer::URI(ssi::vc::URI::String(
+            "did:example:issuer".to_string(),
+        )));
+        let vm = "did:example:issuer#key-1";
+        let signed = sign_credential(&credential, &secret_key, vm, &mut OsRng).unwrap();
+        assert_eq!(verify_credential(&signed, &public_key), Ok(()));
+
+        // Disclose the subject's given name and degree, but not its family name.
+        let redacted =
+            redact_credential(&signed, &public_key, &["degree", "givenName"], &mut OsRng).unwrap();
+        assert_eq!(verify_credential(&redacted, &public_key), Ok(()));
+        let subject = serde_json::to_value(&redacted.credential_subject).unwrap();
+        assert!(subject.get("familyName").is_none());
+        assert_eq!(subject["givenName"], json!("Jane"));
+
+        // Changes to disclosed properties or the rest of the credential do not verify.
+        let mut tampered = serde_json::to_value(&redacted).unwrap();
+        tampered["credentialSubject"]["givenName"] = json!("John");
+        let tampered: Credential = serde_json::from_value(tampered).unwrap();
+        assert!(verify_credential(&tampered, &public_key).is_err());
+        let mut tampered = redacted.clone();
+        tampered.issuer = Some(ssi::vc::Issuer::URI(ssi::vc::URI::String(
+            "did:example:other".to_string(),
+        )));
+        assert!(verify_credential(&tampered, &public_key).is_err());
+
+        // The key must have room for every subject property.
+        let (short_key, _) = keygen(3, &mut OsRng);
+        assert_eq!(
+            sign_credential(&credential, &short_key, vm, &mut OsRng).unwrap_err(),
+            RedactableSignatureError::InvalidMessageCount(3, 4)
+        );
+    }
+}
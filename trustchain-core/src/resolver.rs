@@ -4,12 +4,14 @@ use serde_json::Value;
 use ssi::did::{DIDMethod, Document, Service, ServiceEndpoint};
 use ssi::did_resolve::{
     DIDResolver, DocumentMetadata, Metadata, ResolutionInputMetadata, ResolutionMetadata,
+    ERROR_METHOD_NOT_SUPPORTED,
 };
 use ssi::one_or_many::OneOrMany;
 use std::collections::HashMap;
 use thiserror::Error;
 use tokio::runtime::Runtime;
 
+use crate::utils::canonicalize;
 use crate::TRUSTCHAIN_PROOF_SERVICE_ID_VALUE;
 
 /// An error relating to Trustchain resolution.
@@ -36,8 +38,38 @@ pub enum ResolverError {
     /// DID is not found.
     #[error("DID: {0} is not found.")]
     DIDNotFound(String),
+    /// DID state at the requested version is not available.
+    #[error("DID: {0} is not available at the requested version.")]
+    VersionNotAvailable(String),
+    /// Resolutions of the DID from different nodes could not be cross-checked.
+    #[error("Cross-check failed for DID: {0}.")]
+    CrossCheckFailure(String),
+    /// DID method is not supported by the resolver.
+    #[error("DID method is not supported for DID: {0}.")]
+    MethodNotSupported(String),
+    /// The resolved DID document is invalid.
+    #[error("Invalid DID document for DID: {0}.")]
+    InvalidDocument(String),
+    /// Invalid DID URL.
+    #[error("Invalid DID URL: {0}.")]
+    InvalidDIDURL(String),
+    /// Failed to dereference a DID URL.
+    #[error("Failed to dereference DID URL: {0}. {1}.")]
+    FailedToDereference(String, String),
 }
 
+/// Error value in resolution metadata when the DID state at a requested `versionId` or
+/// `versionTime` cannot be obtained.
+pub const ERROR_VERSION_NOT_AVAILABLE: &str = "versionNotAvailable";
+
+/// Error value in resolution metadata when resolutions of a DID from different nodes differ or
+/// cannot be obtained for comparison.
+pub const ERROR_CROSS_CHECK_FAILED: &str = "crossCheckFailed";
+
+/// Error value in resolution metadata when the retrieved DID document is not a valid
+/// representation of the DID.
+pub const ERROR_INVALID_DID_DOCUMENT: &str = "invalidDidDocument";
+
 /// Type for resolver result.
 type ResolverResult = Result<
     (
@@ -192,21 +224,36 @@ impl<T: DIDResolver + Sync + Send> Resolver<T> {
     /// Sync Trustchain resolve function returning resolution metadata,
     /// DID document and DID document metadata from a passed DID as a `Result` type.
     pub fn resolve_as_result(&self, did: &str) -> ResolverResult {
+        self.resolve_as_result_with_input(did, &ResolutionInputMetadata::default())
+    }
+
+    /// Sync Trustchain resolve function as [resolve_as_result](Self::resolve_as_result), passing
+    /// resolution input metadata (such as `versionTime` or `versionId`) to the wrapped resolver.
+    pub fn resolve_as_result_with_input(
+        &self,
+        did: &str,
+        input_metadata: &ResolutionInputMetadata,
+    ) -> ResolverResult {
         self.runtime.block_on(async {
             // sidetree resolved resolution metadata, document and document metadata
-            let (did_res_meta, did_doc, did_doc_meta) =
-                block_on(self.resolve(&did.to_string(), &ResolutionInputMetadata::default()));
+            let (did_res_meta, did_doc, did_doc_meta) = block_on(self.resolve(did, input_metadata));
 
             // Handle error cases based on string content of the resolution metadata
             if let Some(did_res_meta_error) = &did_res_meta.error {
-                if did_res_meta_error
-                    .starts_with("Error sending HTTP request: error sending request for url")
-                {
+                if did_res_meta_error.starts_with("Error sending HTTP request") {
                     return Err(ResolverError::ConnectionFailure);
                 } else if did_res_meta_error == "invalidDid" {
                     return Err(ResolverError::NonExistentDID(did.to_string()));
                 } else if did_res_meta_error == "notFound" {
                     return Err(ResolverError::DIDNotFound(did.to_string()));
+                } else if did_res_meta_error == ERROR_VERSION_NOT_AVAILABLE {
+                    return Err(ResolverError::VersionNotAvailable(did.to_string()));
+                } else if did_res_meta_error == ERROR_CROSS_CHECK_FAILED {
+                    return Err(ResolverError::CrossCheckFailure(did.to_string()));
+                } else if did_res_meta_error == ERROR_METHOD_NOT_SUPPORTED {
+                    return Err(ResolverError::MethodNotSupported(did.to_string()));
+                } else if did_res_meta_error == ERROR_INVALID_DID_DOCUMENT {
+                    return Err(ResolverError::InvalidDocument(did.to_string()));
                 } else if did_res_meta_error
                     == "Failed to convert to Truschain document and metadata."
                 {
@@ -296,6 +343,20 @@ impl<T: DIDResolver + Sync + Send> Resolver<T> {
         value
     }
 
+    /// Gets the delegation constraints in a Trustchain proof service as a canonicalized JSON
+    /// string, since `Metadata` cannot represent numeric values.
+    fn get_constraints_from_proof_service(&self, proof_service: &Service) -> Option<String> {
+        match proof_service.service_endpoint.as_ref() {
+            Some(OneOrMany::One(ServiceEndpoint::Map(Value::Object(v)))) => {
+                match v.get("constraints") {
+                    Some(constraints @ Value::Object(_)) => canonicalize(constraints).ok(),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
     /// Adds a proof from a DID Document to DocumentMetadata.
     fn add_proof(&self, doc: &Document, mut doc_meta: DocumentMetadata) -> DocumentMetadata {
         // Check if the Trustchain proof service exists in document
@@ -311,9 +372,9 @@ impl<T: DIDResolver + Sync + Send> Resolver<T> {
                 let proof_value = self.get_from_proof_service(proof_service, "proofValue");
                 let controller = self.get_from_proof_service(proof_service, "controller");
                 // If not None, add to new HashMap
-                if let (Some(property_set), Some(proof_value), Some(controller)) =
-                    (doc_meta.property_set.as_mut(), proof_value, controller)
-                {
+                if let (Some(proof_value), Some(controller)) = (proof_value, controller) {
+                    // Metadata from methods other than Sidetree may have no property set.
+                    let property_set = doc_meta.property_set.get_or_insert_with(HashMap::new);
                     // Make new HashMap; add keys and values
                     let mut proof_hash_map: HashMap<String, Metadata> = HashMap::new();
                     proof_hash_map
@@ -326,6 +387,13 @@ impl<T: DIDResolver + Sync + Send> Resolver<T> {
                         String::from("proofValue"),
                         Metadata::String(proof_value.to_owned()),
                     );
+                    // Add delegation constraints (if present) covered by the proof.
+                    if let Some(constraints) =
+                        self.get_constraints_from_proof_service(proof_service)
+                    {
+                        proof_hash_map
+                            .insert(String::from("constraints"), Metadata::String(constraints));
+                    }
 
                     // Insert new HashMap of Metadata::Map()
                     property_set.insert(String::from("proof"), Metadata::Map(proof_hash_map));
@@ -435,7 +503,6 @@ mod tests {
         TEST_TRUSTCHAIN_DOCUMENT, TEST_TRUSTCHAIN_DOCUMENT_METADATA,
     };
 
-    use crate::utils::canonicalize;
     use ssi::did_resolve::HTTPDIDResolver;
 
     // General function for generating a HTTP resolver for tests only
@@ -701,6 +768,35 @@ mod tests {
         assert_eq!(expected_tc_meta, actual_tc_meta);
     }
 
+    #[test]
+    fn add_proof_with_constraints() {
+        // Test adding a proof with delegation constraints to DID Document Metadata.
+
+        // Load a Sidetree-resolved DID Document and add constraints to its proof service.
+        let mut doc_json: Value = serde_json::from_str(TEST_SIDETREE_DOCUMENT).unwrap();
+        doc_json["service"][0]["serviceEndpoint"]["constraints"] =
+            serde_json::json!({"maxPathLength": 0, "canAttest": true});
+        let sidetree_doc: Document = serde_json::from_value(doc_json).unwrap();
+        let sidetree_meta: DocumentMetadata =
+            serde_json::from_str(TEST_SIDETREE_DOCUMENT_METADATA).expect("Failed to load metadata");
+
+        // Construct a Resolver instance.
+        let resolver = Resolver::new(get_http_resolver());
+
+        // Check the constraints are added to the proof as a canonicalized string.
+        let doc_meta = resolver.add_proof(&sidetree_doc, sidetree_meta);
+        let proof = match doc_meta.property_set.unwrap().get("proof") {
+            Some(Metadata::Map(proof)) => proof.to_owned(),
+            _ => panic!(),
+        };
+        match proof.get("constraints") {
+            Some(Metadata::String(s)) => {
+                assert_eq!(s, r#"{"canAttest":true,"maxPathLength":0}"#)
+            }
+            _ => panic!(),
+        }
+    }
+
     #[test]
     fn transform_doc_metadata() {
         // Test transformation of Sidetree-resolved DID Document Metadata to Trustchain format.
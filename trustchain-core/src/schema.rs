@@ -0,0 +1,922 @@
+//! Validation of credentials against the JSON Schemas of their `credentialSchema`.
+//!
+//! Schemas are loaded by ID from a local [`SchemaRegistry`] (the `schemas` directory of the
+//! Trustchain data directory), so that validation works offline. Validation supports the core
+//! JSON Schema (draft 7) keywords for types, objects, arrays, strings, numbers, enumerations,
+//! combinations and local references, with `format` taken as an annotation as by default in
+//! draft 7. Schemas using any other validation keyword, such as `if`, are rejected as invalid
+//! rather than partially applied.
+use crate::TRUSTCHAIN_DATA;
+use regex::Regex;
+use serde_json::{Number, Value};
+use ssi::vc::{Credential, URI};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Type of a `credentialSchema` validating the credential subject with a JSON Schema.
+pub const JSON_SCHEMA_VALIDATOR_2018: &str = "JsonSchemaValidator2018";
+
+/// Type of a `credentialSchema` validating the credential with a JSON Schema.
+pub const JSON_SCHEMA: &str = "JsonSchema";
+
+/// Directory of JSON Schemas in the Trustchain data directory.
+pub const SCHEMAS_DIR: &str = "schemas";
+
+/// Index of a schemas directory, mapping each schema ID to the file name of the schema.
+pub const SCHEMAS_INDEX: &str = "schemas.json";
+
+/// An error relating to credential schemas.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum SchemaError {
+    /// Schema not found in the registry.
+    #[error("Schema not found: {0}")]
+    SchemaNotFound(String),
+    /// Invalid JSON Schema.
+    #[error("Invalid schema {0}: {1}")]
+    InvalidSchema(String, String),
+    /// Credential does not conform to its schemas.
+    #[error("Credential does not conform to its schemas: {0:?}")]
+    Violations(Vec<SchemaViolation>),
+    /// Failed to read a file of a schemas directory.
+    #[error("Failed to read {0}: {1}")]
+    FailedToRead(String, String),
+    /// Failed to save a schema.
+    #[error("Failed to save schema: {0}")]
+    Storage(String),
+    /// No TRUSTCHAIN_DATA environment variable.
+    #[error("No TRUSTCHAIN_DATA environment variable.")]
+    TrustchainDataNotPresent,
+}
+
+/// A violation of a JSON Schema: the JSON pointer to the invalid value and the reason.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaViolation {
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = if self.path.is_empty() {
+            "/"
+        } else {
+            &self.path
+        };
+        write!(f, "{}: {}", path, self.message)
+    }
+}
+
+/// Registry of JSON Schemas by ID.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SchemaRegistry {
+    schemas: HashMap<String, Value>,
+}
+
+/// Returns the path of the schemas directory in the Trustchain data directory.
+pub fn trustchain_schemas_dir() -> Result<PathBuf, SchemaError> {
+    let path = std::env::var(TRUSTCHAIN_DATA).map_err(|_| SchemaError::TrustchainDataNotPresent)?;
+    Ok(Path::new(path.as_str()).join(SCHEMAS_DIR))
+}
+
+/// Reads the index of a schemas directory, or an empty index if there is none.
+fn read_index(dir: &Path) -> Result<BTreeMap<String, String>, SchemaError> {
+    let path = dir.join(SCHEMAS_INDEX);
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let file = File::open(&path)
+        .map_err(|e| SchemaError::FailedToRead(path.display().to_string(), e.to_string()))?;
+    serde_json::from_reader(file)
+        .map_err(|e| SchemaError::FailedToRead(path.display().to_string(), e.to_string()))
+}
+
+/// Reads a schema from a file.
+fn read_schema(id: &str, path: &Path) -> Result<Value, SchemaError> {
+    let file = File::open(path)
+        .map_err(|e| SchemaError::FailedToRead(path.display().to_string(), e.to_string()))?;
+    let schema = serde_json::from_reader(file)
+        .map_err(|e| SchemaError::InvalidSchema(id.to_owned(), e.to_string()))?;
+    check_schema(id, &schema)?;
+    Ok(schema)
+}
+
+/// Checks that a value is a JSON Schema, which is an object or a boolean.
+fn check_schema(id: &str, schema: &Value) -> Result<(), SchemaError> {
+    match schema {
+        Value::Object(_) | Value::Bool(_) => Ok(()),
+        _ => Err(SchemaError::InvalidSchema(
+            id.to_owned(),
+            "expected an object or a boolean".to_owned(),
+        )),
+    }
+}
+
+impl SchemaRegistry {
+    /// Makes a registry of the schemas in the Trustchain data directory, if present. Unreadable
+    /// or invalid schemas are ignored.
+    pub fn load() -> Self {
+        let mut registry = Self::default();
+        if let Ok(dir) = trustchain_schemas_dir() {
+            if let Ok(index) = read_index(&dir) {
+                for (id, file_name) in index {
+                    if let Ok(schema) = read_schema(&id, &dir.join(file_name)) {
+                        registry.schemas.insert(id, schema);
+                    }
+                }
+            }
+        }
+        registry
+    }
+
+    /// Makes a registry of the schemas in the given directory, which has an index file mapping
+    /// each schema ID to the file name of the schema.
+    pub fn from_dir<P: AsRef<Path>>(dir: P) -> Result<Self, SchemaError> {
+        let dir = dir.as_ref();
+        let mut registry = Self::default();
+        for (id, file_name) in read_index(dir)? {
+            let schema = read_schema(&id, &dir.join(file_name))?;
+            registry.schemas.insert(id, schema);
+        }
+        Ok(registry)
+    }
+
+    /// Adds a schema to the registry.
+    pub fn insert(&mut self, id: &str, schema: Value) -> Result<(), SchemaError> {
+        check_schema(id, &schema)?;
+        self.schemas.insert(id.to_owned(), schema);
+        Ok(())
+    }
+
+    /// Returns the schema with the given ID, if any.
+    pub fn get(&self, id: &str) -> Option<&Value> {
+        self.schemas.get(id)
+    }
+
+    /// Returns the IDs of the schemas in the registry, in order.
+    pub fn ids(&self) -> Vec<&str> {
+        let mut ids: Vec<&str> = self.schemas.keys().map(String::as_str).collect();
+        ids.sort_unstable();
+        ids
+    }
+}
+
+/// Saves a schema to the given schemas directory, adding it to the directory's index under the
+/// given file name. Returns the path of the saved schema.
+pub fn save_schema<P: AsRef<Path>>(
+    dir: P,
+    id: &str,
+    file_name: &str,
+    schema: &Value,
+) -> Result<PathBuf, SchemaError> {
+    let dir = dir.as_ref();
+    check_schema(id, schema)?;
+    if Path::new(file_name).file_name() != Some(file_name.as_ref()) || file_name == SCHEMAS_INDEX {
+        return Err(SchemaError::Storage(format!(
+            "invalid file name: {}",
+            file_name
+        )));
+    }
+    std::fs::create_dir_all(dir).map_err(|e| SchemaError::Storage(e.to_string()))?;
+    let path = dir.join(file_name);
+    let file = File::create(&path).map_err(|e| SchemaError::Storage(e.to_string()))?;
+    serde_json::to_writer_pretty(file, schema).map_err(|e| SchemaError::Storage(e.to_string()))?;
+
+    let mut index = read_index(dir)?;
+    index.insert(id.to_owned(), file_name.to_owned());
+    let file =
+        File::create(dir.join(SCHEMAS_INDEX)).map_err(|e| SchemaError::Storage(e.to_string()))?;
+    serde_json::to_writer_pretty(file, &index).map_err(|e| SchemaError::Storage(e.to_string()))?;
+    Ok(path)
+}
+
+/// Keywords of a schema that are applied in validation.
+const VALIDATION_KEYWORDS: [&str; 25] = [
+    "$ref",
+    "type",
+    "enum",
+    "const",
+    "required",
+    "properties",
+    "patternProperties",
+    "additionalProperties",
+    "minItems",
+    "maxItems",
+    "uniqueItems",
+    "items",
+    "contains",
+    "minLength",
+    "maxLength",
+    "pattern",
+    "minimum",
+    "maximum",
+    "exclusiveMinimum",
+    "exclusiveMaximum",
+    "multipleOf",
+    "allOf",
+    "anyOf",
+    "oneOf",
+    "not",
+];
+
+/// Keywords of a schema that do not affect validation: annotations and containers of subschemas.
+/// As in draft 7 by default, `format` is an annotation, not asserted in validation.
+const ANNOTATION_KEYWORDS: [&str; 12] = [
+    "$schema",
+    "$id",
+    "definitions",
+    "$defs",
+    "$comment",
+    "title",
+    "description",
+    "default",
+    "examples",
+    "readOnly",
+    "writeOnly",
+    "format",
+];
+
+/// Validator of values against a schema, with `$ref`s resolved within the root schema.
+struct Validator<'a> {
+    id: &'a str,
+    root: &'a Value,
+    /// References being followed, with the path of the value they are applied to, to detect
+    /// circular references.
+    references: RefCell<Vec<(&'a str, String)>>,
+}
+
+/// Returns the JSON type of a value.
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(number) if number.is_i64() || number.is_u64() => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Returns whether a number is a multiple of a positive divisor: exactly for integers, and
+/// otherwise within rounding error, as a decimal such as 0.3 is not exactly a multiple of 0.1 in
+/// floating point.
+fn is_multiple(number: &Number, divisor: &Number) -> bool {
+    if let (Some(number), Some(divisor)) = (number.as_i64(), divisor.as_i64()) {
+        return number % divisor == 0;
+    }
+    let quotient = number.as_f64().unwrap() / divisor.as_f64().unwrap();
+    (quotient - quotient.round()).abs() <= quotient.abs().max(1.0) * 1e-9
+}
+
+/// Returns whether a value has the given JSON Schema type.
+fn has_type(value: &Value, type_: &str) -> bool {
+    match (type_, value) {
+        ("number", Value::Number(_)) => true,
+        ("integer", Value::Number(number)) => number.as_f64().is_some_and(|n| n.fract() == 0.0),
+        _ => type_name(value) == type_,
+    }
+}
+
+impl<'a> Validator<'a> {
+    fn invalid(&self, reason: &str) -> SchemaError {
+        SchemaError::InvalidSchema(self.id.to_owned(), reason.to_owned())
+    }
+
+    /// Validates a value at the given path, adding any violations.
+    fn validate(
+        &self,
+        schema: &'a Value,
+        value: &Value,
+        path: &str,
+        violations: &mut Vec<SchemaViolation>,
+    ) -> Result<(), SchemaError> {
+        let mut violation = |message: String| {
+            violations.push(SchemaViolation {
+                path: path.to_owned(),
+                message,
+            })
+        };
+        let schema = match schema {
+            Value::Bool(true) => return Ok(()),
+            Value::Bool(false) => {
+                violation("no value is allowed".to_owned());
+                return Ok(());
+            }
+            Value::Object(schema) => schema,
+            _ => return Err(self.invalid("expected an object or a boolean")),
+        };
+        if let Some(keyword) = schema.keys().find(|keyword| {
+            !VALIDATION_KEYWORDS.contains(&keyword.as_str())
+                && !ANNOTATION_KEYWORDS.contains(&keyword.as_str())
+        }) {
+            return Err(self.invalid(&format!("unsupported keyword: {}", keyword)));
+        }
+
+        // References replace any other keywords, as in draft 7.
+        if let Some(reference) = schema.get("$ref") {
+            let pointer = reference
+                .as_str()
+                .and_then(|reference| reference.strip_prefix('#'))
+                .ok_or_else(|| self.invalid("only local references are supported"))?;
+            let target = self
+                .root
+                .pointer(pointer)
+                .ok_or_else(|| self.invalid(&format!("unresolved reference: #{}", pointer)))?;
+            // A reference followed again for the same value never reaches a decision.
+            let reference = (pointer, path.to_owned());
+            if self.references.borrow().contains(&reference) {
+                return Err(self.invalid(&format!("circular reference: #{}", pointer)));
+            }
+            self.references.borrow_mut().push(reference);
+            let result = self.validate(target, value, path, violations);
+            self.references.borrow_mut().pop();
+            return result;
+        }
+
+        if let Some(types) = schema.get("type") {
+            let types: Vec<&str> = match types {
+                Value::String(type_) => vec![type_],
+                Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+                _ => return Err(self.invalid("invalid type")),
+            };
+            if !types.iter().any(|type_| has_type(value, type_)) {
+                violation(format!(
+                    "expected {} but found {}",
+                    types.join(" or "),
+                    type_name(value)
+                ));
+            }
+        }
+        if let Some(Value::Array(values)) = schema.get("enum") {
+            if !values.contains(value) {
+                violation(format!("expected one of {}", Value::Array(values.clone())));
+            }
+        }
+        if let Some(expected) = schema.get("const") {
+            if expected != value {
+                violation(format!("expected {}", expected));
+            }
+        }
+
+        match value {
+            Value::Object(object) => {
+                if let Some(Value::Array(required)) = schema.get("required") {
+                    for name in required.iter().filter_map(Value::as_str) {
+                        if !object.contains_key(name) {
+                            violation(format!("missing required property: {}", name));
+                        }
+                    }
+                }
+                let properties = schema.get("properties").and_then(Value::as_object);
+                let pattern_properties = match schema.get("patternProperties") {
+                    Some(Value::Object(pattern_properties)) => pattern_properties
+                        .iter()
+                        .map(|(pattern, property_schema)| {
+                            Regex::new(pattern)
+                                .map(|regex| (regex, property_schema))
+                                .map_err(|_| self.invalid("invalid pattern property"))
+                        })
+                        .collect::<Result<Vec<_>, _>>()?,
+                    Some(_) => return Err(self.invalid("invalid patternProperties")),
+                    None => Vec::new(),
+                };
+                for (name, property) in object {
+                    let property_path = format!("{}/{}", path, name);
+                    let property_schema = properties.and_then(|properties| properties.get(name));
+                    if let Some(property_schema) = property_schema {
+                        self.validate(property_schema, property, &property_path, violations)?;
+                    }
+                    let mut matched = property_schema.is_some();
+                    for (regex, pattern_schema) in &pattern_properties {
+                        if regex.is_match(name) {
+                            matched = true;
+                            self.validate(pattern_schema, property, &property_path, violations)?;
+                        }
+                    }
+                    if !matched {
+                        match schema.get("additionalProperties") {
+                            Some(Value::Bool(false)) => violations.push(SchemaViolation {
+                                path: path.to_owned(),
+                                message: format!("additional property not allowed: {}", name),
+                            }),
+                            Some(additional) => {
+                                self.validate(additional, property, &property_path, violations)?
+                            }
+                            None => (),
+                        }
+                    }
+                }
+            }
+            Value::Array(items) => {
+                if let Some(min) = schema.get("minItems").and_then(Value::as_u64) {
+                    if (items.len() as u64) < min {
+                        violations.push(SchemaViolation {
+                            path: path.to_owned(),
+                            message: format!("expected at least {} items", min),
+                        });
+                    }
+                }
+                if let Some(max) = schema.get("maxItems").and_then(Value::as_u64) {
+                    if items.len() as u64 > max {
+                        violations.push(SchemaViolation {
+                            path: path.to_owned(),
+                            message: format!("expected at most {} items", max),
+                        });
+                    }
+                }
+                if let Some(Value::Bool(true)) = schema.get("uniqueItems") {
+                    if (1..items.len()).any(|i| items[..i].contains(&items[i])) {
+                        violations.push(SchemaViolation {
+                            path: path.to_owned(),
+                            message: "expected unique items".to_owned(),
+                        });
+                    }
+                }
+                match schema.get("items") {
+                    Some(Value::Array(item_schemas)) => {
+                        for (index, (item_schema, item)) in
+                            item_schemas.iter().zip(items).enumerate()
+                        {
+                            let item_path = format!("{}/{}", path, index);
+                            self.validate(item_schema, item, &item_path, violations)?;
+                        }
+                    }
+                    Some(item_schema) => {
+                        for (index, item) in items.iter().enumerate() {
+                            let item_path = format!("{}/{}", path, index);
+                            self.validate(item_schema, item, &item_path, violations)?;
+                        }
+                    }
+                    None => (),
+                }
+                if let Some(contained) = schema.get("contains") {
+                    let mut contains = false;
+                    for (index, item) in items.iter().enumerate() {
+                        let item_path = format!("{}/{}", path, index);
+                        let mut item_violations = Vec::new();
+                        self.validate(contained, item, &item_path, &mut item_violations)?;
+                        contains |= item_violations.is_empty();
+                    }
+                    if !contains {
+                        violations.push(SchemaViolation {
+                            path: path.to_owned(),
+                            message: "expected an item matching the schema of contains".to_owned(),
+                        });
+                    }
+                }
+            }
+            Value::String(string) => {
+                let length = string.chars().count() as u64;
+                if let Some(min) = schema.get("minLength").and_then(Value::as_u64) {
+                    if length < min {
+                        violation(format!("expected at least {} characters", min));
+                    }
+                }
+                if let Some(max) = schema.get("maxLength").and_then(Value::as_u64) {
+                    if length > max {
+                        violation(format!("expected at most {} characters", max));
+                    }
+                }
+                if let Some(pattern) = schema.get("pattern") {
+                    let regex = pattern
+                        .as_str()
+                        .and_then(|pattern| Regex::new(pattern).ok())
+                        .ok_or_else(|| self.invalid("invalid pattern"))?;
+                    if !regex.is_match(string) {
+                        violation(format!("expected to match pattern: {}", regex));
+                    }
+                }
+            }
+            Value::Number(value) => {
+                if let Some(divisor) = schema.get("multipleOf") {
+                    let divisor = match divisor {
+                        Value::Number(divisor) if divisor.as_f64().unwrap() > 0.0 => divisor,
+                        _ => return Err(self.invalid("multipleOf must be greater than 0")),
+                    };
+                    if !is_multiple(value, divisor) {
+                        violation(format!("expected a multiple of {}", divisor));
+                    }
+                }
+                let number = value.as_f64().unwrap();
+                let bound = |keyword: &str| schema.get(keyword).and_then(Value::as_f64);
+                if let Some(min) = bound("minimum").filter(|min| number < *min) {
+                    violation(format!("expected at least {}", min));
+                }
+                if let Some(max) = bound("maximum").filter(|max| number > *max) {
+                    violation(format!("expected at most {}", max));
+                }
+                if let Some(min) = bound("exclusiveMinimum").filter(|min| number <= *min) {
+                    violation(format!("expected more than {}", min));
+                }
+                if let Some(max) = bound("exclusiveMaximum").filter(|max| number >= *max) {
+                    violation(format!("expected less than {}", max));
+                }
+            }
+            Value::Null | Value::Bool(_) => (),
+        }
+
+        // Combinations of schemas.
+        let subschemas = |keyword: &str| schema.get(keyword).and_then(Value::as_array);
+        if let Some(all_of) = subschemas("allOf") {
+            for subschema in all_of {
+                self.validate(subschema, value, path, violations)?;
+            }
+        }
+        if let Some(any_of) = subschemas("anyOf") {
+            if self.count_valid(any_of, value, path)? == 0 {
+                violations.push(SchemaViolation {
+                    path: path.to_owned(),
+                    message: "expected to match at least one schema of anyOf".to_owned(),
+                });
+            }
+        }
+        if let Some(one_of) = subschemas("oneOf") {
+            if self.count_valid(one_of, value, path)? != 1 {
+                violations.push(SchemaViolation {
+                    path: path.to_owned(),
+                    message: "expected to match exactly one schema of oneOf".to_owned(),
+                });
+            }
+        }
+        if let Some(not) = schema.get("not") {
+            if self.count_valid(std::slice::from_ref(not), value, path)? == 1 {
+                violations.push(SchemaViolation {
+                    path: path.to_owned(),
+                    message: "expected not to match the schema of not".to_owned(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the number of schemas that a value is valid against.
+    fn count_valid(
+        &self,
+        schemas: &'a [Value],
+        value: &Value,
+        path: &str,
+    ) -> Result<usize, SchemaError> {
+        let mut count = 0;
+        for schema in schemas {
+            let mut violations = Vec::new();
+            self.validate(schema, value, path, &mut violations)?;
+            if violations.is_empty() {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+}
+
+/// Validates a value against a JSON Schema, returning any violations.
+pub fn validate(
+    id: &str,
+    schema: &Value,
+    value: &Value,
+) -> Result<Vec<SchemaViolation>, SchemaError> {
+    let validator = Validator {
+        id,
+        root: schema,
+        references: RefCell::new(Vec::new()),
+    };
+    let mut violations = Vec::new();
+    validator.validate(schema, value, "", &mut violations)?;
+    Ok(violations)
+}
+
+/// Returns whether a `credentialSchema` type is supported.
+pub fn is_supported_schema_type(type_: &str) -> bool {
+    type_ == JSON_SCHEMA_VALIDATOR_2018 || type_ == JSON_SCHEMA
+}
+
+/// Validates a credential against each of its schemas of a supported type, loaded from the
+/// registry: the credential (without its proof) for `JsonSchema`, or its subject for
+/// `JsonSchemaValidator2018`. Returns any violations, with paths relative to the credential.
+pub fn validate_credential(
+    credential: &Credential,
+    registry: &SchemaRegistry,
+) -> Result<Vec<SchemaViolation>, SchemaError> {
+    let mut value = serde_json::to_value(credential)
+        .map_err(|e| SchemaError::InvalidSchema(String::new(), e.to_string()))?;
+    if let Some(object) = value.as_object_mut() {
+        object.remove("proof");
+    }
+    let mut violations = Vec::new();
+    for schema in credential.credential_schema.iter().flatten() {
+        if !is_supported_schema_type(&schema.type_) {
+            continue;
+        }
+        let URI::String(id) = &schema.id;
+        let json_schema = registry
+            .get(id)
+            .ok_or_else(|| SchemaError::SchemaNotFound(id.to_owned()))?;
+        let (instance, path) = match schema.type_.as_str() {
+            JSON_SCHEMA => (&value, ""),
+            _ => (&value["credentialSubject"], "/credentialSubject"),
+        };
+        violations.extend(
+            validate(id, json_schema, instance)?
+                .into_iter()
+                .map(|mut violation| {
+                    violation.path = format!("{}{}", path, violation.path);
+                    violation
+                }),
+        );
+    }
+    Ok(violations)
+}
+
+/// Checks that a credential conforms to each of its schemas of a supported type, as required
+/// before it is signed.
+pub fn ensure_conforms(
+    credential: &Credential,
+    registry: &SchemaRegistry,
+) -> Result<(), SchemaError> {
+    let violations = validate_credential(credential, registry)?;
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(SchemaError::Violations(violations))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::TEST_CREDENTIAL;
+    use serde_json::json;
+
+    /// Returns the violations in order, independently of the order of object properties.
+    fn violations(schema: &Value, value: &Value) -> Vec<String> {
+        let mut violations: Vec<String> = validate("test", schema, value)
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        violations.sort();
+        violations
+    }
+
+    #[test]
+    fn test_validate() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "name": {"type": "string", "minLength": 1, "pattern": "^[A-Z]"},
+                "age": {"type": "integer", "minimum": 0, "exclusiveMaximum": 150},
+                "tags": {"type": "array", "items": {"enum": ["a", "b"]}, "uniqueItems": true},
+                "address": {"$ref": "#/definitions/address"}
+            },
+            "required": ["name"],
+            "additionalProperties": false,
+            "definitions": {
+                "address": {
+                    "type": "object",
+                    "properties": {"postcode": {"type": "string"}},
+                    "required": ["postcode"]
+                }
+            }
+        });
+        let valid = json!({"name": "Jane", "age": 30, "tags": ["a"], "address": {"postcode": "X"}});
+        assert!(violations(&schema, &valid).is_empty());
+
+        let invalid = json!({
+            "name": "jane",
+            "age": 30.5,
+            "tags": ["a", "a", "c"],
+            "address": {},
+            "other": true
+        });
+        assert_eq!(
+            violations(&schema, &invalid),
+            vec![
+                "/: additional property not allowed: other",
+                "/address: missing required property: postcode",
+                "/age: expected integer but found number",
+                "/name: expected to match pattern: ^[A-Z]",
+                "/tags/2: expected one of [\"a\",\"b\"]",
+                "/tags: expected unique items",
+            ]
+        );
+        assert_eq!(
+            violations(&schema, &json!({"age": 150})),
+            vec![
+                "/: missing required property: name",
+                "/age: expected less than 150"
+            ]
+        );
+
+        // Multiples of decimals are not rejected for rounding error.
+        let schema = json!({"multipleOf": 0.1});
+        for multiple in [json!(0.3), json!(0.7), json!(3), json!(-1.2)] {
+            assert!(violations(&schema, &multiple).is_empty(), "{}", multiple);
+        }
+        assert_eq!(
+            violations(&schema, &json!(0.35)),
+            vec!["/: expected a multiple of 0.1"]
+        );
+        assert_eq!(
+            violations(&json!({"multipleOf": 7}), &json!(9007199254740993i64)),
+            vec!["/: expected a multiple of 7"]
+        );
+        for divisor in [json!(0), json!(-2), json!("2")] {
+            assert_eq!(
+                validate("test", &json!({"multipleOf": divisor}), &json!(4)),
+                Err(SchemaError::InvalidSchema(
+                    "test".to_owned(),
+                    "multipleOf must be greater than 0".to_owned()
+                ))
+            );
+        }
+
+        // Arrays containing an item matching a schema.
+        let schema = json!({"type": "array", "contains": {"const": "DegreeCredential"}});
+        assert!(violations(
+            &schema,
+            &json!(["VerifiableCredential", "DegreeCredential"])
+        )
+        .is_empty());
+        assert_eq!(
+            violations(&schema, &json!(["VerifiableCredential"])),
+            vec!["/: expected an item matching the schema of contains"]
+        );
+        assert_eq!(violations(&schema, &json!([])).len(), 1);
+
+        // Combinations and boolean schemas.
+        let schema =
+            json!({"oneOf": [{"type": "string"}, {"maxLength": 3}], "not": {"const": "no"}});
+        assert!(violations(&schema, &json!(1)).is_empty());
+        assert!(violations(&schema, &json!("long")).is_empty());
+        assert_eq!(violations(&schema, &json!("ok")).len(), 1);
+        assert_eq!(violations(&schema, &json!("no")).len(), 2);
+        assert_eq!(violations(&json!(false), &json!(1)).len(), 1);
+
+        // Invalid schemas are errors.
+        assert!(validate("test", &json!({"pattern": "["}), &json!("a")).is_err());
+        assert!(validate("test", &json!({"$ref": "#/missing"}), &json!(1)).is_err());
+    }
+
+    #[test]
+    fn test_validate_pattern_properties() {
+        let schema = json!({
+            "type": "object",
+            "properties": {"name": {"type": "string"}},
+            "patternProperties": {"^x-": {"type": "integer"}},
+            "additionalProperties": false
+        });
+        assert!(violations(&schema, &json!({"name": "Jane", "x-age": 30})).is_empty());
+        assert_eq!(
+            violations(&schema, &json!({"x-age": "30", "other": 1})),
+            vec![
+                "/: additional property not allowed: other",
+                "/x-age: expected integer but found string",
+            ]
+        );
+        assert!(validate("test", &json!({"patternProperties": {"[": {}}}), &json!({})).is_err());
+    }
+
+    #[test]
+    fn test_validate_circular_reference() {
+        let schema = json!({
+            "$ref": "#/definitions/a",
+            "definitions": {"a": {"$ref": "#/definitions/b"}, "b": {"not": {"$ref": "#/definitions/a"}}}
+        });
+        for (schema, pointer) in [
+            (json!({"$ref": "#"}), ""),
+            (json!({"allOf": [{"$ref": "#"}]}), ""),
+            (schema, "/definitions/a"),
+        ] {
+            assert_eq!(
+                validate("test", &schema, &json!(1)),
+                Err(SchemaError::InvalidSchema(
+                    "test".to_owned(),
+                    format!("circular reference: #{}", pointer)
+                ))
+            );
+        }
+
+        // Recursive schemas are valid when each reference applies to a nested value.
+        let schema = json!({
+            "type": "object",
+            "properties": {"children": {"type": "array", "items": {"$ref": "#"}}}
+        });
+        let tree = json!({"children": [{"children": []}, {"children": [{}]}]});
+        assert!(violations(&schema, &tree).is_empty());
+        assert_eq!(
+            violations(&schema, &json!({"children": [{"children": 1}]})),
+            vec!["/children/0/children: expected array but found integer"]
+        );
+    }
+
+    #[test]
+    fn test_validate_unsupported_keywords() {
+        for (schema, keyword) in [
+            (json!({"if": {"type": "string"}}), "if"),
+            (json!({"then": {"minLength": 1}}), "then"),
+            (json!({"else": {"minLength": 1}}), "else"),
+            (json!({"dependencies": {"a": ["b"]}}), "dependencies"),
+            (json!({"propertyNames": {"pattern": "^a"}}), "propertyNames"),
+            (json!({"minProperties": 1}), "minProperties"),
+            (json!({"maxProperties": 1}), "maxProperties"),
+            (json!({"additionalItems": false}), "additionalItems"),
+        ] {
+            assert_eq!(
+                validate("test", &schema, &json!({"name": "Jane"})),
+                Err(SchemaError::InvalidSchema(
+                    "test".to_owned(),
+                    format!("unsupported keyword: {}", keyword)
+                ))
+            );
+        }
+
+        // Annotations are allowed.
+        let schema = json!({
+            "$schema": "http://json-schema.org/draft-07/schema#",
+            "$id": "https://example.com/schemas/name.json",
+            "title": "Name",
+            "description": "A name.",
+            "$comment": "Used in tests.",
+            "default": "Jane",
+            "examples": ["Jane"],
+            "format": "email",
+            "type": "string"
+        });
+        assert!(violations(&schema, &json!("Jane")).is_empty());
+    }
+
+    #[test]
+    fn test_validate_credential() {
+        let mut credential: Credential = serde_json::from_str(TEST_CREDENTIAL).unwrap();
+        let mut registry = SchemaRegistry::default();
+        // The schema of the test credential has an unsupported type.
+        assert_eq!(validate_credential(&credential, &registry), Ok(vec![]));
+
+        let id = "https://example.com/schemas/degree.json";
+        credential.credential_schema = Some(ssi::one_or_many::OneOrMany::One(
+            serde_json::from_value(json!({"id": id, "type": JSON_SCHEMA_VALIDATOR_2018})).unwrap(),
+        ));
+        assert_eq!(
+            ensure_conforms(&credential, &registry),
+            Err(SchemaError::SchemaNotFound(id.to_owned()))
+        );
+        registry
+            .insert(
+                id,
+                json!({
+                    "type": "object",
+                    "properties": {"degree": {"required": ["type", "name"]}},
+                    "required": ["givenName", "familyName", "degree"]
+                }),
+            )
+            .unwrap();
+        assert_eq!(ensure_conforms(&credential, &registry), Ok(()));
+
+        credential
+            .credential_subject
+            .to_single_mut()
+            .unwrap()
+            .property_set =
+            Some(serde_json::from_value(json!({"givenName": "Jane", "degree": {}})).unwrap());
+        let violations: Vec<String> = validate_credential(&credential, &registry)
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            violations,
+            vec![
+                "/credentialSubject: missing required property: familyName",
+                "/credentialSubject/degree: missing required property: type",
+                "/credentialSubject/degree: missing required property: name",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_schema_registry_from_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let id = "https://example.com/schemas/degree.json";
+        let schema = json!({"type": "object"});
+        assert_eq!(
+            SchemaRegistry::from_dir(dir.path()),
+            Ok(SchemaRegistry::default())
+        );
+        save_schema(dir.path(), id, "degree.json", &schema).unwrap();
+        let registry = SchemaRegistry::from_dir(dir.path()).unwrap();
+        assert_eq!(registry.ids(), vec![id]);
+        assert_eq!(registry.get(id), Some(&schema));
+
+        // A schema saved again under the same ID replaces the previous one.
+        let stricter = json!({"type": "object", "required": ["studentId"]});
+        save_schema(dir.path(), id, "degree.json", &stricter).unwrap();
+        let registry = SchemaRegistry::from_dir(dir.path()).unwrap();
+        assert_eq!(registry.ids(), vec![id]);
+        assert_eq!(registry.get(id), Some(&stricter));
+        assert!(save_schema(dir.path(), id, "degree.json", &json!([])).is_err());
+        assert!(save_schema(dir.path(), id, SCHEMAS_INDEX, &schema).is_err());
+    }
+}
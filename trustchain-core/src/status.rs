@@ -0,0 +1,785 @@
+//! Credential status with [Status List 2021](https://www.w3.org/TR/vc-status-list/), for the
+//! revocation and suspension of credentials issued by Trustchain issuers.
+use crate::context::{add_context, inline_contexts};
+use crate::utils::hash;
+use crate::vc::issuer_did;
+use crate::TRUSTCHAIN_DATA;
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use ssi::did_resolve::DIDResolver;
+use ssi::revocation::{EncodedList, List};
+use ssi::vc::{Credential, Status, URI};
+use std::convert::TryFrom;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use thiserror::Error;
+use url::Url;
+
+/// URL of the Status List 2021 JSON-LD context.
+pub const STATUS_LIST_2021_V1_CONTEXT: &str = "https://w3id.org/vc/status-list/2021/v1";
+
+/// The Status List 2021 JSON-LD context document.
+pub const STATUS_LIST_2021_V1_CONTEXT_DOCUMENT: &str = r##"{
+  "@context": {
+    "@protected": true,
+    "StatusList2021Credential": {
+      "@id": "https://w3id.org/vc/status-list#StatusList2021Credential",
+      "@context": {
+        "@protected": true,
+        "id": "@id",
+        "type": "@type",
+        "description": "http://schema.org/description",
+        "name": "http://schema.org/name"
+      }
+    },
+    "StatusList2021": {
+      "@id": "https://w3id.org/vc/status-list#StatusList2021",
+      "@context": {
+        "@protected": true,
+        "id": "@id",
+        "type": "@type",
+        "statusPurpose": "https://w3id.org/vc/status-list#statusPurpose",
+        "encodedList": "https://w3id.org/vc/status-list#encodedList"
+      }
+    },
+    "StatusList2021Entry": {
+      "@id": "https://w3id.org/vc/status-list#StatusList2021Entry",
+      "@context": {
+        "@protected": true,
+        "id": "@id",
+        "type": "@type",
+        "statusPurpose": "https://w3id.org/vc/status-list#statusPurpose",
+        "statusListIndex": "https://w3id.org/vc/status-list#statusListIndex",
+        "statusListCredential": {
+          "@id": "https://w3id.org/vc/status-list#statusListCredential",
+          "@type": "@id"
+        }
+      }
+    }
+  }
+}"##;
+
+/// Type of a credential status entry in a status list.
+pub const STATUS_LIST_2021_ENTRY: &str = "StatusList2021Entry";
+
+/// Type of a status list credential.
+pub const STATUS_LIST_2021_CREDENTIAL: &str = "StatusList2021Credential";
+
+/// Type of the subject of a status list credential.
+pub const STATUS_LIST_2021: &str = "StatusList2021";
+
+/// Minimum length (in bits) of a status list, for herd privacy of the credentials it covers.
+pub const MIN_STATUS_LIST_LENGTH: usize = 131072;
+
+/// Directory of the Trustchain data directory in which issuer status lists are saved.
+const STATUS_LIST_DIR: &str = "status_lists";
+
+/// Directory of the Trustchain data directory with the status list credentials available locally,
+/// by the hash of their URL.
+const STATUS_LIST_CREDENTIALS_DIR: &str = "status_list_credentials";
+
+/// An error relating to credential status.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum StatusError {
+    /// Failed to decode an encoded status list.
+    #[error("Failed to decode status list: {0}")]
+    FailedToDecode(String),
+    /// Failed to encode a status list.
+    #[error("Failed to encode status list: {0}")]
+    FailedToEncode(String),
+    /// Status list is shorter than the minimum length.
+    #[error("Status list of length: {0} is shorter than the minimum: {1}.")]
+    ListTooShort(usize, usize),
+    /// Index out of bounds of a status list.
+    #[error("Index: {0} is out of bounds for status list of length: {1}.")]
+    OutOfBounds(usize, usize),
+    /// Every index of a status list has been allocated.
+    #[error("Every index of the status list has been allocated.")]
+    ListFull,
+    /// Invalid credential status entry.
+    #[error("Invalid credential status entry: {0}")]
+    InvalidEntry(String),
+    /// Invalid status purpose.
+    #[error("Invalid status purpose: {0}")]
+    InvalidPurpose(String),
+    /// Invalid status list credential.
+    #[error("Invalid status list credential: {0}")]
+    InvalidStatusListCredential(String),
+    /// Failed to load a status list credential.
+    #[error("Failed to load status list credential: {0}: {1}")]
+    FailedToLoad(String, String),
+    /// Invalid proof of a status list credential.
+    #[error("Invalid proof of status list credential: {0:?}")]
+    InvalidProof(Vec<String>),
+    /// Status list credential issuer does not match the credential issuer.
+    #[error("Status list issuer: {0} does not match the credential issuer: {1}.")]
+    MismatchedIssuer(String, String),
+    /// Status list credential ID does not match the URL of the credential status entry.
+    #[error("Status list credential ID: {0} does not match the status list URL: {1}.")]
+    MismatchedId(String, String),
+    /// Status list purpose does not match the credential status entry purpose.
+    #[error("Status list purpose: {0} does not match the credential status purpose: {1}.")]
+    MismatchedPurpose(StatusPurpose, StatusPurpose),
+    /// Issuer status list could not be read or saved.
+    #[error("Failed to read or save status list: {0}")]
+    Storage(String),
+    /// Issuer status list already exists.
+    #[error("Status list already exists.")]
+    ListExists,
+    /// No TRUSTCHAIN_DATA environment variable.
+    #[error("No TRUSTCHAIN_DATA environment variable.")]
+    TrustchainDataNotPresent,
+}
+
+/// Purpose of a status list: the meaning of a set bit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatusPurpose {
+    /// A set bit permanently revokes the credential.
+    Revocation,
+    /// A set bit temporarily suspends the credential.
+    Suspension,
+}
+
+impl fmt::Display for StatusPurpose {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StatusPurpose::Revocation => write!(f, "revocation"),
+            StatusPurpose::Suspension => write!(f, "suspension"),
+        }
+    }
+}
+
+impl FromStr for StatusPurpose {
+    type Err = StatusError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "revocation" => Ok(StatusPurpose::Revocation),
+            "suspension" => Ok(StatusPurpose::Suspension),
+            _ => Err(StatusError::InvalidPurpose(s.to_owned())),
+        }
+    }
+}
+
+/// A status list bitstring, with index zero at the most significant bit of the first byte.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusList(Vec<u8>);
+
+impl Default for StatusList {
+    fn default() -> Self {
+        Self::new(MIN_STATUS_LIST_LENGTH)
+    }
+}
+
+impl StatusList {
+    /// Makes a status list of at least the given length (in bits) with every bit unset.
+    pub fn new(length: usize) -> Self {
+        Self(vec![0; length.div_ceil(8)])
+    }
+
+    /// Returns the length (in bits) of the status list.
+    pub fn len(&self) -> usize {
+        self.0.len() * 8
+    }
+
+    /// Returns whether the status list is empty.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns whether the bit at an index is set.
+    pub fn get(&self, index: usize) -> Result<bool, StatusError> {
+        let byte = self
+            .0
+            .get(index / 8)
+            .ok_or_else(|| StatusError::OutOfBounds(index, self.len()))?;
+        Ok(byte & (0x80 >> (index % 8)) != 0)
+    }
+
+    /// Sets or unsets the bit at an index.
+    pub fn set(&mut self, index: usize, status: bool) -> Result<(), StatusError> {
+        let length = self.len();
+        let byte = self
+            .0
+            .get_mut(index / 8)
+            .ok_or(StatusError::OutOfBounds(index, length))?;
+        if status {
+            *byte |= 0x80 >> (index % 8);
+        } else {
+            *byte &= !(0x80 >> (index % 8));
+        }
+        Ok(())
+    }
+
+    /// Encodes the status list as a GZIP-compressed, base64url-encoded string.
+    pub fn encode(&self) -> Result<String, StatusError> {
+        EncodedList::try_from(&List(self.0.clone()))
+            .map(|encoded| encoded.0)
+            .map_err(|e| StatusError::FailedToEncode(e.to_string()))
+    }
+
+    /// Decodes an encoded status list, which must be of at least the minimum length.
+    pub fn decode(encoded: &str) -> Result<Self, StatusError> {
+        let list = List::try_from(&EncodedList(encoded.to_owned()))
+            .map_err(|e| StatusError::FailedToDecode(e.to_string()))?;
+        let list = Self(list.0);
+        if list.len() < MIN_STATUS_LIST_LENGTH {
+            return Err(StatusError::ListTooShort(
+                list.len(),
+                MIN_STATUS_LIST_LENGTH,
+            ));
+        }
+        Ok(list)
+    }
+}
+
+/// A credential status entry referencing an index of a status list credential.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusList2021Entry {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub status_purpose: StatusPurpose,
+    pub status_list_index: String,
+    pub status_list_credential: String,
+}
+
+impl StatusList2021Entry {
+    /// Makes an entry for an index of the status list credential at the given URL.
+    pub fn new(status_list_credential: &str, status_purpose: StatusPurpose, index: usize) -> Self {
+        Self {
+            id: format!("{}#{}", status_list_credential, index),
+            type_: STATUS_LIST_2021_ENTRY.to_owned(),
+            status_purpose,
+            status_list_index: index.to_string(),
+            status_list_credential: status_list_credential.to_owned(),
+        }
+    }
+
+    /// Returns the index of the entry in the status list.
+    pub fn index(&self) -> Result<usize, StatusError> {
+        self.status_list_index
+            .parse()
+            .map_err(|_| StatusError::InvalidEntry(self.status_list_index.to_owned()))
+    }
+
+    /// Returns the entry as the `credentialStatus` of a credential.
+    pub fn to_status(&self) -> Status {
+        serde_json::from_value(json!(self)).unwrap()
+    }
+}
+
+impl TryFrom<&Status> for StatusList2021Entry {
+    type Error = StatusError;
+
+    fn try_from(status: &Status) -> Result<Self, Self::Error> {
+        let entry: Self = serde_json::to_value(status)
+            .and_then(serde_json::from_value)
+            .map_err(|e| StatusError::InvalidEntry(e.to_string()))?;
+        if entry.type_ != STATUS_LIST_2021_ENTRY {
+            return Err(StatusError::InvalidEntry(entry.type_));
+        }
+        Ok(entry)
+    }
+}
+
+/// Adds a status entry to a credential, with the Status List 2021 context.
+pub fn add_credential_status(credential: &mut Credential, entry: &StatusList2021Entry) {
+    add_context(credential, STATUS_LIST_2021_V1_CONTEXT);
+    credential.credential_status = Some(entry.to_status());
+}
+
+/// Makes an unsigned status list credential, issued by the given DID and published at the given
+/// URL.
+pub fn status_list_credential(
+    issuer: &str,
+    id: &str,
+    status_purpose: StatusPurpose,
+    list: &StatusList,
+) -> Result<Credential, StatusError> {
+    serde_json::from_value(json!({
+        "@context": ["https://www.w3.org/2018/credentials/v1", STATUS_LIST_2021_V1_CONTEXT],
+        "id": id,
+        "type": ["VerifiableCredential", STATUS_LIST_2021_CREDENTIAL],
+        "issuer": issuer,
+        "issuanceDate": Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        "credentialSubject": {
+            "id": format!("{}#list", id),
+            "type": STATUS_LIST_2021,
+            "statusPurpose": status_purpose,
+            "encodedList": list.encode()?
+        }
+    }))
+    .map_err(|e| StatusError::InvalidStatusListCredential(e.to_string()))
+}
+
+/// Returns the purpose and status list of a status list credential.
+pub fn decode_status_list_credential(
+    credential: &Credential,
+) -> Result<(StatusPurpose, StatusList), StatusError> {
+    let invalid = |reason: &str| StatusError::InvalidStatusListCredential(reason.to_owned());
+    if !credential
+        .type_
+        .contains(&STATUS_LIST_2021_CREDENTIAL.to_owned())
+    {
+        return Err(invalid("missing type StatusList2021Credential"));
+    }
+    let subject = credential
+        .credential_subject
+        .to_single()
+        .and_then(|subject| subject.property_set.as_ref())
+        .ok_or_else(|| invalid("expected a single credential subject"))?;
+    if subject.get("type").and_then(|t| t.as_str()) != Some(STATUS_LIST_2021) {
+        return Err(invalid("credential subject is not of type StatusList2021"));
+    }
+    let status_purpose = subject
+        .get("statusPurpose")
+        .cloned()
+        .and_then(|purpose| serde_json::from_value(purpose).ok())
+        .ok_or_else(|| invalid("missing or invalid statusPurpose"))?;
+    let encoded_list = subject
+        .get("encodedList")
+        .and_then(|list| list.as_str())
+        .ok_or_else(|| invalid("missing encodedList"))?;
+    Ok((status_purpose, StatusList::decode(encoded_list)?))
+}
+
+/// Returns the path of the directory of status list credentials available locally in the
+/// Trustchain data directory.
+pub fn trustchain_status_list_credentials_dir() -> Result<PathBuf, StatusError> {
+    let path = std::env::var(TRUSTCHAIN_DATA).map_err(|_| StatusError::TrustchainDataNotPresent)?;
+    Ok(Path::new(path.as_str()).join(STATUS_LIST_CREDENTIALS_DIR))
+}
+
+/// Returns the path in a directory of the status list credential with the given URL.
+fn status_list_credential_path(dir: &Path, url: &str) -> PathBuf {
+    dir.join(format!("{}.json", hash(url)))
+}
+
+/// Saves a status list credential to the given directory, under the URL of its ID, so that it
+/// is loaded locally. Returns the path of the saved credential.
+pub fn save_status_list_credential<P: AsRef<Path>>(
+    dir: P,
+    credential: &Credential,
+) -> Result<PathBuf, StatusError> {
+    let url = match &credential.id {
+        Some(URI::String(url)) => url,
+        None => {
+            return Err(StatusError::InvalidStatusListCredential(
+                "missing id".to_owned(),
+            ))
+        }
+    };
+    let dir = dir.as_ref();
+    std::fs::create_dir_all(dir).map_err(|e| StatusError::Storage(e.to_string()))?;
+    let path = status_list_credential_path(dir, url);
+    let json = serde_json::to_string_pretty(credential).unwrap();
+    std::fs::write(&path, json).map_err(|e| StatusError::Storage(e.to_string()))?;
+    Ok(path)
+}
+
+/// Loads the status list credential with the given URL: from the local status list credentials
+/// directory if saved there (as by an issuer publishing it, or for offline verification), or
+/// otherwise from the URL if HTTP(S), which requires the `http` feature. Other URLs, including
+/// `file://` URLs, are not followed, since the URL is given by the credential.
+pub async fn load_status_list_credential(url: &str) -> Result<Credential, StatusError> {
+    let failed = |e: &dyn fmt::Display| StatusError::FailedToLoad(url.to_owned(), e.to_string());
+    if let Ok(dir) = trustchain_status_list_credentials_dir() {
+        let path = status_list_credential_path(&dir, url);
+        if path.exists() {
+            let body = std::fs::read_to_string(path).map_err(|e| failed(&e))?;
+            return serde_json::from_str(&body).map_err(|e| failed(&e));
+        }
+    }
+    let parsed = Url::parse(url).map_err(|e| failed(&e))?;
+    let body: String = match parsed.scheme() {
+        #[cfg(feature = "http")]
+        "http" | "https" => reqwest::get(url)
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| failed(&e))?
+            .text()
+            .await
+            .map_err(|e| failed(&e))?,
+        #[cfg(not(feature = "http"))]
+        "http" | "https" => return Err(failed(&"not available locally")),
+        scheme => return Err(failed(&format!("unsupported scheme: {}", scheme))),
+    };
+    serde_json::from_str(&body).map_err(|e| failed(&e))
+}
+
+/// Checks the status of a credential with a status entry: loads the status list credential,
+/// verifies its proof and that it has the URL of the entry as its ID and the same issuer and
+/// purpose as the credential status, and returns whether the credential's bit is set.
+pub async fn check_credential_status<T: DIDResolver>(
+    credential: &Credential,
+    entry: &StatusList2021Entry,
+    resolver: &T,
+) -> Result<bool, StatusError> {
+    let list_credential = load_status_list_credential(&entry.status_list_credential).await?;
+    match &list_credential.id {
+        Some(URI::String(id)) if id == &entry.status_list_credential => (),
+        id => {
+            return Err(StatusError::MismatchedId(
+                id.as_ref()
+                    .map(|URI::String(id)| id.to_owned())
+                    .unwrap_or_default(),
+                entry.status_list_credential.to_owned(),
+            ))
+        }
+    }
+    let result = inline_contexts(&list_credential)
+        .verify(None, resolver)
+        .await;
+    if !result.errors.is_empty() {
+        return Err(StatusError::InvalidProof(result.errors));
+    }
+    let (list_issuer, issuer) = (issuer_did(&list_credential), issuer_did(credential));
+    if list_issuer.is_none() || list_issuer != issuer {
+        return Err(StatusError::MismatchedIssuer(
+            list_issuer.unwrap_or_default().to_owned(),
+            issuer.unwrap_or_default().to_owned(),
+        ));
+    }
+    let (status_purpose, list) = decode_status_list_credential(&list_credential)?;
+    if status_purpose != entry.status_purpose {
+        return Err(StatusError::MismatchedPurpose(
+            status_purpose,
+            entry.status_purpose,
+        ));
+    }
+    list.get(entry.index()?)
+}
+
+/// The state of an issuer's status list: the URL at which its status list credential is
+/// published, the list itself and the next index to allocate to an issued credential.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IssuerStatusList {
+    pub id: String,
+    pub status_purpose: StatusPurpose,
+    pub encoded_list: String,
+    pub next_index: usize,
+}
+
+impl IssuerStatusList {
+    /// Makes a status list of the given length (in bits), to be published at the given URL.
+    pub fn new(
+        id: &str,
+        status_purpose: StatusPurpose,
+        length: usize,
+    ) -> Result<Self, StatusError> {
+        if length < MIN_STATUS_LIST_LENGTH {
+            return Err(StatusError::ListTooShort(length, MIN_STATUS_LIST_LENGTH));
+        }
+        Ok(Self {
+            id: id.to_owned(),
+            status_purpose,
+            encoded_list: StatusList::new(length).encode()?,
+            next_index: 0,
+        })
+    }
+
+    /// Returns the decoded status list.
+    pub fn list(&self) -> Result<StatusList, StatusError> {
+        StatusList::decode(&self.encoded_list)
+    }
+
+    /// Allocates the next index of the status list, returning its status entry.
+    pub fn allocate(&mut self) -> Result<StatusList2021Entry, StatusError> {
+        if self.next_index >= self.list()?.len() {
+            return Err(StatusError::ListFull);
+        }
+        let entry = StatusList2021Entry::new(&self.id, self.status_purpose, self.next_index);
+        self.next_index += 1;
+        Ok(entry)
+    }
+
+    /// Sets or unsets the status of an allocated index.
+    pub fn set_status(&mut self, index: usize, status: bool) -> Result<(), StatusError> {
+        let mut list = self.list()?;
+        if index >= self.next_index {
+            return Err(StatusError::OutOfBounds(index, self.next_index));
+        }
+        list.set(index, status)?;
+        self.encoded_list = list.encode()?;
+        Ok(())
+    }
+
+    /// Makes the unsigned status list credential of the list, issued by the given DID.
+    pub fn credential(&self, issuer: &str) -> Result<Credential, StatusError> {
+        status_list_credential(issuer, &self.id, self.status_purpose, &self.list()?)
+    }
+}
+
+/// Management of an issuer's status lists (one per purpose), saved in the Trustchain data
+/// directory.
+pub trait StatusListManager {
+    /// Gets the path of the status list of a given purpose.
+    fn get_status_list_path(
+        &self,
+        did_suffix: &str,
+        status_purpose: StatusPurpose,
+    ) -> Result<PathBuf, StatusError> {
+        let path =
+            std::env::var(TRUSTCHAIN_DATA).map_err(|_| StatusError::TrustchainDataNotPresent)?;
+        Ok(Path::new(path.as_str())
+            .join(STATUS_LIST_DIR)
+            .join(did_suffix)
+            .join(format!("{}_list.json", status_purpose)))
+    }
+
+    /// Reads the status list of a given purpose.
+    fn read_status_list(
+        &self,
+        did_suffix: &str,
+        status_purpose: StatusPurpose,
+    ) -> Result<IssuerStatusList, StatusError> {
+        let path = self.get_status_list_path(did_suffix, status_purpose)?;
+        let file =
+            std::fs::read_to_string(path).map_err(|e| StatusError::Storage(e.to_string()))?;
+        serde_json::from_str(&file).map_err(|e| StatusError::Storage(e.to_string()))
+    }
+
+    /// Saves a status list, replacing an existing list of the same purpose only if `overwrite`.
+    fn save_status_list(
+        &self,
+        did_suffix: &str,
+        list: &IssuerStatusList,
+        overwrite: bool,
+    ) -> Result<(), StatusError> {
+        let path = self.get_status_list_path(did_suffix, list.status_purpose)?;
+        if path.exists() && !overwrite {
+            return Err(StatusError::ListExists);
+        }
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory).map_err(|e| StatusError::Storage(e.to_string()))?;
+        }
+        let json = serde_json::to_string_pretty(list).unwrap();
+        std::fs::write(path, json).map_err(|e| StatusError::Storage(e.to_string()))
+    }
+
+    /// Allocates the next index of the status list of a given purpose to a credential, adding
+    /// its status entry to the credential and saving the allocation.
+    fn allocate_credential_status(
+        &self,
+        did_suffix: &str,
+        status_purpose: StatusPurpose,
+        credential: &mut Credential,
+    ) -> Result<StatusList2021Entry, StatusError> {
+        let mut list = self.read_status_list(did_suffix, status_purpose)?;
+        let entry = list.allocate()?;
+        self.save_status_list(did_suffix, &list, true)?;
+        add_credential_status(credential, &entry);
+        Ok(entry)
+    }
+
+    /// Sets or unsets the status of an index of the status list of a given purpose, saving and
+    /// returning the updated list.
+    fn update_status(
+        &self,
+        did_suffix: &str,
+        status_purpose: StatusPurpose,
+        index: usize,
+        status: bool,
+    ) -> Result<IssuerStatusList, StatusError> {
+        let mut list = self.read_status_list(did_suffix, status_purpose)?;
+        list.set_status(index, status)?;
+        self.save_status_list(did_suffix, &list, true)?;
+        Ok(list)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::TEST_CREDENTIAL;
+    use crate::utils::init;
+    use ssi::vc::URI;
+
+    const TEST_STATUS_LIST_URL: &str = "https://example.com/status/1";
+
+    struct TestStatusListManager;
+
+    impl StatusListManager for TestStatusListManager {}
+
+    #[test]
+    fn test_status_list() {
+        let mut list = StatusList::default();
+        assert_eq!(list.len(), MIN_STATUS_LIST_LENGTH);
+        list.set(0, true).unwrap();
+        list.set(9, true).unwrap();
+        // Index zero is the most significant bit of the first byte.
+        assert_eq!(list.0[..2], [0x80, 0x40]);
+        assert!(list.get(9).unwrap());
+        list.set(9, false).unwrap();
+        assert!(!list.get(9).unwrap());
+        assert_eq!(
+            list.set(MIN_STATUS_LIST_LENGTH, true),
+            Err(StatusError::OutOfBounds(
+                MIN_STATUS_LIST_LENGTH,
+                MIN_STATUS_LIST_LENGTH
+            ))
+        );
+
+        let decoded = StatusList::decode(&list.encode().unwrap()).unwrap();
+        assert_eq!(decoded, list);
+        assert!(matches!(
+            StatusList::decode(&StatusList::new(8).encode().unwrap()),
+            Err(StatusError::ListTooShort(8, MIN_STATUS_LIST_LENGTH))
+        ));
+    }
+
+    #[test]
+    fn test_status_entry() {
+        let entry =
+            StatusList2021Entry::new(TEST_STATUS_LIST_URL, StatusPurpose::Revocation, 94567);
+        let status = entry.to_status();
+        assert_eq!(status.type_, STATUS_LIST_2021_ENTRY);
+        assert_eq!(
+            serde_json::to_value(&status).unwrap()["statusListIndex"],
+            json!("94567")
+        );
+        let parsed = StatusList2021Entry::try_from(&status).unwrap();
+        assert_eq!(parsed, entry);
+        assert_eq!(parsed.index(), Ok(94567));
+
+        let mut credential: Credential = serde_json::from_str(TEST_CREDENTIAL).unwrap();
+        add_credential_status(&mut credential, &entry);
+        assert!(credential.context.contains_uri(STATUS_LIST_2021_V1_CONTEXT));
+        assert!(credential.credential_status.is_some());
+    }
+
+    #[test]
+    fn test_status_list_credential() {
+        let mut list = StatusList::default();
+        list.set(3, true).unwrap();
+        let credential = status_list_credential(
+            "did:example:issuer",
+            TEST_STATUS_LIST_URL,
+            StatusPurpose::Suspension,
+            &list,
+        )
+        .unwrap();
+        assert_eq!(issuer_did(&credential), Some("did:example:issuer"));
+        assert_eq!(
+            decode_status_list_credential(&credential).unwrap(),
+            (StatusPurpose::Suspension, list)
+        );
+
+        let credential: Credential = serde_json::from_str(TEST_CREDENTIAL).unwrap();
+        assert!(matches!(
+            decode_status_list_credential(&credential),
+            Err(StatusError::InvalidStatusListCredential(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_load_status_list_credential() {
+        // A status list credential published locally, as the issuer updates it.
+        init();
+        let dir = trustchain_status_list_credentials_dir().unwrap();
+        let url = "https://example.com/status/load";
+        let mut list =
+            IssuerStatusList::new(url, StatusPurpose::Revocation, MIN_STATUS_LIST_LENGTH).unwrap();
+        let publish = |list: &IssuerStatusList| {
+            let credential = list.credential("did:example:issuer").unwrap();
+            save_status_list_credential(&dir, &credential).unwrap();
+        };
+        publish(&list);
+        let entry = list.allocate().unwrap();
+        let credential = load_status_list_credential(url).await.unwrap();
+        assert_eq!(credential.id, Some(URI::String(url.to_owned())));
+        let (purpose, loaded) = decode_status_list_credential(&credential).unwrap();
+        assert_eq!(purpose, StatusPurpose::Revocation);
+        assert!(!loaded.get(entry.index().unwrap()).unwrap());
+
+        list.set_status(entry.index().unwrap(), true).unwrap();
+        publish(&list);
+        let credential = load_status_list_credential(url).await.unwrap();
+        let (_, loaded) = decode_status_list_credential(&credential).unwrap();
+        assert!(loaded.get(entry.index().unwrap()).unwrap());
+
+        // File URLs given by a credential are not followed, nor are unsupported schemes.
+        let file_dir = tempfile::tempdir().unwrap();
+        let file = file_dir.path().join("list.json");
+        std::fs::write(&file, serde_json::to_string(&credential).unwrap()).unwrap();
+        let file_url = Url::from_file_path(&file).unwrap();
+        assert!(matches!(
+            load_status_list_credential(file_url.as_str()).await,
+            Err(StatusError::FailedToLoad(..))
+        ));
+        assert!(matches!(
+            load_status_list_credential("ftp://example.com/status/1").await,
+            Err(StatusError::FailedToLoad(..))
+        ));
+
+        // A status list credential is only accepted at the URL of its ID.
+        let other_url = "https://example.com/status/other";
+        std::fs::copy(
+            status_list_credential_path(&dir, url),
+            status_list_credential_path(&dir, other_url),
+        )
+        .unwrap();
+        let other = StatusList2021Entry::new(other_url, StatusPurpose::Revocation, 0);
+        let resolver = crate::methods::DIDKeyResolver;
+        assert_eq!(
+            check_credential_status(&credential, &other, &resolver).await,
+            Err(StatusError::MismatchedId(
+                url.to_owned(),
+                other_url.to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_status_list_manager() {
+        init();
+        let manager = TestStatusListManager;
+        let did_suffix = "test_status_list_manager";
+        let list = IssuerStatusList::new(
+            TEST_STATUS_LIST_URL,
+            StatusPurpose::Revocation,
+            MIN_STATUS_LIST_LENGTH,
+        )
+        .unwrap();
+        manager.save_status_list(did_suffix, &list, false).unwrap();
+        assert_eq!(
+            manager.save_status_list(did_suffix, &list, false),
+            Err(StatusError::ListExists)
+        );
+        assert!(matches!(
+            manager.read_status_list(did_suffix, StatusPurpose::Suspension),
+            Err(StatusError::Storage(_))
+        ));
+
+        // Allocated indices are saved.
+        let mut credential: Credential = serde_json::from_str(TEST_CREDENTIAL).unwrap();
+        for index in 0..2 {
+            let entry = manager
+                .allocate_credential_status(did_suffix, StatusPurpose::Revocation, &mut credential)
+                .unwrap();
+            assert_eq!(entry.index(), Ok(index));
+        }
+        let entry = StatusList2021Entry::try_from(credential.credential_status.as_ref().unwrap());
+        assert_eq!(entry.unwrap().index(), Ok(1));
+
+        // Only allocated indices can be updated.
+        let list = manager
+            .update_status(did_suffix, StatusPurpose::Revocation, 1, true)
+            .unwrap();
+        assert!(list.list().unwrap().get(1).unwrap());
+        assert_eq!(
+            manager.read_status_list(did_suffix, StatusPurpose::Revocation),
+            Ok(list)
+        );
+        assert_eq!(
+            manager.update_status(did_suffix, StatusPurpose::Revocation, 2, true),
+            Err(StatusError::OutOfBounds(2, 2))
+        );
+    }
+}
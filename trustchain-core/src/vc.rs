@@ -0,0 +1,407 @@
+//! Verification of Verifiable Credentials with Trustchain.
+use crate::chain::DIDChain;
+use crate::context::inline_contexts;
+use crate::policy::{IssuerPolicy, PolicyViolation};
+use crate::schema::{
+    is_supported_schema_type, validate_credential, SchemaError, SchemaRegistry, SchemaViolation,
+};
+use crate::status::{
+    check_credential_status, StatusError, StatusList2021Entry, StatusPurpose,
+    STATUS_LIST_2021_ENTRY,
+};
+use crate::verifier::{Verifier, VerifierError};
+use chrono::{DateTime, Utc};
+use ssi::did_resolve::DIDResolver;
+use ssi::vc::{Credential, Issuer, Status, VCDateTime, VerificationResult, URI};
+use std::convert::TryFrom;
+use thiserror::Error;
+
+/// An error relating to the Trustchain verification of a credential issuer.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum CredentialError {
+    /// Credential has no issuer.
+    #[error("Credential has no issuer.")]
+    NoIssuer,
+    /// Trustchain verification of the issuer failed.
+    #[error("Trustchain verification of the issuer failed: {0}")]
+    IssuerVerification(VerifierError),
+}
+
+/// Policy for the verification of a credential.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CredentialVerificationPolicy {
+    /// Root event time against which the issuer's DID chain is verified.
+    pub root_event_time: u32,
+    /// Unix time as of which the issuer's DID chain is verified (e.g. the time of issuance), or
+    /// the current state of the chain if `None`.
+    pub version_time: Option<u32>,
+    /// Whether to check the credential proof only, without verifying the issuer's DID chain, the
+    /// credential status or the credential schemas.
+    pub signature_only: bool,
+    /// Unix time at which the validity period is checked, or the current time if `None`.
+    pub validity_time: Option<i64>,
+    /// Policy authorising the issuer by its DID chain to issue the credential's types, or any
+    /// verified issuer if `None`.
+    pub issuer_policy: Option<IssuerPolicy>,
+}
+
+impl CredentialVerificationPolicy {
+    /// Makes a policy verifying the proof, the current state of the issuer's DID chain against
+    /// the given root event time, and the validity period at the current time.
+    pub fn new(root_event_time: u32) -> Self {
+        Self {
+            root_event_time,
+            version_time: None,
+            signature_only: false,
+            validity_time: None,
+            issuer_policy: None,
+        }
+    }
+}
+
+/// Validity of a credential with respect to its issuance and expiration dates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidityPeriod {
+    /// Within the validity period.
+    Valid,
+    /// Before the issuance date.
+    NotYetValid,
+    /// After the expiration date.
+    Expired,
+}
+
+/// Outcome of checking the status of a credential.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CredentialStatusCheck {
+    /// Credential has no `credentialStatus`.
+    NotPresent,
+    /// Status not checked by the policy.
+    NotChecked,
+    /// Credential is neither revoked nor suspended.
+    Active,
+    /// Credential has been revoked.
+    Revoked,
+    /// Credential has been suspended.
+    Suspended,
+    /// Status could not be checked.
+    Invalid(StatusError),
+    /// Credential has a `credentialStatus` of a type that is not checked.
+    Unsupported(String),
+}
+
+impl CredentialStatusCheck {
+    /// Returns whether the status does not invalidate the credential: the credential is neither
+    /// revoked nor suspended, and its status could be checked if of a supported type.
+    pub fn is_valid(&self) -> bool {
+        !matches!(
+            self,
+            CredentialStatusCheck::Revoked
+                | CredentialStatusCheck::Suspended
+                | CredentialStatusCheck::Invalid(_)
+        )
+    }
+}
+
+/// Outcome of validating a credential against its schemas.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CredentialSchemaCheck {
+    /// Credential has no `credentialSchema`.
+    NotPresent,
+    /// Schemas not checked by the policy.
+    NotChecked,
+    /// Credential conforms to its schemas.
+    Valid,
+    /// Credential does not conform to its schemas.
+    Invalid(Vec<SchemaViolation>),
+    /// Schemas could not be checked (e.g. a schema is not in the registry).
+    Failed(SchemaError),
+    /// Credential has a `credentialSchema` of a type that is not checked, and conforms to any
+    /// schemas of a supported type.
+    Unsupported(String),
+}
+
+impl CredentialSchemaCheck {
+    /// Returns whether the credential conforms to its schemas of a supported type.
+    pub fn is_valid(&self) -> bool {
+        !matches!(
+            self,
+            CredentialSchemaCheck::Invalid(_) | CredentialSchemaCheck::Failed(_)
+        )
+    }
+}
+
+/// Outcome of checking that an issuer is authorised to issue a credential.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthorisationCheck {
+    /// Authorisation not checked, as the policy has no issuer policy or the issuer's DID chain
+    /// was not verified.
+    NotChecked,
+    /// Issuer is authorised by the issuer policy.
+    Authorised,
+    /// Issuer is not authorised by the issuer policy.
+    Unauthorised(Vec<PolicyViolation>),
+}
+
+impl AuthorisationCheck {
+    /// Returns whether the issuer is not unauthorised by the issuer policy.
+    pub fn is_valid(&self) -> bool {
+        !matches!(self, AuthorisationCheck::Unauthorised(_))
+    }
+}
+
+/// Result of the verification of a credential.
+#[derive(Debug, Clone)]
+pub struct CredentialVerificationResult {
+    /// Result of verifying the credential proof.
+    pub proof: VerificationResult,
+    /// DID of the issuer, if present.
+    pub issuer: Option<String>,
+    /// Verified DID chain of the issuer, or `None` if not checked by the policy.
+    pub issuer_chain: Option<Result<DIDChain, CredentialError>>,
+    /// Validity with respect to the credential's issuance and expiration dates.
+    pub validity: ValidityPeriod,
+    /// Status of the credential.
+    pub status: CredentialStatusCheck,
+    /// Conformance of the credential to its schemas.
+    pub schema: CredentialSchemaCheck,
+    /// Authorisation of the issuer by the issuer policy.
+    pub authorisation: AuthorisationCheck,
+}
+
+impl CredentialVerificationResult {
+    /// Returns whether the credential proof is valid.
+    pub fn proof_is_valid(&self) -> bool {
+        self.proof.errors.is_empty()
+    }
+
+    /// Returns whether all of the checks required by the policy passed.
+    pub fn is_valid(&self) -> bool {
+        self.proof_is_valid()
+            && !matches!(self.issuer_chain, Some(Err(_)))
+            && self.validity == ValidityPeriod::Valid
+            && self.status.is_valid()
+            && self.schema.is_valid()
+            && self.authorisation.is_valid()
+    }
+}
+
+/// Returns the DID of a credential issuer.
+pub fn issuer_did(credential: &Credential) -> Option<&str> {
+    match credential.issuer.as_ref()? {
+        Issuer::URI(URI::String(did)) => Some(did),
+        Issuer::Object(object) => match &object.id {
+            URI::String(did) => Some(did),
+        },
+    }
+}
+
+/// Returns the Unix time of a credential date.
+fn unixtime(date: &VCDateTime) -> Option<i64> {
+    DateTime::parse_from_rfc3339(&String::from(date.clone()))
+        .ok()
+        .map(|date| date.timestamp())
+}
+
+/// Returns the validity of a credential at the given Unix time.
+pub fn validity_period(credential: &Credential, time: i64) -> ValidityPeriod {
+    if matches!(credential.issuance_date.as_ref().and_then(unixtime), Some(issued) if time < issued)
+    {
+        return ValidityPeriod::NotYetValid;
+    }
+    if matches!(credential.expiration_date.as_ref().and_then(unixtime), Some(expires) if time >= expires)
+    {
+        return ValidityPeriod::Expired;
+    }
+    ValidityPeriod::Valid
+}
+
+/// Verifies a credential with an embedded proof: its proof, the Trustchain DID chain of its
+/// issuer, its validity period, its status (with a status list) and its conformance to its
+/// schemas (from the Trustchain data directory's schema registry), as required by the policy.
+/// If the policy has an issuer policy, the issuer's verified DID chain is evaluated against it.
+pub fn verify_credential<T, V>(
+    credential: &Credential,
+    verifier: &V,
+    policy: &CredentialVerificationPolicy,
+) -> CredentialVerificationResult
+where
+    T: DIDResolver + Sync + Send,
+    V: Verifier<T>,
+{
+    let resolver = verifier.resolver();
+    let proof = resolver
+        .runtime
+        .block_on(inline_contexts(credential).verify(None, resolver));
+    check_credential(credential, proof, verifier, policy)
+}
+
+/// Verifies a JWT-encoded credential (VC-JWT) as for [`verify_credential`], with the JWT
+/// signature as its proof. Returns the decoded credential with the verification result, or an
+/// error if the JWT does not encode a credential.
+pub fn verify_credential_jwt<T, V>(
+    jwt: &str,
+    verifier: &V,
+    policy: &CredentialVerificationPolicy,
+) -> Result<(Credential, CredentialVerificationResult), ssi::error::Error>
+where
+    T: DIDResolver + Sync + Send,
+    V: Verifier<T>,
+{
+    let credential = Credential::from_jwt_unsigned(jwt)?;
+    let resolver = verifier.resolver();
+    let proof = resolver
+        .runtime
+        .block_on(Credential::verify_jwt(jwt, None, resolver));
+    let result = check_credential(&credential, proof, verifier, policy);
+    Ok((credential, result))
+}
+
+/// Completes the verification of a credential given the result of verifying its proof.
+fn check_credential<T, V>(
+    credential: &Credential,
+    proof: VerificationResult,
+    verifier: &V,
+    policy: &CredentialVerificationPolicy,
+) -> CredentialVerificationResult
+where
+    T: DIDResolver + Sync + Send,
+    V: Verifier<T>,
+{
+    let issuer = issuer_did(credential).map(str::to_owned);
+
+    let issuer_chain = if policy.signature_only {
+        None
+    } else {
+        Some(match &issuer {
+            Some(issuer) => match policy.version_time {
+                Some(version_time) => {
+                    verifier.verify_at(issuer, policy.root_event_time, version_time)
+                }
+                None => verifier.verify(issuer, policy.root_event_time),
+            }
+            .map_err(CredentialError::IssuerVerification),
+            None => Err(CredentialError::NoIssuer),
+        })
+    };
+
+    let validity_time = policy
+        .validity_time
+        .unwrap_or_else(|| Utc::now().timestamp());
+    let status = match &credential.credential_status {
+        Some(_) if policy.signature_only => CredentialStatusCheck::NotChecked,
+        Some(status) if status.type_ == STATUS_LIST_2021_ENTRY => {
+            check_status(credential, status, verifier)
+        }
+        Some(status) => CredentialStatusCheck::Unsupported(status.type_.to_owned()),
+        None => CredentialStatusCheck::NotPresent,
+    };
+
+    let schema = match &credential.credential_schema {
+        Some(_) if policy.signature_only => CredentialSchemaCheck::NotChecked,
+        Some(_) => check_schema(credential),
+        None => CredentialSchemaCheck::NotPresent,
+    };
+
+    let authorisation = match (&policy.issuer_policy, &issuer_chain) {
+        (Some(issuer_policy), Some(Ok(chain))) => match issuer_policy.evaluate(credential, chain) {
+            Ok(()) => AuthorisationCheck::Authorised,
+            Err(violations) => AuthorisationCheck::Unauthorised(violations),
+        },
+        _ => AuthorisationCheck::NotChecked,
+    };
+
+    CredentialVerificationResult {
+        proof,
+        issuer,
+        issuer_chain,
+        validity: validity_period(credential, validity_time),
+        status,
+        schema,
+        authorisation,
+    }
+}
+
+/// Checks a status list status entry of a credential.
+fn check_status<T, V>(
+    credential: &Credential,
+    status: &Status,
+    verifier: &V,
+) -> CredentialStatusCheck
+where
+    T: DIDResolver + Sync + Send,
+    V: Verifier<T>,
+{
+    let resolver = verifier.resolver();
+    let entry = match StatusList2021Entry::try_from(status) {
+        Ok(entry) => entry,
+        Err(e) => return CredentialStatusCheck::Invalid(e),
+    };
+    match resolver
+        .runtime
+        .block_on(check_credential_status(credential, &entry, resolver))
+    {
+        Ok(false) => CredentialStatusCheck::Active,
+        Ok(true) => match entry.status_purpose {
+            StatusPurpose::Revocation => CredentialStatusCheck::Revoked,
+            StatusPurpose::Suspension => CredentialStatusCheck::Suspended,
+        },
+        Err(e) => CredentialStatusCheck::Invalid(e),
+    }
+}
+
+/// Validates a credential against its schemas in the Trustchain data directory's registry.
+fn check_schema(credential: &Credential) -> CredentialSchemaCheck {
+    match validate_credential(credential, &SchemaRegistry::load()) {
+        Ok(violations) if !violations.is_empty() => CredentialSchemaCheck::Invalid(violations),
+        Ok(_) => match credential
+            .credential_schema
+            .iter()
+            .flatten()
+            .find(|schema| !is_supported_schema_type(&schema.type_))
+        {
+            Some(schema) => CredentialSchemaCheck::Unsupported(schema.type_.to_owned()),
+            None => CredentialSchemaCheck::Valid,
+        },
+        Err(e) => CredentialSchemaCheck::Failed(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::TEST_CREDENTIAL;
+
+    #[test]
+    fn test_issuer_did() {
+        let mut credential: Credential = serde_json::from_str(TEST_CREDENTIAL).unwrap();
+        assert_eq!(issuer_did(&credential), None);
+        credential.issuer = Some(Issuer::URI(URI::String("did:example:issuer".to_string())));
+        assert_eq!(issuer_did(&credential), Some("did:example:issuer"));
+    }
+
+    #[test]
+    fn test_validity_period() {
+        let mut credential: Credential = serde_json::from_str(TEST_CREDENTIAL).unwrap();
+        // 2022-10-20T11:30:05Z and 2023-10-20T11:30:05Z
+        let (issued, expires) = (1666265405, 1697801405);
+        assert_eq!(validity_period(&credential, issued), ValidityPeriod::Valid);
+
+        credential.issuance_date =
+            Some(VCDateTime::try_from("2022-10-20T11:30:05Z".to_string()).unwrap());
+        credential.expiration_date =
+            Some(VCDateTime::try_from("2023-10-20T11:30:05Z".to_string()).unwrap());
+        assert_eq!(
+            validity_period(&credential, issued - 1),
+            ValidityPeriod::NotYetValid
+        );
+        assert_eq!(validity_period(&credential, issued), ValidityPeriod::Valid);
+        assert_eq!(
+            validity_period(&credential, expires - 1),
+            ValidityPeriod::Valid
+        );
+        assert_eq!(
+            validity_period(&credential, expires),
+            ValidityPeriod::Expired
+        );
+    }
+}
@@ -1,10 +1,40 @@
-use crate::chain::{Chain, DIDChain};
+use crate::chain::{Chain, ChainError, DIDChain};
 use crate::resolver::Resolver;
-use ssi::did_resolve::DIDResolver;
+use async_trait::async_trait;
+use chrono::DateTime;
+use ssi::did::Document;
+use ssi::did_resolve::{
+    DIDResolver, DocumentMetadata, Metadata, ResolutionInputMetadata, ResolutionMetadata,
+};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::marker::PhantomData;
 use thiserror::Error;
 
+/// Resolution input option requesting Trustchain verification of the resolved DID.
+pub const TRUSTCHAIN_VERIFY: &str = "trustchainVerify";
+
+/// Resolution metadata property indicating whether the resolved DID was Trustchain-verified.
+pub const TRUSTCHAIN_VERIFIED: &str = "trustchainVerified";
+
+/// Resolution metadata property giving the root DID of the verified chain.
+pub const TRUSTCHAIN_ROOT: &str = "trustchainRoot";
+
+/// Resolution metadata property giving the level of the resolved DID in the verified chain.
+pub const TRUSTCHAIN_LEVEL: &str = "trustchainLevel";
+
+/// Resolution metadata property listing the reasons that Trustchain verification failed.
+pub const TRUSTCHAIN_ERRORS: &str = "trustchainErrors";
+
+/// Resolution metadata property listing DIDs in the verified chain whose upstream DID was
+/// recovered after attesting to them.
+pub const TRUSTCHAIN_RECOVERED_UPSTREAM: &str = "trustchainRecoveredUpstream";
+
+/// Resolution metadata property listing DIDs in the verified chain that have not been anchored.
+pub const TRUSTCHAIN_UNPUBLISHED: &str = "trustchainUnpublished";
+
 /// An error relating to Trustchain verification.
-#[derive(Error, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Error, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum VerifierError {
     /// Invalid payload in proof compared to resolved document.
     #[error("Invalid payload provided in proof for dDID: {0}.")]
@@ -24,9 +54,15 @@ pub enum VerifierError {
     /// Chain verification failed.
     #[error("Chain verification failed for chain: {0}.")]
     InvalidChain(String),
+    /// Delegation constraint in an attestation violated by the chain.
+    #[error("Delegation constraint violated: {0}")]
+    ConstraintViolation(String),
     /// Failure to get a DID operation.
     #[error("Error getting '{0}' operation for DID: {1}")]
     FailureToGetDIDOperation(String, String),
+    /// Failure to get the operations on a DID.
+    #[error("Error getting operations for DID: {0}: {1}")]
+    FailureToGetDIDOperations(String, String),
     /// Invalid block height.
     #[error("Invalid block height: {0}")]
     InvalidBlockHeight(i32),
@@ -39,6 +75,21 @@ pub enum VerifierError {
     /// Failed to get Unix time from block height.
     #[error("Failed to get Unix time from block height: {0}")]
     FailureToGetUnixTime(u32),
+    /// DID in the chain has been deactivated.
+    #[error("DID: {0} is deactivated.")]
+    DeactivatedDID(String),
+    /// Invalid version time in resolution input metadata.
+    #[error("Invalid version time: {0}")]
+    InvalidVersionTime(String),
+    /// DID in the chain has not been anchored.
+    #[error("DID: {0} is unpublished.")]
+    UnpublishedDID(String),
+    /// Invalid create operation retrieved for a DID.
+    #[error("Invalid create operation for DID: {0}: {1}")]
+    InvalidCreateOperation(String, String),
+    /// Create operation suffix data does not hash to the DID suffix.
+    #[error("Create operation suffix data hashes to: {1}, not the suffix of DID: {0}")]
+    DIDSuffixMismatch(String, String),
 }
 
 /// Verifier of root and downstream DIDs.
@@ -49,49 +100,505 @@ pub trait Verifier<T: Sync + Send + DIDResolver> {
     /// Verify a downstream DID by tracing its chain back to the root.
     fn verify(&self, did: &str, root_timestamp: u32) -> Result<DIDChain, VerifierError> {
         // Build a chain from the given DID to the root.
-        let chain = match DIDChain::new(did, self.resolver()) {
-            Ok(x) => x,
-            Err(e) => {
-                eprintln!("{e}");
-                return Err(VerifierError::ChainBuildFailure(did.to_string()));
-            }
-        };
-
-        // Verify the proofs in the chain.
-        match chain.verify_proofs() {
-            Ok(_) => (),
-            Err(e) => {
-                eprintln!("{e}");
-                return Err(VerifierError::InvalidChain(chain.to_string()));
-            }
-        };
-
-        // Verify the root timestamp.
-        let root = chain.root();
-        if let Ok(block_height) = self.verified_block_height(root) {
-            if let Ok(unixtime) = self.block_height_to_unixtime(block_height) {
-                if unixtime != root_timestamp {
-                    return Err(VerifierError::InvalidRoot(root.to_string()));
-                }
-            } else {
-                return Err(VerifierError::FailureToGetUnixTime(block_height));
-            }
-        } else {
-            return Err(VerifierError::FailureToGetBlockHeight(root.to_owned()));
-        }
+        let chain = build_chain(did, DIDChain::new(did, self.resolver()))?;
+        self.verify_chain(chain, root_timestamp)
+    }
 
-        // TODO: consider whether to set a root event time here for the chain.
+    /// Verify a downstream DID by tracing its chain back to the root, using the state of each DID
+    /// in the chain at a past Unix time (e.g. the time at which the DID signed a credential).
+    fn verify_at(
+        &self,
+        did: &str,
+        root_timestamp: u32,
+        version_time: u32,
+    ) -> Result<DIDChain, VerifierError> {
+        // Build a chain from the given DID to the root as of the version time.
+        let chain = build_chain(did, DIDChain::new_at(did, version_time, self.resolver()))?;
+        verify_chain_as_of(self, chain, root_timestamp, Some(version_time))
+    }
 
-        Ok(chain)
+    /// Verify the proofs, delegation constraints and root timestamp of a DID chain.
+    fn verify_chain(
+        &self,
+        chain: DIDChain,
+        root_timestamp: u32,
+    ) -> Result<DIDChain, VerifierError> {
+        verify_chain_as_of(self, chain, root_timestamp, None)
     }
 
     /// Gets the verified block height for a DID.
     fn verified_block_height(&self, did: &str) -> Result<u32, VerifierError>;
     /// Gets the verified timestamp for a DID as a Unix time.
     fn verified_timestamp(&self, did: &str) -> Result<u32, VerifierError>;
+    /// Gets whether the upstream DID `udid` was recovered after it attested to the DID `did`,
+    /// from the operations anchored up to the version time (a Unix time), if given.
+    fn recovered_since_attestation(
+        &self,
+        did: &str,
+        udid: &str,
+        version_time: Option<u32>,
+    ) -> Result<bool, VerifierError>;
     /// Gets the resolver used for DID verification.
     fn resolver(&self) -> &Resolver<T>;
+    /// Gets whether downstream DIDs that have not been anchored are accepted in verified chains.
+    fn unpublished_allowed(&self) -> bool {
+        false
+    }
+}
+
+/// Verifies the proofs, delegation constraints and root timestamp of a DID chain built as of the
+/// version time (a Unix time), if given, flagging upstream recoveries anchored by that time.
+fn verify_chain_as_of<T, V>(
+    verifier: &V,
+    mut chain: DIDChain,
+    root_timestamp: u32,
+    version_time: Option<u32>,
+) -> Result<DIDChain, VerifierError>
+where
+    T: DIDResolver + Sync + Send,
+    V: Verifier<T> + ?Sized,
+{
+    // Verify the proofs in the chain.
+    match chain.verify_proofs() {
+        Ok(_) => (),
+        Err(e) => {
+            eprintln!("{e}");
+            return Err(VerifierError::InvalidChain(chain.to_string()));
+        }
+    };
+
+    // Verify the delegation constraints along the chain.
+    if let Err(e) = chain.verify_constraints() {
+        return Err(VerifierError::ConstraintViolation(e.to_string()));
+    }
+
+    // The root must be anchored for its timestamp to be verified. Downstream DIDs that have
+    // not been anchored (e.g. long-form DIDs) are only accepted if permitted.
+    let root = chain.root();
+    if chain.is_unpublished(root) {
+        return Err(VerifierError::UnpublishedDID(root.to_owned()));
+    }
+    if !verifier.unpublished_allowed() {
+        if let Some(did) = chain.unpublished().first() {
+            return Err(VerifierError::UnpublishedDID(did.to_owned()));
+        }
+    }
+
+    // Verify the root timestamp.
+    if let Ok(block_height) = verifier.verified_block_height(root) {
+        if let Ok(unixtime) = verifier.block_height_to_unixtime(block_height) {
+            if unixtime != root_timestamp {
+                return Err(VerifierError::InvalidRoot(root.to_string()));
+            }
+        } else {
+            return Err(VerifierError::FailureToGetUnixTime(block_height));
+        }
+    } else {
+        return Err(VerifierError::FailureToGetBlockHeight(root.to_owned()));
+    }
+
+    // Flag links where the upstream DID was recovered after the attestation was made, since
+    // recovery typically signals compromise of the upstream keys.
+    // Unpublished DIDs have no operation history, so cannot have been recovered.
+    let dids: Vec<String> = chain.level_vec().iter().skip(1).cloned().collect();
+    for ddid in dids {
+        let udid = chain.upstream(&ddid).unwrap().to_owned();
+        if chain.is_unpublished(&ddid) || chain.is_unpublished(&udid) {
+            continue;
+        }
+        if verifier.recovered_since_attestation(&ddid, &udid, version_time)? {
+            chain.flag_recovered_upstream(&ddid);
+        }
+    }
+
+    // TODO: consider whether to set a root event time here for the chain.
+
+    Ok(chain)
+}
+
+/// A resolver that, when the [`TRUSTCHAIN_VERIFY`] resolution input option is `true`, builds and
+/// verifies the DID chain of the resolved DID and annotates the resolution metadata with the
+/// outcome ([`TRUSTCHAIN_VERIFIED`], [`TRUSTCHAIN_ROOT`], [`TRUSTCHAIN_LEVEL`] and
+/// [`TRUSTCHAIN_ERRORS`]).
+///
+/// If a `versionTime` is given in the resolution input metadata, the chain is verified as of
+/// that time.
+pub struct VerifyingResolver<T: DIDResolver + Sync + Send, V: Verifier<T>> {
+    verifier: V,
+    root_timestamp: u32,
+    _marker: PhantomData<T>,
+}
+
+impl<T, V> VerifyingResolver<T, V>
+where
+    T: DIDResolver + Sync + Send,
+    V: Verifier<T>,
+{
+    /// Makes a new verifying resolver, verifying chains against the given root timestamp.
+    pub fn new(verifier: V, root_timestamp: u32) -> Self {
+        Self {
+            verifier,
+            root_timestamp,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Gets the verifier used for resolution and verification.
+    pub fn verifier(&self) -> &V {
+        &self.verifier
+    }
+
+    /// Verifies a DID, as of the `versionTime` in the input metadata if present.
+    fn verify(
+        &self,
+        did: &str,
+        input_metadata: &ResolutionInputMetadata,
+    ) -> Result<DIDChain, VerifierError> {
+        match &input_metadata.version_time {
+            Some(version_time) => {
+                let version_time = DateTime::parse_from_rfc3339(version_time)
+                    .ok()
+                    .and_then(|time| u32::try_from(time.timestamp()).ok())
+                    .ok_or_else(|| VerifierError::InvalidVersionTime(version_time.to_owned()))?;
+                self.verifier
+                    .verify_at(did, self.root_timestamp, version_time)
+            }
+            None => self.verifier.verify(did, self.root_timestamp),
+        }
+    }
+}
+
+/// Returns whether Trustchain verification is requested in resolution input metadata.
+pub fn is_verification_requested(input_metadata: &ResolutionInputMetadata) -> bool {
+    match input_metadata
+        .property_set
+        .as_ref()
+        .and_then(|property_set| property_set.get(TRUSTCHAIN_VERIFY))
+    {
+        Some(Metadata::Boolean(verify)) => *verify,
+        // The option is given as a string when passed in a query string.
+        Some(Metadata::String(verify)) => verify == "true",
+        _ => false,
+    }
+}
+
+/// Adds the outcome of Trustchain verification of a DID to resolution metadata.
+pub fn annotate_resolution_metadata(
+    res_meta: &mut ResolutionMetadata,
+    did: &str,
+    result: &Result<DIDChain, VerifierError>,
+) {
+    let property_set = res_meta.property_set.get_or_insert_with(HashMap::new);
+    match result {
+        Ok(chain) => {
+            property_set.insert(TRUSTCHAIN_VERIFIED.to_string(), Metadata::Boolean(true));
+            property_set.insert(
+                TRUSTCHAIN_ROOT.to_string(),
+                Metadata::String(chain.root().to_string()),
+            );
+            // Metadata cannot represent numeric values, so the level is given as a string.
+            if let Some(level) = chain.level(did) {
+                property_set.insert(
+                    TRUSTCHAIN_LEVEL.to_string(),
+                    Metadata::String(level.to_string()),
+                );
+            }
+            if !chain.recovered_upstream().is_empty() {
+                property_set.insert(
+                    TRUSTCHAIN_RECOVERED_UPSTREAM.to_string(),
+                    Metadata::List(
+                        chain
+                            .recovered_upstream()
+                            .iter()
+                            .map(|ddid| Metadata::String(ddid.to_owned()))
+                            .collect(),
+                    ),
+                );
+            }
+            if !chain.unpublished().is_empty() {
+                property_set.insert(
+                    TRUSTCHAIN_UNPUBLISHED.to_string(),
+                    Metadata::List(
+                        chain
+                            .unpublished()
+                            .iter()
+                            .map(|did| Metadata::String(did.to_owned()))
+                            .collect(),
+                    ),
+                );
+            }
+        }
+        Err(e) => {
+            property_set.insert(TRUSTCHAIN_VERIFIED.to_string(), Metadata::Boolean(false));
+            property_set.insert(
+                TRUSTCHAIN_ERRORS.to_string(),
+                Metadata::List(vec![Metadata::String(e.to_string())]),
+            );
+        }
+    }
+}
+
+#[async_trait]
+impl<T, V> DIDResolver for VerifyingResolver<T, V>
+where
+    T: DIDResolver + Sync + Send,
+    V: Verifier<T> + Sync + Send,
+{
+    async fn resolve(
+        &self,
+        did: &str,
+        input_metadata: &ResolutionInputMetadata,
+    ) -> (
+        ResolutionMetadata,
+        Option<Document>,
+        Option<DocumentMetadata>,
+    ) {
+        let (mut res_meta, doc, doc_meta) =
+            self.verifier.resolver().resolve(did, input_metadata).await;
+        if res_meta.error.is_some() || !is_verification_requested(input_metadata) {
+            return (res_meta, doc, doc_meta);
+        }
+
+        // Verification blocks on the resolver's runtime, so is run outside of any async context.
+        let result = std::thread::scope(|scope| {
+            scope
+                .spawn(|| self.verify(did, input_metadata))
+                .join()
+                .unwrap_or_else(|_| Err(VerifierError::ChainBuildFailure(did.to_string())))
+        });
+        // The chain is of the document ID, which is the short form of a long-form DID.
+        let id = doc.as_ref().map_or(did, |doc| doc.id.as_str());
+        annotate_resolution_metadata(&mut res_meta, id, &result);
+        (res_meta, doc, doc_meta)
+    }
+}
+
+/// Maps the result of building a DID chain to a verification result.
+fn build_chain(did: &str, result: Result<DIDChain, ChainError>) -> Result<DIDChain, VerifierError> {
+    match result {
+        Ok(chain) => Ok(chain),
+        Err(ChainError::DeactivatedDID(ddid)) => Err(VerifierError::DeactivatedDID(ddid)),
+        Err(e) => {
+            eprintln!("{e}");
+            Err(VerifierError::ChainBuildFailure(did.to_string()))
+        }
+    }
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+    use crate::data::TEST_DID_CHAIN;
+
+    const ROOT_DID: &str = "did:ion:test:EiCClfEdkTv_aM3UnBBhlOV89LlGhpQAbfeZLFdFxVFkEg";
+    const LEAF_DID: &str = "did:ion:test:EiAtHHKFJWAk5AsM3tgCut3OiBY4ekHTf66AAjoysXL65Q";
+    const ROOT_TIMESTAMP: u32 = 1666265405;
+
+    // Mock resolver returning documents from the test chain, also by the long form
+    // (`{did}:{initial state}`) of their DIDs.
+    struct ChainResolver {
+        chain: DIDChain,
+    }
+
+    #[async_trait]
+    impl DIDResolver for ChainResolver {
+        async fn resolve(
+            &self,
+            did: &str,
+            _: &ResolutionInputMetadata,
+        ) -> (
+            ResolutionMetadata,
+            Option<Document>,
+            Option<DocumentMetadata>,
+        ) {
+            let data = self.chain.data(did).or_else(|| {
+                did.rsplit_once(':')
+                    .and_then(|(short_did, _)| self.chain.data(short_did))
+            });
+            match data {
+                Some((doc, doc_meta)) => (
+                    ResolutionMetadata::default(),
+                    Some(doc.clone()),
+                    Some(doc_meta.clone()),
+                ),
+                None => (ResolutionMetadata::from_error("notFound"), None, None),
+            }
+        }
+    }
+
+    // Mock verifier taking the root timestamp to be a fixed value.
+    struct TestVerifier {
+        resolver: Resolver<ChainResolver>,
+        allow_unpublished: bool,
+    }
+
+    impl Verifier<ChainResolver> for TestVerifier {
+        fn block_height_to_unixtime(&self, _: u32) -> Result<u32, VerifierError> {
+            Ok(ROOT_TIMESTAMP)
+        }
+        fn verified_block_height(&self, _: &str) -> Result<u32, VerifierError> {
+            Ok(0)
+        }
+        fn verified_timestamp(&self, _: &str) -> Result<u32, VerifierError> {
+            Ok(ROOT_TIMESTAMP)
+        }
+        fn recovered_since_attestation(
+            &self,
+            _: &str,
+            _: &str,
+            _: Option<u32>,
+        ) -> Result<bool, VerifierError> {
+            Ok(false)
+        }
+        fn resolver(&self) -> &Resolver<ChainResolver> {
+            &self.resolver
+        }
+        fn unpublished_allowed(&self) -> bool {
+            self.allow_unpublished
+        }
+    }
+
+    fn verifying_resolver(root_timestamp: u32) -> VerifyingResolver<ChainResolver, TestVerifier> {
+        let verifier = TestVerifier {
+            resolver: Resolver::new(ChainResolver {
+                chain: serde_json::from_str(TEST_DID_CHAIN).unwrap(),
+            }),
+            allow_unpublished: false,
+        };
+        VerifyingResolver::new(verifier, root_timestamp)
+    }
+
+    fn verify_input(verify: Metadata) -> ResolutionInputMetadata {
+        ResolutionInputMetadata {
+            property_set: Some(HashMap::from([(TRUSTCHAIN_VERIFY.to_string(), verify)])),
+            ..Default::default()
+        }
+    }
+
+    fn property<'a>(res_meta: &'a ResolutionMetadata, key: &str) -> Option<&'a Metadata> {
+        res_meta.property_set.as_ref().and_then(|ps| ps.get(key))
+    }
+
+    #[test]
+    fn test_is_verification_requested() {
+        assert!(!is_verification_requested(&Default::default()));
+        assert!(is_verification_requested(&verify_input(Metadata::Boolean(
+            true
+        ))));
+        assert!(is_verification_requested(&verify_input(Metadata::String(
+            "true".to_string()
+        ))));
+        assert!(!is_verification_requested(&verify_input(
+            Metadata::Boolean(false)
+        )));
+    }
+
+    #[test]
+    fn test_verifying_resolver() {
+        let resolver = verifying_resolver(ROOT_TIMESTAMP);
+        let runtime = &resolver.verifier().resolver().runtime;
+
+        // Without the option, resolution metadata is not annotated.
+        let (res_meta, doc, _) = runtime.block_on(resolver.resolve(LEAF_DID, &Default::default()));
+        assert!(doc.is_some());
+        assert!(property(&res_meta, TRUSTCHAIN_VERIFIED).is_none());
+
+        let input = verify_input(Metadata::Boolean(true));
+        let (res_meta, doc, _) = runtime.block_on(resolver.resolve(LEAF_DID, &input));
+        assert!(doc.is_some());
+        assert!(matches!(
+            property(&res_meta, TRUSTCHAIN_VERIFIED),
+            Some(Metadata::Boolean(true))
+        ));
+        assert!(matches!(
+            property(&res_meta, TRUSTCHAIN_ROOT),
+            Some(Metadata::String(root)) if root == ROOT_DID
+        ));
+        assert!(matches!(
+            property(&res_meta, TRUSTCHAIN_LEVEL),
+            Some(Metadata::String(level)) if level == "2"
+        ));
+        assert!(property(&res_meta, TRUSTCHAIN_ERRORS).is_none());
+
+        // The level of a long-form DID is that of its short form, the ID of its document.
+        let long_form_did = format!("{}:initial-state", LEAF_DID);
+        let (res_meta, _, _) = runtime.block_on(resolver.resolve(&long_form_did, &input));
+        assert!(matches!(
+            property(&res_meta, TRUSTCHAIN_LEVEL),
+            Some(Metadata::String(level)) if level == "2"
+        ));
+    }
+
+    #[test]
+    fn test_verifying_resolver_failure() {
+        // Verification fails against a different root timestamp.
+        let resolver = verifying_resolver(ROOT_TIMESTAMP + 1);
+        let runtime = &resolver.verifier().resolver().runtime;
+        let input = verify_input(Metadata::Boolean(true));
+        let (res_meta, doc, _) = runtime.block_on(resolver.resolve(LEAF_DID, &input));
+        // The document is still returned.
+        assert!(doc.is_some());
+        assert!(res_meta.error.is_none());
+        assert!(matches!(
+            property(&res_meta, TRUSTCHAIN_VERIFIED),
+            Some(Metadata::Boolean(false))
+        ));
+        match property(&res_meta, TRUSTCHAIN_ERRORS) {
+            Some(Metadata::List(errors)) => assert!(matches!(
+                errors.as_slice(),
+                [Metadata::String(e)] if e == &VerifierError::InvalidRoot(ROOT_DID.to_string()).to_string()
+            )),
+            _ => panic!("Expected verification errors."),
+        }
+
+        // An invalid version time is reported as a failure reason.
+        let input = ResolutionInputMetadata {
+            version_time: Some("yesterday".to_string()),
+            ..verify_input(Metadata::Boolean(true))
+        };
+        let (res_meta, _, _) = runtime.block_on(resolver.resolve(LEAF_DID, &input));
+        assert!(matches!(
+            property(&res_meta, TRUSTCHAIN_VERIFIED),
+            Some(Metadata::Boolean(false))
+        ));
+    }
+
+    /// Returns the test chain with the given DIDs marked as unpublished.
+    fn unpublished_chain(dids: &[&str]) -> DIDChain {
+        let mut chain: serde_json::Value = serde_json::from_str(TEST_DID_CHAIN).unwrap();
+        chain["unpublished"] = serde_json::json!(dids);
+        serde_json::from_value(chain).unwrap()
+    }
+
+    #[test]
+    fn test_verify_unpublished() {
+        let mut verifier = verifying_resolver(ROOT_TIMESTAMP).verifier;
+
+        // Unpublished downstream DIDs are rejected unless permitted by the verifier policy.
+        let chain = unpublished_chain(&[LEAF_DID]);
+        assert_eq!(
+            verifier
+                .verify_chain(chain.clone(), ROOT_TIMESTAMP)
+                .unwrap_err(),
+            VerifierError::UnpublishedDID(LEAF_DID.to_string())
+        );
+        verifier.allow_unpublished = true;
+        let chain = verifier.verify_chain(chain, ROOT_TIMESTAMP).unwrap();
+        assert!(chain.is_unpublished(LEAF_DID));
+
+        let mut res_meta = ResolutionMetadata::default();
+        annotate_resolution_metadata(&mut res_meta, LEAF_DID, &Ok(chain));
+        assert!(matches!(
+            property(&res_meta, TRUSTCHAIN_UNPUBLISHED),
+            Some(Metadata::List(dids)) if dids.len() == 1
+        ));
+
+        // An unpublished root cannot be verified.
+        assert_eq!(
+            verifier
+                .verify_chain(unpublished_chain(&[ROOT_DID]), ROOT_TIMESTAMP)
+                .unwrap_err(),
+            VerifierError::UnpublishedDID(ROOT_DID.to_string())
+        );
+    }
+}
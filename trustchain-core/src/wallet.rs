@@ -0,0 +1,480 @@
+//! Holder-side wallet of credentials stored in the Trustchain data directory.
+//!
+//! Credentials are verified when added and stored one per file in the wallet directory, with an
+//! index (`wallet.json`) recording the types, issuer and subjects of each credential by its ID
+//! (the SHA-256 hash of the canonicalized credential) to support queries without reading every file.
+use crate::exchange::{create_presentation, ExchangeError, PresentationDefinition};
+use crate::presentation::is_subject;
+use crate::utils::canonicalize;
+use crate::vc::{
+    issuer_did, verify_credential, verify_credential_jwt, AuthorisationCheck,
+    CredentialSchemaCheck, CredentialStatusCheck, CredentialVerificationPolicy,
+    CredentialVerificationResult, ValidityPeriod,
+};
+use crate::verifier::Verifier;
+use crate::TRUSTCHAIN_DATA;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use ssi::did_resolve::DIDResolver;
+use ssi::one_or_many::OneOrMany;
+use ssi::vc::{Credential, CredentialOrJWT, Presentation, URI};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Directory of the wallet in the Trustchain data directory.
+pub const WALLET_DIR: &str = "wallet";
+/// File name of the index of a wallet directory.
+pub const WALLET_INDEX: &str = "wallet.json";
+
+/// An error relating to the credential wallet.
+#[derive(Error, Debug)]
+pub enum WalletError {
+    /// Failed to read a wallet file.
+    #[error("Failed to read wallet file: {0}: {1}")]
+    FailedToRead(String, String),
+    /// Failed to store a credential or the index.
+    #[error("Failed to store in the wallet: {0}")]
+    Storage(String),
+    /// Trustchain data environment variable is not set.
+    #[error("Trustchain data environment variable is not set.")]
+    TrustchainDataNotPresent,
+    /// Credential is not a valid credential.
+    #[error("Invalid credential: {0}")]
+    InvalidCredential(String),
+    /// Credential failed verification.
+    #[error("Credential failed verification: {0:?}")]
+    Unverified(Vec<String>),
+    /// Credential is already in the wallet.
+    #[error("Credential: {0} is already in the wallet.")]
+    Duplicate(String),
+    /// No credential in the wallet has the given ID (or ID prefix).
+    #[error("No credential in the wallet with ID: {0}")]
+    NotFound(String),
+    /// More than one credential in the wallet has the given ID prefix.
+    #[error("More than one credential in the wallet with ID prefix: {0}")]
+    AmbiguousId(String),
+    /// Holder is not a subject of a credential to be presented.
+    #[error("Holder: {0} is not a subject of credential: {1}")]
+    HolderNotSubject(String, String),
+    /// No presentation satisfying a presentation definition could be made.
+    #[error("Presentation definition not satisfied: {0}")]
+    Exchange(ExchangeError),
+}
+
+impl From<ExchangeError> for WalletError {
+    fn from(err: ExchangeError) -> Self {
+        WalletError::Exchange(err)
+    }
+}
+
+/// Index entry for a credential in the wallet.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WalletRecord {
+    /// ID of the credential in the wallet: the hex-encoded SHA-256 hash of the canonicalized
+    /// (JCS) credential.
+    pub id: String,
+    /// Types of the credential.
+    pub types: Vec<String>,
+    /// DID of the issuer.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub issuer: Option<String>,
+    /// IDs of the credential subjects.
+    pub subjects: Vec<String>,
+    /// Whether the credential is stored as a JWT (VC-JWT).
+    pub jwt: bool,
+    /// Time at which the credential was added (RFC 3339).
+    pub added: String,
+}
+
+/// Query for credentials in the wallet, matching those with all of the given properties.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WalletQuery {
+    /// Type of the credential.
+    pub credential_type: Option<String>,
+    /// DID of the issuer.
+    pub issuer: Option<String>,
+    /// ID of a credential subject.
+    pub subject: Option<String>,
+}
+
+impl WalletQuery {
+    /// Returns whether a credential in the wallet matches the query.
+    pub fn matches(&self, record: &WalletRecord) -> bool {
+        self.credential_type
+            .as_ref()
+            .is_none_or(|credential_type| record.types.contains(credential_type))
+            && self
+                .issuer
+                .as_ref()
+                .is_none_or(|issuer| record.issuer.as_ref() == Some(issuer))
+            && self
+                .subject
+                .as_ref()
+                .is_none_or(|subject| record.subjects.contains(subject))
+    }
+}
+
+/// Returns the path of the wallet directory in the Trustchain data directory.
+pub fn trustchain_wallet_dir() -> Result<PathBuf, WalletError> {
+    let path = std::env::var(TRUSTCHAIN_DATA).map_err(|_| WalletError::TrustchainDataNotPresent)?;
+    Ok(Path::new(path.as_str()).join(WALLET_DIR))
+}
+
+/// Returns the decoded credential of a credential with an embedded proof or a JWT.
+fn decode(credential: &CredentialOrJWT) -> Result<Credential, WalletError> {
+    match credential {
+        CredentialOrJWT::Credential(credential) => Ok(credential.clone()),
+        CredentialOrJWT::JWT(jwt) => Credential::from_jwt_unsigned(jwt)
+            .map_err(|e| WalletError::InvalidCredential(e.to_string())),
+    }
+}
+
+/// Returns the reasons a credential failed verification.
+fn verification_failures(result: &CredentialVerificationResult) -> Vec<String> {
+    let mut failures = Vec::new();
+    if !result.proof_is_valid() {
+        failures.push(format!("Invalid proof: {:?}", result.proof.errors));
+    }
+    if let Some(Err(e)) = &result.issuer_chain {
+        failures.push(e.to_string());
+    }
+    if result.validity != ValidityPeriod::Valid {
+        failures.push(format!("Validity period: {:?}", result.validity));
+    }
+    if !result.status.is_valid() {
+        failures.push(match &result.status {
+            CredentialStatusCheck::Invalid(e) => format!("Status: {}", e),
+            status => format!("Status: {:?}", status),
+        });
+    }
+    if let CredentialSchemaCheck::Invalid(_) | CredentialSchemaCheck::Failed(_) = &result.schema {
+        failures.push(format!("Schema: {:?}", result.schema));
+    }
+    if let AuthorisationCheck::Unauthorised(violations) = &result.authorisation {
+        failures.extend(violations.iter().map(|violation| violation.to_string()));
+    }
+    failures
+}
+
+/// A wallet of credentials stored in a directory.
+#[derive(Debug, Clone)]
+pub struct Wallet {
+    dir: PathBuf,
+    index: BTreeMap<String, WalletRecord>,
+}
+
+impl Wallet {
+    /// Opens the wallet in the Trustchain data directory.
+    pub fn open() -> Result<Self, WalletError> {
+        Self::from_dir(trustchain_wallet_dir()?)
+    }
+
+    /// Opens the wallet in the given directory, which need not exist until a credential is added.
+    pub fn from_dir<P: AsRef<Path>>(dir: P) -> Result<Self, WalletError> {
+        let dir = dir.as_ref().to_path_buf();
+        let path = dir.join(WALLET_INDEX);
+        let index = if path.exists() {
+            let file = File::open(&path).map_err(|e| {
+                WalletError::FailedToRead(path.display().to_string(), e.to_string())
+            })?;
+            serde_json::from_reader(file)
+                .map_err(|e| WalletError::FailedToRead(path.display().to_string(), e.to_string()))?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Self { dir, index })
+    }
+
+    /// Returns the credentials in the wallet, ordered by ID.
+    pub fn records(&self) -> impl Iterator<Item = &WalletRecord> {
+        self.index.values()
+    }
+
+    /// Returns the credentials in the wallet matching a query, ordered by ID.
+    pub fn query<'a>(&'a self, query: &'a WalletQuery) -> impl Iterator<Item = &'a WalletRecord> {
+        self.records().filter(move |record| query.matches(record))
+    }
+
+    /// Returns the credential in the wallet with the given ID, or unique ID prefix.
+    pub fn get(&self, id: &str) -> Result<(&WalletRecord, CredentialOrJWT), WalletError> {
+        let record = self.find(id)?;
+        let path = self.path(&record.id);
+        let file = File::open(&path)
+            .map_err(|e| WalletError::FailedToRead(path.display().to_string(), e.to_string()))?;
+        let credential = serde_json::from_reader(file)
+            .map_err(|e| WalletError::FailedToRead(path.display().to_string(), e.to_string()))?;
+        Ok((record, credential))
+    }
+
+    /// Verifies a credential (its proof and the Trustchain DID chain of its issuer, as well as the
+    /// other checks required by the policy) and adds it to the wallet, returning its index entry.
+    pub fn add<T, V>(
+        &mut self,
+        credential: CredentialOrJWT,
+        verifier: &V,
+        policy: &CredentialVerificationPolicy,
+    ) -> Result<WalletRecord, WalletError>
+    where
+        T: DIDResolver + Sync + Send,
+        V: Verifier<T>,
+    {
+        let result = match &credential {
+            CredentialOrJWT::Credential(credential) => {
+                verify_credential(credential, verifier, policy)
+            }
+            CredentialOrJWT::JWT(jwt) => {
+                verify_credential_jwt(jwt, verifier, policy)
+                    .map_err(|e| WalletError::InvalidCredential(e.to_string()))?
+                    .1
+            }
+        };
+        if !result.is_valid() {
+            return Err(WalletError::Unverified(verification_failures(&result)));
+        }
+        self.insert(credential)
+    }
+
+    /// Adds a credential to the wallet without verifying it, returning its index entry.
+    pub fn insert(&mut self, credential: CredentialOrJWT) -> Result<WalletRecord, WalletError> {
+        let decoded = decode(&credential)?;
+        let bytes = serde_json::to_vec_pretty(&credential)
+            .map_err(|e| WalletError::Storage(e.to_string()))?;
+        let canonical =
+            canonicalize(&credential).map_err(|e| WalletError::Storage(e.to_string()))?;
+        let id: String = Sha256::digest(canonical.as_bytes())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        if self.index.contains_key(&id) {
+            return Err(WalletError::Duplicate(id));
+        }
+        let record = WalletRecord {
+            id: id.to_owned(),
+            issuer: issuer_did(&decoded).map(str::to_owned),
+            types: decoded.type_.into_iter().collect(),
+            subjects: decoded
+                .credential_subject
+                .into_iter()
+                .filter_map(|subject| subject.id.map(|URI::String(id)| id))
+                .collect(),
+            jwt: matches!(credential, CredentialOrJWT::JWT(_)),
+            added: Utc::now().to_rfc3339(),
+        };
+        std::fs::create_dir_all(&self.dir).map_err(|e| WalletError::Storage(e.to_string()))?;
+        std::fs::write(self.path(&id), bytes).map_err(|e| WalletError::Storage(e.to_string()))?;
+        self.index.insert(id, record.clone());
+        self.save_index()?;
+        Ok(record)
+    }
+
+    /// Removes the credential with the given ID, or unique ID prefix, from the wallet, returning
+    /// its index entry.
+    pub fn remove(&mut self, id: &str) -> Result<WalletRecord, WalletError> {
+        let id = self.find(id)?.id.to_owned();
+        let record = self.index.remove(&id).unwrap();
+        self.save_index()?;
+        std::fs::remove_file(self.path(&id)).map_err(|e| WalletError::Storage(e.to_string()))?;
+        Ok(record)
+    }
+
+    /// Makes an unsigned presentation by the holder of the credentials with the given IDs (or
+    /// unique ID prefixes), of each of which the holder must be a subject.
+    pub fn present(&self, ids: &[&str], holder: &str) -> Result<Presentation, WalletError> {
+        let credentials = ids
+            .iter()
+            .map(|id| {
+                let (record, credential) = self.get(id)?;
+                match is_subject(&decode(&credential)?, holder) {
+                    true => Ok(credential),
+                    false => Err(WalletError::HolderNotSubject(
+                        holder.to_owned(),
+                        record.id.to_owned(),
+                    )),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Presentation {
+            verifiable_credential: Some(OneOrMany::Many(credentials)),
+            holder: Some(URI::String(holder.to_owned())),
+            ..Default::default()
+        })
+    }
+
+    /// Makes an unsigned presentation by the holder satisfying a presentation definition, with
+    /// credentials in the wallet of which the holder is a subject and a presentation submission.
+    pub fn present_definition(
+        &self,
+        definition: &PresentationDefinition,
+        holder: &str,
+    ) -> Result<Presentation, WalletError> {
+        let credentials = self
+            .index
+            .values()
+            .filter(|record| record.subjects.iter().any(|subject| subject == holder))
+            .map(|record| Ok(self.get(&record.id)?.1))
+            .collect::<Result<Vec<_>, WalletError>>()?;
+        Ok(create_presentation(definition, &credentials, holder)?)
+    }
+
+    /// Returns the record of the credential with the given ID or unique ID prefix.
+    fn find(&self, id: &str) -> Result<&WalletRecord, WalletError> {
+        let mut matches = self
+            .index
+            .range(id.to_owned()..)
+            .take_while(|(key, _)| key.starts_with(id));
+        match (matches.next(), matches.next()) {
+            (Some((_, record)), None) => Ok(record),
+            (Some(_), Some(_)) => Err(WalletError::AmbiguousId(id.to_owned())),
+            (None, _) => Err(WalletError::NotFound(id.to_owned())),
+        }
+    }
+
+    /// Returns the path of the file of the credential with the given ID.
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+
+    /// Writes the index to the wallet directory.
+    fn save_index(&self) -> Result<(), WalletError> {
+        let file = File::create(self.dir.join(WALLET_INDEX))
+            .map_err(|e| WalletError::Storage(e.to_string()))?;
+        serde_json::to_writer_pretty(file, &self.index)
+            .map_err(|e| WalletError::Storage(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::TEST_CREDENTIAL;
+    use ssi::vc::Issuer;
+
+    fn credential(issuer: &str, subject: &str) -> CredentialOrJWT {
+        let mut credential: Credential = serde_json::from_str(TEST_CREDENTIAL).unwrap();
+        credential.issuer = Some(Issuer::URI(URI::String(issuer.to_string())));
+        credential.credential_subject.to_single_mut().unwrap().id =
+            Some(URI::String(subject.to_string()));
+        CredentialOrJWT::Credential(credential)
+    }
+
+    #[test]
+    fn test_insert_query_remove() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut wallet = Wallet::from_dir(dir.path()).unwrap();
+        assert_eq!(wallet.records().count(), 0);
+
+        let a = wallet
+            .insert(credential("did:example:issuer", "did:example:a"))
+            .unwrap();
+        let b = wallet
+            .insert(credential("did:example:other", "did:example:b"))
+            .unwrap();
+        assert_eq!(a.types, vec!["VerifiableCredential".to_string()]);
+        assert_eq!(a.issuer.as_deref(), Some("did:example:issuer"));
+        assert_eq!(a.subjects, vec!["did:example:a".to_string()]);
+        assert!(matches!(
+            wallet.insert(credential("did:example:issuer", "did:example:a")),
+            Err(WalletError::Duplicate(id)) if id == a.id
+        ));
+
+        // The index is persisted.
+        let mut wallet = Wallet::from_dir(dir.path()).unwrap();
+        assert_eq!(wallet.records().count(), 2);
+        let query = WalletQuery {
+            issuer: Some("did:example:other".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(wallet.query(&query).collect::<Vec<_>>(), vec![&b]);
+        let query = WalletQuery {
+            credential_type: Some("VerifiableCredential".to_string()),
+            subject: Some("did:example:a".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(wallet.query(&query).collect::<Vec<_>>(), vec![&a]);
+        let query = WalletQuery {
+            issuer: Some("did:example:issuer".to_string()),
+            subject: Some("did:example:b".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(wallet.query(&query).count(), 0);
+
+        // Credentials are found by unique ID prefix.
+        let (record, stored) = wallet.get(&a.id[..8]).unwrap();
+        assert_eq!(record, &a);
+        assert_eq!(
+            serde_json::to_value(stored).unwrap(),
+            serde_json::to_value(credential("did:example:issuer", "did:example:a")).unwrap()
+        );
+        assert!(matches!(wallet.get(""), Err(WalletError::AmbiguousId(_))));
+        assert!(matches!(wallet.get("xyz"), Err(WalletError::NotFound(_))));
+
+        assert_eq!(wallet.remove(&a.id).unwrap(), a);
+        assert!(matches!(wallet.get(&a.id), Err(WalletError::NotFound(_))));
+        assert!(!dir.path().join(format!("{}.json", a.id)).exists());
+        assert_eq!(Wallet::from_dir(dir.path()).unwrap().records().count(), 1);
+    }
+
+    #[test]
+    fn test_present() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut wallet = Wallet::from_dir(dir.path()).unwrap();
+        let a = wallet
+            .insert(credential("did:example:issuer", "did:example:a"))
+            .unwrap();
+        let b = wallet
+            .insert(credential("did:example:other", "did:example:a"))
+            .unwrap();
+        let presentation = wallet.present(&[&a.id, &b.id], "did:example:a").unwrap();
+        assert_eq!(
+            presentation.holder,
+            Some(URI::String("did:example:a".to_string()))
+        );
+        assert_eq!(presentation.verifiable_credential.unwrap().len(), 2);
+        assert!(matches!(
+            wallet.present(&[&a.id], "did:example:b"),
+            Err(WalletError::HolderNotSubject(holder, id)) if holder == "did:example:b" && id == a.id
+        ));
+    }
+
+    #[test]
+    fn test_present_definition() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut wallet = Wallet::from_dir(dir.path()).unwrap();
+        wallet
+            .insert(credential("did:example:issuer", "did:example:a"))
+            .unwrap();
+        wallet
+            .insert(credential("did:example:other", "did:example:b"))
+            .unwrap();
+        let definition = |issuer: &str| -> PresentationDefinition {
+            serde_json::from_value(serde_json::json!({
+                "id": "issued",
+                "input_descriptors": [{
+                    "id": "issuer",
+                    "constraints": {
+                        "fields": [{"path": ["$.issuer"], "filter": {"const": issuer}}]
+                    }
+                }]
+            }))
+            .unwrap()
+        };
+
+        // Only the holder's credentials are presented.
+        let presentation = wallet
+            .present_definition(&definition("did:example:issuer"), "did:example:a")
+            .unwrap();
+        assert_eq!(
+            presentation.holder,
+            Some(URI::String("did:example:a".to_string()))
+        );
+        assert_eq!(presentation.verifiable_credential.unwrap().len(), 1);
+        assert!(matches!(
+            wallet.present_definition(&definition("did:example:other"), "did:example:a"),
+            Err(WalletError::Exchange(ExchangeError::NoMatch(id))) if id == "issuer"
+        ));
+    }
+}
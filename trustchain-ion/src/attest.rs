@@ -4,24 +4,31 @@ use did_ion::sidetree::{DIDSuffix, Operation, Sidetree};
 use did_ion::ION;
 use serde_json::to_string_pretty as to_json;
 use std::convert::TryFrom;
+use trustchain_core::constraints::DelegationConstraints;
 use trustchain_core::controller::Controller;
 use trustchain_core::key_manager::{ControllerKeyManager, KeyType};
 use trustchain_core::subject::Subject;
 use trustchain_core::utils::get_operations_path;
 use trustchain_core::TRUSTCHAIN_PROOF_SERVICE_ID_VALUE;
 
+use crate::config::TrustchainConfig;
 use crate::controller::IONController;
-use crate::get_ion_resolver;
+use crate::get_ion_resolver_from_config;
+use crate::longform::decode_long_form;
 
 // Function to resolve a controlled DID, attest to its contents and perform an update
 // operation on the controlled DID to add the attestation proof within a service endpoint.
+// The controlled DID may be given in long form if it has not yet been anchored.
 pub fn attest_operation(
     did: &str,
     controlled_did: &str,
+    constraints: Option<DelegationConstraints>,
+    config: &TrustchainConfig,
     verbose: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
     // 1.1. Load controller from passed controlled_did to be signed and controller DID
-    let controller = IONController::new(did, controlled_did)?;
+    let short_did = decode_long_form(controlled_did).map(|(short_did, _)| short_did);
+    let controller = IONController::new(did, short_did.as_deref().unwrap_or(controlled_did))?;
 
     if verbose {
         println!("DID: {}", controller.did());
@@ -30,7 +37,7 @@ pub fn attest_operation(
 
     // 1.2. Resolve controlled_did document with Trustchain resolver
     // Construct a Trustchain Resolver from a Sidetree (ION) DIDMethod.
-    let resolver = get_ion_resolver("http://localhost:3000/");
+    let resolver = get_ion_resolver_from_config(config);
 
     // Extract resolution items
     let (_, doc, doc_meta) = match resolver.resolve_as_result(controlled_did) {
@@ -69,10 +76,13 @@ pub fn attest_operation(
 
     // 2.2. Controller performs attestation to Document to generate proof data
     // Sign the document from the controller using the "Attestor" trait method
-    let proof = controller.to_attestor().attest(&doc, None)?;
+    let proof =
+        controller
+            .to_attestor()
+            .attest_with_constraints(&doc, constraints.as_ref(), None)?;
 
     // 2.3. Proof service is constructed from the proof data and make an AddService patch
-    patches.push(controller.add_proof_service(controller.did(), &proof));
+    patches.push(controller.add_proof_service(controller.did(), &proof, constraints.as_ref()));
 
     // 2.4  Generate new update key
     controller.generate_next_update_key()?;
@@ -1,13 +1,21 @@
 use async_trait::async_trait;
+use chrono::Utc;
 use did_ion::sidetree::Sidetree;
 use did_ion::ION;
 use ssi::did::Document;
 use ssi::did_resolve::DIDResolver;
-use ssi::vc::{Credential, LinkedDataProofOptions};
+use ssi::jwk::Algorithm;
+use ssi::jws::{sign_bytes_b64, Header};
+use ssi::vc::{Credential, LinkedDataProofOptions, Presentation, ProofPurpose, URI};
 use ssi::{jwk::JWK, one_or_many::OneOrMany};
 use std::convert::TryFrom;
-use trustchain_core::issuer::{Issuer, IssuerError};
+use trustchain_core::constraints::{attestation_payload, DelegationConstraints};
+use trustchain_core::context::{inline_contexts, inline_presentation_contexts};
+use trustchain_core::holder::{Holder, HolderError, KeyProofClaims, KEY_PROOF_JWT_TYPE};
+use trustchain_core::issuer::{resolve_verification_method, Issuer, IssuerError, ProofOptions};
 use trustchain_core::key_manager::KeyType;
+use trustchain_core::schema::{ensure_conforms, SchemaRegistry};
+use trustchain_core::status::StatusListManager;
 use trustchain_core::{
     attestor::{Attestor, AttestorError},
     key_manager::{AttestorKeyManager, KeyManager, KeyManagerError},
@@ -23,6 +31,8 @@ impl AttestorKeyManager for IONAttestor {}
 
 impl KeyManager for IONAttestor {}
 
+impl StatusListManager for IONAttestor {}
+
 impl IONAttestor {
     /// Construct a new TrustchainSubject instance.
     pub fn new(did: &str) -> Self {
@@ -100,7 +110,12 @@ impl Subject for IONAttestor {
 }
 
 impl Attestor for IONAttestor {
-    fn attest(&self, doc: &Document, key_id: Option<&str>) -> Result<String, AttestorError> {
+    fn attest_with_constraints(
+        &self,
+        doc: &Document,
+        constraints: Option<&DelegationConstraints>,
+        key_id: Option<&str>,
+    ) -> Result<String, AttestorError> {
         let algorithm = ION::SIGNATURE_ALGORITHM;
 
         // Add controller to document
@@ -109,13 +124,10 @@ impl Attestor for IONAttestor {
         // Use full short-form DID as controller
         doc.controller = Some(OneOrMany::One(self.did().to_string()));
 
-        // Canonicalize document
-        let doc_canon = ION::json_canonicalization_scheme(&doc)
+        // Canonicalize and hash document (with any constraints)
+        let doc_canon_hash = attestation_payload(&doc, constraints)
             .map_err(|_| AttestorError::InvalidDocumentParameters(doc.id.clone()))?;
 
-        // Hash canonicalized document
-        let doc_canon_hash = ION::hash(doc_canon.as_bytes());
-
         // Get the signing key.
         let signing_key = match self.signing_key(key_id) {
             Ok(key) => key,
@@ -140,19 +152,27 @@ impl Attestor for IONAttestor {
 
 #[async_trait]
 impl Issuer for IONAttestor {
-    // Attests to a given credential returning the credential with proof. The `@context` of the credential has linked-data fields strictly checked as part of proof generation.
+    // Attests to a given credential returning the credential with proof. The `@context` of the credential has linked-data fields strictly checked as part of proof generation, with contexts not built into the SSI library loaded offline by the default `ContextLoader`.
     async fn sign<T: DIDResolver>(
         &self,
         credential: &Credential,
-        key_id: Option<&str>,
+        options: &ProofOptions,
         resolver: &T,
     ) -> Result<Credential, IssuerError> {
-        // Get the signing key.
-        let signing_key = self.signing_key(key_id)?;
-
-        // Generate proof
-        let proof = credential
-            .generate_proof(&signing_key, &LinkedDataProofOptions::default(), resolver)
+        // Refuse to sign a credential not conforming to its schemas.
+        ensure_conforms(credential, &SchemaRegistry::load())?;
+
+        // Get the signing key and its verification method.
+        let signing_key = self.signing_key(options.key_id.as_deref())?;
+        let verification_method = self.verification_method(options, resolver).await?;
+
+        // Generate proof, with any contexts not loaded by the SSI library inlined.
+        let proof = inline_contexts(credential)
+            .generate_proof(
+                &signing_key,
+                &options.to_ldp_options(&verification_method),
+                resolver,
+            )
             .await?;
 
         // Add proof to credential
@@ -160,6 +180,137 @@ impl Issuer for IONAttestor {
         vc.add_proof(proof);
         Ok(vc)
     }
+
+    // Returns the verification method of the options, or of the signing key in the resolved DID
+    // document.
+    async fn verification_method<T: DIDResolver>(
+        &self,
+        options: &ProofOptions,
+        resolver: &T,
+    ) -> Result<String, IssuerError> {
+        match &options.verification_method {
+            Some(verification_method) => Ok(verification_method.to_owned()),
+            None => {
+                let signing_key = self.signing_key(options.key_id.as_deref())?;
+                resolve_verification_method(self.did(), &signing_key, resolver).await
+            }
+        }
+    }
+
+    // Signs a given credential returning it as a JWT.
+    async fn sign_jwt<T: DIDResolver>(
+        &self,
+        credential: &Credential,
+        options: &ProofOptions,
+        algorithm: Option<Algorithm>,
+        resolver: &T,
+    ) -> Result<String, IssuerError> {
+        // Refuse to sign a credential not conforming to its schemas.
+        ensure_conforms(credential, &SchemaRegistry::load())?;
+
+        // Get the signing key, with the `kid` header taken from the issuer's verification method
+        // rather than the key's own ID.
+        let mut signing_key = self.signing_key(options.key_id.as_deref())?;
+        let verification_method = self.verification_method(options, resolver).await?;
+        signing_key.key_id = None;
+        if algorithm.is_some() {
+            signing_key.algorithm = algorithm;
+        }
+
+        // Options encodable as JWT claims only.
+        let options = LinkedDataProofOptions {
+            verification_method: Some(URI::String(verification_method)),
+            proof_purpose: options.proof_purpose.clone(),
+            created: options.created,
+            challenge: options.challenge.clone(),
+            domain: options.domain.clone(),
+            checks: None,
+            ..Default::default()
+        };
+        Ok(credential
+            .generate_jwt(Some(&signing_key), &options, resolver)
+            .await?)
+    }
+}
+
+#[async_trait]
+impl Holder for IONAttestor {
+    // Signs a presentation with an authentication proof, returning the presentation with proof.
+    async fn sign_presentation<T: DIDResolver>(
+        &self,
+        presentation: &Presentation,
+        key_id: Option<&str>,
+        challenge: Option<&str>,
+        domain: Option<&str>,
+        resolver: &T,
+    ) -> Result<Presentation, HolderError> {
+        // Set the holder, which must be the attestor DID if already present.
+        let mut vp = presentation.clone();
+        match &vp.holder {
+            Some(URI::String(holder)) if holder != self.did() => {
+                return Err(HolderError::MismatchedHolder(
+                    holder.to_owned(),
+                    self.did().to_owned(),
+                ))
+            }
+            Some(_) => (),
+            None => vp.holder = Some(URI::String(self.did().to_owned())),
+        }
+
+        // Get the signing key.
+        let signing_key = self.signing_key(key_id)?;
+
+        // Generate proof
+        let options = LinkedDataProofOptions {
+            proof_purpose: Some(ProofPurpose::Authentication),
+            challenge: challenge.map(str::to_owned),
+            domain: domain.map(str::to_owned),
+            ..Default::default()
+        };
+        let proof = inline_presentation_contexts(&vp)
+            .generate_proof(&signing_key, &options, resolver)
+            .await?;
+
+        // Add proof to presentation
+        vp.add_proof(proof);
+        Ok(vp)
+    }
+
+    // Signs a key proof JWT for a credential issuer, identifying the key by its verification method.
+    async fn sign_key_proof<T: DIDResolver>(
+        &self,
+        audience: &str,
+        nonce: Option<&str>,
+        key_id: Option<&str>,
+        resolver: &T,
+    ) -> Result<String, HolderError> {
+        let signing_key = self.signing_key(key_id)?;
+        let verification_method = resolve_verification_method(self.did(), &signing_key, resolver)
+            .await
+            .map_err(HolderError::VerificationMethod)?;
+        let algorithm = signing_key
+            .get_algorithm()
+            .ok_or(ssi::error::Error::MissingAlgorithm)?;
+        let header = Header {
+            algorithm,
+            key_id: Some(verification_method),
+            type_: Some(KEY_PROOF_JWT_TYPE.to_string()),
+            ..Default::default()
+        };
+        let claims = KeyProofClaims {
+            aud: audience.to_owned(),
+            iat: Utc::now().timestamp(),
+            nonce: nonce.map(str::to_owned),
+        };
+        let encode = |bytes: Vec<u8>| base64::encode_config(bytes, base64::URL_SAFE_NO_PAD);
+        let signing_input = format!(
+            "{}.{}",
+            encode(serde_json::to_vec(&header).map_err(ssi::error::Error::from)?),
+            encode(serde_json::to_vec(&claims).map_err(ssi::error::Error::from)?)
+        );
+        let signature = sign_bytes_b64(algorithm, signing_input.as_bytes(), &signing_key)?;
+        Ok(format!("{}.{}", signing_input, signature))
+    }
 }
 
 #[cfg(test)]
@@ -235,6 +386,37 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_attest_with_constraints() -> Result<(), Box<dyn std::error::Error>> {
+        // Initialize temp path for saving keys
+        init();
+
+        // Set-up keys and attestor
+        let did = "did:example:test_attest_with_constraints";
+        let keys: OneOrMany<JWK> = serde_json::from_str(TEST_SIGNING_KEYS)?;
+        let target = IONAttestor::try_from(AttestorData::new(did.to_string(), keys.clone()))?;
+        let doc = Document::from_json(TEST_TRUSTCHAIN_DOCUMENT).expect("Document failed to load.");
+        let constraints = DelegationConstraints {
+            max_path_length: Some(0),
+            ..Default::default()
+        };
+
+        // Attest to doc with constraints
+        let proof = target.attest_with_constraints(&doc, Some(&constraints), None)?;
+        let decoded: String = ssi::jwt::decode_verify(&proof, keys.first().unwrap())?;
+
+        // Check payload covers the constraints
+        let mut doc_with_controller = doc.clone();
+        doc_with_controller.controller = Some(OneOrMany::One(target.did().to_string()));
+        assert_eq!(
+            decoded,
+            attestation_payload(&doc_with_controller, Some(&constraints))?
+        );
+        assert_ne!(decoded, attestation_payload(&doc_with_controller, None)?);
+
+        Ok(())
+    }
+
     #[test]
     fn test_attest_credential() {
         // Initialize temp path for saving keys
@@ -254,11 +436,16 @@ mod tests {
             ))
             .unwrap();
 
-            // Load credential. Issuer is "None" here so no resolution is required.
+            // Load credential. Issuer is "None" here and the verification method is given, so no
+            // resolution is required.
             let vc = serde_json::from_str(TEST_CREDENTIAL).unwrap();
+            let options = ProofOptions {
+                verification_method: Some(format!("{}#key-1", did)),
+                ..Default::default()
+            };
 
             // Attest to doc
-            let vc_with_proof = target.sign(&vc, None, &resolver).await;
+            let vc_with_proof = target.sign(&vc, &options, &resolver).await;
 
             // Check attest was ok
             assert!(vc_with_proof.is_ok());
@@ -1,15 +1,52 @@
 //! Trustchain CLI binary
-use clap::{arg, ArgAction, Command};
+use chrono::{DateTime, Utc};
+use clap::{arg, Arg, ArgAction, Command};
 use serde_json::to_string_pretty;
-use ssi::vc::{Credential, URI};
+use ssi::jwk::Algorithm;
+use ssi::one_or_many::OneOrMany;
+use ssi::vc::{Credential, CredentialOrJWT, Presentation, ProofPurpose, URI};
 use std::{
+    collections::HashMap,
+    convert::TryFrom,
     fs::File,
-    io::{stdin, BufReader},
+    io::{stdin, BufReader, Read},
+    net::TcpListener,
+    path::Path,
+    sync::Arc,
+};
+use trustchain_core::{
+    batch::{issue_batch, read_subjects, BatchOutput, SubjectFormat},
+    chain::Chain,
+    constraints::DelegationConstraints,
+    context::{save_context, trustchain_contexts_dir, ContextLoader},
+    exchange::{verify_submission, PresentationDefinition},
+    holder::Holder,
+    issuer::{Issuer, ProofOptions},
+    policy::IssuerPolicy,
+    presentation::verify_presentation_with_policy,
+    schema::{save_schema, trustchain_schemas_dir, SchemaRegistry},
+    status::{
+        save_status_list_credential, trustchain_status_list_credentials_dir, IssuerStatusList,
+        StatusError, StatusList2021Entry, StatusListManager, StatusPurpose, MIN_STATUS_LIST_LENGTH,
+    },
+    subject::Subject,
+    vc::{
+        verify_credential, verify_credential_jwt, AuthorisationCheck, CredentialSchemaCheck,
+        CredentialStatusCheck, CredentialVerificationPolicy, ValidityPeriod,
+    },
+    verifier::Verifier,
+    wallet::{Wallet, WalletQuery},
+    ROOT_EVENT_TIME_2378493,
 };
-use trustchain_core::{issuer::Issuer, verifier::Verifier, ROOT_EVENT_TIME_2378493};
 use trustchain_ion::{
-    attest::attest_operation, attestor::IONAttestor, create::create_operation, get_ion_resolver,
-    resolve::main_resolve, verifier::IONVerifier,
+    attest::attest_operation,
+    attestor::IONAttestor,
+    config::{CacheBackend, ResolverConfig, TrustchainConfig},
+    create::create_operation,
+    get_ion_resolver_from_config,
+    oid4vc::{OID4VCServer, OID4VCService},
+    resolve::{main_dereference, main_resolve},
+    verifier::IONVerifier,
 };
 
 fn cli() -> Command {
@@ -18,9 +55,30 @@ fn cli() -> Command {
         .subcommand_required(true)
         .arg_required_else_help(true)
         .allow_external_subcommands(true)
+        .arg(
+            arg!(--endpoint <ENDPOINT> "Sidetree node endpoint, in order of preference")
+                .required(false)
+                .global(true)
+                .action(ArgAction::Append),
+        )
+        .arg(
+            arg!(--config <CONFIG> "JSON file with Trustchain configuration (ION network, MongoDB, Bitcoin Core)")
+                .required(false)
+                .global(true),
+        )
+        .arg(
+            arg!(--resolver_config <RESOLVER_CONFIG> "JSON file with resolver configuration")
+                .required(false)
+                .global(true),
+        )
+        .arg(
+            arg!(--no_cache "Bypass any configured cache of DID resolutions")
+                .action(ArgAction::SetTrue)
+                .global(true),
+        )
         .subcommand(
             Command::new("did")
-                .about("DID functionality: create, attest, resolve.")
+                .about("DID functionality: create, attest, resolve, dereference.")
                 .subcommand_required(true)
                 .arg_required_else_help(true)
                 .allow_external_subcommands(true)
@@ -36,13 +94,41 @@ fn cli() -> Command {
                         .arg(arg!(-v - -verbose).action(ArgAction::SetTrue))
                         .arg(arg!(-d --did <DID>).required(true))
                         .arg(arg!(-c --controlled_did <CONTROLLED_DID>).required(true))
-                        .arg(arg!(-k --key_id <KEY_ID>).required(false)),
+                        .arg(arg!(-k --key_id <KEY_ID>).required(false))
+                        .arg(
+                            arg!(--max_path_length <MAX_PATH_LENGTH>)
+                                .required(false)
+                                .value_parser(clap::value_parser!(usize)),
+                        )
+                        .arg(
+                            arg!(--can_attest <CAN_ATTEST>)
+                                .required(false)
+                                .value_parser(clap::value_parser!(bool)),
+                        )
+                        .arg(
+                            arg!(--permitted_domain <PERMITTED_DOMAIN>)
+                                .required(false)
+                                .action(ArgAction::Append),
+                        ),
                 )
                 .subcommand(
                     Command::new("resolve")
                         .about("Resolves a DID.")
                         .arg(arg!(-v - -verbose).action(ArgAction::SetTrue))
-                        .arg(arg!(-d --did <DID>).required(true)),
+                        .arg(arg!(-d --did <DID>).required(true))
+                        .arg(arg!(--trustchain_verify).action(ArgAction::SetTrue))
+                        .arg(arg!(--allow_unpublished).action(ArgAction::SetTrue))
+                        .arg(
+                            arg!(-t --root_event_time <ROOT_EVENT_TIME>)
+                                .required(false)
+                                .value_parser(clap::value_parser!(u32)),
+                        ),
+                )
+                .subcommand(
+                    Command::new("dereference")
+                        .about("Dereferences a DID URL.")
+                        .arg(arg!(-v - -verbose).action(ArgAction::SetTrue))
+                        .arg(arg!(-u --did_url <DID_URL>).required(true)),
                 )
                 .subcommand(
                     Command::new("verify")
@@ -52,7 +138,6 @@ fn cli() -> Command {
                 ),
         )
         .subcommand(
-            // TODO: refactor into library code
             Command::new("vc")
                 .about("Verifiable credential functionality: sign and verify.")
                 .subcommand_required(true)
@@ -64,7 +149,43 @@ fn cli() -> Command {
                         .arg(arg!(-v - -verbose).action(ArgAction::SetTrue))
                         .arg(arg!(-d --did <DID>).required(true))
                         .arg(arg!(-f --credential_file <CREDENTIAL_FILE>).required(false))
-                        .arg(arg!(--key_id <KEY_ID>).required(false)),
+                        .args(proof_option_args())
+                        .arg(arg!(--jwt "Output the credential as a JWT (VC-JWT)").action(ArgAction::SetTrue))
+                        .arg(
+                            arg!(--algorithm <ALGORITHM> "JWT signing algorithm, e.g. ES256K")
+                                .required(false)
+                                .requires("jwt"),
+                        )
+                        .arg(
+                            arg!(--status <PURPOSE> "Add a status list entry: revocation or suspension")
+                                .required(false)
+                                .value_parser(clap::value_parser!(StatusPurpose)),
+                        ),
+                )
+                .subcommand(
+                    Command::new("batch")
+                        .about("Signs a credential from a template for each subject in a JSONL or CSV file, writing a manifest of the issued credentials.")
+                        .arg(arg!(-d --did <DID>).required(true))
+                        .arg(arg!(--template <TEMPLATE_FILE> "Credential template").required(true))
+                        .arg(
+                            arg!(--subjects <SUBJECTS_FILE> "Subject data: a JSON object per line, or CSV with a header row")
+                                .required(true),
+                        )
+                        .arg(
+                            arg!(--format <FORMAT> "Format of the subject data, if not given by its extension")
+                                .required(false)
+                                .value_parser(["jsonl", "csv"]),
+                        )
+                        .arg(
+                            arg!(--output_dir <OUTPUT_DIR> "Directory for the credentials and manifest")
+                                .required_unless_present("output_jsonl"),
+                        )
+                        .arg(
+                            arg!(--output_jsonl <OUTPUT_FILE> "JSONL file for the credentials, with the manifest alongside")
+                                .required(false)
+                                .conflicts_with("output_dir"),
+                        )
+                        .args(proof_option_args()),
                 )
                 .subcommand(
                     Command::new("verify")
@@ -72,14 +193,352 @@ fn cli() -> Command {
                         .arg(arg!(-v - -verbose).action(ArgAction::Count))
                         .arg(arg!(-f --credential_file <CREDENTIAL_FILE>).required(false))
                         .arg(arg!(-s - -signature_only).action(ArgAction::SetTrue))
-                        .arg(arg!(-t --root_event_time <ROOT_EVENT_TIME>).required(false)),
+                        .arg(arg!(--allow_unpublished).action(ArgAction::SetTrue))
+                        .arg(arg!(-t --root_event_time <ROOT_EVENT_TIME>).required(false))
+                        .arg(
+                            arg!(--version_time <VERSION_TIME>)
+                                .required(false)
+                                .value_parser(clap::value_parser!(u32)),
+                        )
+                        .arg(arg!(--issuer_policy <ISSUER_POLICY_FILE>).required(false)),
+                )
+                .subcommand(
+                    Command::new("status")
+                        .about("Credential status functionality: status lists to revoke and suspend credentials.")
+                        .subcommand_required(true)
+                        .arg_required_else_help(true)
+                        .subcommand(
+                            Command::new("create")
+                                .about("Creates a status list, saving and printing its signed status list credential.")
+                                .arg(arg!(-d --did <DID>).required(true))
+                                .arg(
+                                    arg!(--purpose <PURPOSE> "Status purpose: revocation or suspension")
+                                        .required(true)
+                                        .value_parser(clap::value_parser!(StatusPurpose)),
+                                )
+                                .arg(
+                                    arg!(--url <URL> "URL at which the status list credential is published")
+                                        .required(true),
+                                )
+                                .arg(
+                                    arg!(--length <LENGTH> "Length of the status list in bits")
+                                        .required(false)
+                                        .value_parser(clap::value_parser!(usize)),
+                                )
+                                .arg(arg!(--key_id <KEY_ID>).required(false)),
+                        )
+                        .subcommand(status_update_command("revoke", "Revokes a credential."))
+                        .subcommand(status_update_command("suspend", "Suspends a credential."))
+                        .subcommand(status_update_command(
+                            "reinstate",
+                            "Reinstates a suspended credential.",
+                        )),
+                )
+                .subcommand(
+                    Command::new("context")
+                        .about("JSON-LD contexts loaded offline for signing and verification.")
+                        .subcommand_required(true)
+                        .arg_required_else_help(true)
+                        .subcommand(
+                            Command::new("add")
+                                .about("Saves a context document to the Trustchain data directory.")
+                                .arg(arg!(--url <URL> "URL of the context").required(true))
+                                .arg(arg!(-f --file <FILE> "JSON-LD context document").required(true)),
+                        )
+                        .subcommand(Command::new("list").about("Lists the loaded context URLs.")),
+                )
+                .subcommand(
+                    Command::new("schema")
+                        .about("JSON Schemas against which credentials are validated.")
+                        .subcommand_required(true)
+                        .arg_required_else_help(true)
+                        .subcommand(
+                            Command::new("add")
+                                .about("Saves a JSON Schema to the Trustchain data directory.")
+                                .arg(arg!(--id <ID> "ID of the schema, as in credentialSchema").required(true))
+                                .arg(arg!(-f --file <FILE> "JSON Schema").required(true)),
+                        )
+                        .subcommand(Command::new("list").about("Lists the IDs of the saved schemas.")),
+                ),
+        )
+        .subcommand(
+            Command::new("vp")
+                .about("Verifiable presentation functionality: create and verify.")
+                .subcommand_required(true)
+                .arg_required_else_help(true)
+                .allow_external_subcommands(true)
+                .subcommand(
+                    Command::new("create")
+                        .about("Creates a presentation of credentials signed by the holder.")
+                        .arg(arg!(-d --did <DID>).required(true))
+                        .arg(
+                            arg!(-f --credential_file <CREDENTIAL_FILE>)
+                                .required(true)
+                                .action(ArgAction::Append),
+                        )
+                        .arg(arg!(--key_id <KEY_ID>).required(false))
+                        .arg(arg!(--challenge <CHALLENGE>).required(false))
+                        .arg(arg!(--domain <DOMAIN>).required(false)),
+                )
+                .subcommand(
+                    Command::new("verify")
+                        .about("Verifies a presentation, its credentials, issuers and holder.")
+                        .arg(arg!(-v - -verbose).action(ArgAction::SetTrue))
+                        .arg(arg!(-f --presentation_file <PRESENTATION_FILE>).required(false))
+                        .arg(arg!(--challenge <CHALLENGE>).required(false))
+                        .arg(arg!(--domain <DOMAIN>).required(false))
+                        .arg(arg!(--allow_unpublished).action(ArgAction::SetTrue))
+                        .arg(
+                            arg!(-t --root_event_time <ROOT_EVENT_TIME>)
+                                .required(false)
+                                .value_parser(clap::value_parser!(u32)),
+                        )
+                        .arg(arg!(--issuer_policy <ISSUER_POLICY_FILE>).required(false))
+                        .arg(
+                            arg!(--definition <DEFINITION_FILE> "Presentation definition the presentation submission must satisfy")
+                                .required(false),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("wallet")
+                .about("Credential wallet functionality: add, list, show, remove and present credentials.")
+                .subcommand_required(true)
+                .arg_required_else_help(true)
+                .allow_external_subcommands(true)
+                .subcommand(
+                    Command::new("add")
+                        .about("Verifies a credential and adds it to the wallet.")
+                        .arg(arg!(-f --credential_file <CREDENTIAL_FILE>).required(false))
+                        .arg(arg!(--allow_unpublished).action(ArgAction::SetTrue))
+                        .arg(
+                            arg!(-t --root_event_time <ROOT_EVENT_TIME>)
+                                .required(false)
+                                .value_parser(clap::value_parser!(u32)),
+                        )
+                        .arg(arg!(--issuer_policy <ISSUER_POLICY_FILE>).required(false)),
+                )
+                .subcommand(
+                    Command::new("list")
+                        .about("Lists the credentials in the wallet, matching any query.")
+                        .arg(arg!(--type <TYPE> "Credential type").required(false))
+                        .arg(arg!(--issuer <ISSUER> "DID of the issuer").required(false))
+                        .arg(arg!(--subject <SUBJECT> "ID of a credential subject").required(false)),
+                )
+                .subcommand(
+                    Command::new("show")
+                        .about("Shows a credential in the wallet.")
+                        .arg(arg!(--id <ID> "ID, or unique ID prefix, of the credential").required(true)),
+                )
+                .subcommand(
+                    Command::new("remove")
+                        .about("Removes a credential from the wallet.")
+                        .arg(arg!(--id <ID> "ID, or unique ID prefix, of the credential").required(true)),
+                )
+                .subcommand(
+                    Command::new("present")
+                        .about("Creates a presentation of credentials in the wallet signed by the holder.")
+                        .arg(arg!(-d --did <DID>).required(true))
+                        .arg(
+                            arg!(--id <ID> "ID, or unique ID prefix, of a credential")
+                                .required_unless_present("definition")
+                                .action(ArgAction::Append),
+                        )
+                        .arg(
+                            arg!(--definition <DEFINITION_FILE> "Presentation definition to satisfy with credentials in the wallet")
+                                .required(false)
+                                .conflicts_with("id"),
+                        )
+                        .arg(arg!(--key_id <KEY_ID>).required(false))
+                        .arg(arg!(--challenge <CHALLENGE>).required(false))
+                        .arg(arg!(--domain <DOMAIN>).required(false)),
+                ),
+        )
+        .subcommand(
+            Command::new("oid4vc")
+                .about("OpenID for Verifiable Credentials functionality: serve.")
+                .subcommand_required(true)
+                .arg_required_else_help(true)
+                .allow_external_subcommands(true)
+                .subcommand(
+                    Command::new("serve")
+                        .about("Serves OID4VCI credential issuance and OID4VP presentation endpoints, printing the credential offer and authorization request URIs.")
+                        .arg(
+                            arg!(--base_url <BASE_URL> "Public URL of the server, identifying the credential issuer")
+                                .required(true),
+                        )
+                        .arg(
+                            arg!(--address <ADDRESS> "Socket address to listen on")
+                                .required(false)
+                                .default_value("127.0.0.1:8080"),
+                        )
+                        .arg(arg!(-d --did <DID> "DID of the issuer").required(false).requires("template"))
+                        .arg(
+                            arg!(--template <TEMPLATE_FILE> "Credential template, offered as the credential configuration named by its file stem")
+                                .required(false)
+                                .requires("did"),
+                        )
+                        .arg(
+                            arg!(--subjects <SUBJECTS_FILE> "Subject data for which to offer credentials: a JSON object per line, or CSV with a header row")
+                                .required(false)
+                                .requires("template"),
+                        )
+                        .arg(
+                            arg!(--format <FORMAT> "Format of the subject data, if not given by its extension")
+                                .required(false)
+                                .value_parser(["jsonl", "csv"]),
+                        )
+                        .arg(
+                            arg!(--definition <DEFINITION_FILE> "Presentation definition for which to make an authorization request")
+                                .required(false),
+                        )
+                        .arg(arg!(--allow_unpublished).action(ArgAction::SetTrue))
+                        .arg(
+                            arg!(-t --root_event_time <ROOT_EVENT_TIME>)
+                                .required(false)
+                                .value_parser(clap::value_parser!(u32)),
+                        )
+                        .arg(arg!(--issuer_policy <ISSUER_POLICY_FILE>).required(false)),
                 ),
         )
 }
 
+/// Makes a subcommand updating the status of a credential, saving and printing the signed status
+/// list credential for publication.
+fn status_update_command(name: &'static str, about: &'static str) -> Command {
+    Command::new(name)
+        .about(about)
+        .arg(arg!(-d --did <DID>).required(true))
+        .arg(arg!(-f --credential_file <CREDENTIAL_FILE>).required_unless_present("index"))
+        .arg(
+            arg!(--index <INDEX> "Status list index of the credential")
+                .required(false)
+                .conflicts_with("credential_file")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(arg!(--key_id <KEY_ID>).required(false))
+}
+
+/// Returns the status list index of a credential passed as a file or an index argument, checking
+/// that any status entry of the credential has the given purpose.
+fn status_index(
+    sub_matches: &clap::ArgMatches,
+    status_purpose: StatusPurpose,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    if let Some(index) = sub_matches.get_one::<usize>("index") {
+        return Ok(*index);
+    }
+    let path = sub_matches.get_one::<String>("credential_file").unwrap();
+    let credential = match parse_credential(&std::fs::read_to_string(path)?)? {
+        CredentialOrJWT::Credential(credential) => credential,
+        CredentialOrJWT::JWT(jwt) => Credential::from_jwt_unsigned(&jwt)?,
+    };
+    let entry = StatusList2021Entry::try_from(
+        credential
+            .credential_status
+            .as_ref()
+            .ok_or("Credential has no credentialStatus.")?,
+    )?;
+    if entry.status_purpose != status_purpose {
+        return Err(StatusError::MismatchedPurpose(entry.status_purpose, status_purpose).into());
+    }
+    Ok(entry.index()?)
+}
+
+/// Returns the arguments for proof options of the `vc sign` and `vc batch` commands.
+fn proof_option_args() -> [Arg; 6] {
+    [
+        arg!(--key_id <KEY_ID>).required(false),
+        arg!(--verification_method <VERIFICATION_METHOD> "Verification method of the proof, if not that of the signing key")
+            .required(false),
+        arg!(--proof_purpose <PROOF_PURPOSE> "Proof purpose, e.g. assertionMethod")
+            .required(false),
+        arg!(--created <CREATED> "Creation time of the proof (RFC 3339)").required(false),
+        arg!(--domain <DOMAIN> "Domain to which the proof is bound").required(false),
+        arg!(--challenge <CHALLENGE> "Challenge to which the proof is bound").required(false),
+    ]
+}
+
+/// Returns the proof options given by the arguments of the `vc sign` and `vc batch` commands.
+fn proof_options(
+    sub_matches: &clap::ArgMatches,
+) -> Result<ProofOptions, Box<dyn std::error::Error>> {
+    let string = |name: &str| sub_matches.get_one::<String>(name).cloned();
+    Ok(ProofOptions {
+        key_id: string("key_id"),
+        verification_method: string("verification_method"),
+        proof_purpose: string("proof_purpose")
+            .map(|purpose| serde_json::from_value::<ProofPurpose>(purpose.into()))
+            .transpose()?,
+        created: string("created")
+            .map(|created| DateTime::parse_from_rfc3339(&created))
+            .transpose()?
+            .map(|created| created.with_timezone(&Utc)),
+        domain: string("domain"),
+        challenge: string("challenge"),
+    })
+}
+
+/// Reads the issuer authorisation policy given by the `--issuer_policy` argument, if any.
+fn issuer_policy(
+    sub_matches: &clap::ArgMatches,
+) -> Result<Option<IssuerPolicy>, Box<dyn std::error::Error>> {
+    Ok(sub_matches
+        .get_one::<String>("issuer_policy")
+        .map(IssuerPolicy::from_file)
+        .transpose()?)
+}
+
+/// Returns the format of subject data given by the `--format` argument, or else by the extension
+/// of its file.
+fn subject_format(
+    sub_matches: &clap::ArgMatches,
+    path: &str,
+) -> Result<SubjectFormat, Box<dyn std::error::Error>> {
+    Ok(match sub_matches.get_one::<String>("format") {
+        Some(format) if format == "csv" => SubjectFormat::Csv,
+        Some(_) => SubjectFormat::Jsonl,
+        None => SubjectFormat::from_path(path)
+            .ok_or("Unrecognised subject data format: use --format.")?,
+    })
+}
+
+/// Reads a presentation definition from a file.
+fn read_definition(path: &str) -> Result<PresentationDefinition, Box<dyn std::error::Error>> {
+    Ok(serde_json::from_reader(File::open(path)?)?)
+}
+
+/// Parses a credential with an embedded proof (JSON) or encoded as a JWT (VC-JWT).
+fn parse_credential(input: &str) -> Result<CredentialOrJWT, serde_json::Error> {
+    let input = input.trim();
+    if input.starts_with('{') {
+        Ok(CredentialOrJWT::Credential(serde_json::from_str(input)?))
+    } else {
+        Ok(CredentialOrJWT::JWT(input.to_owned()))
+    }
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let matches = cli().get_matches();
 
+    // Trustchain configuration from file and environment, with any resolver configuration file
+    // and endpoints passed as arguments taking precedence.
+    let mut config = TrustchainConfig::load(
+        matches
+            .get_one::<String>("config")
+            .map(|path| path.as_str()),
+    )?;
+    if let Some(path) = matches.get_one::<String>("resolver_config") {
+        config.resolver = ResolverConfig::from_file(path)?;
+    }
+    if let Some(endpoints) = matches.get_many::<String>("endpoint") {
+        config.resolver.endpoints = endpoints.cloned().collect();
+        config.resolver.validate()?;
+    }
+    if matches!(matches.get_one::<bool>("no_cache"), Some(true)) {
+        config.resolver.cache = CacheBackend::None;
+    }
+
     match matches.subcommand() {
         Some(("did", sub_matches)) => {
             match sub_matches.subcommand() {
@@ -97,7 +556,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     };
 
                     // Read from the file path to a "Reader"
-                    create_operation(doc_state, verbose)?;
+                    create_operation(doc_state, &config, verbose)?;
                 }
                 Some(("attest", sub_matches)) => {
                     let did = sub_matches.get_one::<String>("did").unwrap();
@@ -106,25 +565,47 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     let _key_id = sub_matches
                         .get_one::<String>("key_id")
                         .map(|string| string.as_str());
+                    let constraints = DelegationConstraints {
+                        max_path_length: sub_matches.get_one::<usize>("max_path_length").copied(),
+                        can_attest: sub_matches.get_one::<bool>("can_attest").copied(),
+                        permitted_domains: sub_matches
+                            .get_many::<String>("permitted_domain")
+                            .map(|domains| domains.cloned().collect()),
+                    };
                     // TODO: pass optional key_id
-                    attest_operation(did, controlled_did, verbose)?;
+                    attest_operation(did, controlled_did, Some(constraints), &config, verbose)?;
                 }
                 Some(("resolve", sub_matches)) => {
                     let did = sub_matches.get_one::<String>("did").unwrap();
                     let verbose = matches!(sub_matches.get_one::<bool>("verbose"), Some(true));
-                    main_resolve(did, verbose)?;
+                    // Trustchain-verify the DID during resolution if requested.
+                    let root_event_time = match sub_matches.get_one::<bool>("trustchain_verify") {
+                        Some(true) => Some(
+                            sub_matches
+                                .get_one::<u32>("root_event_time")
+                                .copied()
+                                .unwrap_or(ROOT_EVENT_TIME_2378493),
+                        ),
+                        _ => None,
+                    };
+                    let allow_unpublished =
+                        matches!(sub_matches.get_one::<bool>("allow_unpublished"), Some(true));
+                    main_resolve(did, &config, root_event_time, allow_unpublished, verbose)?;
+                }
+                Some(("dereference", sub_matches)) => {
+                    let did_url = sub_matches.get_one::<String>("did_url").unwrap();
+                    let verbose = matches!(sub_matches.get_one::<bool>("verbose"), Some(true));
+                    main_dereference(did_url, &config, verbose)?;
                 }
                 _ => panic!("Unrecognised DID subcommand."),
             }
         }
         Some(("vc", sub_matches)) => {
-            let resolver = get_ion_resolver("http://localhost:3000/");
+            let resolver = get_ion_resolver_from_config(&config);
             match sub_matches.subcommand() {
                 Some(("sign", sub_matches)) => {
                     let did = sub_matches.get_one::<String>("did").unwrap();
-                    let key_id = sub_matches
-                        .get_one::<String>("key_id")
-                        .map(|string| string.as_str());
+                    let options = proof_options(sub_matches)?;
                     let mut credential: Credential =
                         if let Some(path) = sub_matches.get_one::<String>("credential_file") {
                             serde_json::from_reader(&*std::fs::read(path).unwrap()).unwrap()
@@ -134,11 +615,73 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                         };
                     credential.issuer = Some(ssi::vc::Issuer::URI(URI::String(did.to_string())));
                     let attestor = IONAttestor::new(did);
-                    resolver.runtime.block_on(async {
-                        let credential_with_proof =
-                            attestor.sign(&credential, key_id, &resolver).await.unwrap();
-                        println!("{}", &to_string_pretty(&credential_with_proof).unwrap());
-                    });
+                    if let Some(status_purpose) = sub_matches.get_one::<StatusPurpose>("status") {
+                        attestor.allocate_credential_status(
+                            attestor.did_suffix(),
+                            *status_purpose,
+                            &mut credential,
+                        )?;
+                    }
+                    if matches!(sub_matches.get_one::<bool>("jwt"), Some(true)) {
+                        let algorithm = sub_matches
+                            .get_one::<String>("algorithm")
+                            .map(|algorithm| {
+                                serde_json::from_value::<Algorithm>(algorithm.as_str().into())
+                            })
+                            .transpose()?;
+                        let jwt = resolver.runtime.block_on(attestor.sign_jwt(
+                            &credential,
+                            &options,
+                            algorithm,
+                            &resolver,
+                        ))?;
+                        println!("{}", jwt);
+                    } else {
+                        let credential_with_proof = resolver.runtime.block_on(attestor.sign(
+                            &credential,
+                            &options,
+                            &resolver,
+                        ))?;
+                        println!("{}", &to_string_pretty(&credential_with_proof)?);
+                    }
+                }
+                Some(("batch", sub_matches)) => {
+                    let did = sub_matches.get_one::<String>("did").unwrap();
+                    let options = proof_options(sub_matches)?;
+                    let mut template: Credential = serde_json::from_reader(File::open(
+                        sub_matches.get_one::<String>("template").unwrap(),
+                    )?)?;
+                    template.issuer = Some(ssi::vc::Issuer::URI(URI::String(did.to_string())));
+                    let path = sub_matches.get_one::<String>("subjects").unwrap();
+                    let subjects = read_subjects(
+                        &std::fs::read_to_string(path)?,
+                        subject_format(sub_matches, path)?,
+                    )?;
+                    let output = match sub_matches.get_one::<String>("output_jsonl") {
+                        Some(path) => BatchOutput::Jsonl(path.into()),
+                        None => BatchOutput::Directory(
+                            sub_matches.get_one::<String>("output_dir").unwrap().into(),
+                        ),
+                    };
+                    // Sign every credential with the same attestor and resolver.
+                    let attestor = IONAttestor::new(did);
+                    let manifest = resolver.runtime.block_on(issue_batch(
+                        &attestor, &template, &subjects, &options, &resolver, &output,
+                    ))?;
+                    for entry in manifest.failures() {
+                        println!(
+                            "Record {} (line {})... ❌ {}",
+                            entry.index,
+                            entry.line,
+                            entry.error.as_deref().unwrap_or_default()
+                        );
+                    }
+                    println!(
+                        "Issued {} credential(s), {} failed. Manifest: {}",
+                        manifest.issued,
+                        manifest.failed,
+                        output.manifest_path().display()
+                    );
                 }
                 Some(("verify", sub_matches)) => {
                     let verbose = sub_matches.get_one::<u8>("verbose");
@@ -147,47 +690,93 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                         Some(time) => time.parse::<u32>().unwrap(),
                         None => ROOT_EVENT_TIME_2378493,
                     };
-                    let credential: Credential =
-                        if let Some(path) = sub_matches.get_one::<String>("credential_file") {
-                            serde_json::from_reader(&*std::fs::read(path).unwrap()).unwrap()
-                        } else {
-                            let buffer = BufReader::new(stdin());
-                            serde_json::from_reader(buffer).unwrap()
-                        };
-                    resolver.runtime.block_on(async {
-                        let verify_result = credential.verify(None, &resolver).await;
-                        if verify_result.errors.is_empty() {
-                            println!("Proof... ✅")
-                        } else {
-                            println!(
-                                "Proof... Invalid\n{}",
-                                &to_string_pretty(&verify_result).unwrap()
-                            );
-                        }
-                    });
-
-                    // Return if only checking signature
-                    if let Some(true) = signature_only {
-                        return Ok(());
-                    }
-
-                    // Trustchain verify the issued credential
-                    let verifier = IONVerifier::new(get_ion_resolver("http://localhost:3000/"));
+                    let input = if let Some(path) = sub_matches.get_one::<String>("credential_file")
+                    {
+                        std::fs::read_to_string(path)?
+                    } else {
+                        let mut buffer = String::new();
+                        stdin().read_to_string(&mut buffer)?;
+                        buffer
+                    };
+                    let allow_unpublished =
+                        matches!(sub_matches.get_one::<bool>("allow_unpublished"), Some(true));
+                    let verifier = IONVerifier::with_config(resolver, config.clone())
+                        .allow_unpublished(allow_unpublished);
 
-                    let issuer = match credential.issuer {
-                        Some(ssi::vc::Issuer::URI(URI::String(did))) => did,
-                        _ => panic!("No issuer present in credential."),
+                    // Verify the issuer as of a past Unix time if given, e.g. the time at which
+                    // the credential was issued.
+                    let policy = CredentialVerificationPolicy {
+                        version_time: sub_matches.get_one::<u32>("version_time").copied(),
+                        signature_only: matches!(signature_only, Some(true)),
+                        issuer_policy: issuer_policy(sub_matches)?,
+                        ..CredentialVerificationPolicy::new(root_event_time)
+                    };
+                    // Accept either a credential with an embedded proof or a JWT (VC-JWT).
+                    let result = match parse_credential(&input)? {
+                        CredentialOrJWT::Credential(credential) => {
+                            verify_credential(&credential, &verifier, &policy)
+                        }
+                        CredentialOrJWT::JWT(jwt) => {
+                            verify_credential_jwt(&jwt, &verifier, &policy)?.1
+                        }
                     };
 
-                    let result = verifier.verify(&issuer, root_event_time);
+                    if result.proof_is_valid() {
+                        println!("Proof... ✅")
+                    } else {
+                        println!(
+                            "Proof... Invalid\n{}",
+                            &to_string_pretty(&result.proof).unwrap()
+                        );
+                    }
+                    match result.validity {
+                        ValidityPeriod::Valid => println!("Validity period... ✅"),
+                        validity => println!("Validity period... ❌ ({:?})", validity),
+                    }
+                    match &result.status {
+                        CredentialStatusCheck::Active => println!("Status... ✅"),
+                        CredentialStatusCheck::Revoked => println!("Status... ❌ (Revoked)"),
+                        CredentialStatusCheck::Suspended => println!("Status... ❌ (Suspended)"),
+                        CredentialStatusCheck::Invalid(e) => println!("Status... ❌ ({})", e),
+                        CredentialStatusCheck::Unsupported(type_) => {
+                            println!("Status... unsupported type: {}", type_)
+                        }
+                        CredentialStatusCheck::NotPresent | CredentialStatusCheck::NotChecked => (),
+                    }
+                    match &result.schema {
+                        CredentialSchemaCheck::Valid => println!("Schema... ✅"),
+                        CredentialSchemaCheck::Invalid(violations) => {
+                            println!("Schema... ❌");
+                            violations
+                                .iter()
+                                .for_each(|violation| println!("  {}", violation));
+                        }
+                        CredentialSchemaCheck::Failed(e) => println!("Schema... ❌ ({})", e),
+                        CredentialSchemaCheck::Unsupported(type_) => {
+                            println!("Schema... unsupported type: {}", type_)
+                        }
+                        CredentialSchemaCheck::NotPresent | CredentialSchemaCheck::NotChecked => (),
+                    }
+                    match &result.authorisation {
+                        AuthorisationCheck::Authorised => println!("Authorisation... ✅"),
+                        AuthorisationCheck::Unauthorised(violations) => {
+                            println!("Authorisation... ❌");
+                            violations
+                                .iter()
+                                .for_each(|violation| println!("  {}", violation));
+                        }
+                        AuthorisationCheck::NotChecked => (),
+                    }
 
-                    match result {
-                        Ok(chain) => {
+                    // Trustchain verification of the issuer, unless only checking the signature.
+                    let issuer = result.issuer.as_deref().unwrap_or("none");
+                    match result.issuer_chain {
+                        Some(Ok(chain)) => {
                             println!("Issuer: {}... ✅", issuer);
                             if let Some(&verbose_count) = verbose {
                                 if verbose_count > 1 {
                                     let (_, doc, doc_meta) =
-                                        resolver.resolve_as_result(&issuer).unwrap();
+                                        verifier.resolver().resolve_as_result(issuer).unwrap();
                                     println!("---");
                                     println!("Issuer DID doc:");
                                     println!(
@@ -209,14 +798,385 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                                 }
                             }
                         }
-                        _ => {
+                        Some(Err(e)) => {
                             println!("Issuer: {}... ❌", issuer);
+                            if verbose.copied().unwrap_or_default() > 0 {
+                                println!("{}", e);
+                            }
                         }
+                        None => (),
                     }
                 }
+                Some(("status", sub_matches)) => {
+                    let (name, sub_matches) = sub_matches.subcommand().unwrap();
+                    let did = sub_matches.get_one::<String>("did").unwrap();
+                    let key_id = sub_matches
+                        .get_one::<String>("key_id")
+                        .map(|string| string.as_str());
+                    let attestor = IONAttestor::new(did);
+                    let list = match name {
+                        "create" => {
+                            let list = IssuerStatusList::new(
+                                sub_matches.get_one::<String>("url").unwrap(),
+                                *sub_matches.get_one::<StatusPurpose>("purpose").unwrap(),
+                                sub_matches
+                                    .get_one::<usize>("length")
+                                    .copied()
+                                    .unwrap_or(MIN_STATUS_LIST_LENGTH),
+                            )?;
+                            attestor.save_status_list(attestor.did_suffix(), &list, false)?;
+                            list
+                        }
+                        "revoke" | "suspend" | "reinstate" => {
+                            let (status_purpose, status) = match name {
+                                "revoke" => (StatusPurpose::Revocation, true),
+                                "suspend" => (StatusPurpose::Suspension, true),
+                                _ => (StatusPurpose::Suspension, false),
+                            };
+                            let index = status_index(sub_matches, status_purpose)?;
+                            attestor.update_status(
+                                attestor.did_suffix(),
+                                status_purpose,
+                                index,
+                                status,
+                            )?
+                        }
+                        _ => panic!("Unrecognised VC status subcommand."),
+                    };
+                    // Sign the status list credential, to be published at its URL, and save it to be
+                    // loaded locally in verification.
+                    let credential = list.credential(did)?;
+                    let credential_with_proof = resolver.runtime.block_on(attestor.sign(
+                        &credential,
+                        &ProofOptions::with_key_id(key_id),
+                        &resolver,
+                    ))?;
+                    save_status_list_credential(
+                        trustchain_status_list_credentials_dir()?,
+                        &credential_with_proof,
+                    )?;
+                    println!("{}", &to_string_pretty(&credential_with_proof)?);
+                }
+                Some(("context", sub_matches)) => match sub_matches.subcommand() {
+                    Some(("add", sub_matches)) => {
+                        let url = sub_matches.get_one::<String>("url").unwrap();
+                        let path = Path::new(sub_matches.get_one::<String>("file").unwrap());
+                        let document = serde_json::from_reader(File::open(path)?)?;
+                        let file_name = path
+                            .file_name()
+                            .and_then(|file_name| file_name.to_str())
+                            .unwrap();
+                        let saved =
+                            save_context(trustchain_contexts_dir()?, url, file_name, &document)?;
+                        println!("Saved context: {} to {}", url, saved.display());
+                    }
+                    Some(("list", _)) => {
+                        let loader = ContextLoader::from_dir(trustchain_contexts_dir()?)?;
+                        loader.urls().iter().for_each(|url| println!("{}", url));
+                    }
+                    _ => panic!("Unrecognised VC context subcommand."),
+                },
+                Some(("schema", sub_matches)) => match sub_matches.subcommand() {
+                    Some(("add", sub_matches)) => {
+                        let id = sub_matches.get_one::<String>("id").unwrap();
+                        let path = Path::new(sub_matches.get_one::<String>("file").unwrap());
+                        let schema = serde_json::from_reader(File::open(path)?)?;
+                        let file_name = path
+                            .file_name()
+                            .and_then(|file_name| file_name.to_str())
+                            .unwrap();
+                        let saved = save_schema(trustchain_schemas_dir()?, id, file_name, &schema)?;
+                        println!("Saved schema: {} to {}", id, saved.display());
+                    }
+                    Some(("list", _)) => {
+                        let registry = SchemaRegistry::from_dir(trustchain_schemas_dir()?)?;
+                        registry.ids().iter().for_each(|id| println!("{}", id));
+                    }
+                    _ => panic!("Unrecognised VC schema subcommand."),
+                },
                 _ => panic!("Unrecognised VC subcommand."),
             }
         }
+        Some(("vp", sub_matches)) => {
+            let resolver = get_ion_resolver_from_config(&config);
+            match sub_matches.subcommand() {
+                Some(("create", sub_matches)) => {
+                    let did = sub_matches.get_one::<String>("did").unwrap();
+                    let key_id = sub_matches
+                        .get_one::<String>("key_id")
+                        .map(|string| string.as_str());
+                    let challenge = sub_matches
+                        .get_one::<String>("challenge")
+                        .map(|string| string.as_str());
+                    let domain = sub_matches
+                        .get_one::<String>("domain")
+                        .map(|string| string.as_str());
+                    let credentials = sub_matches
+                        .get_many::<String>("credential_file")
+                        .unwrap()
+                        .map(|path| Ok(parse_credential(&std::fs::read_to_string(path)?)?))
+                        .collect::<Result<Vec<_>, Box<dyn std::error::Error>>>()?;
+                    let presentation = Presentation {
+                        verifiable_credential: Some(OneOrMany::Many(credentials)),
+                        ..Default::default()
+                    };
+                    let holder = IONAttestor::new(did);
+                    let presentation_with_proof =
+                        resolver.runtime.block_on(holder.sign_presentation(
+                            &presentation,
+                            key_id,
+                            challenge,
+                            domain,
+                            &resolver,
+                        ))?;
+                    println!("{}", &to_string_pretty(&presentation_with_proof)?);
+                }
+                Some(("verify", sub_matches)) => {
+                    let verbose = matches!(sub_matches.get_one::<bool>("verbose"), Some(true));
+                    let challenge = sub_matches
+                        .get_one::<String>("challenge")
+                        .map(|string| string.as_str());
+                    let domain = sub_matches
+                        .get_one::<String>("domain")
+                        .map(|string| string.as_str());
+                    let root_event_time = sub_matches
+                        .get_one::<u32>("root_event_time")
+                        .copied()
+                        .unwrap_or(ROOT_EVENT_TIME_2378493);
+                    let presentation: Presentation =
+                        if let Some(path) = sub_matches.get_one::<String>("presentation_file") {
+                            serde_json::from_reader(File::open(path)?)?
+                        } else {
+                            serde_json::from_reader(BufReader::new(stdin()))?
+                        };
+                    let allow_unpublished =
+                        matches!(sub_matches.get_one::<bool>("allow_unpublished"), Some(true));
+                    let verifier = IONVerifier::with_config(resolver, config.clone())
+                        .allow_unpublished(allow_unpublished);
+                    let issuer_policy = issuer_policy(sub_matches)?;
+                    let result = match sub_matches.get_one::<String>("definition") {
+                        Some(path) => verify_submission(
+                            &presentation,
+                            &read_definition(path)?,
+                            challenge,
+                            domain,
+                            root_event_time,
+                            issuer_policy.as_ref(),
+                            &verifier,
+                        )
+                        .map(|(submission, verified)| (Some(submission), verified))
+                        .map_err(|e| e.to_string()),
+                        None => verify_presentation_with_policy(
+                            &presentation,
+                            challenge,
+                            domain,
+                            root_event_time,
+                            issuer_policy.as_ref(),
+                            &verifier,
+                        )
+                        .map(|verified| (None, verified))
+                        .map_err(|e| e.to_string()),
+                    };
+                    match result {
+                        Ok((submission, verified)) => {
+                            println!("Presentation... ✅");
+                            if let Some(submission) = submission {
+                                println!("Submission: {}... ✅", submission.definition_id);
+                            }
+                            println!("Holder: {}... ✅", verified.holder_chain.leaf());
+                            for chain in verified.issuer_chains.iter() {
+                                println!("Issuer: {}... ✅", chain.leaf());
+                            }
+                            if verbose {
+                                println!("---");
+                                println!("Holder chain:");
+                                println!("{}", verified.holder_chain);
+                                for chain in verified.issuer_chains.iter() {
+                                    println!("---");
+                                    println!("Issuer chain:");
+                                    println!("{}", chain);
+                                }
+                                println!("---");
+                            }
+                        }
+                        Err(e) => {
+                            println!("Presentation... ❌");
+                            println!("{}", e);
+                        }
+                    }
+                }
+                _ => panic!("Unrecognised VP subcommand."),
+            }
+        }
+        Some(("wallet", sub_matches)) => {
+            let mut wallet = Wallet::open()?;
+            match sub_matches.subcommand() {
+                Some(("add", sub_matches)) => {
+                    let input = if let Some(path) = sub_matches.get_one::<String>("credential_file")
+                    {
+                        std::fs::read_to_string(path)?
+                    } else {
+                        let mut buffer = String::new();
+                        stdin().read_to_string(&mut buffer)?;
+                        buffer
+                    };
+                    let root_event_time = sub_matches
+                        .get_one::<u32>("root_event_time")
+                        .copied()
+                        .unwrap_or(ROOT_EVENT_TIME_2378493);
+                    let allow_unpublished =
+                        matches!(sub_matches.get_one::<bool>("allow_unpublished"), Some(true));
+                    let verifier = IONVerifier::from_config(config.clone())
+                        .allow_unpublished(allow_unpublished);
+                    let policy = CredentialVerificationPolicy {
+                        issuer_policy: issuer_policy(sub_matches)?,
+                        ..CredentialVerificationPolicy::new(root_event_time)
+                    };
+                    let record = wallet.add(parse_credential(&input)?, &verifier, &policy)?;
+                    println!("{}", record.id);
+                }
+                Some(("list", sub_matches)) => {
+                    let string = |name: &str| sub_matches.get_one::<String>(name).cloned();
+                    let query = WalletQuery {
+                        credential_type: string("type"),
+                        issuer: string("issuer"),
+                        subject: string("subject"),
+                    };
+                    for record in wallet.query(&query) {
+                        println!(
+                            "{}  {}  issuer: {}  subjects: {}",
+                            record.id,
+                            record.types.join(", "),
+                            record.issuer.as_deref().unwrap_or("none"),
+                            record.subjects.join(", ")
+                        );
+                    }
+                }
+                Some(("show", sub_matches)) => {
+                    let (_, credential) =
+                        wallet.get(sub_matches.get_one::<String>("id").unwrap())?;
+                    match credential {
+                        CredentialOrJWT::Credential(credential) => {
+                            println!("{}", &to_string_pretty(&credential)?)
+                        }
+                        CredentialOrJWT::JWT(jwt) => println!("{}", jwt),
+                    }
+                }
+                Some(("remove", sub_matches)) => {
+                    let record = wallet.remove(sub_matches.get_one::<String>("id").unwrap())?;
+                    println!("Removed: {}", record.id);
+                }
+                Some(("present", sub_matches)) => {
+                    let did = sub_matches.get_one::<String>("did").unwrap();
+                    let string = |name: &str| {
+                        sub_matches
+                            .get_one::<String>(name)
+                            .map(|string| string.as_str())
+                    };
+                    let presentation = match sub_matches.get_one::<String>("definition") {
+                        Some(path) => wallet.present_definition(&read_definition(path)?, did)?,
+                        None => {
+                            let ids: Vec<&str> = sub_matches
+                                .get_many::<String>("id")
+                                .unwrap()
+                                .map(|id| id.as_str())
+                                .collect();
+                            wallet.present(&ids, did)?
+                        }
+                    };
+                    let resolver = get_ion_resolver_from_config(&config);
+                    let presentation_with_proof =
+                        resolver
+                            .runtime
+                            .block_on(IONAttestor::new(did).sign_presentation(
+                                &presentation,
+                                string("key_id"),
+                                string("challenge"),
+                                string("domain"),
+                                &resolver,
+                            ))?;
+                    println!("{}", &to_string_pretty(&presentation_with_proof)?);
+                }
+                _ => panic!("Unrecognised wallet subcommand."),
+            }
+        }
+        Some(("oid4vc", sub_matches)) => match sub_matches.subcommand() {
+            Some(("serve", sub_matches)) => {
+                let base_url = sub_matches.get_one::<String>("base_url").unwrap();
+                let root_event_time = sub_matches
+                    .get_one::<u32>("root_event_time")
+                    .copied()
+                    .unwrap_or(ROOT_EVENT_TIME_2378493);
+                let allow_unpublished =
+                    matches!(sub_matches.get_one::<bool>("allow_unpublished"), Some(true));
+                let verifier =
+                    IONVerifier::from_config(config.clone()).allow_unpublished(allow_unpublished);
+                let mut service = OID4VCService::new(base_url, verifier, root_event_time);
+                if let Some(policy) = issuer_policy(sub_matches)? {
+                    service = service.with_issuer_policy(policy);
+                }
+
+                // Issue credentials from the template, offering one for each subject, if any.
+                let mut configuration_id = None;
+                if let (Some(did), Some(path)) = (
+                    sub_matches.get_one::<String>("did"),
+                    sub_matches.get_one::<String>("template"),
+                ) {
+                    let template: Credential = serde_json::from_reader(File::open(path)?)?;
+                    let id = Path::new(path)
+                        .file_stem()
+                        .and_then(|stem| stem.to_str())
+                        .ok_or("Invalid template file name.")?
+                        .to_string();
+                    service = service.with_issuer(
+                        IONAttestor::new(did),
+                        HashMap::from([(id.to_owned(), template)]),
+                    );
+                    configuration_id = Some(id);
+                }
+                let service = Arc::new(service);
+                if let (Some(id), Some(path)) =
+                    (&configuration_id, sub_matches.get_one::<String>("subjects"))
+                {
+                    let subjects = read_subjects(
+                        &std::fs::read_to_string(path)?,
+                        subject_format(sub_matches, path)?,
+                    )?;
+                    for record in subjects {
+                        match record.properties {
+                            Ok(properties) => {
+                                let offer = service.offer_credential(id, properties)?;
+                                println!(
+                                    "Offer (line {}): {}",
+                                    record.line,
+                                    service.credential_offer_uri(&offer)
+                                );
+                            }
+                            Err(e) => println!("Record (line {})... ❌ {}", record.line, e),
+                        }
+                    }
+                }
+                if let Some(path) = sub_matches.get_one::<String>("definition") {
+                    let pending = service.request_presentation(read_definition(path)?)?;
+                    println!(
+                        "Authorization request: {}",
+                        service.authorization_request_uri(&pending.request)
+                    );
+                    println!(
+                        "Result: {}/oid4vp/results/{}",
+                        service.base_url(),
+                        pending.result_code
+                    );
+                }
+
+                let listener =
+                    TcpListener::bind(sub_matches.get_one::<String>("address").unwrap())?;
+                let server = OID4VCServer::start(service, listener)?;
+                println!("Serving on {}...", server.addr());
+                server.wait();
+            }
+            _ => panic!("Unrecognised OID4VC subcommand."),
+        },
         _ => panic!("Unrecognised subcommand."),
     }
     Ok(())
@@ -0,0 +1,554 @@
+//! Configuration of Trustchain ION components.
+use crate::{MONGO_CONNECTION_STRING, SIDETREE_ENDPOINT};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use thiserror::Error;
+use trustchain_core::cache::{DEFAULT_CACHE_CAPACITY, DEFAULT_CACHE_TTL};
+use trustchain_core::TRUSTCHAIN_DATA;
+
+/// Environment variable holding the path of the Trustchain configuration file.
+pub const TRUSTCHAIN_CONFIG: &str = "TRUSTCHAIN_CONFIG";
+/// File name of the Trustchain configuration in the Trustchain data directory.
+pub const TRUSTCHAIN_CONFIG_FILE: &str = "trustchain_config.json";
+
+/// Environment variables overriding fields of the Trustchain configuration.
+pub const ENV_NETWORK: &str = "TRUSTCHAIN_ION_NETWORK";
+pub const ENV_DID_PREFIX: &str = "TRUSTCHAIN_DID_PREFIX";
+pub const ENV_MONGO_URI: &str = "TRUSTCHAIN_MONGO_URI";
+pub const ENV_MONGO_DATABASE: &str = "TRUSTCHAIN_MONGO_DATABASE";
+pub const ENV_BITCOIN_URL: &str = "TRUSTCHAIN_BITCOIN_URL";
+pub const ENV_BITCOIN_RPC_USERNAME: &str = "TRUSTCHAIN_BITCOIN_RPC_USERNAME";
+pub const ENV_BITCOIN_RPC_PASSWORD: &str = "TRUSTCHAIN_BITCOIN_RPC_PASSWORD";
+pub const ENV_BITCOIN_COOKIE_FILE: &str = "TRUSTCHAIN_BITCOIN_COOKIE_FILE";
+
+/// An error relating to Trustchain ION configuration.
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    /// Failed to read the configuration file.
+    #[error("Failed to read configuration file: {0}")]
+    FailedToRead(std::io::Error),
+    /// Failed to parse the configuration.
+    #[error("Failed to parse configuration: {0}")]
+    FailedToParse(serde_json::Error),
+    /// No Sidetree endpoints are configured.
+    #[error("No Sidetree endpoints are configured.")]
+    NoEndpoints,
+    /// Cross-checking requires at least two Sidetree endpoints.
+    #[error("Cross-checking requires at least two Sidetree endpoints.")]
+    InsufficientEndpointsForCrossCheck,
+    /// Unknown Bitcoin network.
+    #[error("Unknown network: {0}. Expected one of: mainnet, testnet, regtest.")]
+    InvalidNetwork(String),
+    /// Invalid DID prefix.
+    #[error("Invalid DID prefix: {0}. Expected a prefix of the form 'did:ion:' or 'did:ion:<network>:'.")]
+    InvalidDIDPrefix(String),
+    /// Bitcoin RPC username given without password or vice versa.
+    #[error("Bitcoin RPC username and password must be given together.")]
+    IncompleteBitcoinCredentials,
+}
+
+/// Bitcoin network on which ION DIDs are anchored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Network {
+    Mainnet,
+    #[default]
+    Testnet,
+    Regtest,
+}
+
+impl FromStr for Network {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mainnet" => Ok(Self::Mainnet),
+            "testnet" => Ok(Self::Testnet),
+            "regtest" => Ok(Self::Regtest),
+            _ => Err(ConfigError::InvalidNetwork(s.to_owned())),
+        }
+    }
+}
+
+impl Display for Network {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Mainnet => "mainnet",
+            Self::Testnet => "testnet",
+            Self::Regtest => "regtest",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Network {
+    /// Prefix of short-form ION DIDs on the network. ION nodes on test networks use the `test`
+    /// method-specific network identifier.
+    pub fn did_prefix(&self) -> &'static str {
+        match self {
+            Self::Mainnet => "did:ion:",
+            Self::Testnet | Self::Regtest => "did:ion:test:",
+        }
+    }
+
+    /// Name of the ION node's core MongoDB database on the network.
+    pub fn mongo_database(&self) -> String {
+        format!("ion-{}-core", self)
+    }
+
+    /// URL of the Bitcoin Core RPC interface on its default port for the network.
+    pub fn bitcoin_url(&self) -> &'static str {
+        match self {
+            Self::Mainnet => "http://localhost:8332",
+            Self::Testnet => "http://localhost:18332",
+            Self::Regtest => "http://localhost:18443",
+        }
+    }
+
+    /// Default location of the Bitcoin Core RPC cookie file for the network.
+    pub fn bitcoin_cookie_file(&self) -> PathBuf {
+        let data_dir = std::env::var_os("HOME")
+            .map(PathBuf::from)
+            .unwrap_or_default()
+            .join(".bitcoin");
+        match self {
+            Self::Mainnet => data_dir.join(".cookie"),
+            Self::Testnet => data_dir.join("testnet3").join(".cookie"),
+            Self::Regtest => data_dir.join("regtest").join(".cookie"),
+        }
+    }
+}
+
+/// Authentication with the Bitcoin Core RPC interface.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BitcoinAuth {
+    /// RPC username and password.
+    UserPass { username: String, password: String },
+    /// Cookie file written by Bitcoin Core.
+    CookieFile(PathBuf),
+}
+
+impl From<BitcoinAuth> for bitcoincore_rpc::Auth {
+    fn from(auth: BitcoinAuth) -> Self {
+        match auth {
+            BitcoinAuth::UserPass { username, password } => Self::UserPass(username, password),
+            BitcoinAuth::CookieFile(path) => Self::CookieFile(path),
+        }
+    }
+}
+
+/// Configuration of the ION node's MongoDB.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MongoConfig {
+    /// Connection string.
+    pub uri: String,
+    /// Core database name, if not the default for the network.
+    pub database: Option<String>,
+}
+
+impl Default for MongoConfig {
+    fn default() -> Self {
+        Self {
+            uri: MONGO_CONNECTION_STRING.to_string(),
+            database: None,
+        }
+    }
+}
+
+/// Configuration of the Bitcoin Core RPC interface.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BitcoinConfig {
+    /// RPC URL, if not the default for the network.
+    pub url: Option<String>,
+    /// RPC authentication, if not the default cookie file for the network.
+    pub auth: Option<BitcoinAuth>,
+}
+
+/// Configuration of the ION network, its MongoDB and Bitcoin Core node, and DID resolution.
+///
+/// Settings not given take defaults for the selected network: a local MongoDB with the
+/// `ion-<network>-core` database and a local Bitcoin Core node on the network's default RPC port,
+/// authenticated with its cookie file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrustchainConfig {
+    /// Bitcoin network on which ION DIDs are anchored.
+    pub network: Network,
+    /// Prefix of short-form ION DIDs, if not the default for the network.
+    pub did_prefix: Option<String>,
+    /// MongoDB configuration.
+    pub mongo: MongoConfig,
+    /// Bitcoin Core configuration.
+    pub bitcoin: BitcoinConfig,
+    /// DID resolution configuration.
+    pub resolver: ResolverConfig,
+}
+
+impl TrustchainConfig {
+    /// Reads a configuration from a JSON file, with defaults for any missing fields.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let file = File::open(path).map_err(ConfigError::FailedToRead)?;
+        let config: Self = serde_json::from_reader(file).map_err(ConfigError::FailedToParse)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Loads the configuration from the given file, or else the file at the path in the
+    /// `TRUSTCHAIN_CONFIG` environment variable, or else `trustchain_config.json` in the
+    /// Trustchain data directory if present, with defaults otherwise. Settings in the
+    /// environment (e.g. `TRUSTCHAIN_ION_NETWORK`) take precedence over those in the file.
+    pub fn load(path: Option<&str>) -> Result<Self, ConfigError> {
+        let path = path
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os(TRUSTCHAIN_CONFIG).map(PathBuf::from))
+            .or_else(|| {
+                std::env::var_os(TRUSTCHAIN_DATA)
+                    .map(|data| Path::new(&data).join(TRUSTCHAIN_CONFIG_FILE))
+                    .filter(|path| path.exists())
+            });
+        let mut config = match path {
+            Some(path) => Self::from_file(path)?,
+            None => Self::default(),
+        };
+        config.apply_env(|key| std::env::var(key).ok())?;
+        Ok(config)
+    }
+
+    /// Overrides settings with values of the configuration environment variables, as read by
+    /// the given function.
+    pub fn apply_env<F>(&mut self, var: F) -> Result<(), ConfigError>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        if let Some(network) = var(ENV_NETWORK) {
+            self.network = network.parse()?;
+        }
+        if let Some(did_prefix) = var(ENV_DID_PREFIX) {
+            self.did_prefix = Some(did_prefix);
+        }
+        if let Some(uri) = var(ENV_MONGO_URI) {
+            self.mongo.uri = uri;
+        }
+        if let Some(database) = var(ENV_MONGO_DATABASE) {
+            self.mongo.database = Some(database);
+        }
+        if let Some(url) = var(ENV_BITCOIN_URL) {
+            self.bitcoin.url = Some(url);
+        }
+        match (var(ENV_BITCOIN_RPC_USERNAME), var(ENV_BITCOIN_RPC_PASSWORD)) {
+            (Some(username), Some(password)) => {
+                self.bitcoin.auth = Some(BitcoinAuth::UserPass { username, password })
+            }
+            (None, None) => (),
+            _ => return Err(ConfigError::IncompleteBitcoinCredentials),
+        }
+        if let Some(path) = var(ENV_BITCOIN_COOKIE_FILE) {
+            self.bitcoin.auth = Some(BitcoinAuth::CookieFile(PathBuf::from(path)));
+        }
+        self.validate()
+    }
+
+    /// Checks that the configuration can be used.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let did_prefix = self.did_prefix();
+        if !did_prefix.starts_with("did:ion:")
+            || !did_prefix.ends_with(':')
+            || did_prefix.matches(':').count() > 3
+        {
+            return Err(ConfigError::InvalidDIDPrefix(did_prefix.to_owned()));
+        }
+        self.resolver.validate()
+    }
+
+    /// Prefix of short-form ION DIDs.
+    pub fn did_prefix(&self) -> &str {
+        self.did_prefix
+            .as_deref()
+            .unwrap_or_else(|| self.network.did_prefix())
+    }
+
+    /// Returns the suffix of a short-form ION DID with the configured prefix.
+    pub fn did_suffix<'a>(&self, did: &'a str) -> Option<&'a str> {
+        did.strip_prefix(self.did_prefix())
+            .filter(|suffix| !suffix.is_empty() && !suffix.contains(':'))
+    }
+
+    /// Makes the short-form ION DID with the given suffix.
+    pub fn did(&self, suffix: &str) -> String {
+        format!("{}{}", self.did_prefix(), suffix)
+    }
+
+    /// Name of the ION node's core MongoDB database.
+    pub fn mongo_database(&self) -> String {
+        self.mongo
+            .database
+            .clone()
+            .unwrap_or_else(|| self.network.mongo_database())
+    }
+
+    /// URL of the Bitcoin Core RPC interface.
+    pub fn bitcoin_url(&self) -> &str {
+        self.bitcoin
+            .url
+            .as_deref()
+            .unwrap_or_else(|| self.network.bitcoin_url())
+    }
+
+    /// Authentication with the Bitcoin Core RPC interface.
+    pub fn bitcoin_auth(&self) -> BitcoinAuth {
+        self.bitcoin
+            .auth
+            .clone()
+            .unwrap_or_else(|| BitcoinAuth::CookieFile(self.network.bitcoin_cookie_file()))
+    }
+}
+
+/// Cache of ION DID resolutions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheBackend {
+    /// No caching: every resolution queries the Sidetree nodes.
+    #[default]
+    None,
+    /// In-memory cache, for the lifetime of the resolver.
+    Memory,
+    /// SQLite cache in the Trustchain data directory, persisting between invocations.
+    Sqlite,
+}
+
+/// Configuration of DID resolution from one or more Sidetree nodes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResolverConfig {
+    /// Sidetree node endpoints in order of preference.
+    pub endpoints: Vec<String>,
+    /// Timeout (in milliseconds) for each request to a Sidetree node.
+    pub timeout_ms: u64,
+    /// Number of retries per endpoint after a failed request.
+    pub retries: u32,
+    /// Delay (in milliseconds) before the first retry, doubled on each subsequent retry.
+    pub backoff_ms: u64,
+    /// Whether to require that two nodes return identical document state.
+    pub cross_check: bool,
+    /// Cache of resolutions.
+    pub cache: CacheBackend,
+    /// Time to live (in seconds) of cached resolutions.
+    pub cache_ttl_secs: u64,
+    /// Capacity of the in-memory cache.
+    pub cache_capacity: usize,
+}
+
+impl Default for ResolverConfig {
+    fn default() -> Self {
+        Self {
+            endpoints: vec![SIDETREE_ENDPOINT.to_string()],
+            timeout_ms: 10_000,
+            retries: 2,
+            backoff_ms: 500,
+            cross_check: false,
+            cache: CacheBackend::None,
+            cache_ttl_secs: DEFAULT_CACHE_TTL.as_secs(),
+            cache_capacity: DEFAULT_CACHE_CAPACITY,
+        }
+    }
+}
+
+impl ResolverConfig {
+    /// Makes a default configuration with a single Sidetree endpoint.
+    pub fn from_endpoint(endpoint: &str) -> Self {
+        Self {
+            endpoints: vec![endpoint.to_string()],
+            ..Self::default()
+        }
+    }
+
+    /// Reads a configuration from a JSON file, with defaults for any missing fields.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let file = File::open(path).map_err(ConfigError::FailedToRead)?;
+        let config: Self = serde_json::from_reader(file).map_err(ConfigError::FailedToParse)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks that the configuration can be used for resolution.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.endpoints.is_empty() {
+            return Err(ConfigError::NoEndpoints);
+        }
+        if self.cross_check && self.endpoints.len() < 2 {
+            return Err(ConfigError::InsufficientEndpointsForCrossCheck);
+        }
+        Ok(())
+    }
+
+    /// Timeout for each request to a Sidetree node.
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+
+    /// Delay before the given retry (starting from zero) with exponential backoff.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        Duration::from_millis(self.backoff_ms.saturating_mul(1 << retry.min(16)))
+    }
+
+    /// Time to live of cached resolutions.
+    pub fn cache_ttl(&self) -> Duration {
+        Duration::from_secs(self.cache_ttl_secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialize_with_defaults() {
+        let config: ResolverConfig = serde_json::from_str(
+            r##"{"endpoints": ["http://node-a:3000/", "http://node-b:3000/"], "cross_check": true}"##,
+        )
+        .unwrap();
+        assert_eq!(config.endpoints.len(), 2);
+        assert!(config.cross_check);
+        assert_eq!(config.timeout_ms, ResolverConfig::default().timeout_ms);
+        assert_eq!(config.retries, ResolverConfig::default().retries);
+        assert_eq!(config.cache, CacheBackend::None);
+        assert!(config.validate().is_ok());
+
+        let config: ResolverConfig =
+            serde_json::from_str(r##"{"cache": "sqlite", "cache_ttl_secs": 60}"##).unwrap();
+        assert_eq!(config.cache, CacheBackend::Sqlite);
+        assert_eq!(config.cache_ttl(), Duration::from_secs(60));
+        assert_eq!(config.cache_capacity, DEFAULT_CACHE_CAPACITY);
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut config = ResolverConfig::default();
+        assert!(config.validate().is_ok());
+        config.cross_check = true;
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::InsufficientEndpointsForCrossCheck)
+        ));
+        config.endpoints.clear();
+        assert!(matches!(config.validate(), Err(ConfigError::NoEndpoints)));
+    }
+
+    #[test]
+    fn test_network_defaults() {
+        let config = TrustchainConfig::default();
+        assert_eq!(config.network, Network::Testnet);
+        assert_eq!(config.did_prefix(), "did:ion:test:");
+        assert_eq!(config.mongo_database(), "ion-testnet-core");
+        assert_eq!(config.bitcoin_url(), "http://localhost:18332");
+
+        let config: TrustchainConfig = serde_json::from_str(
+            r##"{"network": "mainnet", "bitcoin": {"auth": {"user_pass": {"username": "user", "password": "pass"}}}}"##,
+        )
+        .unwrap();
+        assert_eq!(config.did_prefix(), "did:ion:");
+        assert_eq!(config.mongo_database(), "ion-mainnet-core");
+        assert_eq!(config.bitcoin_url(), "http://localhost:8332");
+        assert_eq!(
+            config.bitcoin_auth(),
+            BitcoinAuth::UserPass {
+                username: "user".to_string(),
+                password: "pass".to_string()
+            }
+        );
+        assert_eq!(config.resolver, ResolverConfig::default());
+
+        let config: TrustchainConfig = serde_json::from_str(
+            r##"{"network": "regtest", "mongo": {"database": "ion-local-core"}, "bitcoin": {"auth": {"cookie_file": "/tmp/.cookie"}}}"##,
+        )
+        .unwrap();
+        assert_eq!(config.did_prefix(), "did:ion:test:");
+        assert_eq!(config.mongo_database(), "ion-local-core");
+        assert_eq!(config.bitcoin_url(), "http://localhost:18443");
+        assert_eq!(
+            config.bitcoin_auth(),
+            BitcoinAuth::CookieFile(PathBuf::from("/tmp/.cookie"))
+        );
+    }
+
+    #[test]
+    fn test_apply_env() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |key: &str| {
+                vars.iter()
+                    .find(|(k, _)| *k == key)
+                    .map(|(_, v)| v.to_string())
+            }
+        };
+        let mut config = TrustchainConfig::default();
+        config
+            .apply_env(env(&[
+                (ENV_NETWORK, "mainnet"),
+                (ENV_MONGO_URI, "mongodb://mongo:27017/"),
+                (ENV_BITCOIN_URL, "http://bitcoin:8332"),
+                (ENV_BITCOIN_RPC_USERNAME, "user"),
+                (ENV_BITCOIN_RPC_PASSWORD, "pass"),
+            ]))
+            .unwrap();
+        assert_eq!(config.network, Network::Mainnet);
+        assert_eq!(config.mongo.uri, "mongodb://mongo:27017/");
+        assert_eq!(config.bitcoin_url(), "http://bitcoin:8332");
+        assert!(matches!(
+            config.bitcoin_auth(),
+            BitcoinAuth::UserPass { .. }
+        ));
+
+        let mut config = TrustchainConfig::default();
+        assert!(matches!(
+            config.apply_env(env(&[(ENV_BITCOIN_RPC_USERNAME, "user")])),
+            Err(ConfigError::IncompleteBitcoinCredentials)
+        ));
+        assert!(matches!(
+            config.apply_env(env(&[(ENV_NETWORK, "signet")])),
+            Err(ConfigError::InvalidNetwork(_))
+        ));
+        assert!(matches!(
+            config.apply_env(env(&[(ENV_DID_PREFIX, "did:web:")])),
+            Err(ConfigError::InvalidDIDPrefix(_))
+        ));
+    }
+
+    #[test]
+    fn test_did_suffix() {
+        let config = TrustchainConfig::default();
+        let suffix = "EiCClfEdkTv_aM3UnBBhlOV89LlGhpQAbfeZLFdFxVFkEg";
+        let did = config.did(suffix);
+        assert_eq!(did, format!("did:ion:test:{}", suffix));
+        assert_eq!(config.did_suffix(&did), Some(suffix));
+        assert_eq!(config.did_suffix(&format!("did:ion:{}", suffix)), None);
+        assert_eq!(config.did_suffix(&format!("{}:longformdata", did)), None);
+
+        let config = TrustchainConfig {
+            network: Network::Mainnet,
+            ..Default::default()
+        };
+        assert_eq!(
+            config.did_suffix(&format!("did:ion:{}", suffix)),
+            Some(suffix)
+        );
+        assert_eq!(config.did_suffix(&did), None);
+    }
+
+    #[test]
+    fn test_backoff() {
+        let config = ResolverConfig {
+            backoff_ms: 100,
+            ..ResolverConfig::default()
+        };
+        assert_eq!(config.backoff(0), Duration::from_millis(100));
+        assert_eq!(config.backoff(1), Duration::from_millis(200));
+        assert_eq!(config.backoff(3), Duration::from_millis(800));
+    }
+}
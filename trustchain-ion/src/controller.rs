@@ -8,6 +8,7 @@ use ssi::did_resolve::{DocumentMetadata, Metadata};
 use ssi::jwk::JWK;
 use std::convert::TryFrom;
 use trustchain_core::attestor::Attestor;
+use trustchain_core::constraints::DelegationConstraints;
 use trustchain_core::controller::Controller;
 use trustchain_core::key_manager::{ControllerKeyManager, KeyManager, KeyManagerError, KeyType};
 use trustchain_core::subject::Subject;
@@ -133,11 +134,23 @@ impl IONController {
         }
     }
 
-    /// Returns a patch for adding a proof service.
-    pub fn add_proof_service(&self, did: &str, proof: &str) -> DIDStatePatch {
+    /// Returns a patch for adding a proof service, including any delegation constraints
+    /// covered by the proof.
+    pub fn add_proof_service(
+        &self,
+        did: &str,
+        proof: &str,
+        constraints: Option<&DelegationConstraints>,
+    ) -> DIDStatePatch {
         let mut obj: Map<String, Value> = Map::new();
         obj.insert("controller".to_string(), Value::from(did));
         obj.insert("proofValue".to_string(), Value::from(proof.to_owned()));
+        if let Some(constraints) = constraints.filter(|constraints| !constraints.is_empty()) {
+            obj.insert(
+                "constraints".to_string(),
+                serde_json::to_value(constraints).unwrap(),
+            );
+        }
 
         DIDStatePatch::AddServices {
             services: vec![ServiceEndpointEntry {
@@ -361,7 +374,27 @@ mod tests {
         let controlled_did = "did:example:controlled_add_proof_service";
         let controller = test_controller(did, controlled_did)?;
         let proof = "test_proof_information";
-        let _ = controller.add_proof_service(controlled_did, proof);
+        let _ = controller.add_proof_service(controlled_did, proof, None);
+
+        // Check constraints are added to the service endpoint.
+        let constraints = DelegationConstraints {
+            can_attest: Some(false),
+            ..Default::default()
+        };
+        let patch = controller.add_proof_service(controlled_did, proof, Some(&constraints));
+        if let DIDStatePatch::AddServices { services } = patch {
+            match &services[0].service_endpoint {
+                ServiceEndpoint::Map(value) => {
+                    assert_eq!(
+                        value["constraints"],
+                        serde_json::json!({"canAttest": false})
+                    )
+                }
+                _ => panic!(),
+            }
+        } else {
+            panic!()
+        }
         Ok(())
     }
 }
@@ -1,4 +1,5 @@
 use crate::attestor::{AttestorData, IONAttestor};
+use crate::config::TrustchainConfig;
 use crate::controller::{ControllerData, IONController};
 use did_ion::sidetree::DIDStatePatch;
 use did_ion::sidetree::{DocumentState, PublicKeyEntry, PublicKeyJwk};
@@ -11,8 +12,10 @@ use std::convert::TryFrom;
 use trustchain_core::utils::{generate_key, get_operations_path};
 
 /// Makes a new DID subject to be controlled with correspondong create operation written to file.
+/// The DID takes the prefix of the configured ION network.
 pub fn create_operation(
     document_state: Option<DocumentState>,
+    config: &TrustchainConfig,
     verbose: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
     // Generate random keys
@@ -79,10 +82,15 @@ pub fn create_operation(
         ION::serialize_suffix_data(&create_operation.clone().unwrap().suffix_data)
             .unwrap()
             .to_string();
-    let controlled_did_long = SidetreeDID::<ION>::from_create_operation(&create_operation.unwrap())
+    let long_form_did = SidetreeDID::<ION>::from_create_operation(&create_operation.unwrap())
         .unwrap()
         .to_string();
-    let controlled_did = controlled_did_long.rsplit_once(':').unwrap().0;
+    let controlled_did = &config.did(&controlled_did_suffix);
+    let controlled_did_long = format!(
+        "{}:{}",
+        controlled_did,
+        long_form_did.rsplit_once(':').unwrap().1
+    );
     if verbose {
         println!("Controlled DID suffix: {:?}", controlled_did_suffix);
         println!("Controlled DID (short-form): {:?}", controlled_did);
@@ -159,11 +167,12 @@ mod test {
         init();
 
         // 1. Run create with no document state passed
-        create_operation(None, false)?;
+        let config = TrustchainConfig::default();
+        create_operation(None, &config, false)?;
 
         // 2. Run create with a document state passed
         let doc_state: DocumentState = serde_json::from_reader(TEST_DOC_STATE.as_bytes())?;
-        create_operation(Some(doc_state), false)?;
+        create_operation(Some(doc_state), &config, false)?;
 
         // Try to read outputted create operations and  check they deserialize
         let path = get_operations_path()?;
@@ -0,0 +1,389 @@
+//! DID resolution from multiple Sidetree nodes with timeouts, retries and failover.
+use crate::config::ResolverConfig;
+use async_trait::async_trait;
+use did_ion::sidetree::HTTPSidetreeDIDResolver;
+use did_ion::ION;
+use ssi::did::Document;
+use ssi::did_resolve::{
+    DIDResolver, DocumentMetadata, ResolutionInputMetadata, ResolutionMetadata,
+};
+use trustchain_core::resolver::ERROR_CROSS_CHECK_FAILED;
+
+/// Resolution metadata, document and document metadata.
+type Resolution = (
+    ResolutionMetadata,
+    Option<Document>,
+    Option<DocumentMetadata>,
+);
+
+/// Prefixes of resolution errors arising from transport failures, after which the request is
+/// retried and the next endpoint tried.
+const TRANSIENT_ERROR_PREFIXES: [&str; 3] = [
+    "Error sending HTTP request",
+    "Error reading HTTP response",
+    "Error parsing resolution response",
+];
+
+/// Returns whether a resolution failed for reasons unrelated to the state of the DID.
+fn is_transient(resolution: &Resolution) -> bool {
+    match &resolution.0.error {
+        Some(error) => TRANSIENT_ERROR_PREFIXES
+            .iter()
+            .any(|prefix| error.starts_with(prefix)),
+        None => false,
+    }
+}
+
+/// Returns whether two resolutions have identical document state.
+fn same_state(a: &Resolution, b: &Resolution) -> bool {
+    match (
+        serde_jcs::to_string(&(&a.1, &a.2)),
+        serde_jcs::to_string(&(&b.1, &b.2)),
+    ) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// A resolver querying an ordered list of Sidetree nodes as specified by a [`ResolverConfig`].
+///
+/// Each request is subject to a timeout and retried with exponential backoff on transport
+/// failures before failing over to the next node. If cross-checking is enabled, the document
+/// state is also resolved from a second node and the resolution fails with
+/// [`ERROR_CROSS_CHECK_FAILED`] unless both are identical.
+pub struct FailoverResolver<T: DIDResolver> {
+    /// Endpoints paired with the resolvers querying them, in order of preference.
+    resolvers: Vec<(String, T)>,
+    config: ResolverConfig,
+}
+
+impl<T: DIDResolver> FailoverResolver<T> {
+    /// Makes a new failover resolver from endpoints paired with their resolvers.
+    pub fn new(resolvers: Vec<(String, T)>, config: ResolverConfig) -> Self {
+        Self { resolvers, config }
+    }
+
+    /// Endpoints queried by the resolver, in order of preference.
+    pub fn endpoints(&self) -> Vec<&str> {
+        self.resolvers
+            .iter()
+            .map(|(endpoint, _)| endpoint.as_str())
+            .collect()
+    }
+
+    /// Resolves a DID from the endpoint at the given index, retrying on transport failures.
+    async fn resolve_from(
+        &self,
+        idx: usize,
+        did: &str,
+        input_metadata: &ResolutionInputMetadata,
+    ) -> Resolution {
+        let (endpoint, resolver) = &self.resolvers[idx];
+        let mut retry = 0;
+        loop {
+            let resolution = match tokio::time::timeout(
+                self.config.timeout(),
+                resolver.resolve(did, input_metadata),
+            )
+            .await
+            {
+                Ok(resolution) => resolution,
+                // Reported in the same form as a transport failure from the HTTP resolver.
+                Err(_) => (
+                    ResolutionMetadata::from_error(&format!(
+                        "Error sending HTTP request: error sending request for url ({}): operation timed out",
+                        endpoint
+                    )),
+                    None,
+                    None,
+                ),
+            };
+            if !is_transient(&resolution) || retry >= self.config.retries {
+                return resolution;
+            }
+            tokio::time::sleep(self.config.backoff(retry)).await;
+            retry += 1;
+        }
+    }
+}
+
+impl FailoverResolver<HTTPSidetreeDIDResolver<ION>> {
+    /// Makes a failover resolver querying the ION nodes at the configured endpoints.
+    pub fn from_config(config: &ResolverConfig) -> Self {
+        let resolvers = config
+            .endpoints
+            .iter()
+            .map(|endpoint| {
+                (
+                    endpoint.to_string(),
+                    HTTPSidetreeDIDResolver::<ION>::new(endpoint),
+                )
+            })
+            .collect();
+        Self::new(resolvers, config.clone())
+    }
+}
+
+#[async_trait]
+impl<T: DIDResolver + Sync + Send> DIDResolver for FailoverResolver<T> {
+    async fn resolve(&self, did: &str, input_metadata: &ResolutionInputMetadata) -> Resolution {
+        // Resolve from the first endpoint that does not fail in transport.
+        let mut primary: Option<(usize, Resolution)> = None;
+        let mut last_failure: Option<Resolution> = None;
+        for idx in 0..self.resolvers.len() {
+            let resolution = self.resolve_from(idx, did, input_metadata).await;
+            if is_transient(&resolution) {
+                last_failure = Some(resolution);
+            } else {
+                primary = Some((idx, resolution));
+                break;
+            }
+        }
+        let (primary_idx, resolution) = match primary {
+            Some(primary) => primary,
+            None => {
+                return last_failure.unwrap_or_else(|| {
+                    (
+                        ResolutionMetadata::from_error(
+                            "Error sending HTTP request: error sending request for url: no endpoints configured",
+                        ),
+                        None,
+                        None,
+                    )
+                })
+            }
+        };
+        if !self.config.cross_check || resolution.0.error.is_some() {
+            return resolution;
+        }
+
+        // Cross-check the document state against the next endpoint that does not fail in
+        // transport.
+        for idx in primary_idx + 1..self.resolvers.len() {
+            let other = self.resolve_from(idx, did, input_metadata).await;
+            if is_transient(&other) {
+                continue;
+            }
+            if other.0.error.is_none() && same_state(&resolution, &other) {
+                return resolution;
+            }
+            eprintln!(
+                "Resolution of {} from {} differs from {}.",
+                did, self.resolvers[idx].0, self.resolvers[primary_idx].0
+            );
+            break;
+        }
+        (
+            ResolutionMetadata::from_error(ERROR_CROSS_CHECK_FAILED),
+            None,
+            None,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ssi::did_resolve::ERROR_NOT_FOUND;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    const TEST_DID: &str = "did:ion:test:EiCClfEdkTv_aM3UnBBhlOV89LlGhpQAbfeZLFdFxVFkEg";
+
+    enum Behaviour {
+        /// Returns a document with the given service endpoint.
+        Document(&'static str),
+        NotFound,
+        Unreachable,
+        /// Fails in transport for the given number of calls before returning a document.
+        FailThenDocument(usize),
+        Slow,
+    }
+
+    struct MockResolver {
+        behaviour: Behaviour,
+        calls: AtomicUsize,
+    }
+
+    impl MockResolver {
+        fn new(behaviour: Behaviour) -> Self {
+            Self {
+                behaviour,
+                calls: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    fn document(endpoint: &str) -> Resolution {
+        let doc = Document::from_json(&format!(
+            r##"{{
+                "@context": "https://www.w3.org/ns/did/v1",
+                "id": "{}",
+                "service": [{{"id": "#example", "type": "Example", "serviceEndpoint": "{}"}}]
+            }}"##,
+            TEST_DID, endpoint
+        ))
+        .unwrap();
+        (
+            ResolutionMetadata::default(),
+            Some(doc),
+            Some(DocumentMetadata::default()),
+        )
+    }
+
+    fn unreachable() -> Resolution {
+        (
+            ResolutionMetadata::from_error(
+                "Error sending HTTP request: error sending request for url (http://mock/): connection refused",
+            ),
+            None,
+            None,
+        )
+    }
+
+    #[async_trait]
+    impl DIDResolver for MockResolver {
+        async fn resolve(&self, _: &str, _: &ResolutionInputMetadata) -> Resolution {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            match self.behaviour {
+                Behaviour::Document(endpoint) => document(endpoint),
+                Behaviour::NotFound => {
+                    (ResolutionMetadata::from_error(ERROR_NOT_FOUND), None, None)
+                }
+                Behaviour::Unreachable => unreachable(),
+                Behaviour::FailThenDocument(failures) if call < failures => unreachable(),
+                Behaviour::FailThenDocument(_) => document("https://example.com"),
+                Behaviour::Slow => {
+                    tokio::time::sleep(Duration::from_secs(10)).await;
+                    document("https://example.com")
+                }
+            }
+        }
+    }
+
+    fn failover(
+        behaviours: Vec<Behaviour>,
+        config: ResolverConfig,
+    ) -> FailoverResolver<MockResolver> {
+        let resolvers = behaviours
+            .into_iter()
+            .enumerate()
+            .map(|(idx, behaviour)| {
+                (
+                    format!("http://node-{}/", idx),
+                    MockResolver::new(behaviour),
+                )
+            })
+            .collect();
+        FailoverResolver::new(resolvers, config)
+    }
+
+    fn test_config() -> ResolverConfig {
+        ResolverConfig {
+            timeout_ms: 50,
+            retries: 2,
+            backoff_ms: 1,
+            ..ResolverConfig::default()
+        }
+    }
+
+    fn calls(resolver: &FailoverResolver<MockResolver>, idx: usize) -> usize {
+        resolver.resolvers[idx].1.calls.load(Ordering::SeqCst)
+    }
+
+    #[tokio::test]
+    async fn test_failover() {
+        let resolver = failover(
+            vec![
+                Behaviour::Unreachable,
+                Behaviour::Document("https://example.com"),
+            ],
+            test_config(),
+        );
+        let (res_meta, doc, _) = resolver.resolve(TEST_DID, &Default::default()).await;
+        assert!(res_meta.error.is_none());
+        assert!(doc.is_some());
+        // Initial request and two retries to the unreachable node.
+        assert_eq!(calls(&resolver, 0), 3);
+        assert_eq!(calls(&resolver, 1), 1);
+    }
+
+    #[tokio::test]
+    async fn test_retry() {
+        let resolver = failover(vec![Behaviour::FailThenDocument(2)], test_config());
+        let (res_meta, doc, _) = resolver.resolve(TEST_DID, &Default::default()).await;
+        assert!(res_meta.error.is_none());
+        assert!(doc.is_some());
+        assert_eq!(calls(&resolver, 0), 3);
+    }
+
+    #[tokio::test]
+    async fn test_timeout() {
+        let resolver = failover(vec![Behaviour::Slow, Behaviour::Slow], test_config());
+        let (res_meta, doc, _) = resolver.resolve(TEST_DID, &Default::default()).await;
+        assert!(doc.is_none());
+        let error = res_meta.error.unwrap();
+        assert!(error.starts_with("Error sending HTTP request: error sending request for url"));
+        assert!(error.contains("http://node-1/"));
+    }
+
+    #[tokio::test]
+    async fn test_not_found_is_definitive() {
+        let resolver = failover(
+            vec![
+                Behaviour::NotFound,
+                Behaviour::Document("https://example.com"),
+            ],
+            test_config(),
+        );
+        let (res_meta, _, _) = resolver.resolve(TEST_DID, &Default::default()).await;
+        assert_eq!(res_meta.error, Some(ERROR_NOT_FOUND.to_string()));
+        assert_eq!(calls(&resolver, 0), 1);
+        assert_eq!(calls(&resolver, 1), 0);
+    }
+
+    #[tokio::test]
+    async fn test_cross_check() {
+        let config = ResolverConfig {
+            cross_check: true,
+            ..test_config()
+        };
+
+        // Identical state from the first two reachable nodes.
+        let resolver = failover(
+            vec![
+                Behaviour::Document("https://example.com"),
+                Behaviour::Unreachable,
+                Behaviour::Document("https://example.com"),
+            ],
+            config.clone(),
+        );
+        let (res_meta, doc, _) = resolver.resolve(TEST_DID, &Default::default()).await;
+        assert!(res_meta.error.is_none());
+        assert!(doc.is_some());
+        assert_eq!(calls(&resolver, 2), 1);
+
+        // Differing state.
+        let resolver = failover(
+            vec![
+                Behaviour::Document("https://example.com"),
+                Behaviour::Document("https://attacker.com"),
+            ],
+            config.clone(),
+        );
+        let (res_meta, doc, _) = resolver.resolve(TEST_DID, &Default::default()).await;
+        assert_eq!(res_meta.error, Some(ERROR_CROSS_CHECK_FAILED.to_string()));
+        assert!(doc.is_none());
+
+        // No second node to cross-check against.
+        let resolver = failover(
+            vec![
+                Behaviour::Document("https://example.com"),
+                Behaviour::Unreachable,
+            ],
+            config,
+        );
+        let (res_meta, _, _) = resolver.resolve(TEST_DID, &Default::default()).await;
+        assert_eq!(res_meta.error, Some(ERROR_CROSS_CHECK_FAILED.to_string()));
+    }
+}
@@ -1,32 +1,103 @@
 pub mod attest;
 pub mod attestor;
+pub mod config;
 pub mod controller;
+pub mod failover;
+pub mod longform;
+pub mod oid4vc;
+pub mod replay;
 pub mod verifier;
 
+// Sidetree
+pub const SIDETREE_ENDPOINT: &str = "http://localhost:3000/";
+
 // MongoDB
 pub const MONGO_CONNECTION_STRING: &str = "mongodb://localhost:27017/";
-pub const MONGO_DATABASE_ION_TESTNET_CORE: &str = "ion-testnet-core";
 pub const MONGO_COLLECTION_OPERATIONS: &str = "operations";
 pub const MONGO_FILTER_TYPE: &str = "type";
 pub const MONGO_CREATE_OPERATION: &str = "create";
+pub const MONGO_UPDATE_OPERATION: &str = "update";
+pub const MONGO_RECOVER_OPERATION: &str = "recover";
 pub const MONGO_FILTER_DID_SUFFIX: &str = "didSuffix";
-
-// Bitcoin (TESTNET PORT: 18332!)
-pub const BITCOIN_CONNECTION_STRING: &str = "http://localhost:18332";
-pub const BITCOIN_RPC_USERNAME: &str = "admin";
-pub const BITCOIN_RPC_PASSWORD: &str = "lWrkJlpj8SbnNRUJfO6qwIFEWkD+I9kL4REsFyMBlow=";
+pub const MONGO_FILTER_TXN_NUMBER: &str = "txnNumber";
+pub const MONGO_FILTER_TXN_TIME: &str = "txnTime";
+pub const MONGO_FILTER_OP_INDEX: &str = "opIndex";
+pub const MONGO_OPERATION_BUFFER: &str = "operationBufferBsonBinary";
 pub mod create;
 pub mod resolve;
-use did_ion::{sidetree::SidetreeClient, ION};
+use config::{CacheBackend, ResolverConfig, TrustchainConfig};
+use did_ion::{sidetree::HTTPSidetreeDIDResolver, ION};
+use failover::FailoverResolver;
+use longform::LongFormResolver;
+use replay::ReplayResolver;
 use thiserror::Error;
-use trustchain_core::resolver::{DIDMethodWrapper, Resolver};
+use trustchain_core::cache::{CachingResolver, MemoryCache, SqliteCache};
+use trustchain_core::dispatch::DispatchResolver;
+use trustchain_core::methods::{DIDKeyResolver, DIDWebResolver};
+use trustchain_core::resolver::Resolver;
 
 /// Type alias
-pub type IONResolver = Resolver<DIDMethodWrapper<SidetreeClient<ION>>>;
+pub type IONResolver = Resolver<DispatchResolver>;
+
+/// Type of the resolver used for did:ion DIDs.
+pub type IONMethodResolver =
+    LongFormResolver<ReplayResolver<FailoverResolver<HTTPSidetreeDIDResolver<ION>>>>;
 
 /// Test resolver
 pub fn get_ion_resolver(endpoint: &str) -> IONResolver {
-    IONResolver::from(SidetreeClient::<ION>::new(Some(String::from(endpoint))))
+    get_ion_resolver_with_config(&ResolverConfig::from_endpoint(endpoint))
+}
+
+/// Makes a Trustchain resolver for did:ion DIDs, querying the Sidetree nodes given in the
+/// resolver config, and for did:key and did:web DIDs so that chains may mix methods.
+pub fn get_ion_resolver_with_config(config: &ResolverConfig) -> IONResolver {
+    get_ion_resolver_from_config(&TrustchainConfig {
+        resolver: config.clone(),
+        ..TrustchainConfig::default()
+    })
+}
+
+/// Makes a Trustchain resolver as for [`get_ion_resolver_with_config`], replaying historical
+/// ION DID state from the MongoDB and Bitcoin Core node in the Trustchain config and caching
+/// ION DID resolutions if a cache is configured.
+pub fn get_ion_resolver_from_config(config: &TrustchainConfig) -> IONResolver {
+    let ion_resolver = IONMethodResolver::new(ReplayResolver::with_config(
+        FailoverResolver::from_config(&config.resolver),
+        config.clone(),
+    ));
+    let dispatch_resolver = match config.resolver.cache {
+        CacheBackend::None => DispatchResolver::new().with_method("ion", ion_resolver),
+        CacheBackend::Memory => DispatchResolver::new().with_method(
+            "ion",
+            CachingResolver::new(
+                ion_resolver,
+                MemoryCache::new(config.resolver.cache_capacity),
+            )
+            .with_ttl(config.resolver.cache_ttl()),
+        ),
+        CacheBackend::Sqlite => {
+            // Fall back to an in-memory cache if the cache database cannot be opened.
+            let caching_resolver = match SqliteCache::open_default() {
+                Ok(cache) => CachingResolver::new(ion_resolver, cache),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    CachingResolver::new(
+                        ion_resolver,
+                        MemoryCache::new(config.resolver.cache_capacity),
+                    )
+                }
+            };
+            DispatchResolver::new().with_method(
+                "ion",
+                caching_resolver.with_ttl(config.resolver.cache_ttl()),
+            )
+        }
+    };
+    IONResolver::new(
+        dispatch_resolver
+            .with_method("key", DIDKeyResolver)
+            .with_method("web", DIDWebResolver::new()),
+    )
 }
 
 /// An error relating for rustchain-ion crate.
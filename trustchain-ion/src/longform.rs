@@ -0,0 +1,288 @@
+//! Local resolution of unpublished long-form ION DIDs.
+use crate::replay::{AnchoredOperation, DIDState};
+use async_trait::async_trait;
+use did_ion::sidetree::{CreateOperation, Operation, SidetreeDID};
+use did_ion::ION;
+use ssi::did::Document;
+use ssi::did_resolve::{
+    DIDResolver, DocumentMetadata, Metadata, ResolutionInputMetadata, ResolutionMetadata,
+    ERROR_INVALID_DID, ERROR_NOT_FOUND,
+};
+use std::str::FromStr;
+use trustchain_core::utils::{get_did_suffix, get_operations_path};
+
+/// Decodes a long-form ION DID into its short form and the create operation embedded in it.
+/// Returns `None` if the DID is not a valid long-form ION DID.
+pub fn decode_long_form(did: &str) -> Option<(String, CreateOperation)> {
+    match SidetreeDID::<ION>::from_str(did).ok()? {
+        SidetreeDID::Long {
+            did_suffix,
+            create_operation_data,
+            ..
+        } => {
+            let data =
+                base64::decode_config(create_operation_data, base64::URL_SAFE_NO_PAD).ok()?;
+            let create: CreateOperation = serde_json::from_slice(&data).ok()?;
+            let short_did = SidetreeDID::<ION>::Short { did_suffix }.to_string();
+            Some((short_did, create))
+        }
+        SidetreeDID::Short { .. } => None,
+    }
+}
+
+/// Returns the update operations on a DID suffix saved by `did attest` but not yet published,
+/// from the operations directory in the Trustchain data directory.
+pub fn pending_operations(did_suffix: &str) -> Vec<Operation> {
+    let path = match get_operations_path() {
+        Ok(path) => path.join(format!("attest_operation_{}.json", did_suffix)),
+        Err(_) => return Vec::new(),
+    };
+    std::fs::read(path)
+        .ok()
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .into_iter()
+        .collect()
+}
+
+/// Returns the result of resolving a long-form ION DID from the initial state embedded in it,
+/// followed by any of its pending operations saved locally (see [`pending_operations`]).
+pub fn resolve_unpublished(
+    did: &str,
+) -> (
+    ResolutionMetadata,
+    Option<Document>,
+    Option<DocumentMetadata>,
+) {
+    let pending = match decode_long_form(did) {
+        Some((short_did, _)) => pending_operations(get_did_suffix(&short_did)),
+        None => Vec::new(),
+    };
+    resolve_unpublished_with_operations(did, pending)
+}
+
+/// Returns the result of resolving a long-form ION DID from the initial state embedded in it,
+/// followed by the given operations on the DID that have not been anchored. Invalid operations
+/// are ignored, as in replay of anchored operations.
+///
+/// The document has the short-form DID as its ID, since this is the DID that upstream DIDs
+/// attest to, with the long-form DID as an `equivalentId` in the document metadata and
+/// `method.published` set to `false`.
+pub fn resolve_unpublished_with_operations(
+    did: &str,
+    operations: Vec<Operation>,
+) -> (
+    ResolutionMetadata,
+    Option<Document>,
+    Option<DocumentMetadata>,
+) {
+    let (short_did, create) = match decode_long_form(did) {
+        Some(decoded) => decoded,
+        None => {
+            return (
+                ResolutionMetadata::from_error(ERROR_INVALID_DID),
+                None,
+                None,
+            )
+        }
+    };
+    // Replay checks that the suffix data matches the DID suffix and the delta, and that each
+    // subsequent operation reveals the key committed to by the previous one.
+    let anchored = std::iter::once(Operation::Create(create))
+        .chain(operations)
+        .enumerate()
+        .map(|(index, operation)| AnchoredOperation {
+            operation,
+            txn_time: 0,
+            txn_number: index as i64,
+            op_index: 0,
+        })
+        .collect();
+    let state = match DIDState::replay(get_did_suffix(&short_did), anchored) {
+        Ok(state) => state,
+        Err(_) => {
+            return (
+                ResolutionMetadata::from_error(ERROR_INVALID_DID),
+                None,
+                None,
+            )
+        }
+    };
+    let (res_meta, doc, mut doc_meta) = state.to_resolution(&short_did);
+    if let Some(property_set) = doc_meta
+        .as_mut()
+        .and_then(|doc_meta| doc_meta.property_set.as_mut())
+    {
+        // An unpublished DID has no canonical (anchored) identifier.
+        property_set.remove("canonicalId");
+        property_set.insert(
+            "equivalentId".to_string(),
+            Metadata::List(vec![Metadata::String(did.to_owned())]),
+        );
+        if let Some(Metadata::Map(method)) = property_set.get_mut("method") {
+            method.insert("published".to_string(), Metadata::Boolean(false));
+        }
+    }
+    (res_meta, doc, doc_meta)
+}
+
+/// DID resolver for long-form ION DIDs. If the short form of a long-form DID has been anchored,
+/// its published state is resolved by the wrapped resolver. Otherwise the DID is resolved locally
+/// from the initial state embedded in it, so that newly created DIDs can be resolved before they
+/// are anchored. Short-form DIDs are passed to the wrapped resolver.
+pub struct LongFormResolver<T: DIDResolver> {
+    wrapped_resolver: T,
+}
+
+impl<T: DIDResolver> LongFormResolver<T> {
+    /// Constructs a new LongFormResolver.
+    pub fn new(resolver: T) -> Self {
+        Self {
+            wrapped_resolver: resolver,
+        }
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl<T: DIDResolver + Sync + Send> DIDResolver for LongFormResolver<T> {
+    async fn resolve(
+        &self,
+        did: &str,
+        input_metadata: &ResolutionInputMetadata,
+    ) -> (
+        ResolutionMetadata,
+        Option<Document>,
+        Option<DocumentMetadata>,
+    ) {
+        let short_did = match decode_long_form(did) {
+            Some((short_did, _)) => short_did,
+            None => return self.wrapped_resolver.resolve(did, input_metadata).await,
+        };
+        let resolution = self
+            .wrapped_resolver
+            .resolve(&short_did, input_metadata)
+            .await;
+        // A DID not found at a requested version did not exist then, whether or not it exists
+        // unpublished now.
+        let versioned =
+            input_metadata.version_id.is_some() || input_metadata.version_time.is_some();
+        match resolution.0.error.as_deref() {
+            Some(ERROR_NOT_FOUND) if !versioned => resolve_unpublished(did),
+            _ => resolution,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use did_ion::sidetree::{
+        DIDStatePatch, DocumentState, PublicKeyEntry, PublicKeyJwk, ServiceEndpointEntry, Sidetree,
+    };
+    use ssi::did::ServiceEndpoint;
+    use std::convert::TryFrom;
+    use trustchain_core::chain::is_unpublished;
+    use trustchain_core::utils::generate_key;
+
+    /// Returns a long-form DID with a signing key and a service.
+    fn long_form_did() -> String {
+        let public_key = PublicKeyEntry::try_from(generate_key()).unwrap();
+        let service = ServiceEndpointEntry {
+            id: "example-service".to_string(),
+            r#type: "Example".to_string(),
+            service_endpoint: ServiceEndpoint::URI("https://example.com".to_string()),
+        };
+        let patches = vec![DIDStatePatch::Replace {
+            document: DocumentState {
+                public_keys: Some(vec![public_key]),
+                services: Some(vec![service]),
+            },
+        }];
+        let update_pk = PublicKeyJwk::try_from(generate_key().to_public()).unwrap();
+        let recovery_pk = PublicKeyJwk::try_from(generate_key().to_public()).unwrap();
+        match ION::create_existing(&update_pk, &recovery_pk, patches).unwrap() {
+            Operation::Create(create) => SidetreeDID::<ION>::from_create_operation(&create)
+                .unwrap()
+                .to_string(),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Resolver for which no DID has been published.
+    struct EmptyResolver;
+
+    #[async_trait]
+    impl DIDResolver for EmptyResolver {
+        async fn resolve(
+            &self,
+            _: &str,
+            _: &ResolutionInputMetadata,
+        ) -> (
+            ResolutionMetadata,
+            Option<Document>,
+            Option<DocumentMetadata>,
+        ) {
+            (ResolutionMetadata::from_error(ERROR_NOT_FOUND), None, None)
+        }
+    }
+
+    #[test]
+    fn test_decode_long_form() {
+        let did = long_form_did();
+        let (short_did, _) = decode_long_form(&did).unwrap();
+        assert_eq!(did.rsplit_once(':').unwrap().0, short_did);
+        assert!(decode_long_form(&short_did).is_none());
+        assert!(decode_long_form("did:ion:test:EiAbc:not-base64!").is_none());
+    }
+
+    #[test]
+    fn test_resolve_unpublished() {
+        let did = long_form_did();
+        let (res_meta, doc, doc_meta) = resolve_unpublished(&did);
+        assert!(res_meta.error.is_none());
+        let doc = doc.unwrap();
+        let (short_did, _) = decode_long_form(&did).unwrap();
+        assert_eq!(doc.id, short_did);
+        assert_eq!(doc.verification_method.unwrap().len(), 1);
+        assert_eq!(doc.service.unwrap().len(), 1);
+        let doc_meta = doc_meta.unwrap();
+        assert!(is_unpublished(&doc_meta));
+        let property_set = doc_meta.property_set.unwrap();
+        assert!(!property_set.contains_key("canonicalId"));
+        assert!(matches!(
+            property_set.get("equivalentId"),
+            Some(Metadata::List(ids)) if matches!(ids.as_slice(), [Metadata::String(id)] if id == &did)
+        ));
+
+        // A long-form DID whose embedded create operation does not match its suffix is invalid.
+        let (_, data) = did.rsplit_once(':').unwrap();
+        let tampered = format!("{}:{}", long_form_did().rsplit_once(':').unwrap().0, data);
+        let (res_meta, doc, _) = resolve_unpublished(&tampered);
+        assert_eq!(res_meta.error, Some(ERROR_INVALID_DID.to_string()));
+        assert!(doc.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_long_form_resolver() {
+        let resolver = LongFormResolver::new(EmptyResolver);
+        let did = long_form_did();
+        let (res_meta, doc, doc_meta) = resolver.resolve(&did, &Default::default()).await;
+        assert!(res_meta.error.is_none());
+        assert_eq!(doc.unwrap().id, decode_long_form(&did).unwrap().0);
+        assert!(is_unpublished(&doc_meta.unwrap()));
+
+        // Short-form DIDs are resolved by the wrapped resolver.
+        let (short_did, _) = decode_long_form(&did).unwrap();
+        let (res_meta, _, _) = resolver.resolve(&short_did, &Default::default()).await;
+        assert_eq!(res_meta.error, Some(ERROR_NOT_FOUND.to_string()));
+
+        // Long-form DIDs not found at a requested version are not resolved as unpublished.
+        let input_metadata = ResolutionInputMetadata {
+            version_time: Some("2023-01-01T00:00:00Z".to_string()),
+            ..Default::default()
+        };
+        let (res_meta, doc, _) = resolver.resolve(&did, &input_metadata).await;
+        assert_eq!(res_meta.error, Some(ERROR_NOT_FOUND.to_string()));
+        assert!(doc.is_none());
+    }
+}
@@ -0,0 +1,1223 @@
+//! OpenID for Verifiable Credential Issuance (pre-authorized code flow) and OpenID for Verifiable
+//! Presentations (direct post response mode) HTTP endpoints backed by Trustchain.
+//!
+//! An [`OID4VCService`] issues credentials from templates, signed by an [`IONAttestor`], to the
+//! holder DID whose control is proved by a key proof JWT, and verifies presentations requested by
+//! a DIF Presentation Exchange presentation definition, including the Trustchain DID chains of the
+//! holder and of the issuer of every credential. Credentials are issued with linked-data proofs
+//! (`ldp_vc`), and authorization requests are served unsigned by reference, with the
+//! `redirect_uri` client ID scheme.
+use crate::attestor::IONAttestor;
+use axum::extract::{Path, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Form, Json, Router};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use ssi::did_resolve::DIDResolver;
+use ssi::jws::{decode_unverified, decode_verify};
+use ssi::ldp::resolve_key;
+use ssi::one_or_many::OneOrMany;
+use ssi::vc::{Credential, CredentialOrJWT, Issuer as VCIssuer, Presentation, VCDateTime, URI};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use thiserror::Error;
+use tokio::sync::oneshot;
+use trustchain_core::batch::credential_from_template;
+use trustchain_core::chain::Chain;
+use trustchain_core::exchange::{
+    check_descriptor_map, ExchangeError, PresentationDefinition, PresentationSubmission, LDP_VC,
+};
+use trustchain_core::holder::{KeyProofClaims, KEY_PROOF_JWT_TYPE};
+use trustchain_core::issuer::{Issuer, ProofOptions};
+use trustchain_core::policy::IssuerPolicy;
+use trustchain_core::presentation::verify_presentation_with_policy;
+use trustchain_core::subject::Subject;
+use trustchain_core::verifier::Verifier;
+use url::form_urlencoded::byte_serialize;
+
+/// Grant type of the pre-authorized code flow.
+pub const PRE_AUTHORIZED_CODE_GRANT_TYPE: &str =
+    "urn:ietf:params:oauth:grant-type:pre-authorized_code";
+
+/// Path of the credential issuer metadata.
+pub const CREDENTIAL_ISSUER_METADATA_PATH: &str = "/.well-known/openid-credential-issuer";
+/// Path of the authorization server metadata.
+pub const AUTHORIZATION_SERVER_METADATA_PATH: &str = "/.well-known/oauth-authorization-server";
+/// Path of the token endpoint.
+pub const TOKEN_PATH: &str = "/oid4vci/token";
+/// Path of the credential endpoint.
+pub const CREDENTIAL_PATH: &str = "/oid4vci/credential";
+/// Path of the presentation response endpoint.
+pub const RESPONSE_PATH: &str = "/oid4vp/response";
+
+/// Lifetime, in seconds, of a credential offer and its pre-authorized code.
+const OFFER_LIFETIME: i64 = 3600;
+
+/// Lifetime, in seconds, of an access token and its nonce.
+const TOKEN_LIFETIME: i64 = 300;
+
+/// An error relating to OpenID for Verifiable Credentials.
+#[derive(Error, Debug)]
+pub enum OID4VCError {
+    /// Malformed or incomplete request.
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+    /// Grant type other than the pre-authorized code.
+    #[error("Unsupported grant type: {0}")]
+    UnsupportedGrantType(String),
+    /// Unknown or already used pre-authorized code.
+    #[error("Invalid or already used pre-authorized code.")]
+    InvalidGrant,
+    /// Missing, unknown or expired access token.
+    #[error("Invalid or expired access token.")]
+    InvalidToken,
+    /// Invalid key proof.
+    #[error("Invalid key proof: {0}")]
+    InvalidProof(String),
+    /// Requested credential format is not issued.
+    #[error("Unsupported credential format: {0}")]
+    UnsupportedCredentialFormat(String),
+    /// No credential configuration with the given ID.
+    #[error("Unknown credential configuration: {0}")]
+    UnknownConfiguration(String),
+    /// Service has no issuer, so issues no credentials.
+    #[error("Credential issuance is not configured.")]
+    NoIssuer,
+    /// Failed to make or sign a credential.
+    #[error("Failed to issue credential: {0}")]
+    Issuance(String),
+    /// Invalid presentation definition.
+    #[error("Invalid presentation definition: {0}")]
+    InvalidDefinition(ExchangeError),
+    /// No pending authorization request with the given state.
+    #[error("Unknown or already answered authorization request: {0}")]
+    UnknownRequest(String),
+    /// Presentation failed verification.
+    #[error("Presentation rejected: {0}")]
+    Rejected(String),
+    /// Internal error of the service.
+    #[error("Internal error: {0}")]
+    Internal(String),
+}
+
+impl OID4VCError {
+    /// Returns the HTTP status and the OAuth error code of the error.
+    fn status_code(&self) -> (StatusCode, &'static str) {
+        match self {
+            OID4VCError::UnsupportedGrantType(_) => {
+                (StatusCode::BAD_REQUEST, "unsupported_grant_type")
+            }
+            OID4VCError::InvalidGrant => (StatusCode::BAD_REQUEST, "invalid_grant"),
+            OID4VCError::InvalidToken => (StatusCode::UNAUTHORIZED, "invalid_token"),
+            OID4VCError::InvalidProof(_) => (StatusCode::BAD_REQUEST, "invalid_proof"),
+            OID4VCError::UnsupportedCredentialFormat(_) => {
+                (StatusCode::BAD_REQUEST, "unsupported_credential_format")
+            }
+            OID4VCError::UnknownConfiguration(_) => {
+                (StatusCode::BAD_REQUEST, "unsupported_credential_type")
+            }
+            OID4VCError::InvalidRequest(_)
+            | OID4VCError::InvalidDefinition(_)
+            | OID4VCError::UnknownRequest(_)
+            | OID4VCError::Rejected(_) => (StatusCode::BAD_REQUEST, "invalid_request"),
+            OID4VCError::NoIssuer | OID4VCError::Issuance(_) | OID4VCError::Internal(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, "server_error")
+            }
+        }
+    }
+}
+
+impl IntoResponse for OID4VCError {
+    fn into_response(self) -> Response {
+        let (status, code) = self.status_code();
+        (
+            status,
+            Json(json!({ "error": code, "error_description": self.to_string() })),
+        )
+            .into_response()
+    }
+}
+
+/// A credential offer of the pre-authorized code flow.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CredentialOffer {
+    pub credential_issuer: String,
+    pub credential_configuration_ids: Vec<String>,
+    pub grants: Grants,
+}
+
+/// Grants of a credential offer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Grants {
+    #[serde(rename = "urn:ietf:params:oauth:grant-type:pre-authorized_code")]
+    pub pre_authorized_code: PreAuthorizedCodeGrant,
+}
+
+/// Pre-authorized code grant of a credential offer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PreAuthorizedCodeGrant {
+    #[serde(rename = "pre-authorized_code")]
+    pub pre_authorized_code: String,
+}
+
+/// Token request of the pre-authorized code flow.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenRequest {
+    pub grant_type: String,
+    #[serde(rename = "pre-authorized_code")]
+    pub pre_authorized_code: Option<String>,
+}
+
+/// Token response, with the nonce to which key proofs must be bound.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: i64,
+    pub c_nonce: String,
+    pub c_nonce_expires_in: i64,
+}
+
+/// Credential request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CredentialRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub credential_configuration_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub credential_definition: Option<Value>,
+    pub proof: Option<KeyProof>,
+}
+
+/// Proof of control of the key to which a credential is issued.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyProof {
+    pub proof_type: String,
+    pub jwt: Option<String>,
+}
+
+/// Credential response.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CredentialResponse {
+    pub credential: Credential,
+}
+
+/// An authorization request for a presentation, answered by posting the presentation to the
+/// response URI.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuthorizationRequest {
+    pub client_id: String,
+    pub client_id_scheme: String,
+    pub response_type: String,
+    pub response_mode: String,
+    pub response_uri: String,
+    pub nonce: String,
+    pub state: String,
+    pub presentation_definition: PresentationDefinition,
+}
+
+/// An authorization response, with the presentation (as JSON) and its presentation submission.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuthorizationResponse {
+    pub vp_token: String,
+    pub presentation_submission: String,
+    pub state: String,
+}
+
+/// An authorization request made by the relying party, with the secret code under which the
+/// result of the verification of the presentation answering it is kept. Unlike the state, the
+/// result code is not part of the request given to the wallet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PresentationRequest {
+    pub request: AuthorizationRequest,
+    pub result_code: String,
+}
+
+/// Result of the verification of a presentation answering an authorization request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum PresentationResult {
+    /// Presentation was verified and satisfies the presentation definition.
+    #[serde(rename_all = "camelCase")]
+    Verified {
+        holder: String,
+        issuers: Vec<String>,
+        submission: PresentationSubmission,
+        credentials: Vec<CredentialOrJWT>,
+    },
+    /// Presentation was rejected.
+    Rejected { error: String },
+}
+
+/// A credential offered by pre-authorized code.
+#[derive(Debug, Clone)]
+struct Offer {
+    configuration_id: String,
+    subject: Map<String, Value>,
+    expires: i64,
+}
+
+/// An offered credential for which an access token was issued.
+#[derive(Debug, Clone)]
+struct Grant {
+    offer: Offer,
+    c_nonce: String,
+    expires: i64,
+}
+
+/// Pending offers, grants and authorization requests, and presentation results.
+#[derive(Debug, Default)]
+struct Sessions {
+    offers: HashMap<String, Offer>,
+    grants: HashMap<String, Grant>,
+    requests: HashMap<String, PresentationRequest>,
+    results: HashMap<String, PresentationResult>,
+}
+
+impl Sessions {
+    /// Removes the offers and grants that have expired.
+    fn prune(&mut self) {
+        let now = Utc::now().timestamp();
+        self.offers.retain(|_, offer| offer.expires > now);
+        self.grants.retain(|_, grant| grant.expires > now);
+    }
+}
+
+/// Issuer of the credentials of an [`OID4VCService`].
+struct CredentialIssuer {
+    attestor: IONAttestor,
+    configurations: HashMap<String, Credential>,
+}
+
+/// OpenID for Verifiable Credential Issuance and Presentations service, issuing credentials with
+/// an ION attestor and verifying presentations with a Trustchain verifier.
+pub struct OID4VCService<T, V> {
+    base_url: String,
+    verifier: V,
+    root_event_time: u32,
+    issuer: Option<CredentialIssuer>,
+    issuer_policy: Option<IssuerPolicy>,
+    sessions: Mutex<Sessions>,
+    _resolver: PhantomData<T>,
+}
+
+/// Returns a new random code, token or nonce.
+fn random() -> String {
+    uuid::Uuid::new_v4().simple().to_string()
+}
+
+/// Percent-encodes a URI query component.
+fn encode(component: &str) -> String {
+    byte_serialize(component.as_bytes()).collect()
+}
+
+impl<T, V> OID4VCService<T, V>
+where
+    T: DIDResolver + Sync + Send,
+    V: Verifier<T>,
+{
+    /// Makes a service at the base URL (the credential issuer identifier), verifying
+    /// presentations with DID chains from the root with the given event time.
+    pub fn new(base_url: &str, verifier: V, root_event_time: u32) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_owned(),
+            verifier,
+            root_event_time,
+            issuer: None,
+            issuer_policy: None,
+            sessions: Mutex::new(Sessions::default()),
+            _resolver: PhantomData,
+        }
+    }
+
+    /// Issues credentials signed by the attestor from the templates, by credential
+    /// configuration ID.
+    pub fn with_issuer(
+        mut self,
+        attestor: IONAttestor,
+        configurations: HashMap<String, Credential>,
+    ) -> Self {
+        self.issuer = Some(CredentialIssuer {
+            attestor,
+            configurations,
+        });
+        self
+    }
+
+    /// Verifies presentations against an issuer authorisation policy.
+    pub fn with_issuer_policy(mut self, issuer_policy: IssuerPolicy) -> Self {
+        self.issuer_policy = Some(issuer_policy);
+        self
+    }
+
+    /// Returns the credential issuer identifier.
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Locks the sessions.
+    fn sessions(&self) -> Result<std::sync::MutexGuard<'_, Sessions>, OID4VCError> {
+        self.sessions
+            .lock()
+            .map_err(|e| OID4VCError::Internal(e.to_string()))
+    }
+
+    /// Returns the credential issuer metadata, if the service issues credentials.
+    pub fn issuer_metadata(&self) -> Option<Value> {
+        let issuer = self.issuer.as_ref()?;
+        let configurations: Map<String, Value> = issuer
+            .configurations
+            .iter()
+            .map(|(id, template)| {
+                (
+                    id.to_owned(),
+                    json!({
+                        "format": LDP_VC,
+                        "cryptographic_binding_methods_supported": ["did"],
+                        "proof_types_supported": {"jwt": {"proof_signing_alg_values_supported": ["ES256K"]}},
+                        "credential_definition": {
+                            "@context": template.context,
+                            "type": template.type_,
+                        },
+                    }),
+                )
+            })
+            .collect();
+        Some(json!({
+            "credential_issuer": self.base_url,
+            "token_endpoint": format!("{}{}", self.base_url, TOKEN_PATH),
+            "credential_endpoint": format!("{}{}", self.base_url, CREDENTIAL_PATH),
+            "credential_configurations_supported": configurations,
+        }))
+    }
+
+    /// Returns the authorization server metadata.
+    pub fn authorization_server_metadata(&self) -> Value {
+        json!({
+            "issuer": self.base_url,
+            "token_endpoint": format!("{}{}", self.base_url, TOKEN_PATH),
+            "grant_types_supported": [PRE_AUTHORIZED_CODE_GRANT_TYPE],
+            "pre-authorized_grant_anonymous_access_supported": true,
+        })
+    }
+
+    /// Offers a credential made from the template of a credential configuration with the
+    /// properties of a subject, to be issued to the holder DID proved in the credential request.
+    pub fn offer_credential(
+        &self,
+        configuration_id: &str,
+        subject: Map<String, Value>,
+    ) -> Result<CredentialOffer, OID4VCError> {
+        let issuer = self.issuer.as_ref().ok_or(OID4VCError::NoIssuer)?;
+        if !issuer.configurations.contains_key(configuration_id) {
+            return Err(OID4VCError::UnknownConfiguration(
+                configuration_id.to_owned(),
+            ));
+        }
+        let code = random();
+        self.sessions()?.offers.insert(
+            code.to_owned(),
+            Offer {
+                configuration_id: configuration_id.to_owned(),
+                subject,
+                expires: Utc::now().timestamp() + OFFER_LIFETIME,
+            },
+        );
+        Ok(CredentialOffer {
+            credential_issuer: self.base_url.to_owned(),
+            credential_configuration_ids: vec![configuration_id.to_owned()],
+            grants: Grants {
+                pre_authorized_code: PreAuthorizedCodeGrant {
+                    pre_authorized_code: code,
+                },
+            },
+        })
+    }
+
+    /// Returns the pending credential offer with the given pre-authorized code.
+    pub fn credential_offer(&self, code: &str) -> Result<Option<CredentialOffer>, OID4VCError> {
+        let mut sessions = self.sessions()?;
+        sessions.prune();
+        Ok(sessions.offers.get(code).map(|offer| CredentialOffer {
+            credential_issuer: self.base_url.to_owned(),
+            credential_configuration_ids: vec![offer.configuration_id.to_owned()],
+            grants: Grants {
+                pre_authorized_code: PreAuthorizedCodeGrant {
+                    pre_authorized_code: code.to_owned(),
+                },
+            },
+        }))
+    }
+
+    /// Returns the URI of a credential offer, referencing the offer by URL.
+    pub fn credential_offer_uri(&self, offer: &CredentialOffer) -> String {
+        let url = format!(
+            "{}/oid4vci/offers/{}",
+            self.base_url, offer.grants.pre_authorized_code.pre_authorized_code
+        );
+        format!(
+            "openid-credential-offer://?credential_offer_uri={}",
+            encode(&url)
+        )
+    }
+
+    /// Exchanges a pre-authorized code, which may be used once before the offer expires, for an
+    /// access token.
+    pub fn token(&self, request: &TokenRequest) -> Result<TokenResponse, OID4VCError> {
+        if request.grant_type != PRE_AUTHORIZED_CODE_GRANT_TYPE {
+            return Err(OID4VCError::UnsupportedGrantType(
+                request.grant_type.to_owned(),
+            ));
+        }
+        let code = request.pre_authorized_code.as_deref().ok_or_else(|| {
+            OID4VCError::InvalidRequest("missing pre-authorized_code".to_string())
+        })?;
+        let mut sessions = self.sessions()?;
+        sessions.prune();
+        let offer = sessions
+            .offers
+            .remove(code)
+            .ok_or(OID4VCError::InvalidGrant)?;
+        let (access_token, c_nonce) = (random(), random());
+        sessions.grants.insert(
+            access_token.to_owned(),
+            Grant {
+                offer,
+                c_nonce: c_nonce.to_owned(),
+                expires: Utc::now().timestamp() + TOKEN_LIFETIME,
+            },
+        );
+        Ok(TokenResponse {
+            access_token,
+            token_type: "bearer".to_string(),
+            expires_in: TOKEN_LIFETIME,
+            c_nonce,
+            c_nonce_expires_in: TOKEN_LIFETIME,
+        })
+    }
+
+    /// Issues the credential granted to an access token to the holder DID whose control of a key
+    /// is proved by the key proof of the request. The access token may be used once: it is
+    /// consumed by a request in a supported format, whether or not the credential is issued.
+    pub fn issue(
+        &self,
+        access_token: &str,
+        request: &CredentialRequest,
+    ) -> Result<CredentialResponse, OID4VCError> {
+        let issuer = self.issuer.as_ref().ok_or(OID4VCError::NoIssuer)?;
+        if let Some(format) = request.format.as_deref().filter(|format| *format != LDP_VC) {
+            return Err(OID4VCError::UnsupportedCredentialFormat(format.to_owned()));
+        }
+        // Take the grant, so that concurrent requests with the token cannot both be issued.
+        let grant = {
+            let mut sessions = self.sessions()?;
+            sessions.prune();
+            sessions
+                .grants
+                .remove(access_token)
+                .ok_or(OID4VCError::InvalidToken)?
+        };
+        match request.credential_configuration_id.as_deref() {
+            Some(id) if id != grant.offer.configuration_id => {
+                return Err(OID4VCError::UnknownConfiguration(id.to_owned()))
+            }
+            _ => (),
+        }
+        let holder = self.verify_key_proof(request.proof.as_ref(), &grant.c_nonce)?;
+
+        // Make the credential from the template, issued now to the holder.
+        let mut subject = grant.offer.subject.clone();
+        subject.insert("id".to_string(), Value::String(holder));
+        let template = issuer
+            .configurations
+            .get(&grant.offer.configuration_id)
+            .ok_or(OID4VCError::UnknownConfiguration(
+                grant.offer.configuration_id,
+            ))?;
+        let mut credential = credential_from_template(template, &subject)
+            .map_err(|e| OID4VCError::Issuance(e.to_string()))?;
+        credential.issuer = Some(VCIssuer::URI(URI::String(issuer.attestor.did().to_owned())));
+        credential.issuance_date = Some(VCDateTime::from(Utc::now()));
+
+        let resolver = self.verifier.resolver();
+        let credential = resolver
+            .runtime
+            .block_on(
+                issuer
+                    .attestor
+                    .sign(&credential, &ProofOptions::default(), resolver),
+            )
+            .map_err(|e| OID4VCError::Issuance(e.to_string()))?;
+        Ok(CredentialResponse { credential })
+    }
+
+    /// Verifies a key proof JWT bound to the nonce, returning the DID of its verification method.
+    fn verify_key_proof(
+        &self,
+        proof: Option<&KeyProof>,
+        c_nonce: &str,
+    ) -> Result<String, OID4VCError> {
+        let invalid = |reason: &str| OID4VCError::InvalidProof(reason.to_owned());
+        let jwt = match proof {
+            Some(KeyProof {
+                proof_type,
+                jwt: Some(jwt),
+            }) if proof_type == "jwt" => jwt,
+            _ => return Err(invalid("a jwt proof is required")),
+        };
+        let (header, _) = decode_unverified(jwt).map_err(|e| invalid(&e.to_string()))?;
+        if header.type_.as_deref() != Some(KEY_PROOF_JWT_TYPE) {
+            return Err(invalid("typ must be openid4vci-proof+jwt"));
+        }
+        let kid = header
+            .key_id
+            .ok_or_else(|| invalid("kid must be a DID URL"))?;
+        let did = match kid.split_once('#') {
+            Some((did, _)) if did.starts_with("did:") => did.to_owned(),
+            _ => return Err(invalid("kid must be a DID URL")),
+        };
+        let resolver = self.verifier.resolver();
+        let key = resolver
+            .runtime
+            .block_on(resolve_key(&kid, resolver))
+            .map_err(|e| invalid(&e.to_string()))?;
+        let (_, claims) = decode_verify(jwt, &key).map_err(|e| invalid(&e.to_string()))?;
+        let claims: KeyProofClaims =
+            serde_json::from_slice(&claims).map_err(|e| invalid(&e.to_string()))?;
+        if claims.aud != self.base_url {
+            return Err(invalid("aud must be the credential issuer"));
+        }
+        if claims.nonce.as_deref() != Some(c_nonce) {
+            return Err(invalid("nonce must be the c_nonce of the token response"));
+        }
+        Ok(did)
+    }
+
+    /// Makes an authorization request for a presentation satisfying a presentation definition,
+    /// returned with the code of its result.
+    pub fn request_presentation(
+        &self,
+        presentation_definition: PresentationDefinition,
+    ) -> Result<PresentationRequest, OID4VCError> {
+        presentation_definition
+            .validate()
+            .map_err(OID4VCError::InvalidDefinition)?;
+        let response_uri = format!("{}{}", self.base_url, RESPONSE_PATH);
+        let request = AuthorizationRequest {
+            client_id: response_uri.to_owned(),
+            client_id_scheme: "redirect_uri".to_string(),
+            response_type: "vp_token".to_string(),
+            response_mode: "direct_post".to_string(),
+            response_uri,
+            nonce: random(),
+            state: random(),
+            presentation_definition,
+        };
+        let pending = PresentationRequest {
+            request,
+            result_code: random(),
+        };
+        self.sessions()?
+            .requests
+            .insert(pending.request.state.to_owned(), pending.clone());
+        Ok(pending)
+    }
+
+    /// Returns the pending authorization request with the given state.
+    pub fn authorization_request(
+        &self,
+        state: &str,
+    ) -> Result<Option<AuthorizationRequest>, OID4VCError> {
+        Ok(self
+            .sessions()?
+            .requests
+            .get(state)
+            .map(|pending| pending.request.clone()))
+    }
+
+    /// Returns the URI of an authorization request, referencing the request by URL.
+    pub fn authorization_request_uri(&self, request: &AuthorizationRequest) -> String {
+        let url = format!("{}/oid4vp/requests/{}", self.base_url, request.state);
+        format!(
+            "openid4vp://?client_id={}&request_uri={}",
+            encode(&request.client_id),
+            encode(&url)
+        )
+    }
+
+    /// Verifies the presentation answering an authorization request, which may be answered
+    /// once: the presentation, bound to the nonce and client ID of the request, with the
+    /// Trustchain DID chains of its holder and issuers (and the issuer policy, if any), and its
+    /// presentation submission against the presentation definition. The request remains pending
+    /// until answered by a verified presentation, and the latest result is kept under its
+    /// result code until taken by [`OID4VCService::presentation_result`].
+    pub fn respond(
+        &self,
+        response: &AuthorizationResponse,
+    ) -> Result<PresentationResult, OID4VCError> {
+        let pending = self
+            .sessions()?
+            .requests
+            .get(&response.state)
+            .cloned()
+            .ok_or_else(|| OID4VCError::UnknownRequest(response.state.to_owned()))?;
+        let result = match self.verify_response(&pending.request, response) {
+            Ok(result) => result,
+            Err(e) => PresentationResult::Rejected {
+                error: e.to_string(),
+            },
+        };
+        let mut sessions = self.sessions()?;
+        if matches!(result, PresentationResult::Verified { .. }) {
+            // Take the request, so that concurrent verified responses cannot both be accepted.
+            if sessions.requests.remove(&response.state).is_none() {
+                return Err(OID4VCError::UnknownRequest(response.state.to_owned()));
+            }
+        } else if !sessions.requests.contains_key(&response.state) {
+            return Err(OID4VCError::UnknownRequest(response.state.to_owned()));
+        }
+        sessions.results.insert(pending.result_code, result.clone());
+        Ok(result)
+    }
+
+    /// Verifies the presentation of an authorization response.
+    fn verify_response(
+        &self,
+        request: &AuthorizationRequest,
+        response: &AuthorizationResponse,
+    ) -> Result<PresentationResult, OID4VCError> {
+        let presentation: Presentation = serde_json::from_str(&response.vp_token).map_err(|e| {
+            OID4VCError::InvalidRequest(format!(
+                "vp_token must be a presentation with a linked-data proof: {}",
+                e
+            ))
+        })?;
+        let submission: PresentationSubmission =
+            serde_json::from_str(&response.presentation_submission)
+                .map_err(|e| OID4VCError::InvalidRequest(e.to_string()))?;
+        check_descriptor_map(&presentation, &submission, &request.presentation_definition)
+            .map_err(|e| OID4VCError::Rejected(e.to_string()))?;
+        let verified = verify_presentation_with_policy(
+            &presentation,
+            Some(&request.nonce),
+            Some(&request.client_id),
+            self.root_event_time,
+            self.issuer_policy.as_ref(),
+            &self.verifier,
+        )
+        .map_err(|e| OID4VCError::Rejected(e.to_string()))?;
+        Ok(PresentationResult::Verified {
+            holder: verified.holder_chain.leaf().to_owned(),
+            issuers: verified
+                .issuer_chains
+                .iter()
+                .map(|chain| chain.leaf().to_owned())
+                .collect(),
+            submission,
+            credentials: presentation
+                .verifiable_credential
+                .map(OneOrMany::into_iter)
+                .into_iter()
+                .flatten()
+                .collect(),
+        })
+    }
+
+    /// Takes the result of the verification of the presentation answering the authorization
+    /// request with the given result code, if answered.
+    pub fn presentation_result(
+        &self,
+        result_code: &str,
+    ) -> Result<Option<PresentationResult>, OID4VCError> {
+        Ok(self.sessions()?.results.remove(result_code))
+    }
+}
+
+/// Shared OID4VC service.
+type Service<T, V> = Arc<OID4VCService<T, V>>;
+
+/// Runs a function on a new thread outside the server's runtime, as Trustchain resolution and
+/// verification block on the resolver's own runtime.
+async fn blocking<F, R>(f: F) -> Result<R, OID4VCError>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let (sender, receiver) = oneshot::channel();
+    std::thread::spawn(move || sender.send(f()).ok());
+    receiver
+        .await
+        .map_err(|e| OID4VCError::Internal(e.to_string()))
+}
+
+/// Serializes a result as a JSON response, or its error as an OAuth error response.
+fn respond_json<R: Serialize>(result: Result<R, OID4VCError>) -> Response {
+    match result {
+        Ok(body) => (StatusCode::OK, Json(body)).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Returns a JSON response, or not found.
+fn found<R: Serialize>(result: Result<Option<R>, OID4VCError>) -> Response {
+    match result {
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        result => respond_json(result),
+    }
+}
+
+async fn issuer_metadata<T, V>(State(service): State<Service<T, V>>) -> Response
+where
+    T: DIDResolver + Sync + Send,
+    V: Verifier<T>,
+{
+    found(Ok(service.issuer_metadata()))
+}
+
+async fn authorization_server_metadata<T, V>(State(service): State<Service<T, V>>) -> Response
+where
+    T: DIDResolver + Sync + Send,
+    V: Verifier<T>,
+{
+    Json(service.authorization_server_metadata()).into_response()
+}
+
+async fn credential_offer<T, V>(
+    State(service): State<Service<T, V>>,
+    Path(code): Path<String>,
+) -> Response
+where
+    T: DIDResolver + Sync + Send,
+    V: Verifier<T>,
+{
+    found(service.credential_offer(&code))
+}
+
+async fn token<T, V>(
+    State(service): State<Service<T, V>>,
+    Form(request): Form<TokenRequest>,
+) -> Response
+where
+    T: DIDResolver + Sync + Send,
+    V: Verifier<T>,
+{
+    respond_json(service.token(&request))
+}
+
+async fn credential<T, V>(
+    State(service): State<Service<T, V>>,
+    headers: HeaderMap,
+    Json(request): Json<CredentialRequest>,
+) -> Response
+where
+    T: DIDResolver + Sync + Send + 'static,
+    V: Verifier<T> + Send + Sync + 'static,
+{
+    let access_token = match headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+    {
+        Some(access_token) => access_token.to_owned(),
+        None => return OID4VCError::InvalidToken.into_response(),
+    };
+    respond_json(
+        blocking(move || service.issue(&access_token, &request))
+            .await
+            .and_then(|result| result),
+    )
+}
+
+async fn authorization_request<T, V>(
+    State(service): State<Service<T, V>>,
+    Path(state): Path<String>,
+) -> Response
+where
+    T: DIDResolver + Sync + Send,
+    V: Verifier<T>,
+{
+    found(service.authorization_request(&state))
+}
+
+async fn response<T, V>(
+    State(service): State<Service<T, V>>,
+    Form(response): Form<AuthorizationResponse>,
+) -> Response
+where
+    T: DIDResolver + Sync + Send + 'static,
+    V: Verifier<T> + Send + Sync + 'static,
+{
+    match blocking(move || service.respond(&response))
+        .await
+        .and_then(|result| result)
+    {
+        Ok(PresentationResult::Rejected { error }) => OID4VCError::Rejected(error).into_response(),
+        result => respond_json(result.map(|_| json!({}))),
+    }
+}
+
+async fn presentation_result<T, V>(
+    State(service): State<Service<T, V>>,
+    Path(result_code): Path<String>,
+) -> Response
+where
+    T: DIDResolver + Sync + Send,
+    V: Verifier<T>,
+{
+    found(service.presentation_result(&result_code))
+}
+
+/// Returns the HTTP routes of an OID4VC service.
+pub fn router<T, V>(service: Service<T, V>) -> Router
+where
+    T: DIDResolver + Sync + Send + 'static,
+    V: Verifier<T> + Send + Sync + 'static,
+{
+    Router::new()
+        .route(CREDENTIAL_ISSUER_METADATA_PATH, get(issuer_metadata))
+        .route(
+            AUTHORIZATION_SERVER_METADATA_PATH,
+            get(authorization_server_metadata),
+        )
+        .route("/oid4vci/offers/:code", get(credential_offer))
+        .route(TOKEN_PATH, post(token))
+        .route(CREDENTIAL_PATH, post(credential))
+        .route("/oid4vp/requests/:state", get(authorization_request))
+        .route(RESPONSE_PATH, post(response))
+        .route("/oid4vp/results/:result_code", get(presentation_result))
+        .with_state(service)
+}
+
+/// An OID4VC HTTP server running on a background thread until dropped.
+///
+/// Besides the issuer and authorization server metadata, the server exposes the credential offer
+/// (`GET /oid4vci/offers/{code}`), token (`POST /oid4vci/token`) and credential
+/// (`POST /oid4vci/credential`) endpoints, and the authorization request
+/// (`GET /oid4vp/requests/{state}`), response (`POST /oid4vp/response`) and result
+/// (`GET /oid4vp/results/{result_code}`) endpoints.
+pub struct OID4VCServer {
+    addr: SocketAddr,
+    shutdown: Option<oneshot::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+    // Held so that the service (and the runtime of its resolver) is dropped outside the server's
+    // runtime.
+    _service: Arc<dyn std::any::Any + Send + Sync>,
+}
+
+impl OID4VCServer {
+    /// Starts serving the service on the listener.
+    pub fn start<T, V>(service: Service<T, V>, listener: TcpListener) -> std::io::Result<Self>
+    where
+        T: DIDResolver + Sync + Send + 'static,
+        V: Verifier<T> + Send + Sync + 'static,
+    {
+        listener.set_nonblocking(true)?;
+        let addr = listener.local_addr()?;
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        // The listener is registered with the reactor of the server's runtime.
+        let server = {
+            let _guard = runtime.enter();
+            axum::Server::from_tcp(listener).map_err(std::io::Error::other)?
+        };
+        let (shutdown, shutdown_signal) = oneshot::channel::<()>();
+        let router = router(service.clone());
+        let thread = std::thread::spawn(move || {
+            runtime.block_on(async move {
+                let server = server
+                    .serve(router.into_make_service())
+                    .with_graceful_shutdown(async {
+                        shutdown_signal.await.ok();
+                    });
+                if let Err(e) = server.await {
+                    eprintln!("OID4VC server error: {}", e);
+                }
+            })
+        });
+        Ok(Self {
+            addr,
+            shutdown: Some(shutdown),
+            thread: Some(thread),
+            _service: service,
+        })
+    }
+
+    /// Returns the address the server is listening on.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Blocks until the server stops.
+    pub fn wait(mut self) {
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+impl Drop for OID4VCServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send(()).ok();
+        }
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::verifier::IONVerifier;
+    use ssi::did_resolve::HTTPDIDResolver;
+    use trustchain_core::data::TEST_CREDENTIAL;
+    use trustchain_core::resolver::Resolver;
+
+    // Service with a placeholder HTTP resolver, only for tests not resolving DIDs.
+    fn service() -> OID4VCService<HTTPDIDResolver, IONVerifier<HTTPDIDResolver>> {
+        let verifier = IONVerifier::new(Resolver::new(HTTPDIDResolver::new(
+            "http://localhost:3000/",
+        )));
+        let template: Credential = serde_json::from_str(TEST_CREDENTIAL).unwrap();
+        OID4VCService::new("https://issuer.example/", verifier, 0).with_issuer(
+            IONAttestor::new("did:example:issuer"),
+            HashMap::from([("TestCredential".to_string(), template)]),
+        )
+    }
+
+    fn token_request(code: &str) -> TokenRequest {
+        TokenRequest {
+            grant_type: PRE_AUTHORIZED_CODE_GRANT_TYPE.to_string(),
+            pre_authorized_code: Some(code.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_offer_token() {
+        let service = service();
+        assert!(matches!(
+            service.offer_credential("Other", Map::new()),
+            Err(OID4VCError::UnknownConfiguration(_))
+        ));
+        let offer = service
+            .offer_credential("TestCredential", Map::new())
+            .unwrap();
+        assert_eq!(offer.credential_issuer, "https://issuer.example");
+        assert!(
+            service.issuer_metadata().unwrap()["credential_configurations_supported"]
+                ["TestCredential"]["credential_definition"]["type"]
+                .is_array()
+        );
+        let code = offer.grants.pre_authorized_code.pre_authorized_code.clone();
+        assert_eq!(
+            service.credential_offer_uri(&offer),
+            format!(
+                "openid-credential-offer://?credential_offer_uri=https%3A%2F%2Fissuer.example%2Foid4vci%2Foffers%2F{}",
+                code
+            )
+        );
+        assert_eq!(service.credential_offer(&code).unwrap(), Some(offer));
+        let offer_json = serde_json::to_value(service.credential_offer(&code).unwrap()).unwrap();
+        assert!(
+            offer_json["grants"][PRE_AUTHORIZED_CODE_GRANT_TYPE]["pre-authorized_code"].is_string()
+        );
+
+        let mut request = token_request(&code);
+        request.grant_type = "authorization_code".to_string();
+        assert!(matches!(
+            service.token(&request),
+            Err(OID4VCError::UnsupportedGrantType(_))
+        ));
+        let token = service.token(&token_request(&code)).unwrap();
+        assert_eq!(token.token_type, "bearer");
+        // The code may be used once, and the offer is no longer pending.
+        assert!(matches!(
+            service.token(&token_request(&code)),
+            Err(OID4VCError::InvalidGrant)
+        ));
+        assert_eq!(service.credential_offer(&code).unwrap(), None);
+
+        let mut request = CredentialRequest {
+            format: Some("jwt_vc".to_string()),
+            credential_configuration_id: None,
+            credential_definition: None,
+            proof: None,
+        };
+        assert!(matches!(
+            service.issue(&token.access_token, &request),
+            Err(OID4VCError::UnsupportedCredentialFormat(_))
+        ));
+        request.format = Some(LDP_VC.to_string());
+        assert!(matches!(
+            service.issue("unknown", &request),
+            Err(OID4VCError::InvalidToken)
+        ));
+        assert!(matches!(
+            service.issue(&token.access_token, &request),
+            Err(OID4VCError::InvalidProof(_))
+        ));
+        // The token is consumed by the request, even though no credential was issued.
+        request.proof = Some(KeyProof {
+            proof_type: "jwt".to_string(),
+            jwt: Some("e30.e30.sig".to_string()),
+        });
+        assert!(matches!(
+            service.issue(&token.access_token, &request),
+            Err(OID4VCError::InvalidToken)
+        ));
+        let offer = service
+            .offer_credential("TestCredential", Map::new())
+            .unwrap();
+        let token = service
+            .token(&token_request(
+                &offer.grants.pre_authorized_code.pre_authorized_code,
+            ))
+            .unwrap();
+        assert!(matches!(
+            service.issue(&token.access_token, &request),
+            Err(OID4VCError::InvalidProof(_))
+        ));
+    }
+
+    #[test]
+    fn test_expiry() {
+        let service = service();
+        let expire = |service: &OID4VCService<_, _>| {
+            let mut sessions = service.sessions().unwrap();
+            let sessions = &mut *sessions;
+            for offer in sessions.offers.values_mut() {
+                offer.expires = Utc::now().timestamp() - 1;
+            }
+            for grant in sessions.grants.values_mut() {
+                grant.expires = Utc::now().timestamp() - 1;
+            }
+        };
+
+        // Expired offers are no longer pending, and their codes are rejected.
+        let offer = service
+            .offer_credential("TestCredential", Map::new())
+            .unwrap();
+        let code = offer.grants.pre_authorized_code.pre_authorized_code;
+        expire(&service);
+        assert_eq!(service.credential_offer(&code).unwrap(), None);
+        assert!(matches!(
+            service.token(&token_request(&code)),
+            Err(OID4VCError::InvalidGrant)
+        ));
+        assert!(service.sessions().unwrap().offers.is_empty());
+
+        // Expired access tokens are rejected and removed.
+        let offer = service
+            .offer_credential("TestCredential", Map::new())
+            .unwrap();
+        let token = service
+            .token(&token_request(
+                &offer.grants.pre_authorized_code.pre_authorized_code,
+            ))
+            .unwrap();
+        expire(&service);
+        let request = CredentialRequest {
+            format: None,
+            credential_configuration_id: None,
+            credential_definition: None,
+            proof: None,
+        };
+        assert!(matches!(
+            service.issue(&token.access_token, &request),
+            Err(OID4VCError::InvalidToken)
+        ));
+        assert!(service.sessions().unwrap().grants.is_empty());
+    }
+
+    #[test]
+    fn test_request_respond() {
+        let service = service();
+        let mut definition: PresentationDefinition = serde_json::from_value(json!({
+            "id": "check",
+            "input_descriptors": [{"id": "any"}]
+        }))
+        .unwrap();
+        let pending = service.request_presentation(definition.clone()).unwrap();
+        let request = &pending.request;
+        assert_eq!(request.client_id, "https://issuer.example/oid4vp/response");
+        assert_eq!(
+            service.authorization_request(&request.state).unwrap(),
+            Some(request.clone())
+        );
+        assert_ne!(pending.result_code, request.state);
+        assert!(!service
+            .authorization_request_uri(request)
+            .contains(&pending.result_code));
+        assert!(service.authorization_request_uri(request).starts_with(
+            "openid4vp://?client_id=https%3A%2F%2Fissuer.example%2Foid4vp%2Fresponse&request_uri="
+        ));
+
+        // An invalid response is rejected, leaving the request pending.
+        let response = AuthorizationResponse {
+            vp_token: "not a presentation".to_string(),
+            presentation_submission: "{}".to_string(),
+            state: request.state.to_owned(),
+        };
+        for _ in 0..2 {
+            assert!(matches!(
+                service.respond(&response),
+                Ok(PresentationResult::Rejected { .. })
+            ));
+        }
+        assert_eq!(
+            service.authorization_request(&request.state).unwrap(),
+            Some(request.clone())
+        );
+        // The result is kept under the result code, not the state.
+        assert!(service
+            .presentation_result(&request.state)
+            .unwrap()
+            .is_none());
+        assert!(matches!(
+            service.presentation_result(&pending.result_code).unwrap(),
+            Some(PresentationResult::Rejected { .. })
+        ));
+        assert!(service
+            .presentation_result(&pending.result_code)
+            .unwrap()
+            .is_none());
+        assert!(matches!(
+            service.respond(&AuthorizationResponse {
+                state: "unknown".to_string(),
+                ..response
+            }),
+            Err(OID4VCError::UnknownRequest(_))
+        ));
+
+        definition.submission_requirements = Some(json!([]));
+        assert!(matches!(
+            service.request_presentation(definition),
+            Err(OID4VCError::InvalidDefinition(_))
+        ));
+    }
+
+    #[test]
+    fn test_error_response() {
+        assert_eq!(
+            OID4VCError::InvalidToken.status_code(),
+            (StatusCode::UNAUTHORIZED, "invalid_token")
+        );
+        assert_eq!(
+            OID4VCError::InvalidGrant.into_response().status(),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            OID4VCError::Issuance(String::new()).status_code(),
+            (StatusCode::INTERNAL_SERVER_ERROR, "server_error")
+        );
+    }
+}
@@ -0,0 +1,866 @@
+//! Local replay of Sidetree operations to resolve the historical state of ION DIDs.
+use crate::config::TrustchainConfig;
+use crate::verifier::block_height_to_unixtime;
+use crate::{
+    MONGO_COLLECTION_OPERATIONS, MONGO_FILTER_DID_SUFFIX, MONGO_FILTER_OP_INDEX,
+    MONGO_FILTER_TXN_NUMBER, MONGO_FILTER_TXN_TIME, MONGO_OPERATION_BUFFER,
+};
+use async_trait::async_trait;
+use chrono::DateTime;
+use did_ion::sidetree::{
+    CreateOperation, DIDStatePatch, DIDSuffix, DocumentState, Operation,
+    PartiallyVerifiedOperation, PublicKey, Sidetree, SidetreeDID, SidetreeOperation,
+};
+use did_ion::ION;
+use futures::TryStreamExt;
+use mongodb::options::FindOptions;
+use mongodb::{bson::doc, options::ClientOptions, Client};
+use serde_json::{json, Map, Value};
+use ssi::did::Document;
+use ssi::did_resolve::{
+    DIDResolver, DocumentMetadata, Metadata, ResolutionInputMetadata, ResolutionMetadata,
+    ERROR_INVALID_DID, ERROR_NOT_FOUND,
+};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use thiserror::Error;
+use trustchain_core::resolver::ERROR_VERSION_NOT_AVAILABLE;
+use trustchain_core::TRUSTCHAIN_PROOF_SERVICE_ID_VALUE;
+
+/// An error relating to the replay of Sidetree operations.
+#[derive(Error, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ReplayError {
+    /// No valid create operation for the DID.
+    #[error("No valid create operation for DID suffix: {0}")]
+    NoCreateOperation(String),
+    /// Invalid version time.
+    #[error("Invalid version time: {0}")]
+    InvalidVersionTime(String),
+    /// Invalid version ID.
+    #[error("Invalid version ID: {0}")]
+    InvalidVersionId(String),
+    /// Failed to get the Unix time of the block in which an operation was anchored.
+    #[error("Failed to get Unix time from block height: {0}")]
+    FailureToGetUnixTime(u32),
+}
+
+/// A Sidetree operation anchored on the PoW ledger.
+#[derive(Debug, Clone)]
+pub struct AnchoredOperation {
+    /// The operation.
+    pub operation: Operation,
+    /// Block height of the anchoring transaction.
+    pub txn_time: u32,
+    /// ION transaction number of the anchoring transaction, used as the DID `versionId`.
+    pub txn_number: i64,
+    /// Index of the operation within the anchoring transaction.
+    pub op_index: i32,
+}
+
+/// State of a DID obtained by replaying its Sidetree operations.
+#[derive(Debug, Clone, Default)]
+pub struct DIDState {
+    /// Public keys and services.
+    pub document: DocumentState,
+    /// Commitment to the update key for the next update operation.
+    pub update_commitment: Option<String>,
+    /// Commitment to the recovery key for the next recover or deactivate operation.
+    pub recovery_commitment: Option<String>,
+    /// Whether the DID has been deactivated.
+    pub deactivated: bool,
+}
+
+/// Returns the commitment opened by a reveal value, i.e. the hash of the (unprefixed) hash of the
+/// revealed public key.
+fn commitment(reveal_value: &str) -> Option<String> {
+    let multihash = base64::decode_config(reveal_value, base64::URL_SAFE_NO_PAD).ok()?;
+    // Strip the two byte multihash prefix (hash function code and length).
+    multihash.get(2..).map(ION::hash)
+}
+
+impl DIDState {
+    /// Replays the operations on a DID suffix in ledger order. As specified by the Sidetree
+    /// protocol, operations that are invalid or do not reveal the current commitment are ignored.
+    pub fn replay(
+        did_suffix: &str,
+        mut operations: Vec<AnchoredOperation>,
+    ) -> Result<Self, ReplayError> {
+        operations.sort_by_key(|op| (op.txn_number, op.op_index));
+        let mut state: Option<DIDState> = None;
+        for anchored in operations {
+            if let Some(state) = state.as_mut() {
+                state.apply(did_suffix, anchored.operation);
+            } else if let Operation::Create(create) = anchored.operation {
+                state = Self::create(did_suffix, create);
+            }
+        }
+        state.ok_or_else(|| ReplayError::NoCreateOperation(did_suffix.to_owned()))
+    }
+
+    /// Returns the transaction number of the operation that published the current Trustchain
+    /// proof service of a DID, replaying its operations as in [`DIDState::replay`]. This is the
+    /// earliest operation since which the DID has carried its current proof, so later updates
+    /// leaving the proof unchanged do not affect it. Returns `None` if the DID has no proof.
+    pub fn proof_transaction_number(
+        did_suffix: &str,
+        mut operations: Vec<AnchoredOperation>,
+    ) -> Option<i64> {
+        operations.sort_by_key(|op| (op.txn_number, op.op_index));
+        let mut state: Option<DIDState> = None;
+        let mut proof: Option<Value> = None;
+        let mut txn_number = None;
+        for anchored in operations {
+            let anchored_txn_number = anchored.txn_number;
+            if let Some(state) = state.as_mut() {
+                state.apply(did_suffix, anchored.operation);
+            } else if let Operation::Create(create) = anchored.operation {
+                state = Self::create(did_suffix, create);
+            }
+            let current = state.as_ref().and_then(DIDState::proof_service);
+            if current != proof {
+                txn_number = current.as_ref().map(|_| anchored_txn_number);
+                proof = current;
+            }
+        }
+        txn_number
+    }
+
+    /// Returns the Trustchain proof service of the DID, if any.
+    fn proof_service(&self) -> Option<Value> {
+        self.document
+            .services
+            .iter()
+            .flatten()
+            .find(|service| service.id == TRUSTCHAIN_PROOF_SERVICE_ID_VALUE)
+            .and_then(|service| serde_json::to_value(service).ok())
+    }
+
+    /// Returns the initial state of a DID from its create operation, if valid.
+    fn create(did_suffix: &str, create: CreateOperation) -> Option<Self> {
+        let suffix = DIDSuffix::from(SidetreeDID::<ION>::from_create_operation(&create).ok()?);
+        if suffix.0 != did_suffix {
+            return None;
+        }
+        let patches = create.delta.patches.clone();
+        let verified = Operation::Create(create).partial_verify::<ION>().ok()?;
+        let mut state = DIDState {
+            update_commitment: verified.update_commitment().map(str::to_owned),
+            recovery_commitment: verified.recovery_commitment().map(str::to_owned),
+            ..Default::default()
+        };
+        state.apply_patches(patches);
+        Some(state)
+    }
+
+    /// Applies an update, recover or deactivate operation, if valid.
+    fn apply(&mut self, did_suffix: &str, operation: Operation) {
+        if self.deactivated {
+            return;
+        }
+        // The operation must reveal the key committed to by the previous relevant operation.
+        let (suffix, reveal_value, expected_commitment, patches) = match &operation {
+            Operation::Create(_) => return,
+            Operation::Update(op) => (
+                &op.did_suffix,
+                &op.reveal_value,
+                &self.update_commitment,
+                Some(op.delta.patches.clone()),
+            ),
+            Operation::Recover(op) => (
+                &op.did_suffix,
+                &op.reveal_value,
+                &self.recovery_commitment,
+                Some(op.delta.patches.clone()),
+            ),
+            Operation::Deactivate(op) => (
+                &op.did_suffix,
+                &op.reveal_value,
+                &self.recovery_commitment,
+                None,
+            ),
+        };
+        if suffix.0 != did_suffix
+            || expected_commitment.is_none()
+            || commitment(reveal_value) != *expected_commitment
+        {
+            return;
+        }
+        let verified = match operation.partial_verify::<ION>() {
+            Ok(verified) => verified,
+            Err(_) => return,
+        };
+        match verified {
+            PartiallyVerifiedOperation::Update(_) => {
+                self.update_commitment = verified.update_commitment().map(str::to_owned);
+                self.apply_patches(patches.unwrap_or_default());
+            }
+            PartiallyVerifiedOperation::Recover(_) => {
+                self.update_commitment = verified.update_commitment().map(str::to_owned);
+                self.recovery_commitment = verified.recovery_commitment().map(str::to_owned);
+                // Recovery resets the document state.
+                self.document = DocumentState::default();
+                self.apply_patches(patches.unwrap_or_default());
+            }
+            PartiallyVerifiedOperation::Deactivate(_) => {
+                self.deactivated = true;
+                self.update_commitment = None;
+                self.recovery_commitment = None;
+                self.document = DocumentState::default();
+            }
+            PartiallyVerifiedOperation::Create(_) => (),
+        }
+    }
+
+    /// Applies DID state patches, leaving the document unchanged if any patch cannot be applied.
+    fn apply_patches(&mut self, patches: Vec<DIDStatePatch>) {
+        let mut document = self.document.clone();
+        for patch in patches {
+            match patch {
+                DIDStatePatch::AddPublicKeys { public_keys } => {
+                    let keys = document.public_keys.get_or_insert_with(Vec::new);
+                    for key in public_keys {
+                        keys.retain(|existing| existing.id != key.id);
+                        keys.push(key);
+                    }
+                }
+                DIDStatePatch::RemovePublicKeys { ids } => {
+                    if let Some(keys) = document.public_keys.as_mut() {
+                        keys.retain(|key| !ids.contains(&key.id));
+                    }
+                }
+                DIDStatePatch::AddServices { services } => {
+                    let existing_services = document.services.get_or_insert_with(Vec::new);
+                    for service in services {
+                        existing_services.retain(|existing| existing.id != service.id);
+                        existing_services.push(service);
+                    }
+                }
+                DIDStatePatch::RemoveServices { ids } => {
+                    if let Some(services) = document.services.as_mut() {
+                        services.retain(|service| !ids.contains(&service.id));
+                    }
+                }
+                DIDStatePatch::Replace {
+                    document: replacement,
+                } => document = replacement,
+                // JSON patches are not supported by ION.
+                DIDStatePatch::IetfJsonPatch { .. } => return,
+            }
+        }
+        self.document = document;
+    }
+
+    /// Converts the state to a DID document in the format returned by the ION node.
+    fn to_document(&self, did: &str) -> Result<Document, serde_json::Error> {
+        let mut doc = json!({
+            "@context": ["https://www.w3.org/ns/did/v1", { "@base": did }],
+            "id": did
+        });
+        let mut verification_methods = Vec::new();
+        let mut relationships = Map::new();
+        for key in self.document.public_keys.iter().flatten() {
+            let id = format!("#{}", key.id);
+            let mut method = json!({
+                "id": id,
+                "type": key.r#type,
+                "controller": key.controller.as_deref().unwrap_or(did)
+            });
+            match &key.public_key {
+                PublicKey::PublicKeyJwk(jwk) => method["publicKeyJwk"] = serde_json::to_value(jwk)?,
+                PublicKey::PublicKeyMultibase(multibase) => {
+                    method["publicKeyMultibase"] = json!(multibase)
+                }
+            }
+            verification_methods.push(method);
+            for purpose in &key.purposes {
+                if let Value::String(purpose) = serde_json::to_value(purpose)? {
+                    if let Value::Array(ids) = relationships.entry(purpose).or_insert(json!([])) {
+                        ids.push(json!(id));
+                    }
+                }
+            }
+        }
+        if !verification_methods.is_empty() {
+            doc["verificationMethod"] = Value::Array(verification_methods);
+        }
+        for (purpose, ids) in relationships {
+            doc[purpose.as_str()] = ids;
+        }
+        let services: Vec<Value> = self
+            .document
+            .services
+            .iter()
+            .flatten()
+            .map(|service| {
+                json!({
+                    "id": format!("#{}", service.id),
+                    "type": service.r#type,
+                    "serviceEndpoint": service.service_endpoint
+                })
+            })
+            .collect();
+        if !services.is_empty() {
+            doc["service"] = Value::Array(services);
+        }
+        serde_json::from_value(doc)
+    }
+
+    /// Converts the state to DID document metadata in the format returned by the ION node.
+    fn to_document_metadata(&self, did: &str) -> DocumentMetadata {
+        let mut method = HashMap::new();
+        method.insert("published".to_string(), Metadata::Boolean(true));
+        if let Some(update_commitment) = &self.update_commitment {
+            method.insert(
+                "updateCommitment".to_string(),
+                Metadata::String(update_commitment.to_owned()),
+            );
+        }
+        if let Some(recovery_commitment) = &self.recovery_commitment {
+            method.insert(
+                "recoveryCommitment".to_string(),
+                Metadata::String(recovery_commitment.to_owned()),
+            );
+        }
+        let mut property_set = HashMap::new();
+        property_set.insert("canonicalId".to_string(), Metadata::String(did.to_owned()));
+        property_set.insert("method".to_string(), Metadata::Map(method));
+        DocumentMetadata {
+            deactivated: if self.deactivated { Some(true) } else { None },
+            property_set: Some(property_set),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the result of resolving a DID with this state.
+    pub fn to_resolution(
+        &self,
+        did: &str,
+    ) -> (
+        ResolutionMetadata,
+        Option<Document>,
+        Option<DocumentMetadata>,
+    ) {
+        match self.to_document(did) {
+            Ok(doc) => (
+                ResolutionMetadata::default(),
+                Some(doc),
+                Some(self.to_document_metadata(did)),
+            ),
+            Err(e) => {
+                eprintln!("{}", e);
+                (
+                    ResolutionMetadata::from_error(ERROR_VERSION_NOT_AVAILABLE),
+                    None,
+                    None,
+                )
+            }
+        }
+    }
+}
+
+/// Selects the operations anchored up to the `versionId` (an ION transaction number) and
+/// `versionTime` requested in the resolution input metadata. The passed function gets the Unix
+/// time of a block from its height.
+pub fn select_operations<F>(
+    mut operations: Vec<AnchoredOperation>,
+    input_metadata: &ResolutionInputMetadata,
+    block_height_to_unixtime: F,
+) -> Result<Vec<AnchoredOperation>, ReplayError>
+where
+    F: FnMut(u32) -> Result<u32, ReplayError>,
+{
+    if let Some(version_id) = &input_metadata.version_id {
+        let txn_number = version_id
+            .parse::<i64>()
+            .map_err(|_| ReplayError::InvalidVersionId(version_id.to_owned()))?;
+        // The version ID must identify an operation on the DID.
+        if !operations.iter().any(|op| op.txn_number == txn_number) {
+            return Err(ReplayError::InvalidVersionId(version_id.to_owned()));
+        }
+        operations.retain(|op| op.txn_number <= txn_number);
+    }
+    if let Some(version_time) = &input_metadata.version_time {
+        let unixtime = DateTime::parse_from_rfc3339(version_time)
+            .map_err(|_| ReplayError::InvalidVersionTime(version_time.to_owned()))?
+            .timestamp();
+        operations = select_operations_at(operations, unixtime, block_height_to_unixtime)?;
+    }
+    Ok(operations)
+}
+
+/// Selects the operations anchored in blocks with a Unix time up to the given time. The passed
+/// function gets the Unix time of a block from its height.
+pub fn select_operations_at<F>(
+    operations: Vec<AnchoredOperation>,
+    unixtime: i64,
+    mut block_height_to_unixtime: F,
+) -> Result<Vec<AnchoredOperation>, ReplayError>
+where
+    F: FnMut(u32) -> Result<u32, ReplayError>,
+{
+    let mut block_times: HashMap<u32, u32> = HashMap::new();
+    let mut selected = Vec::new();
+    for op in operations {
+        let block_time = match block_times.get(&op.txn_time) {
+            Some(block_time) => *block_time,
+            None => {
+                let block_time = block_height_to_unixtime(op.txn_time)?;
+                block_times.insert(op.txn_time, block_time);
+                block_time
+            }
+        };
+        if i64::from(block_time) <= unixtime {
+            selected.push(op);
+        }
+    }
+    Ok(selected)
+}
+
+/// Queries the configured ION MongoDB for all operations on a DID suffix.
+pub(crate) async fn query_operations(
+    config: &TrustchainConfig,
+    did_suffix: &str,
+) -> Result<Vec<AnchoredOperation>, Box<dyn std::error::Error>> {
+    let client_options = ClientOptions::parse(&config.mongo.uri).await?;
+    let client = Client::with_options(client_options)?;
+    let options = FindOptions::builder()
+        .sort(doc! { MONGO_FILTER_TXN_NUMBER : 1, MONGO_FILTER_OP_INDEX : 1 })
+        .build();
+
+    let mut cursor = client
+        .database(&config.mongo_database())
+        .collection::<mongodb::bson::Document>(MONGO_COLLECTION_OPERATIONS)
+        .find(doc! { MONGO_FILTER_DID_SUFFIX : did_suffix }, options)
+        .await?;
+
+    let mut operations = Vec::new();
+    while let Some(doc) = cursor.try_next().await? {
+        // The operation buffer holds the operation request as submitted to the ION node.
+        let operation = serde_json::from_slice(doc.get_binary_generic(MONGO_OPERATION_BUFFER)?)?;
+        operations.push(AnchoredOperation {
+            operation,
+            txn_time: u32::try_from(doc.get_i32(MONGO_FILTER_TXN_TIME)?)?,
+            txn_number: doc.get_i64(MONGO_FILTER_TXN_NUMBER)?,
+            op_index: doc.get_i32(MONGO_FILTER_OP_INDEX)?,
+        });
+    }
+    Ok(operations)
+}
+
+/// DID resolver for historical ION DID state. Since the ION node only resolves the current state of
+/// a DID, resolutions with a `versionId` or `versionTime` are performed by replaying the DID's
+/// operations from the ION MongoDB. Other resolutions are passed to the wrapped resolver.
+pub struct ReplayResolver<T: DIDResolver> {
+    wrapped_resolver: T,
+    config: TrustchainConfig,
+}
+
+impl<T: DIDResolver> ReplayResolver<T> {
+    /// Constructs a new ReplayResolver with the default configuration.
+    pub fn new(resolver: T) -> Self {
+        Self::with_config(resolver, TrustchainConfig::default())
+    }
+
+    /// Constructs a new ReplayResolver querying the ION network, MongoDB and Bitcoin Core node
+    /// in the given configuration.
+    pub fn with_config(resolver: T, config: TrustchainConfig) -> Self {
+        Self {
+            wrapped_resolver: resolver,
+            config,
+        }
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl<T: DIDResolver + Sync + Send> DIDResolver for ReplayResolver<T> {
+    async fn resolve(
+        &self,
+        did: &str,
+        input_metadata: &ResolutionInputMetadata,
+    ) -> (
+        ResolutionMetadata,
+        Option<Document>,
+        Option<DocumentMetadata>,
+    ) {
+        if input_metadata.version_id.is_none() && input_metadata.version_time.is_none() {
+            return self.wrapped_resolver.resolve(did, input_metadata).await;
+        }
+
+        // Only short-form DIDs on the configured network have anchored operations to replay.
+        let did_suffix = match self.config.did_suffix(did) {
+            Some(did_suffix) => did_suffix,
+            None => {
+                return (
+                    ResolutionMetadata::from_error(ERROR_INVALID_DID),
+                    None,
+                    None,
+                )
+            }
+        };
+
+        let operations = match query_operations(&self.config, did_suffix).await {
+            Ok(operations) => operations,
+            Err(e) => {
+                eprintln!("{}", e);
+                return (
+                    ResolutionMetadata::from_error(ERROR_VERSION_NOT_AVAILABLE),
+                    None,
+                    None,
+                );
+            }
+        };
+        let operations = match select_operations(operations, input_metadata, |block_height| {
+            block_height_to_unixtime(&self.config, block_height)
+                .map_err(|_| ReplayError::FailureToGetUnixTime(block_height))
+        }) {
+            Ok(operations) => operations,
+            Err(e) => {
+                eprintln!("{}", e);
+                return (
+                    ResolutionMetadata::from_error(ERROR_VERSION_NOT_AVAILABLE),
+                    None,
+                    None,
+                );
+            }
+        };
+
+        // If no valid create operation precedes the requested version, the DID did not exist.
+        match DIDState::replay(did_suffix, operations) {
+            Ok(state) => state.to_resolution(did),
+            Err(_) => (ResolutionMetadata::from_error(ERROR_NOT_FOUND), None, None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use did_ion::sidetree::{PublicKeyEntry, PublicKeyJwk, ServiceEndpointEntry};
+    use ssi::did::ServiceEndpoint;
+    use ssi::jwk::JWK;
+    use trustchain_core::utils::generate_key;
+
+    // Helper function anchors an operation in the given transaction.
+    fn anchored(operation: Operation, txn_time: u32, txn_number: i64) -> AnchoredOperation {
+        AnchoredOperation {
+            operation,
+            txn_time,
+            txn_number,
+            op_index: 0,
+        }
+    }
+
+    fn public_key(key: &JWK) -> PublicKeyJwk {
+        PublicKeyJwk::try_from(key.to_public()).unwrap()
+    }
+
+    fn service(id: &str) -> ServiceEndpointEntry {
+        ServiceEndpointEntry {
+            id: id.to_string(),
+            r#type: "Identity".to_string(),
+            service_endpoint: ServiceEndpoint::URI(format!("https://example.com/{}", id)),
+        }
+    }
+
+    fn service_ids(state: &DIDState) -> Vec<String> {
+        state
+            .document
+            .services
+            .iter()
+            .flatten()
+            .map(|service| service.id.to_owned())
+            .collect()
+    }
+
+    // Helper function returns a DID suffix and its create, update, recover and deactivate
+    // operations, together with an update operation signed with the wrong key.
+    fn test_operations() -> (String, Vec<AnchoredOperation>, AnchoredOperation) {
+        let (update_key, recovery_key) = (generate_key(), generate_key());
+        let signing_key = generate_key();
+        let create = ION::create_existing(
+            &public_key(&update_key),
+            &public_key(&recovery_key),
+            vec![DIDStatePatch::Replace {
+                document: DocumentState {
+                    public_keys: Some(vec![PublicKeyEntry::try_from(signing_key).unwrap()]),
+                    services: Some(vec![service("first")]),
+                },
+            }],
+        )
+        .unwrap();
+        let did_suffix = match &create {
+            Operation::Create(create) => {
+                DIDSuffix::from(SidetreeDID::<ION>::from_create_operation(create).unwrap())
+            }
+            _ => panic!(),
+        };
+
+        let next_update_key = generate_key();
+        let update = ION::update(
+            did_suffix.clone(),
+            &update_key,
+            &public_key(&next_update_key),
+            vec![DIDStatePatch::AddServices {
+                services: vec![service("second")],
+            }],
+        )
+        .unwrap();
+        let invalid_update = ION::update(
+            did_suffix.clone(),
+            &generate_key(),
+            &public_key(&generate_key()),
+            vec![DIDStatePatch::RemoveServices {
+                ids: vec!["first".to_string()],
+            }],
+        )
+        .unwrap();
+
+        let next_recovery_key = generate_key();
+        let recover = ION::recover_existing(
+            did_suffix.clone(),
+            &recovery_key,
+            &public_key(&generate_key()),
+            &public_key(&next_recovery_key),
+            vec![DIDStatePatch::Replace {
+                document: DocumentState {
+                    public_keys: None,
+                    services: Some(vec![service("recovered")]),
+                },
+            }],
+        )
+        .unwrap();
+        let deactivate = ION::deactivate(did_suffix.clone(), next_recovery_key).unwrap();
+
+        (
+            did_suffix.0,
+            vec![
+                anchored(create, 100, 1000),
+                anchored(Operation::Update(update), 101, 1010),
+                anchored(recover, 102, 1020),
+                anchored(Operation::Deactivate(deactivate), 103, 1030),
+            ],
+            anchored(Operation::Update(invalid_update), 101, 1011),
+        )
+    }
+
+    #[test]
+    fn test_replay() {
+        let (did_suffix, operations, invalid_update) = test_operations();
+
+        // No state without a create operation.
+        let result = DIDState::replay(&did_suffix, operations[1..].to_vec());
+        assert_eq!(
+            result.unwrap_err(),
+            ReplayError::NoCreateOperation(did_suffix.to_owned())
+        );
+
+        let state = DIDState::replay(&did_suffix, operations[..1].to_vec()).unwrap();
+        assert_eq!(service_ids(&state), vec!["first"]);
+        assert_eq!(state.document.public_keys.as_ref().unwrap().len(), 1);
+        assert!(state.update_commitment.is_some());
+        assert!(!state.deactivated);
+
+        // Operations are replayed in ledger order and invalid operations are ignored.
+        let mut replayed = vec![invalid_update, operations[1].clone(), operations[0].clone()];
+        let state = DIDState::replay(&did_suffix, replayed.clone()).unwrap();
+        assert_eq!(service_ids(&state), vec!["first", "second"]);
+
+        // Recovery replaces the document state.
+        replayed.push(operations[2].clone());
+        let state = DIDState::replay(&did_suffix, replayed.clone()).unwrap();
+        assert_eq!(service_ids(&state), vec!["recovered"]);
+        assert!(state.document.public_keys.is_none());
+
+        replayed.push(operations[3].clone());
+        let state = DIDState::replay(&did_suffix, replayed).unwrap();
+        assert!(state.deactivated);
+        assert!(state.recovery_commitment.is_none());
+
+        // Operations on another DID suffix are ignored.
+        let (other_suffix, other_operations, _) = test_operations();
+        let mut replayed = operations[..1].to_vec();
+        replayed.push(other_operations[1].clone());
+        let state = DIDState::replay(&did_suffix, replayed).unwrap();
+        assert_eq!(service_ids(&state), vec!["first"]);
+        assert_ne!(did_suffix, other_suffix);
+    }
+
+    #[test]
+    fn test_proof_transaction_number() {
+        let update_key = generate_key();
+        let create = ION::create_existing(
+            &public_key(&update_key),
+            &public_key(&generate_key()),
+            vec![],
+        )
+        .unwrap();
+        let did_suffix = match &create {
+            Operation::Create(create) => {
+                DIDSuffix::from(SidetreeDID::<ION>::from_create_operation(create).unwrap())
+            }
+            _ => panic!(),
+        };
+        let mut update_keys = vec![update_key];
+        let mut update = |patches| {
+            let next_update_key = generate_key();
+            let update = ION::update(
+                did_suffix.clone(),
+                update_keys.last().unwrap(),
+                &public_key(&next_update_key),
+                patches,
+            )
+            .unwrap();
+            update_keys.push(next_update_key);
+            Operation::Update(update)
+        };
+        let add_proof = |proof: &str| DIDStatePatch::AddServices {
+            services: vec![ServiceEndpointEntry {
+                id: TRUSTCHAIN_PROOF_SERVICE_ID_VALUE.to_string(),
+                r#type: "TrustchainProofService".to_string(),
+                service_endpoint: ServiceEndpoint::URI(proof.to_string()),
+            }],
+        };
+        let remove_proof = || DIDStatePatch::RemoveServices {
+            ids: vec![TRUSTCHAIN_PROOF_SERVICE_ID_VALUE.to_string()],
+        };
+        let mut operations = vec![
+            anchored(create, 100, 1000),
+            anchored(update(vec![add_proof("first")]), 101, 1010),
+            anchored(
+                update(vec![DIDStatePatch::AddServices {
+                    services: vec![service("unrelated")],
+                }]),
+                102,
+                1020,
+            ),
+        ];
+        let suffix = &did_suffix.0;
+        assert_eq!(
+            DIDState::proof_transaction_number(suffix, operations[..1].to_vec()),
+            None
+        );
+        // The later update leaves the proof unchanged.
+        assert_eq!(
+            DIDState::proof_transaction_number(suffix, operations.clone()),
+            Some(1010)
+        );
+
+        // Replacing the proof publishes a new one.
+        operations.push(anchored(
+            update(vec![remove_proof(), add_proof("second")]),
+            103,
+            1030,
+        ));
+        assert_eq!(
+            DIDState::proof_transaction_number(suffix, operations.clone()),
+            Some(1030)
+        );
+        operations.push(anchored(update(vec![remove_proof()]), 104, 1040));
+        assert_eq!(DIDState::proof_transaction_number(suffix, operations), None);
+    }
+
+    #[test]
+    fn test_to_resolution() {
+        let (did_suffix, operations, _) = test_operations();
+        let did = format!("did:ion:test:{}", did_suffix);
+
+        let state = DIDState::replay(&did_suffix, operations[..2].to_vec()).unwrap();
+        let (res_meta, doc, doc_meta) = state.to_resolution(&did);
+        assert!(res_meta.error.is_none());
+        let doc = doc.unwrap();
+        assert_eq!(doc.id, did);
+        let key_id = &state.document.public_keys.as_ref().unwrap()[0].id;
+        assert_eq!(
+            serde_json::to_value(&doc.assertion_method).unwrap(),
+            json!([format!("#{}", key_id)])
+        );
+        assert_eq!(doc.service.unwrap().len(), 2);
+        let doc_meta = doc_meta.unwrap();
+        assert!(doc_meta.deactivated.is_none());
+        match doc_meta.property_set.unwrap().get("method") {
+            Some(Metadata::Map(method)) => match method.get("updateCommitment") {
+                Some(Metadata::String(update_commitment)) => {
+                    assert_eq!(Some(update_commitment), state.update_commitment.as_ref())
+                }
+                _ => panic!(),
+            },
+            _ => panic!(),
+        }
+
+        let state = DIDState::replay(&did_suffix, operations).unwrap();
+        let (_, doc, doc_meta) = state.to_resolution(&did);
+        assert!(doc.unwrap().verification_method.is_none());
+        assert_eq!(doc_meta.unwrap().deactivated, Some(true));
+    }
+
+    #[test]
+    fn test_select_operations() {
+        let (_, operations, _) = test_operations();
+        let block_time = |block_height: u32| Ok(1666000000 + 100 * (block_height - 100));
+
+        // Without a version all operations are selected.
+        let input_metadata = ResolutionInputMetadata::default();
+        let selected = select_operations(operations.clone(), &input_metadata, block_time).unwrap();
+        assert_eq!(selected.len(), 4);
+
+        let input_metadata = ResolutionInputMetadata {
+            version_id: Some("1010".to_string()),
+            ..Default::default()
+        };
+        let selected = select_operations(operations.clone(), &input_metadata, block_time).unwrap();
+        assert_eq!(selected.len(), 2);
+
+        let input_metadata = ResolutionInputMetadata {
+            version_id: Some("1011".to_string()),
+            ..Default::default()
+        };
+        let result = select_operations(operations.clone(), &input_metadata, block_time);
+        assert_eq!(
+            result.unwrap_err(),
+            ReplayError::InvalidVersionId("1011".to_string())
+        );
+
+        // 2022-10-17T09:50:00Z is the time of the third block.
+        let input_metadata = ResolutionInputMetadata {
+            version_time: Some("2022-10-17T09:50:00Z".to_string()),
+            ..Default::default()
+        };
+        let selected = select_operations(operations.clone(), &input_metadata, block_time).unwrap();
+        assert_eq!(selected.len(), 3);
+
+        let input_metadata = ResolutionInputMetadata {
+            version_time: Some("not a time".to_string()),
+            ..Default::default()
+        };
+        let result = select_operations(operations, &input_metadata, block_time);
+        assert!(matches!(result, Err(ReplayError::InvalidVersionTime(_))));
+    }
+
+    #[test]
+    #[ignore = "Integration test requires MongoDB and Bitcoin Core"]
+    fn test_replay_resolver() {
+        let resolver = trustchain_core::resolver::Resolver::new(ReplayResolver::new(
+            ssi::did_resolve::HTTPDIDResolver::new("http://localhost:3000/"),
+        ));
+        let did = "did:ion:test:EiCClfEdkTv_aM3UnBBhlOV89LlGhpQAbfeZLFdFxVFkEg";
+
+        // The root DID was created in block 2377445 at Unix time 1666265405.
+        let input_metadata = ResolutionInputMetadata {
+            version_time: Some("2022-10-20T11:30:05Z".to_string()),
+            ..Default::default()
+        };
+        let (_, doc, _) = resolver
+            .resolve_as_result_with_input(did, &input_metadata)
+            .unwrap();
+        assert_eq!(doc.unwrap().id, did);
+
+        let input_metadata = ResolutionInputMetadata {
+            version_time: Some("2022-10-20T11:30:04Z".to_string()),
+            ..Default::default()
+        };
+        assert!(resolver
+            .resolve_as_result_with_input(did, &input_metadata)
+            .is_err());
+    }
+}
@@ -1,22 +1,57 @@
-use did_ion::{sidetree::SidetreeClient, ION};
 use serde_json::to_string_pretty as to_json;
+use ssi::did_resolve::{DIDResolver, Metadata, ResolutionInputMetadata};
+use std::collections::HashMap;
+use trustchain_core::verifier::{Verifier, VerifyingResolver, TRUSTCHAIN_VERIFY};
 
-use crate::IONResolver;
+use crate::config::TrustchainConfig;
+use crate::get_ion_resolver_from_config;
+use crate::verifier::IONVerifier;
 
-// Binary to resolve a passed DID from the command line.
-pub fn main_resolve(did: &str, _verbose: bool) -> Result<(), Box<dyn std::error::Error>> {
+// Binary to resolve a passed DID from the command line. If a root event time is passed, the DID
+// is also Trustchain-verified and the outcome added to the resolution metadata, accepting
+// unpublished DIDs in the chain if allowed.
+pub fn main_resolve(
+    did: &str,
+    config: &TrustchainConfig,
+    root_event_time: Option<u32>,
+    allow_unpublished: bool,
+    _verbose: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
     // Construct a Trustchain Resolver from a Sidetree (ION) DIDMethod.
-    let resolver = IONResolver::from(SidetreeClient::<ION>::new(Some(String::from(
-        "http://localhost:3000/",
-    ))));
+    let resolver = get_ion_resolver_from_config(config);
 
     // Result metadata, Document, Document metadata
-    let result = resolver.resolve_as_result(did);
+    let result = match root_event_time {
+        Some(root_event_time) => {
+            let verifier = IONVerifier::with_config(resolver, config.clone())
+                .allow_unpublished(allow_unpublished);
+            let resolver = VerifyingResolver::new(verifier, root_event_time);
+            let input_metadata = ResolutionInputMetadata {
+                property_set: Some(HashMap::from([(
+                    TRUSTCHAIN_VERIFY.to_string(),
+                    Metadata::Boolean(true),
+                )])),
+                ..Default::default()
+            };
+            let (res_meta, doc, doc_meta) = resolver
+                .verifier()
+                .resolver()
+                .runtime
+                .block_on(resolver.resolve(did, &input_metadata));
+            match res_meta.error {
+                Some(e) => Err(e.into()),
+                None => Ok((res_meta, doc, doc_meta)),
+            }
+        }
+        None => resolver
+            .resolve_as_result(did)
+            .map_err(|e| -> Box<dyn std::error::Error> { Box::new(e) }),
+    };
     let (res_meta, doc, doc_meta) = match result {
         Ok(x) => x,
         Err(e) => {
             eprintln!("{e}");
-            return Err(Box::new(e));
+            return Err(e);
         }
     };
 
@@ -43,3 +78,36 @@ pub fn main_resolve(did: &str, _verbose: bool) -> Result<(), Box<dyn std::error:
     );
     Ok(())
 }
+
+// Binary to dereference a passed DID URL from the command line.
+pub fn main_dereference(
+    did_url: &str,
+    config: &TrustchainConfig,
+    _verbose: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let resolver = get_ion_resolver_from_config(config);
+    let (deref_meta, content, content_meta) = match resolver.dereference_as_result(did_url) {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("{e}");
+            return Err(Box::new(e));
+        }
+    };
+
+    // Print results
+    println!("---");
+    println!("Trustchain dereferenced content, content metadata and dereferencing metadata");
+    println!("---");
+    println!("Content:");
+    println!("{}", to_json(&content).expect("Cannot convert to JSON."));
+    println!("---");
+    println!("Content metadata:");
+    println!(
+        "{}",
+        to_json(&content_meta).expect("Cannot convert to JSON.")
+    );
+    println!("---");
+    println!("Dereferencing metadata:");
+    println!("{}", to_json(&deref_meta).expect("Cannot convert to JSON."));
+    Ok(())
+}
@@ -1,13 +1,19 @@
+use crate::config::TrustchainConfig;
+use crate::replay::{
+    query_operations, select_operations_at, AnchoredOperation, DIDState, ReplayError,
+};
 use crate::{
-    BITCOIN_CONNECTION_STRING, BITCOIN_RPC_PASSWORD, BITCOIN_RPC_USERNAME,
-    MONGO_COLLECTION_OPERATIONS, MONGO_CONNECTION_STRING, MONGO_CREATE_OPERATION,
-    MONGO_DATABASE_ION_TESTNET_CORE, MONGO_FILTER_DID_SUFFIX, MONGO_FILTER_TYPE,
+    get_ion_resolver_from_config, MONGO_COLLECTION_OPERATIONS, MONGO_CREATE_OPERATION,
+    MONGO_FILTER_DID_SUFFIX, MONGO_FILTER_TXN_TIME, MONGO_FILTER_TYPE, MONGO_OPERATION_BUFFER,
 };
 use bitcoincore_rpc::RpcApi;
+use did_ion::sidetree::{Operation, Sidetree, SidetreeOperation};
+use did_ion::ION;
 use futures::executor::block_on;
 use mongodb::{bson::doc, options::ClientOptions, Client};
 use ssi::did_resolve::DIDResolver;
 use std::convert::TryFrom;
+use trustchain_core::dispatch::{did_method, DispatchResolver};
 use trustchain_core::resolver::Resolver;
 use trustchain_core::utils::get_did_suffix;
 use trustchain_core::verifier::{Verifier, VerifierError};
@@ -21,23 +27,61 @@ where
     T: Sync + Send + DIDResolver,
 {
     resolver: Resolver<T>,
+    config: TrustchainConfig,
+    allow_unpublished: bool,
+}
+
+impl IONVerifier<DispatchResolver> {
+    /// Constructs a new IONVerifier with the resolver made from the given configuration, caching
+    /// ION DID resolutions if a cache is configured.
+    pub fn from_config(config: TrustchainConfig) -> Self {
+        Self::with_config(get_ion_resolver_from_config(&config), config)
+    }
 }
 
 impl<T> IONVerifier<T>
 where
     T: Send + Sync + DIDResolver,
 {
-    /// Constructs a new IONVerifier.
+    /// Constructs a new IONVerifier with the default configuration.
     pub fn new(resolver: Resolver<T>) -> Self {
-        Self { resolver }
+        Self::with_config(resolver, TrustchainConfig::default())
+    }
+
+    /// Constructs a new IONVerifier querying the ION network, MongoDB and Bitcoin Core node in
+    /// the given configuration.
+    pub fn with_config(resolver: Resolver<T>, config: TrustchainConfig) -> Self {
+        Self {
+            resolver,
+            config,
+            allow_unpublished: false,
+        }
+    }
+
+    /// Sets whether downstream DIDs that have not been anchored (i.e. long-form DIDs resolved from
+    /// their initial state) are accepted in verified chains. Such links are marked as
+    /// unpublished in the chain.
+    pub fn allow_unpublished(mut self, allow_unpublished: bool) -> Self {
+        self.allow_unpublished = allow_unpublished;
+        self
+    }
+
+    /// Returns the suffix of a short-form DID on the configured ION network.
+    fn did_suffix<'a>(&self, did: &'a str) -> Result<&'a str, VerifierError> {
+        self.config.did_suffix(did).ok_or_else(|| {
+            VerifierError::FailureToGetDIDOperations(
+                did.to_owned(),
+                format!("Not a short-form DID on ION {}.", self.config.network),
+            )
+        })
     }
 
     /// Returns the ledger transaction representing the ION DID operation.
     fn transaction_index(&self, did: &str) -> Result<TransactionIndex, VerifierError> {
-        let suffix = get_did_suffix(did);
+        let suffix = self.did_suffix(did)?;
         self.resolver().runtime.block_on(async {
             // Query the database.
-            let doc = match block_on(Self::query_mongo(suffix)) {
+            let doc = match block_on(Self::query_mongo(&self.config, suffix)) {
                 Ok(x) => x,
                 Err(e) => {
                     return Err(VerifierError::FailureToGetDIDOperation(
@@ -47,8 +91,11 @@ where
                 }
             };
 
+            // Check the stored create operation commits to the DID before trusting its anchoring.
+            verify_create_operation(did, &doc)?;
+
             // Extract the block height.
-            let block_height: u32 = match doc.get_i32("txnTime") {
+            let block_height: u32 = match doc.get_i32(MONGO_FILTER_TXN_TIME) {
                 Ok(x) => match u32::try_from(x) {
                     Ok(y) => y,
                     Err(_) => return Err(VerifierError::InvalidBlockHeight(x)),
@@ -95,13 +142,61 @@ where
         })
     }
 
+    /// Returns the anchored operations on a DID, only those anchored up to the version time (a
+    /// Unix time) if given.
+    fn operations(
+        &self,
+        did: &str,
+        version_time: Option<u32>,
+    ) -> Result<Vec<AnchoredOperation>, VerifierError> {
+        let suffix = self.did_suffix(did)?;
+        let operations = self
+            .resolver()
+            .runtime
+            .block_on(query_operations(&self.config, suffix))
+            .map_err(|e| VerifierError::FailureToGetDIDOperations(did.to_owned(), e.to_string()))?;
+        match version_time {
+            Some(version_time) => {
+                select_operations_at(operations, version_time.into(), |block_height| {
+                    block_height_to_unixtime(&self.config, block_height)
+                        .map_err(|_| ReplayError::FailureToGetUnixTime(block_height))
+                })
+                .map_err(|e| {
+                    VerifierError::FailureToGetDIDOperations(did.to_owned(), e.to_string())
+                })
+            }
+            None => Ok(operations),
+        }
+    }
+
+    /// Returns the transaction number of the operation that published the Trustchain proof of a
+    /// DID, current as of the version time if given.
+    fn proof_transaction_number(
+        &self,
+        did: &str,
+        version_time: Option<u32>,
+    ) -> Result<i64, VerifierError> {
+        let suffix = self.did_suffix(did)?;
+        DIDState::proof_transaction_number(suffix, self.operations(did, version_time)?).ok_or_else(
+            || {
+                VerifierError::FailureToGetDIDOperations(
+                    did.to_owned(),
+                    "No operation published the Trustchain proof.".to_string(),
+                )
+            },
+        )
+    }
+
     /// Queries the ION MongoDB for a DID operation.
-    async fn query_mongo(did: &str) -> Result<mongodb::bson::Document, Box<dyn std::error::Error>> {
-        let client_options = ClientOptions::parse(MONGO_CONNECTION_STRING).await?;
+    async fn query_mongo(
+        config: &TrustchainConfig,
+        did: &str,
+    ) -> Result<mongodb::bson::Document, Box<dyn std::error::Error>> {
+        let client_options = ClientOptions::parse(&config.mongo.uri).await?;
         let client = Client::with_options(client_options)?;
 
         let query_result = client
-            .database(MONGO_DATABASE_ION_TESTNET_CORE)
+            .database(&config.mongo_database())
             .collection(MONGO_COLLECTION_OPERATIONS)
             .find_one(
                 doc! {
@@ -141,35 +236,214 @@ where
         todo!()
     }
 
+    fn recovered_since_attestation(
+        &self,
+        did: &str,
+        udid: &str,
+        version_time: Option<u32>,
+    ) -> Result<bool, VerifierError> {
+        // Recovery is only recorded for DIDs anchored with ION.
+        if did_method(udid) != Some("ion") {
+            return Ok(false);
+        }
+        // If the upstream DID had not been recovered (by the version time, if given) there is
+        // nothing to flag.
+        let recovery = match self
+            .operations(udid, version_time)?
+            .iter()
+            .filter(|op| matches!(op.operation, Operation::Recover(_)))
+            .map(|op| op.txn_number)
+            .max()
+        {
+            Some(txn_number) => txn_number,
+            None => return Ok(false),
+        };
+        // The time of attestation to a DID of another method is unknown, so it is flagged.
+        if did_method(did) != Some("ion") {
+            return Ok(true);
+        }
+        // Attestations are published in update operations (or in the create operation). The
+        // operation publishing the current proof is taken as the time of attestation, so that
+        // later updates to the DID do not hide a recovery of the upstream DID.
+        let attestation = self.proof_transaction_number(did, version_time)?;
+        Ok(recovery > attestation)
+    }
+
     fn resolver(&self) -> &Resolver<T> {
         &self.resolver
     }
+    fn unpublished_allowed(&self) -> bool {
+        self.allow_unpublished
+    }
     fn block_height_to_unixtime(&self, block_height: u32) -> Result<u32, VerifierError> {
-        let rpc = bitcoincore_rpc::Client::new(
-            BITCOIN_CONNECTION_STRING,
-            bitcoincore_rpc::Auth::UserPass(
-                BITCOIN_RPC_USERNAME.to_string(),
-                BITCOIN_RPC_PASSWORD.to_string(),
-            ),
-        )
-        .unwrap();
+        block_height_to_unixtime(&self.config, block_height)
+    }
+}
 
-        let block_hash = rpc.get_block_hash(u64::from(block_height)).unwrap();
-        let block_header = rpc.get_block_header(&block_hash).unwrap();
-        Ok(block_header.time)
+/// Verifies that the create operation in an ION MongoDB operation record is the create operation
+/// of the DID: its suffix data must hash to the DID suffix and its delta (the initial DID state)
+/// must match the delta hash committed to in the suffix data.
+fn verify_create_operation(did: &str, doc: &mongodb::bson::Document) -> Result<(), VerifierError> {
+    let suffix = get_did_suffix(did);
+    let invalid = |reason: String| VerifierError::InvalidCreateOperation(did.to_owned(), reason);
+
+    match doc.get_str(MONGO_FILTER_DID_SUFFIX) {
+        Ok(record_suffix) if record_suffix == suffix => (),
+        Ok(record_suffix) => {
+            return Err(invalid(format!(
+                "operation record is for DID suffix: {}",
+                record_suffix
+            )))
+        }
+        Err(e) => return Err(invalid(e.to_string())),
     }
+
+    // The operation buffer holds the operation request as submitted to the ION node.
+    let buffer = doc
+        .get_binary_generic(MONGO_OPERATION_BUFFER)
+        .map_err(|e| invalid(e.to_string()))?;
+    let create = match serde_json::from_slice(buffer) {
+        Ok(Operation::Create(create)) => create,
+        Ok(_) => return Err(invalid("not a create operation".to_string())),
+        Err(e) => return Err(invalid(e.to_string())),
+    };
+
+    let derived_suffix =
+        ION::serialize_suffix_data(&create.suffix_data).map_err(|e| invalid(e.to_string()))?;
+    if derived_suffix.0 != suffix {
+        return Err(VerifierError::DIDSuffixMismatch(
+            did.to_owned(),
+            derived_suffix.0,
+        ));
+    }
+    Operation::Create(create)
+        .partial_verify::<ION>()
+        .map_err(|e| invalid(e.to_string()))?;
+    Ok(())
+}
+
+/// Converts a block height to the Unix time in the block header, queried from the configured
+/// Bitcoin Core node.
+pub fn block_height_to_unixtime(
+    config: &TrustchainConfig,
+    block_height: u32,
+) -> Result<u32, VerifierError> {
+    let rpc = bitcoincore_rpc::Client::new(config.bitcoin_url(), config.bitcoin_auth().into())
+        .map_err(|_| VerifierError::FailureToGetUnixTime(block_height))?;
+
+    let block_hash = rpc
+        .get_block_hash(u64::from(block_height))
+        .map_err(|_| VerifierError::FailureToGetUnixTime(block_height))?;
+    let block_header = rpc
+        .get_block_header(&block_hash)
+        .map_err(|_| VerifierError::FailureToGetUnixTime(block_height))?;
+    Ok(block_header.time)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use did_ion::sidetree::{DIDSuffix, PublicKeyJwk, SidetreeDID};
+    use mongodb::bson::spec::BinarySubtype;
+    use mongodb::bson::Binary;
     use ssi::did_resolve::HTTPDIDResolver;
+    use trustchain_core::utils::generate_key;
 
     // Helper function for generating a placeholder HTTP resolver only for tests not querying ION.
     fn get_http_resolver() -> HTTPDIDResolver {
         HTTPDIDResolver::new("http://localhost:3000/")
     }
 
+    /// Returns a new create operation with its short-form DID.
+    fn create_operation() -> (String, Operation) {
+        let update_pk = PublicKeyJwk::try_from(generate_key().to_public()).unwrap();
+        let recovery_pk = PublicKeyJwk::try_from(generate_key().to_public()).unwrap();
+        let operation = ION::create_existing(&update_pk, &recovery_pk, vec![]).unwrap();
+        let did = match &operation {
+            Operation::Create(create) => SidetreeDID::<ION>::from_create_operation(create).unwrap(),
+            _ => unreachable!(),
+        };
+        let did = did.to_string().rsplit_once(':').unwrap().0.to_owned();
+        (did, operation)
+    }
+
+    /// Returns an ION MongoDB create operation record for a DID.
+    fn operation_record(did: &str, operation: &Operation) -> mongodb::bson::Document {
+        doc! {
+            MONGO_FILTER_TYPE: MONGO_CREATE_OPERATION,
+            MONGO_FILTER_DID_SUFFIX: get_did_suffix(did),
+            MONGO_OPERATION_BUFFER: Binary {
+                subtype: BinarySubtype::Generic,
+                bytes: serde_json::to_vec(operation).unwrap(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_verify_create_operation() {
+        let (did, operation) = create_operation();
+        assert!(verify_create_operation(&did, &operation_record(&did, &operation)).is_ok());
+
+        // Create operation of another DID stored against the DID suffix.
+        let (other_did, other_operation) = create_operation();
+        assert_eq!(
+            verify_create_operation(&did, &operation_record(&did, &other_operation)),
+            Err(VerifierError::DIDSuffixMismatch(
+                did.to_owned(),
+                get_did_suffix(&other_did).to_owned()
+            ))
+        );
+
+        // Record for another DID suffix.
+        assert!(matches!(
+            verify_create_operation(&did, &operation_record(&other_did, &operation)),
+            Err(VerifierError::InvalidCreateOperation(_, _))
+        ));
+
+        // Initial state not matching the delta hash in the suffix data.
+        let tampered = match operation.clone() {
+            Operation::Create(mut create) => {
+                create.delta.update_commitment = "EiAtampered".to_string();
+                Operation::Create(create)
+            }
+            _ => unreachable!(),
+        };
+        assert!(matches!(
+            verify_create_operation(&did, &operation_record(&did, &tampered)),
+            Err(VerifierError::InvalidCreateOperation(_, _))
+        ));
+
+        // Not a create operation.
+        let deactivate = Operation::Deactivate(
+            ION::deactivate(DIDSuffix(get_did_suffix(&did).to_owned()), generate_key()).unwrap(),
+        );
+        assert!(matches!(
+            verify_create_operation(&did, &operation_record(&did, &deactivate)),
+            Err(VerifierError::InvalidCreateOperation(_, _))
+        ));
+    }
+
+    #[test]
+    fn test_did_suffix() {
+        let target = IONVerifier::new(Resolver::new(get_http_resolver()));
+        let suffix = "EiCClfEdkTv_aM3UnBBhlOV89LlGhpQAbfeZLFdFxVFkEg";
+        assert_eq!(
+            target.did_suffix(&format!("did:ion:test:{}", suffix)),
+            Ok(suffix)
+        );
+
+        // DIDs on another network are rejected before any operations are queried.
+        for did in [
+            format!("did:ion:{}", suffix),
+            format!("did:ion:test:{}:longformdata", suffix),
+        ] {
+            assert!(matches!(
+                target.operations(&did, None),
+                Err(VerifierError::FailureToGetDIDOperations(..))
+            ));
+        }
+    }
+
     #[test]
     #[ignore = "Integration test requires MongoDB"]
     fn test_transaction() {
@@ -195,6 +469,18 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    #[ignore = "Integration test requires MongoDB"]
+    fn test_recovered_since_attestation() {
+        let resolver = Resolver::new(get_http_resolver());
+        let target = IONVerifier::new(resolver);
+
+        // Root and root-plus-1 DIDs: the root has not been recovered.
+        let udid = "did:ion:test:EiCClfEdkTv_aM3UnBBhlOV89LlGhpQAbfeZLFdFxVFkEg";
+        let did = "did:ion:test:EiBVpjUxXeSRJpvj2TewlX9zNF3GKMCKWwGmKBZqF6pk_A";
+        assert!(!target.recovered_since_attestation(did, udid, None).unwrap());
+    }
+
     #[test]
     #[ignore = "Requires connection to a Bitcoin core testnet node on http://localhost:18332"]
     fn test_block_height_to_unixtime() {
@@ -1,6 +1,6 @@
 use ssi::ldp::now_ms;
 use std::convert::TryFrom;
-use trustchain_core::issuer::{Issuer, IssuerError};
+use trustchain_core::issuer::{Issuer, IssuerError, ProofOptions};
 use trustchain_ion::attestor::IONAttestor;
 use trustchain_ion::get_ion_resolver;
 
@@ -54,7 +54,10 @@ fn test_sign_credential() {
     // 4. Generate VC and verify
     resolver.runtime.block_on(async {
         // Use attest_credential method instead of generating and adding proof
-        let mut vc_with_proof = attestor.sign(&vc, None, &resolver).await.unwrap();
+        let mut vc_with_proof = attestor
+            .sign(&vc, &ProofOptions::default(), &resolver)
+            .await
+            .unwrap();
 
         // Verify: expect no warnings or errors
         let verification_result = vc_with_proof.verify(None, &resolver).await;
@@ -89,7 +92,9 @@ fn test_sign_credential_failure() {
     // 4. Generate VC and verify
     resolver.runtime.block_on(async {
         // Sign credential (expect failure).
-        let vc_with_proof = attestor.sign(&vc, None, &resolver).await;
+        let vc_with_proof = attestor
+            .sign(&vc, &ProofOptions::default(), &resolver)
+            .await;
         assert!(vc_with_proof.is_err());
         assert!(matches!(
             vc_with_proof,
@@ -0,0 +1,229 @@
+//! Holder client of the OpenID for Verifiable Credentials endpoints, acting as a wallet in tests of
+//! an OID4VC server.
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use ssi::did_resolve::DIDResolver;
+use ssi::vc::{Credential, Presentation};
+use thiserror::Error;
+use tokio::runtime::Runtime;
+use trustchain_core::exchange::presentation_submission;
+use trustchain_core::holder::{Holder, HolderError};
+use trustchain_core::resolver::Resolver;
+use trustchain_core::subject::Subject;
+use trustchain_core::wallet::{Wallet, WalletError};
+use trustchain_ion::attestor::IONAttestor;
+use trustchain_ion::oid4vc::{
+    AuthorizationRequest, AuthorizationResponse, CredentialOffer, CredentialRequest,
+    CredentialResponse, KeyProof, TokenResponse, CREDENTIAL_PATH, PRE_AUTHORIZED_CODE_GRANT_TYPE,
+    TOKEN_PATH,
+};
+use url::Url;
+
+/// An error relating to the OID4VC test client.
+#[derive(Error, Debug)]
+pub enum ClientError {
+    /// Offer or request URI without the expected query parameter.
+    #[error("Invalid URI: {0}")]
+    InvalidUri(String),
+    /// HTTP request failed.
+    #[error("HTTP request failed: {0}")]
+    Http(reqwest::Error),
+    /// Server responded with an error.
+    #[error("Server responded with status {0}: {1}")]
+    Response(u16, String),
+    /// Holder failed to sign.
+    #[error("Holder failed to sign: {0}")]
+    Holder(HolderError),
+    /// Wallet failed to make a presentation.
+    #[error("Wallet failed to make a presentation: {0}")]
+    Wallet(WalletError),
+}
+
+impl From<reqwest::Error> for ClientError {
+    fn from(err: reqwest::Error) -> Self {
+        ClientError::Http(err)
+    }
+}
+
+/// Holder client of an OID4VC server, receiving credentials by the pre-authorized code flow and
+/// answering authorization requests with presentations from a wallet. HTTP requests are made on
+/// the client's runtime and signing on the runtime of the holder's resolver.
+pub struct OID4VCClient {
+    runtime: Runtime,
+    http: reqwest::Client,
+}
+
+impl Default for OID4VCClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OID4VCClient {
+    /// Constructs a new client.
+    pub fn new() -> Self {
+        Self {
+            runtime: tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap(),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Returns the JSON body of a response, or the error of an unsuccessful response.
+    async fn json<R: DeserializeOwned>(response: reqwest::Response) -> Result<R, ClientError> {
+        match response.status() {
+            status if status.is_success() => Ok(response.json().await?),
+            status => Err(ClientError::Response(
+                status.as_u16(),
+                response.text().await?,
+            )),
+        }
+    }
+
+    /// Gets a JSON resource.
+    fn get<R: DeserializeOwned>(&self, url: &str) -> Result<R, ClientError> {
+        self.runtime
+            .block_on(async { Self::json(self.http.get(url).send().await?).await })
+    }
+
+    /// Posts a form, returning the JSON response.
+    fn post_form<B: Serialize, R: DeserializeOwned>(
+        &self,
+        url: &str,
+        body: &B,
+    ) -> Result<R, ClientError> {
+        self.runtime
+            .block_on(async { Self::json(self.http.post(url).form(body).send().await?).await })
+    }
+
+    /// Returns the value of a query parameter of a URI.
+    fn query_parameter(uri: &str, name: &str) -> Result<String, ClientError> {
+        Url::parse(uri)
+            .map_err(|_| ClientError::InvalidUri(uri.to_owned()))?
+            .query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+            .ok_or_else(|| ClientError::InvalidUri(uri.to_owned()))
+    }
+
+    /// Fetches the credential offer referenced by a credential offer URI.
+    pub fn fetch_offer(&self, offer_uri: &str) -> Result<CredentialOffer, ClientError> {
+        self.get(&Self::query_parameter(offer_uri, "credential_offer_uri")?)
+    }
+
+    /// Receives the credential offered by a credential offer URI: exchanges the pre-authorized
+    /// code for an access token, and requests the credential with a key proof by the holder.
+    pub fn receive_credential<T: DIDResolver + Sync + Send>(
+        &self,
+        offer_uri: &str,
+        holder: &IONAttestor,
+        resolver: &Resolver<T>,
+    ) -> Result<Credential, ClientError> {
+        let offer = self.fetch_offer(offer_uri)?;
+        let token: TokenResponse = self.post_form(
+            &format!("{}{}", offer.credential_issuer, TOKEN_PATH),
+            &[
+                ("grant_type", PRE_AUTHORIZED_CODE_GRANT_TYPE),
+                (
+                    "pre-authorized_code",
+                    &offer.grants.pre_authorized_code.pre_authorized_code,
+                ),
+            ],
+        )?;
+        let jwt = resolver
+            .runtime
+            .block_on(holder.sign_key_proof(
+                &offer.credential_issuer,
+                Some(&token.c_nonce),
+                None,
+                resolver,
+            ))
+            .map_err(ClientError::Holder)?;
+        let request = CredentialRequest {
+            format: Some("ldp_vc".to_string()),
+            credential_configuration_id: offer.credential_configuration_ids.first().cloned(),
+            credential_definition: None,
+            proof: Some(KeyProof {
+                proof_type: "jwt".to_string(),
+                jwt: Some(jwt),
+            }),
+        };
+        let response: CredentialResponse = self.runtime.block_on(async {
+            Self::json(
+                self.http
+                    .post(format!("{}{}", offer.credential_issuer, CREDENTIAL_PATH))
+                    .bearer_auth(&token.access_token)
+                    .json(&request)
+                    .send()
+                    .await?,
+            )
+            .await
+        })?;
+        Ok(response.credential)
+    }
+
+    /// Fetches the authorization request referenced by an authorization request URI.
+    pub fn fetch_request(&self, request_uri: &str) -> Result<AuthorizationRequest, ClientError> {
+        self.get(&Self::query_parameter(request_uri, "request_uri")?)
+    }
+
+    /// Posts a presentation, with the presentation submission it contains, in response to an
+    /// authorization request.
+    pub fn respond(
+        &self,
+        request: &AuthorizationRequest,
+        presentation: &Presentation,
+    ) -> Result<(), ClientError> {
+        let submission = presentation_submission(presentation)
+            .map(|submission| serde_json::to_string(&submission).unwrap())
+            .unwrap_or_default();
+        let response = AuthorizationResponse {
+            vp_token: serde_json::to_string(presentation).unwrap(),
+            presentation_submission: submission,
+            state: request.state.to_owned(),
+        };
+        self.post_form::<_, serde_json::Value>(&request.response_uri, &response)
+            .map(|_| ())
+    }
+
+    /// Answers the authorization request referenced by an authorization request URI with a
+    /// presentation by the holder, signed for the request, of credentials in the wallet
+    /// satisfying its presentation definition.
+    pub fn present<T: DIDResolver + Sync + Send>(
+        &self,
+        request_uri: &str,
+        wallet: &Wallet,
+        holder: &IONAttestor,
+        resolver: &Resolver<T>,
+    ) -> Result<(), ClientError> {
+        let request = self.fetch_request(request_uri)?;
+        let presentation = self.sign_presentation(&request, wallet, holder, resolver)?;
+        self.respond(&request, &presentation)
+    }
+
+    /// Makes a presentation by the holder of credentials in the wallet satisfying the
+    /// presentation definition of an authorization request, signed for its nonce and client ID.
+    pub fn sign_presentation<T: DIDResolver + Sync + Send>(
+        &self,
+        request: &AuthorizationRequest,
+        wallet: &Wallet,
+        holder: &IONAttestor,
+        resolver: &Resolver<T>,
+    ) -> Result<Presentation, ClientError> {
+        let presentation = wallet
+            .present_definition(&request.presentation_definition, holder.did())
+            .map_err(ClientError::Wallet)?;
+        resolver
+            .runtime
+            .block_on(holder.sign_presentation(
+                &presentation,
+                None,
+                Some(&request.nonce),
+                Some(&request.client_id),
+                resolver,
+            ))
+            .map_err(ClientError::Holder)
+    }
+}
@@ -0,0 +1,159 @@
+//! ION DIDs with locally held keys, for making operations to submit to a test node.
+use did_ion::sidetree::{
+    DIDStatePatch, DIDSuffix, DocumentState, Operation, PublicKeyEntry, PublicKeyJwk, Sidetree,
+    SidetreeDID,
+};
+use did_ion::ION;
+use ssi::jwk::JWK;
+use std::convert::TryFrom;
+use trustchain_core::utils::generate_key;
+
+/// An ION DID together with its signing, update and recovery keys.
+///
+/// Each operation made rotates the keys it reveals, so the [`TestDID`] always holds the keys
+/// needed for the next operation (once the previous one has been anchored).
+#[derive(Debug, Clone)]
+pub struct TestDID {
+    did: String,
+    long_form_did: String,
+    suffix: String,
+    signing_key: JWK,
+    update_key: JWK,
+    recovery_key: JWK,
+}
+
+/// Returns the document state with a single signing key.
+fn document_state(signing_key: &JWK) -> DocumentState {
+    DocumentState {
+        public_keys: Some(vec![PublicKeyEntry::try_from(signing_key.clone()).unwrap()]),
+        services: None,
+    }
+}
+
+/// Returns the public key JWK of a key.
+fn public_key(key: &JWK) -> PublicKeyJwk {
+    PublicKeyJwk::try_from(key.to_public()).unwrap()
+}
+
+impl TestDID {
+    /// Generates keys for a new DID, returning it with its create operation. The DID document
+    /// contains the signing key.
+    pub fn generate() -> (Self, Operation) {
+        Self::generate_with_patches(Vec::new())
+    }
+
+    /// Generates keys for a new DID, returning it with its create operation. The DID document
+    /// contains the signing key, followed by the changes in the given patches.
+    pub fn generate_with_patches(patches: Vec<DIDStatePatch>) -> (Self, Operation) {
+        let signing_key = generate_key();
+        let update_key = generate_key();
+        let recovery_key = generate_key();
+        let mut all_patches = vec![DIDStatePatch::Replace {
+            document: document_state(&signing_key),
+        }];
+        all_patches.extend(patches);
+        let operation = ION::create_existing(
+            &public_key(&update_key),
+            &public_key(&recovery_key),
+            all_patches,
+        )
+        .unwrap();
+        let long_form_did = match &operation {
+            Operation::Create(create) => SidetreeDID::<ION>::from_create_operation(create).unwrap(),
+            _ => unreachable!(),
+        };
+        let long_form_did = long_form_did.to_string();
+        let suffix = long_form_did.rsplit(':').nth(1).unwrap().to_owned();
+        let did = SidetreeDID::<ION>::Short {
+            did_suffix: DIDSuffix(suffix.to_owned()),
+        }
+        .to_string();
+        let test_did = Self {
+            did,
+            long_form_did,
+            suffix,
+            signing_key,
+            update_key,
+            recovery_key,
+        };
+        (test_did, operation)
+    }
+
+    /// Returns the short-form DID.
+    pub fn did(&self) -> &str {
+        &self.did
+    }
+
+    /// Returns the long-form DID, embedding the initial state of the DID.
+    pub fn long_form_did(&self) -> &str {
+        &self.long_form_did
+    }
+
+    /// Returns the DID suffix.
+    pub fn suffix(&self) -> &str {
+        &self.suffix
+    }
+
+    /// Returns the signing key in the DID document.
+    pub fn signing_key(&self) -> &JWK {
+        &self.signing_key
+    }
+
+    /// Returns the key revealed by the next update operation.
+    pub fn update_key(&self) -> &JWK {
+        &self.update_key
+    }
+
+    /// Returns the key revealed by the next recover or deactivate operation.
+    pub fn recovery_key(&self) -> &JWK {
+        &self.recovery_key
+    }
+
+    /// Makes an update operation applying the given patches, rotating the update key.
+    pub fn update(&mut self, patches: Vec<DIDStatePatch>) -> Operation {
+        let next_update_key = generate_key();
+        let operation = ION::update(
+            DIDSuffix(self.suffix.to_owned()),
+            &self.update_key,
+            &public_key(&next_update_key),
+            patches,
+        )
+        .unwrap();
+        self.update_key = next_update_key;
+        Operation::Update(operation)
+    }
+
+    /// Makes a recover operation replacing the DID document with one containing a new signing
+    /// key, rotating all keys.
+    pub fn recover(&mut self) -> Operation {
+        self.recover_with_signing_key(generate_key())
+    }
+
+    /// Makes a recover operation replacing the DID document with one containing the given signing
+    /// key, rotating the update and recovery keys.
+    pub fn recover_with_signing_key(&mut self, signing_key: JWK) -> Operation {
+        let update_key = generate_key();
+        let recovery_key = generate_key();
+        let operation = ION::recover_existing(
+            DIDSuffix(self.suffix.to_owned()),
+            &self.recovery_key,
+            &public_key(&update_key),
+            &public_key(&recovery_key),
+            vec![DIDStatePatch::Replace {
+                document: document_state(&signing_key),
+            }],
+        )
+        .unwrap();
+        self.signing_key = signing_key;
+        self.update_key = update_key;
+        self.recovery_key = recovery_key;
+        operation
+    }
+
+    /// Makes a deactivate operation.
+    pub fn deactivate(&self) -> Operation {
+        Operation::Deactivate(
+            ION::deactivate(DIDSuffix(self.suffix.to_owned()), self.recovery_key.clone()).unwrap(),
+        )
+    }
+}
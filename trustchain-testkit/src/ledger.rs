@@ -0,0 +1,126 @@
+//! Simulated PoW ledger in which Sidetree operations are anchored.
+use crate::node::NodeError;
+use trustchain_core::ROOT_EVENT_TIME;
+
+/// Block height of the first block mined on a default simulated ledger.
+pub const DEFAULT_START_BLOCK_HEIGHT: u32 = 2377445;
+
+/// Default interval (in seconds) between the timestamps of consecutive blocks.
+pub const DEFAULT_BLOCK_INTERVAL: u32 = 600;
+
+/// Configuration of the block heights and times of a simulated ledger.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LedgerConfig {
+    /// Height of the first block mined.
+    pub start_block_height: u32,
+    /// Unix time of the first block mined.
+    pub start_time: u32,
+    /// Interval (in seconds) between the timestamps of consecutive blocks.
+    pub block_interval: u32,
+}
+
+impl Default for LedgerConfig {
+    fn default() -> Self {
+        Self {
+            start_block_height: DEFAULT_START_BLOCK_HEIGHT,
+            start_time: ROOT_EVENT_TIME,
+            block_interval: DEFAULT_BLOCK_INTERVAL,
+        }
+    }
+}
+
+/// A ledger of mined blocks, each with a height and a Unix time.
+#[derive(Debug, Clone)]
+pub struct SimulatedLedger {
+    config: LedgerConfig,
+    block_times: Vec<u32>,
+}
+
+impl SimulatedLedger {
+    /// Makes a new ledger with no mined blocks.
+    pub fn new(config: LedgerConfig) -> Self {
+        Self {
+            config,
+            block_times: Vec::new(),
+        }
+    }
+
+    /// Returns the ledger configuration.
+    pub fn config(&self) -> &LedgerConfig {
+        &self.config
+    }
+
+    /// Returns the height of the most recently mined block, if any.
+    pub fn block_height(&self) -> Option<u32> {
+        self.block_times
+            .len()
+            .checked_sub(1)
+            .map(|n| self.config.start_block_height + n as u32)
+    }
+
+    /// Returns the height of the next block to be mined.
+    pub fn next_block_height(&self) -> u32 {
+        self.config.start_block_height + self.block_times.len() as u32
+    }
+
+    /// Returns the Unix time scheduled for the next block, one block interval after the previous
+    /// block (or the configured start time for the first block).
+    pub fn next_block_time(&self) -> u32 {
+        match self.block_times.last() {
+            Some(time) => time + self.config.block_interval,
+            None => self.config.start_time,
+        }
+    }
+
+    /// Returns the Unix time of a mined block.
+    pub fn block_time(&self, block_height: u32) -> Option<u32> {
+        let index = block_height.checked_sub(self.config.start_block_height)?;
+        self.block_times.get(index as usize).copied()
+    }
+
+    /// Mines a block at the given Unix time (or at the next scheduled time), returning its
+    /// height. Block times must not decrease.
+    pub fn mine(&mut self, unixtime: Option<u32>) -> Result<u32, NodeError> {
+        let unixtime = unixtime.unwrap_or_else(|| self.next_block_time());
+        if matches!(self.block_times.last(), Some(last) if unixtime < *last) {
+            return Err(NodeError::InvalidBlockTime(unixtime));
+        }
+        let block_height = self.next_block_height();
+        self.block_times.push(unixtime);
+        Ok(block_height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mine() {
+        let config = LedgerConfig {
+            start_block_height: 100,
+            start_time: 1000,
+            block_interval: 10,
+        };
+        let mut ledger = SimulatedLedger::new(config);
+        assert_eq!(ledger.block_height(), None);
+        assert_eq!(ledger.next_block_height(), 100);
+
+        assert_eq!(ledger.mine(None).unwrap(), 100);
+        assert_eq!(ledger.mine(None).unwrap(), 101);
+        assert_eq!(ledger.mine(Some(5000)).unwrap(), 102);
+        assert_eq!(ledger.block_height(), Some(102));
+        assert_eq!(ledger.block_time(100), Some(1000));
+        assert_eq!(ledger.block_time(101), Some(1010));
+        assert_eq!(ledger.block_time(102), Some(5000));
+        assert_eq!(ledger.block_time(99), None);
+        assert_eq!(ledger.block_time(103), None);
+
+        // Block times cannot go backwards.
+        assert!(matches!(
+            ledger.mine(Some(4999)),
+            Err(NodeError::InvalidBlockTime(4999))
+        ));
+        assert_eq!(ledger.next_block_time(), 5010);
+    }
+}
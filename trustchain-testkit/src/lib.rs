@@ -0,0 +1,25 @@
+//! Test kit for running the Trustchain create → attest → resolve → verify flow without external
+//! services.
+//!
+//! A [`SidetreeNode`] accepts ION create, update, recover and deactivate operations, anchors them
+//! on a simulated ledger with configurable block heights and times, and resolves DIDs by
+//! replaying the anchored operations. A [`TestServer`] serves the node's resolution and operation
+//! HTTP API on a local port, so it can stand in for an ION node at a Sidetree endpoint, and a
+//! [`TestVerifier`] verifies DID chains against the simulated ledger. An [`OID4VCClient`] acts
+//! as a holder's wallet against an OpenID for Verifiable Credentials server. A [`TestNetwork`]
+//! sets these up with a root DID attesting to an issuer DID and a holder DID.
+pub mod client;
+pub mod did;
+pub mod ledger;
+pub mod network;
+pub mod node;
+pub mod server;
+pub mod verifier;
+
+pub use client::OID4VCClient;
+pub use did::TestDID;
+pub use ledger::LedgerConfig;
+pub use network::{attestor, TestNetwork};
+pub use node::{NodeError, SidetreeNode};
+pub use server::TestServer;
+pub use verifier::TestVerifier;
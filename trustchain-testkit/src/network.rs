@@ -0,0 +1,110 @@
+//! A root DID with attested issuer and holder DIDs on a node served over HTTP, as the common
+//! setup of tests of the flows downstream of DID creation.
+use crate::{SidetreeNode, TestDID, TestServer, TestVerifier};
+use ssi::one_or_many::OneOrMany;
+use ssi::vc::{Credential, URI};
+use std::convert::TryFrom;
+use trustchain_core::attestor::Attestor;
+use trustchain_core::data::TEST_CREDENTIAL;
+use trustchain_core::dispatch::DispatchResolver;
+use trustchain_core::utils::init;
+use trustchain_ion::attestor::{AttestorData, IONAttestor};
+use trustchain_ion::controller::IONController;
+use trustchain_ion::{get_ion_resolver, IONResolver};
+
+/// Returns the attestor for a DID, saving its signing key unless already saved.
+pub fn attestor(subject: &TestDID) -> IONAttestor {
+    IONAttestor::try_from(AttestorData::new(
+        subject.did().to_string(),
+        OneOrMany::One(subject.signing_key().clone()),
+    ))
+    .unwrap_or_else(|_| IONAttestor::new(subject.did()))
+}
+
+/// A [`SidetreeNode`] served by a [`TestServer`], on which a root DID has attested to an issuer
+/// DID and a holder DID (root - issuer, root - holder).
+pub struct TestNetwork {
+    pub node: SidetreeNode,
+    pub server: TestServer,
+    pub root: TestDID,
+    pub issuer: TestDID,
+    pub holder: TestDID,
+    /// Time of the block anchoring the root DID.
+    pub root_timestamp: u32,
+    /// Resolver querying the server.
+    pub resolver: IONResolver,
+    /// Verifier resolving from the server and anchoring on the node.
+    pub verifier: TestVerifier<DispatchResolver>,
+}
+
+impl TestNetwork {
+    /// Initialises the Trustchain data directory for the test and starts a node with the root,
+    /// issuer and holder DIDs anchored and attested.
+    pub fn new() -> Self {
+        init();
+        let node = SidetreeNode::default();
+        let server = TestServer::start(node.clone()).unwrap();
+        let (root, root_block_height) = create(&node);
+        let (mut issuer, _) = create(&node);
+        let (mut holder, _) = create(&node);
+        attest(&node, &server, &root, &mut issuer);
+        attest(&node, &server, &root, &mut holder);
+        let root_timestamp = node.block_time(root_block_height).unwrap();
+        let resolver = get_ion_resolver(&server.endpoint());
+        let verifier = TestVerifier::new(get_ion_resolver(&server.endpoint()), node.clone());
+        Self {
+            node,
+            server,
+            root,
+            issuer,
+            holder,
+            root_timestamp,
+            resolver,
+            verifier,
+        }
+    }
+
+    /// Anchors a new DID on the node, returning it with the height of its anchoring block.
+    pub fn create(&self) -> (TestDID, u32) {
+        create(&self.node)
+    }
+
+    /// Attests to the current document of a downstream DID by publishing the upstream DID's
+    /// proof in an update operation, as performed by the `did attest` CLI command.
+    pub fn attest(&self, upstream: &TestDID, downstream: &mut TestDID) {
+        attest(&self.node, &self.server, upstream, downstream)
+    }
+
+    /// Returns the test credential, unsigned, from the issuer to the given subject.
+    pub fn credential(&self, subject: &TestDID) -> Credential {
+        let mut credential: Credential = serde_json::from_str(TEST_CREDENTIAL).unwrap();
+        credential.issuer = Some(ssi::vc::Issuer::URI(URI::String(
+            self.issuer.did().to_string(),
+        )));
+        credential.credential_subject.to_single_mut().unwrap().id =
+            Some(URI::String(subject.did().to_string()));
+        credential
+    }
+}
+
+impl Default for TestNetwork {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn create(node: &SidetreeNode) -> (TestDID, u32) {
+    let (subject, operation) = TestDID::generate();
+    node.submit(operation).unwrap();
+    (subject, node.anchor().unwrap())
+}
+
+fn attest(node: &SidetreeNode, server: &TestServer, upstream: &TestDID, downstream: &mut TestDID) {
+    let resolver = get_ion_resolver(&server.endpoint());
+    let (_, doc, _) = resolver.resolve_as_result(downstream.did()).unwrap();
+    let proof = attestor(upstream).attest(&doc.unwrap(), None).unwrap();
+    let controller = IONController::new(upstream.did(), downstream.did()).unwrap();
+    let patch = controller.add_proof_service(upstream.did(), &proof, None);
+    node.submit(downstream.update(vec![patch])).unwrap();
+    node.anchor().unwrap();
+}
@@ -0,0 +1,448 @@
+//! In-process Sidetree node accepting ION operations and anchoring them on a simulated ledger.
+use crate::ledger::{LedgerConfig, SimulatedLedger};
+use async_trait::async_trait;
+use did_ion::sidetree::{DIDSuffix, Operation, SidetreeDID, SidetreeOperation};
+use did_ion::ION;
+use ssi::did::Document;
+use ssi::did_resolve::{
+    DIDResolver, DocumentMetadata, ResolutionInputMetadata, ResolutionMetadata, ERROR_INVALID_DID,
+    ERROR_NOT_FOUND,
+};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use thiserror::Error;
+use trustchain_core::resolver::ERROR_VERSION_NOT_AVAILABLE;
+use trustchain_ion::replay::{select_operations, AnchoredOperation, DIDState, ReplayError};
+
+/// An error relating to the in-process Sidetree node.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum NodeError {
+    /// The operation is malformed or its signature or commitments are invalid.
+    #[error("Invalid operation: {0}")]
+    InvalidOperation(String),
+    /// A create operation for the DID suffix has already been submitted.
+    #[error("DID suffix already exists: {0}")]
+    DuplicateDID(String),
+    /// No create operation has been submitted for the DID suffix.
+    #[error("Unknown DID suffix: {0}")]
+    UnknownDID(String),
+    /// An operation on the DID suffix is already awaiting anchoring.
+    #[error("An operation is already pending for DID suffix: {0}")]
+    PendingOperation(String),
+    /// The block time precedes the time of the previous block.
+    #[error("Invalid block time: {0}")]
+    InvalidBlockTime(u32),
+}
+
+/// Operations and ledger held by the node.
+#[derive(Debug)]
+struct NodeState {
+    ledger: SimulatedLedger,
+    pending: Vec<(String, Operation)>,
+    anchored: HashMap<String, Vec<AnchoredOperation>>,
+}
+
+/// Returns the DID suffix targeted by an operation.
+fn operation_suffix(operation: &Operation) -> Result<String, NodeError> {
+    match operation {
+        Operation::Create(create) => SidetreeDID::<ION>::from_create_operation(create)
+            .map(|did| DIDSuffix::from(did).0)
+            .map_err(|e| NodeError::InvalidOperation(e.to_string())),
+        Operation::Update(update) => Ok(update.did_suffix.0.to_owned()),
+        Operation::Recover(recover) => Ok(recover.did_suffix.0.to_owned()),
+        Operation::Deactivate(deactivate) => Ok(deactivate.did_suffix.0.to_owned()),
+    }
+}
+
+/// Returns the commitments and deactivation status of a DID state, which change with every
+/// operation applied.
+fn state_summary(state: &DIDState) -> (Option<String>, Option<String>, bool) {
+    (
+        state.update_commitment.clone(),
+        state.recovery_commitment.clone(),
+        state.deactivated,
+    )
+}
+
+/// An in-process stand-in for an ION (Sidetree) node.
+///
+/// Submitted operations are validated and held until [`anchor`](SidetreeNode::anchor) mines a
+/// block on the simulated ledger, after which they take effect in resolution. As with ION, at
+/// most one operation per DID can be anchored in each batch. The node is cheap to clone, with all
+/// clones sharing the same state.
+#[derive(Debug, Clone)]
+pub struct SidetreeNode {
+    state: Arc<Mutex<NodeState>>,
+}
+
+impl Default for SidetreeNode {
+    fn default() -> Self {
+        Self::new(LedgerConfig::default())
+    }
+}
+
+impl SidetreeNode {
+    /// Makes a new node with an empty ledger.
+    pub fn new(config: LedgerConfig) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(NodeState {
+                ledger: SimulatedLedger::new(config),
+                pending: Vec::new(),
+                anchored: HashMap::new(),
+            })),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, NodeState> {
+        self.state.lock().unwrap()
+    }
+
+    /// Returns the short-form ION DID with the given suffix.
+    pub fn did(did_suffix: &str) -> String {
+        SidetreeDID::<ION>::Short {
+            did_suffix: DIDSuffix(did_suffix.to_owned()),
+        }
+        .to_string()
+    }
+
+    /// Validates an operation and queues it for anchoring, returning the DID suffix it targets.
+    ///
+    /// Operations other than creates must target a DID that has been submitted and must reveal
+    /// its current update or recovery commitment.
+    pub fn submit(&self, operation: Operation) -> Result<String, NodeError> {
+        let suffix = operation_suffix(&operation)?;
+        operation
+            .clone()
+            .partial_verify::<ION>()
+            .map_err(|e| NodeError::InvalidOperation(e.to_string()))?;
+
+        let mut state = self.lock();
+        if state.pending.iter().any(|(s, _)| s == &suffix) {
+            return Err(NodeError::PendingOperation(suffix));
+        }
+        let anchored = state.anchored.get(&suffix).cloned().unwrap_or_default();
+        match operation {
+            Operation::Create(_) if !anchored.is_empty() => {
+                return Err(NodeError::DuplicateDID(suffix))
+            }
+            Operation::Create(_) => {
+                // Check that the suffix data matches the delta.
+                DIDState::replay(&suffix, vec![Self::provisional(operation.clone())])
+                    .map_err(|e| NodeError::InvalidOperation(e.to_string()))?;
+            }
+            _ => {
+                let current = DIDState::replay(&suffix, anchored.clone())
+                    .map_err(|_| NodeError::UnknownDID(suffix.to_owned()))?;
+                let mut operations = anchored;
+                operations.push(Self::provisional(operation.clone()));
+                let next = DIDState::replay(&suffix, operations)
+                    .map_err(|e| NodeError::InvalidOperation(e.to_string()))?;
+                // Operations that do not apply are ignored by replay, leaving the state unchanged.
+                if state_summary(&current) == state_summary(&next) {
+                    return Err(NodeError::InvalidOperation(format!(
+                        "operation does not apply to the current state of DID suffix {}",
+                        suffix
+                    )));
+                }
+            }
+        }
+        state.pending.push((suffix.to_owned(), operation));
+        Ok(suffix)
+    }
+
+    /// Wraps a pending operation for replay after all anchored operations.
+    fn provisional(operation: Operation) -> AnchoredOperation {
+        AnchoredOperation {
+            operation,
+            txn_time: u32::MAX,
+            txn_number: i64::MAX,
+            op_index: 0,
+        }
+    }
+
+    /// Anchors the pending operations in a new block mined at the next scheduled block time,
+    /// returning the block height.
+    pub fn anchor(&self) -> Result<u32, NodeError> {
+        self.anchor_with_time(None)
+    }
+
+    /// Anchors the pending operations in a new block mined at the given Unix time, returning the
+    /// block height.
+    pub fn anchor_at(&self, unixtime: u32) -> Result<u32, NodeError> {
+        self.anchor_with_time(Some(unixtime))
+    }
+
+    fn anchor_with_time(&self, unixtime: Option<u32>) -> Result<u32, NodeError> {
+        let mut state = self.lock();
+        let block_height = state.ledger.mine(unixtime)?;
+        // All pending operations form a single anchoring transaction (at index zero), numbered
+        // as by ION with the block height in the high 32 bits and the index in the low 32 bits.
+        let transaction_index = 0;
+        let txn_number = (i64::from(block_height) << 32) | transaction_index;
+        let pending = std::mem::take(&mut state.pending);
+        for (op_index, (suffix, operation)) in pending.into_iter().enumerate() {
+            state
+                .anchored
+                .entry(suffix)
+                .or_default()
+                .push(AnchoredOperation {
+                    operation,
+                    txn_time: block_height,
+                    txn_number,
+                    op_index: op_index as i32,
+                });
+        }
+        Ok(block_height)
+    }
+
+    /// Returns the number of operations awaiting anchoring.
+    pub fn pending(&self) -> usize {
+        self.lock().pending.len()
+    }
+
+    /// Returns the anchored operations on a DID suffix in ledger order.
+    pub fn operations(&self, did_suffix: &str) -> Vec<AnchoredOperation> {
+        self.lock()
+            .anchored
+            .get(did_suffix)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Returns the height of the most recently mined block, if any.
+    pub fn block_height(&self) -> Option<u32> {
+        self.lock().ledger.block_height()
+    }
+
+    /// Returns the Unix time of a mined block.
+    pub fn block_time(&self, block_height: u32) -> Option<u32> {
+        self.lock().ledger.block_time(block_height)
+    }
+
+    /// Resolves a short-form ION DID from the anchored operations, at the `versionId` or
+    /// `versionTime` given in the input metadata if any.
+    pub fn resolve_did(
+        &self,
+        did: &str,
+        input_metadata: &ResolutionInputMetadata,
+    ) -> (
+        ResolutionMetadata,
+        Option<Document>,
+        Option<DocumentMetadata>,
+    ) {
+        let did_suffix = match SidetreeDID::<ION>::from_str(did) {
+            Ok(SidetreeDID::Short { did_suffix }) => did_suffix.0,
+            _ => {
+                return (
+                    ResolutionMetadata::from_error(ERROR_INVALID_DID),
+                    None,
+                    None,
+                )
+            }
+        };
+        let operations = self.operations(&did_suffix);
+        if operations.is_empty() {
+            return (ResolutionMetadata::from_error(ERROR_NOT_FOUND), None, None);
+        }
+        let operations = match select_operations(operations, input_metadata, |block_height| {
+            self.block_time(block_height)
+                .ok_or(ReplayError::FailureToGetUnixTime(block_height))
+        }) {
+            Ok(operations) => operations,
+            Err(_) => {
+                return (
+                    ResolutionMetadata::from_error(ERROR_VERSION_NOT_AVAILABLE),
+                    None,
+                    None,
+                )
+            }
+        };
+        match DIDState::replay(&did_suffix, operations) {
+            Ok(state) => state.to_resolution(did),
+            Err(_) => (ResolutionMetadata::from_error(ERROR_NOT_FOUND), None, None),
+        }
+    }
+}
+
+#[async_trait]
+impl DIDResolver for SidetreeNode {
+    async fn resolve(
+        &self,
+        did: &str,
+        input_metadata: &ResolutionInputMetadata,
+    ) -> (
+        ResolutionMetadata,
+        Option<Document>,
+        Option<DocumentMetadata>,
+    ) {
+        self.resolve_did(did, input_metadata)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::did::TestDID;
+    use did_ion::sidetree::{DIDStatePatch, DocumentState};
+
+    fn config() -> LedgerConfig {
+        LedgerConfig {
+            start_block_height: 1000,
+            start_time: 1_600_000_000,
+            block_interval: 600,
+        }
+    }
+
+    #[test]
+    fn test_submit_and_anchor() {
+        let node = SidetreeNode::new(config());
+        let (mut subject, create) = TestDID::generate();
+        assert_eq!(node.submit(create.clone()).unwrap(), subject.suffix());
+
+        // Operations take effect once anchored.
+        let (res_meta, _, _) = node.resolve_did(subject.did(), &Default::default());
+        assert_eq!(res_meta.error, Some(ERROR_NOT_FOUND.to_string()));
+        assert_eq!(node.pending(), 1);
+        assert_eq!(node.anchor().unwrap(), 1000);
+        assert_eq!(node.pending(), 0);
+        let (res_meta, doc, doc_meta) = node.resolve_did(subject.did(), &Default::default());
+        assert!(res_meta.error.is_none());
+        assert_eq!(doc.unwrap().id, subject.did());
+        assert!(doc_meta.unwrap().deactivated.is_none());
+
+        let operations = node.operations(subject.suffix());
+        assert_eq!(operations.len(), 1);
+        assert_eq!(operations[0].txn_time, 1000);
+        assert_eq!(operations[0].txn_number, 1000 << 32);
+
+        // A second create for the same suffix is rejected.
+        assert_eq!(
+            node.submit(create),
+            Err(NodeError::DuplicateDID(subject.suffix().to_owned()))
+        );
+
+        // Only one operation per DID may be pending.
+        let patch = DIDStatePatch::Replace {
+            document: DocumentState::default(),
+        };
+        node.submit(subject.update(vec![patch.clone()])).unwrap();
+        assert_eq!(
+            node.submit(subject.update(vec![patch])),
+            Err(NodeError::PendingOperation(subject.suffix().to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_reject_invalid_operations() {
+        let node = SidetreeNode::new(config());
+        let (mut subject, create) = TestDID::generate();
+
+        // Operations on unknown DIDs are rejected.
+        let update = subject.clone().update(vec![]);
+        assert_eq!(
+            node.submit(update),
+            Err(NodeError::UnknownDID(subject.suffix().to_owned()))
+        );
+
+        node.submit(create).unwrap();
+        node.anchor().unwrap();
+
+        // An update revealing a superseded update key does not apply.
+        let mut stale = subject.clone();
+        node.submit(subject.update(vec![])).unwrap();
+        node.anchor().unwrap();
+        assert!(matches!(
+            node.submit(stale.update(vec![])),
+            Err(NodeError::InvalidOperation(_))
+        ));
+    }
+
+    #[test]
+    fn test_update_recover_deactivate() {
+        let node = SidetreeNode::new(config());
+        let (mut subject, create) = TestDID::generate();
+        node.submit(create).unwrap();
+        node.anchor().unwrap();
+
+        node.submit(subject.update(vec![DIDStatePatch::Replace {
+            document: DocumentState::default(),
+        }]))
+        .unwrap();
+        node.anchor().unwrap();
+        let (_, doc, _) = node.resolve_did(subject.did(), &Default::default());
+        assert!(doc.unwrap().verification_method.is_none());
+
+        node.submit(subject.recover()).unwrap();
+        node.anchor().unwrap();
+        let (_, doc, _) = node.resolve_did(subject.did(), &Default::default());
+        assert!(doc.unwrap().verification_method.is_some());
+
+        node.submit(subject.deactivate()).unwrap();
+        node.anchor().unwrap();
+        let (res_meta, _, doc_meta) = node.resolve_did(subject.did(), &Default::default());
+        assert!(res_meta.error.is_none());
+        assert_eq!(doc_meta.unwrap().deactivated, Some(true));
+
+        // A deactivated DID accepts no further operations.
+        assert!(matches!(
+            node.submit(subject.update(vec![])),
+            Err(NodeError::InvalidOperation(_))
+        ));
+    }
+
+    #[test]
+    fn test_resolve_version() {
+        let node = SidetreeNode::new(config());
+        let (mut subject, create) = TestDID::generate();
+        node.submit(create).unwrap();
+        node.anchor().unwrap();
+        node.submit(subject.update(vec![DIDStatePatch::Replace {
+            document: DocumentState::default(),
+        }]))
+        .unwrap();
+        // Mine an empty block, then anchor the update an hour after creation.
+        node.anchor_at(1_600_000_600).unwrap();
+        node.anchor_at(1_600_003_600).unwrap();
+
+        // Before creation the DID did not exist.
+        let input = ResolutionInputMetadata {
+            version_time: Some("2020-09-13T12:00:00Z".to_string()),
+            ..Default::default()
+        };
+        let (res_meta, _, _) = node.resolve_did(subject.did(), &input);
+        assert_eq!(res_meta.error, Some(ERROR_NOT_FOUND.to_string()));
+
+        // Between creation and update the created document is resolved.
+        let input = ResolutionInputMetadata {
+            version_time: Some("2020-09-13T12:30:00Z".to_string()),
+            ..Default::default()
+        };
+        let (_, doc, _) = node.resolve_did(subject.did(), &input);
+        assert!(doc.unwrap().verification_method.is_some());
+
+        // The version ID of the create operation is its transaction number.
+        let input = ResolutionInputMetadata {
+            version_id: Some((1000_i64 << 32).to_string()),
+            ..Default::default()
+        };
+        let (_, doc, _) = node.resolve_did(subject.did(), &input);
+        assert!(doc.unwrap().verification_method.is_some());
+
+        let input = ResolutionInputMetadata {
+            version_id: Some("12345".to_string()),
+            ..Default::default()
+        };
+        let (res_meta, _, _) = node.resolve_did(subject.did(), &input);
+        assert_eq!(
+            res_meta.error,
+            Some(ERROR_VERSION_NOT_AVAILABLE.to_string())
+        );
+
+        let (_, doc, _) = node.resolve_did(subject.did(), &Default::default());
+        assert!(doc.unwrap().verification_method.is_none());
+
+        let (res_meta, _, _) = node.resolve_did("did:ion:test:", &Default::default());
+        assert_eq!(res_meta.error, Some(ERROR_INVALID_DID.to_string()));
+    }
+}
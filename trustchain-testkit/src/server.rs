@@ -0,0 +1,195 @@
+//! HTTP API of the in-process Sidetree node, compatible with the ION node resolution and operation
+//! endpoints.
+use crate::node::SidetreeNode;
+use axum::extract::{Path, Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use did_ion::sidetree::Operation;
+use serde_json::json;
+use ssi::did_resolve::{
+    ResolutionInputMetadata, ResolutionResult, ERROR_NOT_FOUND, TYPE_DID_RESOLUTION,
+};
+use std::collections::HashMap;
+use std::net::{SocketAddr, TcpListener};
+use std::thread::JoinHandle;
+use tokio::sync::oneshot;
+
+/// Context of a DID resolution result.
+const DID_RESOLUTION_V1_CONTEXT: &str = "https://w3id.org/did-resolution/v1";
+
+/// Resolves a DID, returning the DID resolution result.
+async fn resolve(
+    State(node): State<SidetreeNode>,
+    Path(did): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Response {
+    let input_metadata = ResolutionInputMetadata {
+        version_id: params.get("versionId").cloned(),
+        version_time: params.get("versionTime").cloned(),
+        ..Default::default()
+    };
+    let (res_meta, doc, doc_meta) = node.resolve_did(&did, &input_metadata);
+    let status = match res_meta.error.as_deref() {
+        None => StatusCode::OK,
+        Some(ERROR_NOT_FOUND) => StatusCode::NOT_FOUND,
+        Some(_) => StatusCode::BAD_REQUEST,
+    };
+    let result = ResolutionResult {
+        context: Some(json!(DID_RESOLUTION_V1_CONTEXT)),
+        did_document: doc,
+        did_resolution_metadata: Some(res_meta),
+        did_document_metadata: doc_meta,
+        property_set: None,
+    };
+    (
+        status,
+        [(header::CONTENT_TYPE, TYPE_DID_RESOLUTION)],
+        serde_json::to_string(&result).unwrap(),
+    )
+        .into_response()
+}
+
+/// Submits an operation for anchoring, returning the DID suffix it targets.
+async fn submit(State(node): State<SidetreeNode>, Json(operation): Json<Operation>) -> Response {
+    match node.submit(operation) {
+        Ok(did_suffix) => (
+            StatusCode::OK,
+            Json(json!({ "didSuffix": did_suffix, "did": SidetreeNode::did(&did_suffix) })),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "code": "invalid_operation", "message": e.to_string() })),
+        )
+            .into_response(),
+    }
+}
+
+/// Returns the HTTP routes of a Sidetree node.
+pub fn router(node: SidetreeNode) -> Router {
+    Router::new()
+        .route("/identifiers/:did", get(resolve))
+        .route("/operations", post(submit))
+        .with_state(node)
+}
+
+/// A Sidetree node HTTP server running on a background thread, listening on a local port until
+/// dropped.
+///
+/// The server exposes `GET /identifiers/{did}` (with optional `versionId` and `versionTime` query
+/// parameters) and `POST /operations`, so the node can be used in place of an ION node wherever a
+/// Sidetree endpoint is configured.
+pub struct TestServer {
+    addr: SocketAddr,
+    shutdown: Option<oneshot::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl TestServer {
+    /// Starts serving the node on an unused local port.
+    pub fn start(node: SidetreeNode) -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        listener.set_nonblocking(true)?;
+        let addr = listener.local_addr()?;
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        // The listener is registered with the reactor of the server's runtime.
+        let server = {
+            let _guard = runtime.enter();
+            axum::Server::from_tcp(listener).map_err(std::io::Error::other)?
+        };
+        let (shutdown, shutdown_signal) = oneshot::channel::<()>();
+        let thread = std::thread::spawn(move || {
+            runtime.block_on(async move {
+                let server = server
+                    .serve(router(node).into_make_service())
+                    .with_graceful_shutdown(async {
+                        shutdown_signal.await.ok();
+                    });
+                if let Err(e) = server.await {
+                    eprintln!("Sidetree node server error: {}", e);
+                }
+            })
+        });
+        Ok(Self {
+            addr,
+            shutdown: Some(shutdown),
+            thread: Some(thread),
+        })
+    }
+
+    /// Returns the address the server is listening on.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Returns the Sidetree API endpoint of the server, e.g. `http://127.0.0.1:38421/`.
+    pub fn endpoint(&self) -> String {
+        format!("http://{}/", self.addr)
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send(()).ok();
+        }
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::did::TestDID;
+    use did_ion::sidetree::HTTPSidetreeDIDResolver;
+    use did_ion::ION;
+    use ssi::did_resolve::DIDResolver;
+
+    #[tokio::test]
+    async fn test_server() {
+        let node = SidetreeNode::default();
+        let server = TestServer::start(node.clone()).unwrap();
+        let (subject, create) = TestDID::generate();
+
+        // Submit the create operation over HTTP.
+        let client = reqwest::Client::new();
+        let response = client
+            .post(format!("{}operations", server.endpoint()))
+            .json(&create)
+            .send()
+            .await
+            .unwrap();
+        assert!(response.status().is_success());
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["did"], json!(subject.did()));
+
+        // Resubmission is rejected.
+        let response = client
+            .post(format!("{}operations", server.endpoint()))
+            .json(&create)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let resolver = HTTPSidetreeDIDResolver::<ION>::new(&server.endpoint());
+        let (res_meta, _, _) = resolver.resolve(subject.did(), &Default::default()).await;
+        assert_eq!(res_meta.error, Some(ERROR_NOT_FOUND.to_string()));
+
+        node.anchor().unwrap();
+        let (res_meta, doc, doc_meta) = resolver.resolve(subject.did(), &Default::default()).await;
+        assert!(res_meta.error.is_none());
+        assert_eq!(doc.unwrap().id, subject.did());
+        assert!(doc_meta
+            .unwrap()
+            .property_set
+            .unwrap()
+            .contains_key("method"));
+    }
+}
@@ -0,0 +1,144 @@
+//! Trustchain verifier reading anchoring information from the in-process Sidetree node.
+use crate::node::SidetreeNode;
+use did_ion::sidetree::Operation;
+use ssi::did_resolve::DIDResolver;
+use trustchain_core::dispatch::did_method;
+use trustchain_core::resolver::Resolver;
+use trustchain_core::utils::get_did_suffix;
+use trustchain_core::verifier::{Verifier, VerifierError};
+use trustchain_ion::replay::{select_operations_at, AnchoredOperation, DIDState, ReplayError};
+
+/// Trustchain Verifier for ION DIDs anchored on a [`SidetreeNode`], taking block heights, block
+/// times and operation history from the node's simulated ledger in place of the ION MongoDB and
+/// Bitcoin Core. The generic type parameterises the wrapped DID resolver.
+pub struct TestVerifier<T>
+where
+    T: Sync + Send + DIDResolver,
+{
+    resolver: Resolver<T>,
+    node: SidetreeNode,
+    allow_unpublished: bool,
+}
+
+impl<T> TestVerifier<T>
+where
+    T: Sync + Send + DIDResolver,
+{
+    /// Constructs a new TestVerifier.
+    pub fn new(resolver: Resolver<T>, node: SidetreeNode) -> Self {
+        Self {
+            resolver,
+            node,
+            allow_unpublished: false,
+        }
+    }
+
+    /// Sets whether downstream DIDs that have not been anchored are accepted in verified chains,
+    /// as for the IONVerifier.
+    pub fn allow_unpublished(mut self, allow_unpublished: bool) -> Self {
+        self.allow_unpublished = allow_unpublished;
+        self
+    }
+
+    /// Returns the anchored create operation of a DID.
+    fn create_operation(&self, did: &str) -> Result<AnchoredOperation, VerifierError> {
+        self.node
+            .operations(get_did_suffix(did))
+            .into_iter()
+            .find(|op| matches!(op.operation, Operation::Create(_)))
+            .ok_or_else(|| {
+                VerifierError::FailureToGetDIDOperation("create".to_owned(), did.to_owned())
+            })
+    }
+
+    /// Returns the anchored operations on a DID, only those anchored up to the version time (a
+    /// Unix time) if given.
+    fn operations(
+        &self,
+        did: &str,
+        version_time: Option<u32>,
+    ) -> Result<Vec<AnchoredOperation>, VerifierError> {
+        let operations = self.node.operations(get_did_suffix(did));
+        match version_time {
+            Some(version_time) => {
+                select_operations_at(operations, version_time.into(), |block_height| {
+                    self.node
+                        .block_time(block_height)
+                        .ok_or(ReplayError::FailureToGetUnixTime(block_height))
+                })
+                .map_err(|e| {
+                    VerifierError::FailureToGetDIDOperations(did.to_owned(), e.to_string())
+                })
+            }
+            None => Ok(operations),
+        }
+    }
+}
+
+impl<T> Verifier<T> for TestVerifier<T>
+where
+    T: Sync + Send + DIDResolver,
+{
+    fn verified_block_height(&self, did: &str) -> Result<u32, VerifierError> {
+        self.create_operation(did)
+            .map(|op| op.txn_time)
+            .map_err(|_| VerifierError::FailureToGetBlockHeight(did.to_owned()))
+    }
+
+    fn verified_timestamp(&self, did: &str) -> Result<u32, VerifierError> {
+        let block_height = self.verified_block_height(did)?;
+        self.block_height_to_unixtime(block_height)
+    }
+
+    fn recovered_since_attestation(
+        &self,
+        did: &str,
+        udid: &str,
+        version_time: Option<u32>,
+    ) -> Result<bool, VerifierError> {
+        // As for the IONVerifier, only recovery of ION DIDs is recorded.
+        if did_method(udid) != Some("ion") {
+            return Ok(false);
+        }
+        let recovery = match self
+            .operations(udid, version_time)?
+            .iter()
+            .filter(|op| matches!(op.operation, Operation::Recover(_)))
+            .map(|op| op.txn_number)
+            .max()
+        {
+            Some(txn_number) => txn_number,
+            None => return Ok(false),
+        };
+        if did_method(did) != Some("ion") {
+            return Ok(true);
+        }
+        // The attestation is the operation publishing the proof current as of the version time,
+        // as for the IONVerifier.
+        let attestation = DIDState::proof_transaction_number(
+            get_did_suffix(did),
+            self.operations(did, version_time)?,
+        )
+        .ok_or_else(|| {
+            VerifierError::FailureToGetDIDOperations(
+                did.to_owned(),
+                "No operation published the Trustchain proof.".to_owned(),
+            )
+        })?;
+        Ok(recovery > attestation)
+    }
+
+    fn resolver(&self) -> &Resolver<T> {
+        &self.resolver
+    }
+
+    fn unpublished_allowed(&self) -> bool {
+        self.allow_unpublished
+    }
+
+    fn block_height_to_unixtime(&self, block_height: u32) -> Result<u32, VerifierError> {
+        self.node
+            .block_time(block_height)
+            .ok_or(VerifierError::FailureToGetUnixTime(block_height))
+    }
+}
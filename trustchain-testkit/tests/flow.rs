@@ -0,0 +1,1267 @@
+use chrono::{TimeZone, Utc};
+use ssi::did_resolve::ResolutionInputMetadata;
+use ssi::one_or_many::OneOrMany;
+use ssi::vc::{Credential, CredentialOrJWT, Presentation, ProofPurpose, VCDateTime, URI};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::sync::Arc;
+use trustchain_core::attestor::Attestor;
+use trustchain_core::batch::{issue_batch, read_subjects, BatchOutput, SubjectFormat};
+use trustchain_core::chain::{is_unpublished, Chain};
+use trustchain_core::context::{save_context, trustchain_contexts_dir};
+use trustchain_core::data::TEST_CREDENTIAL;
+use trustchain_core::exchange::{verify_submission, ExchangeError, PresentationDefinition};
+use trustchain_core::holder::Holder;
+use trustchain_core::issuer::{Issuer, IssuerError, ProofOptions};
+use trustchain_core::policy::PolicyViolation;
+use trustchain_core::presentation::{
+    is_subject, verify_presentation, verify_presentation_with_policy, PresentationError,
+};
+use trustchain_core::resolver::Resolver;
+use trustchain_core::schema::{
+    save_schema, trustchain_schemas_dir, SchemaError, JSON_SCHEMA_VALIDATOR_2018,
+};
+use trustchain_core::status::{
+    save_status_list_credential, trustchain_status_list_credentials_dir, IssuerStatusList,
+    StatusListManager, StatusPurpose, MIN_STATUS_LIST_LENGTH,
+};
+use trustchain_core::subject::Subject;
+use trustchain_core::utils::get_operations_path;
+use trustchain_core::vc::{
+    issuer_did, verify_credential, verify_credential_jwt, AuthorisationCheck,
+    CredentialSchemaCheck, CredentialStatusCheck, CredentialVerificationPolicy,
+};
+use trustchain_core::verifier::{Verifier, VerifierError};
+use trustchain_core::wallet::{Wallet, WalletError};
+use trustchain_ion::config::{CacheBackend, ResolverConfig, TrustchainConfig};
+use trustchain_ion::controller::IONController;
+use trustchain_ion::failover::FailoverResolver;
+use trustchain_ion::oid4vc::{OID4VCServer, OID4VCService, PresentationResult};
+use trustchain_ion::{get_ion_resolver, get_ion_resolver_from_config};
+use trustchain_testkit::client::ClientError;
+use trustchain_testkit::{attestor, OID4VCClient, TestDID, TestNetwork, TestVerifier};
+
+#[test]
+fn create_attest_resolve_verify() {
+    let network = TestNetwork::new();
+
+    // root - root-plus-1 (the issuer) - root-plus-2
+    let (mut level2, _) = network.create();
+    network.attest(&network.issuer, &mut level2);
+
+    let verifier = &network.verifier;
+    let chain = verifier
+        .verify(level2.did(), network.root_timestamp)
+        .unwrap();
+    assert_eq!(chain.len(), 3);
+    assert_eq!(chain.root(), network.root.did());
+    assert_eq!(chain.leaf(), level2.did());
+    assert!(chain.recovered_upstream().is_empty());
+    assert_eq!(
+        verifier.verified_timestamp(network.root.did()),
+        Ok(network.root_timestamp)
+    );
+
+    // Verification against a different root event time fails.
+    assert!(matches!(
+        verifier.verify(level2.did(), network.root_timestamp + 1),
+        Err(VerifierError::InvalidRoot(_))
+    ));
+}
+
+#[test]
+fn verify_recovered_and_deactivated() {
+    let mut network = TestNetwork::new();
+
+    // root - root-plus-1 (the issuer) - root-plus-2
+    let (mut level2, _) = network.create();
+    network.attest(&network.issuer, &mut level2);
+    let root_timestamp = network.root_timestamp;
+    let node = &network.node;
+
+    // Recovery of an upstream DID after its attestation is flagged.
+    let signing_key = network.root.signing_key().clone();
+    node.submit(network.root.recover_with_signing_key(signing_key))
+        .unwrap();
+    node.anchor().unwrap();
+    let chain = network
+        .verifier
+        .verify(level2.did(), root_timestamp)
+        .unwrap();
+    assert_eq!(
+        chain.recovered_upstream(),
+        &vec![network.issuer.did().to_string()]
+    );
+
+    // A later update to the downstream DID, leaving its proof unchanged, does not hide the
+    // recovery.
+    node.submit(network.issuer.update(vec![])).unwrap();
+    node.anchor().unwrap();
+    let chain = network
+        .verifier
+        .verify(level2.did(), root_timestamp)
+        .unwrap();
+    assert_eq!(
+        chain.recovered_upstream(),
+        &vec![network.issuer.did().to_string()]
+    );
+
+    // Recovery replacing the signing key (and the DID document, with its upstream proof) breaks
+    // the chain.
+    node.submit(network.issuer.recover()).unwrap();
+    node.anchor().unwrap();
+    assert!(matches!(
+        network.verifier.verify(level2.did(), root_timestamp),
+        Err(VerifierError::InvalidChain(_))
+    ));
+
+    // Deactivated DIDs fail verification.
+    node.submit(level2.deactivate()).unwrap();
+    node.anchor().unwrap();
+    assert!(matches!(
+        network.verifier.verify(level2.did(), root_timestamp),
+        Err(VerifierError::DeactivatedDID(did)) if did == level2.did()
+    ));
+}
+
+#[test]
+fn verify_at_version_time() {
+    let mut network = TestNetwork::new();
+
+    // root - root-plus-1 (the issuer) - root-plus-2
+    let (mut level2, _) = network.create();
+    network.attest(&network.issuer, &mut level2);
+    let root_timestamp = network.root_timestamp;
+    let node = &network.node;
+    let attested_time = node.block_time(node.block_height().unwrap()).unwrap();
+
+    // The root is recovered after attesting to the issuer.
+    let signing_key = network.root.signing_key().clone();
+    node.submit(network.root.recover_with_signing_key(signing_key))
+        .unwrap();
+    let recovered_time = node.block_time(node.anchor().unwrap()).unwrap();
+    assert!(recovered_time > attested_time);
+    let issuer = vec![network.issuer.did().to_string()];
+    // Historical DID state is resolved from the node by the server, in place of replay of the
+    // operations in the ION MongoDB.
+    let resolver = Resolver::new(FailoverResolver::from_config(
+        &ResolverConfig::from_endpoint(&network.server.endpoint()),
+    ));
+    let verifier = TestVerifier::new(resolver, node.clone());
+    assert_eq!(
+        verifier
+            .verify(level2.did(), root_timestamp)
+            .unwrap()
+            .recovered_upstream(),
+        &issuer
+    );
+
+    // As of a time before the recovery, the recovery is not flagged.
+    let chain = verifier
+        .verify_at(level2.did(), root_timestamp, attested_time)
+        .unwrap();
+    assert_eq!(chain.len(), 3);
+    assert_eq!(chain.leaf(), level2.did());
+    assert!(chain.recovered_upstream().is_empty());
+    assert_eq!(
+        verifier
+            .verify_at(level2.did(), root_timestamp, recovered_time)
+            .unwrap()
+            .recovered_upstream(),
+        &issuer
+    );
+
+    // Before the DID was attested to, it had no chain.
+    assert!(verifier
+        .verify_at(level2.did(), root_timestamp, root_timestamp)
+        .is_err());
+}
+
+#[test]
+fn resolve_long_form() {
+    let network = TestNetwork::new();
+    let (node, resolver) = (&network.node, &network.resolver);
+    let (mut subject, operation) = TestDID::generate();
+
+    // Before anchoring, the long-form DID is resolved from its initial state.
+    node.submit(operation).unwrap();
+    let (_, doc, doc_meta) = resolver.resolve_as_result(subject.long_form_did()).unwrap();
+    assert_eq!(doc.unwrap().id, subject.did());
+    assert!(is_unpublished(&doc_meta.unwrap()));
+    assert!(resolver.resolve_as_result(subject.did()).is_err());
+
+    // Once anchored, the published state is resolved.
+    node.anchor().unwrap();
+    node.submit(subject.update(vec![])).unwrap();
+    node.anchor().unwrap();
+    let (_, doc, doc_meta) = resolver.resolve_as_result(subject.long_form_did()).unwrap();
+    assert_eq!(doc.unwrap().id, subject.did());
+    assert!(!is_unpublished(&doc_meta.unwrap()));
+}
+
+#[test]
+fn resolve_cached() {
+    let network = TestNetwork::new();
+    let config = TrustchainConfig {
+        resolver: ResolverConfig {
+            cache: CacheBackend::Memory,
+            ..ResolverConfig::from_endpoint(&network.server.endpoint())
+        },
+        ..TrustchainConfig::default()
+    };
+    let resolver = get_ion_resolver_from_config(&config);
+    let (mut subject, _) = network.create();
+    let (_, doc, _) = resolver.resolve_as_result(subject.did()).unwrap();
+    let doc = serde_json::to_value(doc.unwrap()).unwrap();
+
+    // Until it expires, the cached resolution is returned after the DID is updated.
+    network.attest(&network.root, &mut subject);
+    let (_, cached_doc, _) = resolver.resolve_as_result(subject.did()).unwrap();
+    assert_eq!(serde_json::to_value(cached_doc.unwrap()).unwrap(), doc);
+
+    // The noCache option refreshes the cached resolution.
+    let input_metadata = ResolutionInputMetadata {
+        no_cache: Some(true),
+        ..ResolutionInputMetadata::default()
+    };
+    let (_, fresh_doc, _) = resolver
+        .resolve_as_result_with_input(subject.did(), &input_metadata)
+        .unwrap();
+    let fresh_doc = serde_json::to_value(fresh_doc.unwrap()).unwrap();
+    assert_ne!(fresh_doc, doc);
+    let (_, cached_doc, _) = resolver.resolve_as_result(subject.did()).unwrap();
+    assert_eq!(
+        serde_json::to_value(cached_doc.unwrap()).unwrap(),
+        fresh_doc
+    );
+
+    // Without a cache, every resolution is fresh.
+    let (_, doc, _) = network.resolver.resolve_as_result(subject.did()).unwrap();
+    assert_eq!(serde_json::to_value(doc.unwrap()).unwrap(), fresh_doc);
+}
+
+#[test]
+fn verify_unpublished() {
+    let network = TestNetwork::new();
+    let (node, resolver) = (&network.node, &network.resolver);
+
+    // The root attests to a DID before it is anchored, saving the update operation publishing
+    // its proof as `did attest` does.
+    let (mut subject, create_operation) = TestDID::generate();
+    let (_, doc, _) = resolver.resolve_as_result(subject.long_form_did()).unwrap();
+    let proof = attestor(&network.root).attest(&doc.unwrap(), None).unwrap();
+    let controller = IONController::new(network.root.did(), subject.did()).unwrap();
+    let patch = controller.add_proof_service(network.root.did(), &proof, None);
+    let update_operation = subject.update(vec![patch]);
+    let path = get_operations_path()
+        .unwrap()
+        .join(format!("attest_operation_{}.json", subject.suffix()));
+    std::fs::write(path, serde_json::to_string(&update_operation).unwrap()).unwrap();
+
+    // The long-form DID is verified only if unpublished DIDs are allowed.
+    assert!(matches!(
+        network.verifier.verify(subject.long_form_did(), network.root_timestamp),
+        Err(VerifierError::UnpublishedDID(did)) if did == subject.did()
+    ));
+    let verifier = network.verifier.allow_unpublished(true);
+    let chain = verifier
+        .verify(subject.long_form_did(), network.root_timestamp)
+        .unwrap();
+    assert_eq!(chain.root(), network.root.did());
+    assert_eq!(chain.leaf(), subject.did());
+    assert_eq!(chain.unpublished(), &vec![subject.did().to_string()]);
+
+    // Once anchored, the short-form DID is verified without the unpublished marker.
+    node.submit(create_operation).unwrap();
+    node.anchor().unwrap();
+    node.submit(update_operation).unwrap();
+    node.anchor().unwrap();
+    let chain = verifier
+        .verify(subject.did(), network.root_timestamp)
+        .unwrap();
+    assert!(chain.unpublished().is_empty());
+}
+
+#[test]
+fn create_sign_present_verify() {
+    // root - issuer, root - holder, and a holder not attested to by the root.
+    let network = TestNetwork::new();
+    let (unattested, _) = network.create();
+
+    // Issue a credential to a subject and present it, signed by the holder.
+    let present = |subject: &TestDID, holder: &TestDID| {
+        let credential = network.credential(subject);
+        let presentation = Presentation {
+            verifiable_credential: Some(OneOrMany::One(CredentialOrJWT::Credential(
+                network
+                    .resolver
+                    .runtime
+                    .block_on(attestor(&network.issuer).sign(
+                        &credential,
+                        &ProofOptions::default(),
+                        &network.resolver,
+                    ))
+                    .unwrap(),
+            ))),
+            ..Default::default()
+        };
+        network
+            .resolver
+            .runtime
+            .block_on(attestor(holder).sign_presentation(
+                &presentation,
+                None,
+                Some("challenge"),
+                Some("example.com"),
+                &network.resolver,
+            ))
+            .unwrap()
+    };
+
+    let presentation = present(&network.holder, &network.holder);
+    let verified = verify_presentation(
+        &presentation,
+        Some("challenge"),
+        Some("example.com"),
+        network.root_timestamp,
+        &network.verifier,
+    )
+    .unwrap();
+    assert_eq!(verified.holder_chain.leaf(), network.holder.did());
+    assert_eq!(verified.issuer_chains.len(), 1);
+    assert_eq!(verified.issuer_chains[0].leaf(), network.issuer.did());
+
+    // The proof is bound to the challenge.
+    assert!(matches!(
+        verify_presentation(
+            &presentation,
+            Some("other challenge"),
+            Some("example.com"),
+            network.root_timestamp,
+            &network.verifier
+        ),
+        Err(PresentationError::InvalidProof(_))
+    ));
+
+    // The holder must be the subject of the credentials.
+    let presentation = present(&unattested, &network.holder);
+    assert!(matches!(
+        verify_presentation(&presentation, None, None, network.root_timestamp, &network.verifier),
+        Err(PresentationError::HolderNotSubject(did, 0)) if did == network.holder.did()
+    ));
+
+    // The holder must be verifiable.
+    let presentation = present(&unattested, &unattested);
+    assert!(matches!(
+        verify_presentation(
+            &presentation,
+            None,
+            None,
+            network.root_timestamp,
+            &network.verifier
+        ),
+        Err(PresentationError::HolderVerification(_))
+    ));
+}
+
+#[test]
+fn sign_verify_credential_jwt() {
+    let network = TestNetwork::new();
+    let mut credential = network.credential(&network.holder);
+    credential.issuance_date =
+        Some(VCDateTime::try_from("2022-10-20T11:30:05Z".to_string()).unwrap());
+    let jwt = network
+        .resolver
+        .runtime
+        .block_on(attestor(&network.issuer).sign_jwt(
+            &credential,
+            &ProofOptions::default(),
+            None,
+            &network.resolver,
+        ))
+        .unwrap();
+
+    // The JWT is signed with the issuer's verification method and its issuer chain verified.
+    let policy = CredentialVerificationPolicy::new(network.root_timestamp);
+    let (decoded, result) = verify_credential_jwt(&jwt, &network.verifier, &policy).unwrap();
+    assert_eq!(issuer_did(&decoded), Some(network.issuer.did()));
+    assert!(result.is_valid(), "{:?}", result);
+    assert_eq!(
+        result.issuer_chain.unwrap().unwrap().leaf(),
+        network.issuer.did()
+    );
+
+    // A tampered JWT signature is invalid.
+    let tampered = format!("{}AA", &jwt[..jwt.len() - 2]);
+    let (_, result) = verify_credential_jwt(&tampered, &network.verifier, &policy).unwrap();
+    assert!(!result.proof_is_valid());
+
+    // A JWT credential is verified within a presentation.
+    let presentation = Presentation {
+        verifiable_credential: Some(OneOrMany::One(CredentialOrJWT::JWT(jwt))),
+        ..Default::default()
+    };
+    let presentation = network
+        .resolver
+        .runtime
+        .block_on(attestor(&network.holder).sign_presentation(
+            &presentation,
+            None,
+            None,
+            None,
+            &network.resolver,
+        ))
+        .unwrap();
+    let verified = verify_presentation(
+        &presentation,
+        None,
+        None,
+        network.root_timestamp,
+        &network.verifier,
+    )
+    .unwrap();
+    assert_eq!(verified.issuer_chains[0].leaf(), network.issuer.did());
+}
+
+#[test]
+fn issue_revoke_verify_status() {
+    let network = TestNetwork::new();
+
+    // The issuer publishes its signed status list credential, available to the verifier locally.
+    let url = "https://example.com/status/revocation";
+    let issuer_attestor = attestor(&network.issuer);
+    let list =
+        IssuerStatusList::new(url, StatusPurpose::Revocation, MIN_STATUS_LIST_LENGTH).unwrap();
+    issuer_attestor
+        .save_status_list(issuer_attestor.did_suffix(), &list, false)
+        .unwrap();
+    let publish = |list: &IssuerStatusList| {
+        let credential = network
+            .resolver
+            .runtime
+            .block_on(issuer_attestor.sign(
+                &list.credential(network.issuer.did()).unwrap(),
+                &ProofOptions::default(),
+                &network.resolver,
+            ))
+            .unwrap();
+        save_status_list_credential(
+            trustchain_status_list_credentials_dir().unwrap(),
+            &credential,
+        )
+        .unwrap();
+    };
+    publish(&list);
+
+    // Issue a credential with a status list entry.
+    let mut credential = network.credential(&network.holder);
+    let entry = issuer_attestor
+        .allocate_credential_status(
+            issuer_attestor.did_suffix(),
+            StatusPurpose::Revocation,
+            &mut credential,
+        )
+        .unwrap();
+    let credential = network
+        .resolver
+        .runtime
+        .block_on(issuer_attestor.sign(&credential, &ProofOptions::default(), &network.resolver))
+        .unwrap();
+    let policy = CredentialVerificationPolicy::new(network.root_timestamp);
+    let result = verify_credential(&credential, &network.verifier, &policy);
+    assert!(result.is_valid(), "{:?}", result);
+    assert_eq!(result.status, CredentialStatusCheck::Active);
+
+    // A presentation of the credential verifies until the credential is revoked.
+    let presentation = Presentation {
+        verifiable_credential: Some(OneOrMany::One(CredentialOrJWT::Credential(
+            credential.clone(),
+        ))),
+        ..Default::default()
+    };
+    let presentation = network
+        .resolver
+        .runtime
+        .block_on(attestor(&network.holder).sign_presentation(
+            &presentation,
+            None,
+            None,
+            None,
+            &network.resolver,
+        ))
+        .unwrap();
+    assert!(verify_presentation(
+        &presentation,
+        None,
+        None,
+        network.root_timestamp,
+        &network.verifier
+    )
+    .is_ok());
+
+    let list = issuer_attestor
+        .update_status(
+            issuer_attestor.did_suffix(),
+            StatusPurpose::Revocation,
+            entry.index().unwrap(),
+            true,
+        )
+        .unwrap();
+    publish(&list);
+    let result = verify_credential(&credential, &network.verifier, &policy);
+    assert!(!result.is_valid());
+    assert_eq!(result.status, CredentialStatusCheck::Revoked);
+    assert!(matches!(
+        verify_presentation(
+            &presentation,
+            None,
+            None,
+            network.root_timestamp,
+            &network.verifier
+        ),
+        Err(PresentationError::InvalidStatus(
+            0,
+            CredentialStatusCheck::Revoked
+        ))
+    ));
+
+    // Status is not checked when only checking the signature.
+    let policy = CredentialVerificationPolicy {
+        signature_only: true,
+        ..policy
+    };
+    let result = verify_credential(&credential, &network.verifier, &policy);
+    assert_eq!(result.status, CredentialStatusCheck::NotChecked);
+}
+
+#[test]
+fn sign_verify_custom_context() {
+    let network = TestNetwork::new();
+
+    // A credential with a context that is neither built into the SSI library nor bundled.
+    let url = "https://example.com/contexts/membership/v1";
+    let credential: Credential = serde_json::from_value(serde_json::json!({
+        "@context": ["https://www.w3.org/2018/credentials/v1", url],
+        "type": ["VerifiableCredential", "MembershipCredential"],
+        "issuer": network.issuer.did(),
+        "issuanceDate": "2022-10-20T11:30:05Z",
+        "credentialSubject": {"id": "did:example:member", "memberOf": "Example Society"}
+    }))
+    .unwrap();
+    let issuer_attestor = attestor(&network.issuer);
+    assert!(network
+        .resolver
+        .runtime
+        .block_on(issuer_attestor.sign(&credential, &ProofOptions::default(), &network.resolver))
+        .is_err());
+
+    // Once saved to the Trustchain data directory, the context is loaded offline.
+    let document = serde_json::json!({
+        "@context": {
+            "@version": 1.1,
+            "@protected": true,
+            "MembershipCredential": "https://example.com/vocab#MembershipCredential",
+            "memberOf": "https://example.com/vocab#memberOf"
+        }
+    });
+    save_context(
+        trustchain_contexts_dir().unwrap(),
+        url,
+        "membership-v1.jsonld",
+        &document,
+    )
+    .unwrap();
+    let signed = network
+        .resolver
+        .runtime
+        .block_on(issuer_attestor.sign(&credential, &ProofOptions::default(), &network.resolver))
+        .unwrap();
+    assert_eq!(
+        serde_json::to_value(&signed.context).unwrap(),
+        serde_json::to_value(&credential.context).unwrap()
+    );
+    let policy = CredentialVerificationPolicy::new(network.root_timestamp);
+    assert!(verify_credential(&signed, &network.verifier, &policy).is_valid());
+
+    // Changes to the credential are detected.
+    let mut tampered = signed.clone();
+    tampered
+        .credential_subject
+        .to_single_mut()
+        .unwrap()
+        .property_set = Some(serde_json::from_str(r#"{"memberOf": "Other Society"}"#).unwrap());
+    assert!(!verify_credential(&tampered, &network.verifier, &policy).proof_is_valid());
+}
+
+#[test]
+fn sign_with_proof_options() {
+    let network = TestNetwork::new();
+    let (_, doc, _) = network
+        .resolver
+        .resolve_as_result(network.issuer.did())
+        .unwrap();
+    let vm_fragment = match &doc.unwrap().verification_method.unwrap()[0] {
+        ssi::did::VerificationMethod::Map(vm) => vm.id.to_owned(),
+        _ => panic!("Expected a verification method map."),
+    };
+
+    let mut credential: Credential = serde_json::from_str(TEST_CREDENTIAL).unwrap();
+    credential.issuer = Some(ssi::vc::Issuer::URI(URI::String(
+        network.issuer.did().to_string(),
+    )));
+    let issuer_attestor = attestor(&network.issuer);
+    let sign = |options: &ProofOptions| {
+        network.resolver.runtime.block_on(issuer_attestor.sign(
+            &credential,
+            options,
+            &network.resolver,
+        ))
+    };
+
+    // The verification method is resolved from the issuer's DID document for the signing key.
+    let created = Utc.with_ymd_and_hms(2022, 10, 20, 11, 30, 5).unwrap();
+    let options = ProofOptions {
+        created: Some(created),
+        domain: Some("example.com".to_string()),
+        challenge: Some("challenge".to_string()),
+        ..Default::default()
+    };
+    let signed = sign(&options).unwrap();
+    let proof = signed.proof.as_ref().unwrap().first().unwrap();
+    assert_eq!(
+        proof.verification_method,
+        Some(format!("{}{}", network.issuer.did(), vm_fragment))
+    );
+    assert_eq!(proof.proof_purpose, Some(ProofPurpose::AssertionMethod));
+    assert_eq!(proof.created, Some(created));
+    assert_eq!(proof.domain.as_deref(), Some("example.com"));
+    assert_eq!(proof.challenge.as_deref(), Some("challenge"));
+    let policy = CredentialVerificationPolicy::new(network.root_timestamp);
+    assert!(verify_credential(&signed, &network.verifier, &policy).is_valid());
+
+    // An unknown signing key or a verification method not in the issuer's document fails.
+    assert!(sign(&ProofOptions::with_key_id(Some("unknown"))).is_err());
+    let options = ProofOptions {
+        verification_method: Some(format!("{}#unknown", network.issuer.did())),
+        ..Default::default()
+    };
+    assert!(sign(&options).is_err());
+
+    // As does a proof purpose for which the verification method is not authorised.
+    let options = ProofOptions {
+        proof_purpose: Some(ProofPurpose::CapabilityDelegation),
+        ..Default::default()
+    };
+    assert!(sign(&options).is_err());
+}
+
+#[test]
+fn issue_verify_credential_schema() {
+    let network = TestNetwork::new();
+
+    // A credential with a schema requiring the subject's names and degree.
+    let schema_id = "https://example.com/schemas/degree-credential.json";
+    let schema = serde_json::json!({
+        "type": "object",
+        "properties": {"degree": {"type": "object", "required": ["type", "name"]}},
+        "required": ["givenName", "familyName", "degree"]
+    });
+    let mut credential = network.credential(&network.holder);
+    credential.credential_schema = Some(OneOrMany::One(
+        serde_json::from_value(serde_json::json!({
+            "id": schema_id,
+            "type": JSON_SCHEMA_VALIDATOR_2018
+        }))
+        .unwrap(),
+    ));
+    let issuer_attestor = attestor(&network.issuer);
+    let sign = |credential: &Credential| {
+        network.resolver.runtime.block_on(issuer_attestor.sign(
+            credential,
+            &ProofOptions::default(),
+            &network.resolver,
+        ))
+    };
+
+    // The schema must be in the registry for the credential to be issued.
+    assert!(matches!(
+        sign(&credential),
+        Err(IssuerError::Schema(SchemaError::SchemaNotFound(id))) if id == schema_id
+    ));
+    save_schema(
+        trustchain_schemas_dir().unwrap(),
+        schema_id,
+        "degree-credential.json",
+        &schema,
+    )
+    .unwrap();
+    let signed = sign(&credential).unwrap();
+    let policy = CredentialVerificationPolicy::new(network.root_timestamp);
+    let result = verify_credential(&signed, &network.verifier, &policy);
+    assert_eq!(result.schema, CredentialSchemaCheck::Valid);
+    assert!(result.is_valid());
+
+    // A non-conforming credential is not issued.
+    let mut invalid = credential.clone();
+    invalid
+        .credential_subject
+        .to_single_mut()
+        .unwrap()
+        .property_set
+        .as_mut()
+        .unwrap()
+        .remove("familyName");
+    assert!(matches!(
+        sign(&invalid),
+        Err(IssuerError::Schema(SchemaError::Violations(_)))
+    ));
+
+    // Nor verified, with the violations reported, including within a presentation.
+    let mut tampered = signed.clone();
+    tampered.credential_subject = invalid.credential_subject.clone();
+    let result = verify_credential(&tampered, &network.verifier, &policy);
+    assert!(matches!(
+        &result.schema,
+        CredentialSchemaCheck::Invalid(violations) if violations.len() == 1
+    ));
+    assert!(!result.is_valid());
+
+    // A credential presented after its schema is tightened no longer conforms.
+    let stricter = serde_json::json!({"type": "object", "required": ["studentId"]});
+    save_schema(
+        trustchain_schemas_dir().unwrap(),
+        schema_id,
+        "degree-credential.json",
+        &stricter,
+    )
+    .unwrap();
+    let presentation = Presentation {
+        verifiable_credential: Some(OneOrMany::One(CredentialOrJWT::Credential(signed))),
+        ..Default::default()
+    };
+    let presentation = network
+        .resolver
+        .runtime
+        .block_on(attestor(&network.holder).sign_presentation(
+            &presentation,
+            None,
+            None,
+            None,
+            &network.resolver,
+        ))
+        .unwrap();
+    assert!(matches!(
+        verify_presentation(
+            &presentation,
+            None,
+            None,
+            network.root_timestamp,
+            &network.verifier
+        ),
+        Err(PresentationError::InvalidSchema(
+            0,
+            CredentialSchemaCheck::Invalid(_)
+        ))
+    ));
+}
+
+#[test]
+fn verify_issuer_policy() {
+    let network = TestNetwork::new();
+    let mut credential = network.credential(&network.holder);
+    credential.type_ = OneOrMany::Many(vec![
+        "VerifiableCredential".to_string(),
+        "UniversityDegreeCredential".to_string(),
+    ]);
+    let credential = network
+        .resolver
+        .runtime
+        .block_on(attestor(&network.issuer).sign(
+            &credential,
+            &ProofOptions::default(),
+            &network.resolver,
+        ))
+        .unwrap();
+    let policy_with_rule = |upstream: &str, levels: serde_json::Value| {
+        let policy = serde_json::json!({
+            "rules": [{
+                "credentialTypes": ["UniversityDegreeCredential"],
+                "upstream": upstream,
+                "levels": levels
+            }]
+        });
+        CredentialVerificationPolicy {
+            issuer_policy: Some(serde_json::from_value(policy).unwrap()),
+            ..CredentialVerificationPolicy::new(network.root_timestamp)
+        }
+    };
+
+    // Not checked without an issuer policy.
+    let result = verify_credential(
+        &credential,
+        &network.verifier,
+        &CredentialVerificationPolicy::new(network.root_timestamp),
+    );
+    assert_eq!(result.authorisation, AuthorisationCheck::NotChecked);
+    assert!(result.is_valid());
+
+    // The issuer is a child of the root.
+    let result = verify_credential(
+        &credential,
+        &network.verifier,
+        &policy_with_rule(network.root.did(), serde_json::json!([1])),
+    );
+    assert_eq!(result.authorisation, AuthorisationCheck::Authorised);
+    assert!(result.is_valid());
+
+    // But not a grandchild of the root, nor downstream of the holder.
+    let result = verify_credential(
+        &credential,
+        &network.verifier,
+        &policy_with_rule(network.root.did(), serde_json::json!([2])),
+    );
+    assert_eq!(
+        result.authorisation,
+        AuthorisationCheck::Unauthorised(vec![PolicyViolation::InvalidLevel(
+            "UniversityDegreeCredential".to_string(),
+            network.issuer.did().to_string(),
+            network.root.did().to_string(),
+            1
+        )])
+    );
+    assert!(!result.is_valid());
+    let policy = policy_with_rule(network.holder.did(), serde_json::Value::Null);
+    let result = verify_credential(&credential, &network.verifier, &policy);
+    assert_eq!(
+        result.authorisation,
+        AuthorisationCheck::Unauthorised(vec![PolicyViolation::NotDownstream(
+            "UniversityDegreeCredential".to_string(),
+            network.issuer.did().to_string(),
+            vec![network.holder.did().to_string()]
+        )])
+    );
+
+    // Presentations are verified against the issuer policy.
+    let presentation = Presentation {
+        verifiable_credential: Some(OneOrMany::One(CredentialOrJWT::Credential(credential))),
+        ..Default::default()
+    };
+    let presentation = network
+        .resolver
+        .runtime
+        .block_on(attestor(&network.holder).sign_presentation(
+            &presentation,
+            None,
+            None,
+            None,
+            &network.resolver,
+        ))
+        .unwrap();
+    assert!(verify_presentation_with_policy(
+        &presentation,
+        None,
+        None,
+        network.root_timestamp,
+        policy_with_rule(network.root.did(), serde_json::json!([1]))
+            .issuer_policy
+            .as_ref(),
+        &network.verifier,
+    )
+    .is_ok());
+    assert!(matches!(
+        verify_presentation_with_policy(
+            &presentation,
+            None,
+            None,
+            network.root_timestamp,
+            policy.issuer_policy.as_ref(),
+            &network.verifier,
+        ),
+        Err(PresentationError::Unauthorised(0, violations)) if violations.len() == 1
+    ));
+}
+
+#[test]
+fn issue_batch_verify() {
+    let network = TestNetwork::new();
+    let policy = CredentialVerificationPolicy::new(network.root_timestamp);
+
+    let mut template: Credential = serde_json::from_str(TEST_CREDENTIAL).unwrap();
+    template.issuer = Some(ssi::vc::Issuer::URI(URI::String(
+        network.issuer.did().to_string(),
+    )));
+    let csv = "id,givenName,degree.name\n\
+        did:example:a,Ann,Bachelor of Arts\n\
+        did:example:b,Bob\n\
+        did:example:c,Cat,\"Master of Science, Engineering\"\n";
+    let subjects = read_subjects(csv, SubjectFormat::Csv).unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let issuer_attestor = attestor(&network.issuer);
+    let issue = |output: &BatchOutput| {
+        network
+            .resolver
+            .runtime
+            .block_on(issue_batch(
+                &issuer_attestor,
+                &template,
+                &subjects,
+                &ProofOptions::default(),
+                &network.resolver,
+                output,
+            ))
+            .unwrap()
+    };
+
+    // Credentials written to a directory and to a JSONL file are verified, with the record
+    // missing a field reported in the manifest.
+    let output = BatchOutput::Directory(dir.path().join("credentials"));
+    let manifest = issue(&output);
+    assert_eq!((manifest.issued, manifest.failed), (2, 1));
+    for entry in manifest
+        .entries
+        .iter()
+        .filter(|entry| entry.error.is_none())
+    {
+        let path = dir
+            .path()
+            .join("credentials")
+            .join(entry.file.as_ref().unwrap());
+        let credential: Credential = serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap();
+        assert!(verify_credential(&credential, &network.verifier, &policy).is_valid());
+    }
+
+    let path = dir.path().join("credentials.jsonl");
+    let manifest = issue(&BatchOutput::Jsonl(path.clone()));
+    assert_eq!((manifest.issued, manifest.failed), (2, 1));
+    for line in std::fs::read_to_string(path).unwrap().lines() {
+        let credential: Credential = serde_json::from_str(line).unwrap();
+        assert!(verify_credential(&credential, &network.verifier, &policy).is_valid());
+    }
+}
+
+#[test]
+fn wallet_add_query_present() {
+    let network = TestNetwork::new();
+    let policy = CredentialVerificationPolicy::new(network.root_timestamp);
+
+    let mut credential = network.credential(&network.holder);
+    credential.issuance_date =
+        Some(VCDateTime::try_from("2022-10-20T11:30:05Z".to_string()).unwrap());
+    let issuer_attestor = attestor(&network.issuer);
+    let signed = network
+        .resolver
+        .runtime
+        .block_on(issuer_attestor.sign(&credential, &ProofOptions::default(), &network.resolver))
+        .unwrap();
+    let jwt = network
+        .resolver
+        .runtime
+        .block_on(issuer_attestor.sign_jwt(
+            &credential,
+            &ProofOptions::default(),
+            None,
+            &network.resolver,
+        ))
+        .unwrap();
+
+    // Credentials are verified on import.
+    let dir = tempfile::tempdir().unwrap();
+    let mut wallet = Wallet::from_dir(dir.path()).unwrap();
+    let record = wallet
+        .add(
+            CredentialOrJWT::Credential(signed.clone()),
+            &network.verifier,
+            &policy,
+        )
+        .unwrap();
+    assert_eq!(record.issuer.as_deref(), Some(network.issuer.did()));
+    assert_eq!(record.subjects, vec![network.holder.did().to_string()]);
+    let jwt_record = wallet
+        .add(CredentialOrJWT::JWT(jwt), &network.verifier, &policy)
+        .unwrap();
+    assert!(jwt_record.jwt);
+    let mut tampered = signed;
+    tampered.credential_subject.to_single_mut().unwrap().id =
+        Some(URI::String(network.root.did().to_string()));
+    assert!(matches!(
+        wallet.add(
+            CredentialOrJWT::Credential(tampered),
+            &network.verifier,
+            &policy
+        ),
+        Err(WalletError::Unverified(_))
+    ));
+    assert_eq!(wallet.records().count(), 2);
+
+    // And presented by the holder.
+    let ids: Vec<String> = wallet.records().map(|record| record.id.clone()).collect();
+    let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+    let presentation = wallet.present(&ids, network.holder.did()).unwrap();
+    let presentation = network
+        .resolver
+        .runtime
+        .block_on(attestor(&network.holder).sign_presentation(
+            &presentation,
+            None,
+            Some("challenge"),
+            None,
+            &network.resolver,
+        ))
+        .unwrap();
+    let verified = verify_presentation(
+        &presentation,
+        Some("challenge"),
+        None,
+        network.root_timestamp,
+        &network.verifier,
+    )
+    .unwrap();
+    assert_eq!(verified.issuer_chains.len(), 2);
+}
+
+#[test]
+fn presentation_exchange() {
+    let network = TestNetwork::new();
+    let policy = CredentialVerificationPolicy::new(network.root_timestamp);
+
+    let mut credential = network.credential(&network.holder);
+    credential.issuance_date =
+        Some(VCDateTime::try_from("2022-10-20T11:30:05Z".to_string()).unwrap());
+    let issuer_attestor = attestor(&network.issuer);
+    let signed = network
+        .resolver
+        .runtime
+        .block_on(issuer_attestor.sign(&credential, &ProofOptions::default(), &network.resolver))
+        .unwrap();
+    let jwt = network
+        .resolver
+        .runtime
+        .block_on(issuer_attestor.sign_jwt(
+            &credential,
+            &ProofOptions::default(),
+            None,
+            &network.resolver,
+        ))
+        .unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let mut wallet = Wallet::from_dir(dir.path()).unwrap();
+    wallet
+        .add(
+            CredentialOrJWT::Credential(signed),
+            &network.verifier,
+            &policy,
+        )
+        .unwrap();
+    wallet
+        .add(CredentialOrJWT::JWT(jwt), &network.verifier, &policy)
+        .unwrap();
+
+    // A definition requesting a VC-JWT from the issuer and a credential with a given name.
+    let definition = |family_name: &str| -> PresentationDefinition {
+        serde_json::from_value(serde_json::json!({
+            "id": "exchange",
+            "input_descriptors": [
+                {
+                    "id": "issued",
+                    "format": {"jwt_vc": {"alg": ["ES256K"]}},
+                    "constraints": {
+                        "fields": [{"path": ["$.iss"], "filter": {"const": network.issuer.did()}}]
+                    }
+                },
+                {
+                    "id": "named",
+                    "constraints": {
+                        "fields": [{
+                            "path": ["$.credentialSubject.familyName", "$.vc.credentialSubject.familyName"],
+                            "filter": {"type": "string", "const": family_name}
+                        }]
+                    }
+                }
+            ]
+        }))
+        .unwrap()
+    };
+    let family_name = serde_json::to_value(&credential.credential_subject).unwrap()["familyName"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let definition = definition(&family_name);
+    let presentation = wallet
+        .present_definition(&definition, network.holder.did())
+        .unwrap();
+    let presentation = network
+        .resolver
+        .runtime
+        .block_on(attestor(&network.holder).sign_presentation(
+            &presentation,
+            None,
+            Some("challenge"),
+            None,
+            &network.resolver,
+        ))
+        .unwrap();
+    let (submission, verified) = verify_submission(
+        &presentation,
+        &definition,
+        Some("challenge"),
+        None,
+        network.root_timestamp,
+        None,
+        &network.verifier,
+    )
+    .unwrap();
+    // The VC-JWT satisfies both descriptors, so is the only credential presented.
+    assert_eq!(submission.descriptor_map.len(), 2);
+    assert_eq!(verified.issuer_chains.len(), 1);
+
+    // The presentation does not satisfy a different definition.
+    let mut other = definition.clone();
+    other.input_descriptors[1].constraints.fields[0].filter =
+        Some(serde_json::json!({"const": "Other"}));
+    assert!(matches!(
+        verify_submission(
+            &presentation,
+            &other,
+            Some("challenge"),
+            None,
+            network.root_timestamp,
+            None,
+            &network.verifier,
+        ),
+        Err(ExchangeError::NotSatisfied(id, _)) if id == "named"
+    ));
+}
+
+#[test]
+fn oid4vc_issue_present() {
+    let network = TestNetwork::new();
+
+    // Issuer and verifier service, issuing credentials from the test credential as a template.
+    let mut template: Credential = serde_json::from_str(TEST_CREDENTIAL).unwrap();
+    template.credential_subject.to_single_mut().unwrap().id = None;
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let service = Arc::new(
+        OID4VCService::new(
+            &base_url,
+            TestVerifier::new(
+                get_ion_resolver(&network.server.endpoint()),
+                network.node.clone(),
+            ),
+            network.root_timestamp,
+        )
+        .with_issuer(
+            attestor(&network.issuer),
+            HashMap::from([("TestCredential".to_string(), template)]),
+        ),
+    );
+    let oid4vc_server = OID4VCServer::start(service.clone(), listener).unwrap();
+    let client = OID4VCClient::new();
+
+    // The holder receives the offered credential, issued to the holder DID.
+    let subject = serde_json::json!({"familyName": "Holder"});
+    let offer = service
+        .offer_credential("TestCredential", subject.as_object().unwrap().clone())
+        .unwrap();
+    let offer_uri = service.credential_offer_uri(&offer);
+    assert!(offer_uri.starts_with("openid-credential-offer://?credential_offer_uri="));
+    let credential = client
+        .receive_credential(&offer_uri, &attestor(&network.holder), &network.resolver)
+        .unwrap();
+    assert!(is_subject(&credential, network.holder.did()));
+    assert_eq!(issuer_did(&credential), Some(network.issuer.did()));
+    // The pre-authorized code may be used once.
+    assert!(matches!(
+        client.receive_credential(&offer_uri, &attestor(&network.holder), &network.resolver),
+        Err(ClientError::Response(404, _))
+    ));
+
+    // The credential is verified into the holder's wallet.
+    let dir = tempfile::tempdir().unwrap();
+    let mut wallet = Wallet::from_dir(dir.path()).unwrap();
+    wallet
+        .add(
+            CredentialOrJWT::Credential(credential),
+            &network.verifier,
+            &CredentialVerificationPolicy::new(network.root_timestamp),
+        )
+        .unwrap();
+
+    // And presented in answer to an authorization request.
+    let definition: PresentationDefinition = serde_json::from_value(serde_json::json!({
+        "id": "holder-check",
+        "input_descriptors": [{
+            "id": "holder",
+            "constraints": {
+                "fields": [{"path": ["$.credentialSubject.familyName"], "filter": {"const": "Holder"}}]
+            }
+        }]
+    }))
+    .unwrap();
+    let pending = service.request_presentation(definition.clone()).unwrap();
+    let request_uri = service.authorization_request_uri(&pending.request);
+    client
+        .present(
+            &request_uri,
+            &wallet,
+            &attestor(&network.holder),
+            &network.resolver,
+        )
+        .unwrap();
+    match service.presentation_result(&pending.result_code).unwrap() {
+        Some(PresentationResult::Verified {
+            holder: did,
+            issuers,
+            ..
+        }) => {
+            assert_eq!(did, network.holder.did());
+            assert_eq!(issuers, vec![network.issuer.did().to_string()]);
+        }
+        result => panic!("Unexpected result: {:?}", result),
+    }
+    // A request may be answered once.
+    assert!(matches!(
+        client.present(
+            &request_uri,
+            &wallet,
+            &attestor(&network.holder),
+            &network.resolver
+        ),
+        Err(ClientError::Response(404, _))
+    ));
+
+    // A presentation signed for another request is rejected.
+    let first = service.request_presentation(definition.clone()).unwrap();
+    let second = service.request_presentation(definition).unwrap();
+    let presentation = client
+        .sign_presentation(
+            &first.request,
+            &wallet,
+            &attestor(&network.holder),
+            &network.resolver,
+        )
+        .unwrap();
+    assert!(matches!(
+        client.respond(&second.request, &presentation),
+        Err(ClientError::Response(400, _))
+    ));
+    assert!(matches!(
+        service.presentation_result(&second.result_code).unwrap(),
+        Some(PresentationResult::Rejected { .. })
+    ));
+    // The rejected response does not consume the request, which is still answered.
+    let presentation = client
+        .sign_presentation(
+            &second.request,
+            &wallet,
+            &attestor(&network.holder),
+            &network.resolver,
+        )
+        .unwrap();
+    client.respond(&second.request, &presentation).unwrap();
+    assert!(matches!(
+        service.presentation_result(&second.result_code).unwrap(),
+        Some(PresentationResult::Verified { .. })
+    ));
+    drop(oid4vc_server);
+}